@@ -0,0 +1,135 @@
+use image::{imageops, RgbaImage};
+use rayon::ThreadPool;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::calibration::ToneCurve;
+use crate::canvas_pool::CanvasPool;
+use crate::compare::{self, Metric};
+use crate::planes::Planes;
+use crate::queue::Signal;
+use crate::render;
+use crate::RenderOptions;
+
+// Longest side of the source the drafts are rendered from, small enough
+// that a search of a few dozen renders takes seconds.
+const DRAFT_SIDE: u32 = 120;
+// Blur over the draft and the source before comparing, in source pixels,
+// so the marks are judged by the tones they add up to and not by their
+// edges.
+const BLUR: f32 = 1.5;
+// Settings tried. Cell sizes past the current one, or the middle one if
+// that is smaller, are left out so the tuned render stays as quick.
+const CELLS: [u32; 7] = [4, 6, 8, 10, 12, 16, 20];
+const GAMMAS: [f32; 7] = [0.5, 0.7, 0.85, 1.0, 1.2, 1.5, 2.0];
+const DENSITIES: [f32; 5] = [0.7, 0.85, 1.0, 1.15, 1.3];
+// Each round tries every value of one setting with the others fixed at
+// their best so far.
+const ROUNDS: usize = 2;
+// Points of the tone curve that carries the gamma and density.
+const CURVE_POINTS: usize = 17;
+
+// The options that draw the source closest to the photo, found by
+// `tune`. The gamma and density are folded into the tone curve.
+#[derive(Clone, Serialize)]
+pub struct Tuned {
+    pub options: RenderOptions,
+    pub gamma: f32,
+    pub density: f32,
+    // Mean color difference of the blurred draft from the blurred source.
+    pub delta_e: f32,
+}
+
+// Search the cell size, and a gamma and density for the darkness of the
+// marks, for the render of `source` with `options` that looks most like
+// it from a distance. Drafts are rendered from a small copy of the source
+// without its masks or secondary image.
+pub fn tune(
+    source: &RgbaImage,
+    options: &RenderOptions,
+    pool: &ThreadPool,
+    canvases: &CanvasPool,
+) -> Tuned {
+    let draft = draft_source(source);
+    let planes = Planes::new(&draft, options.needs_hue());
+    let target = imageops::blur(&draft, BLUR);
+    let mut scores = HashMap::new();
+    let mut score = |cell: u32, g: usize, d: usize| -> f32 {
+        *scores.entry((cell, g, d)).or_insert_with(|| {
+            let tried = with_tuning(options, cell, GAMMAS[g], DENSITIES[d]);
+            let img = render::generate(&planes, &tried, &Signal::default(), pool, canvases)
+                .expect("An unsignalled render can not be interrupted");
+            let small = imageops::resize(
+                &img,
+                draft.width(),
+                draft.height(),
+                imageops::FilterType::Triangle,
+            );
+            canvases.recycle_image(img);
+            compare::compare(&target, &imageops::blur(&small, BLUR), Metric::DeltaE)
+                .0
+                .delta_e
+        })
+    };
+    let one = |values: &[f32]| values.iter().position(|&v| v == 1.0).unwrap_or(0);
+    let (mut cell, mut g, mut d) = (options.cell, one(&GAMMAS), one(&DENSITIES));
+    let cells: Vec<u32> = CELLS
+        .into_iter()
+        .chain([options.cell])
+        .filter(|&c| c <= options.cell.max(CELLS[CELLS.len() / 2]))
+        .collect();
+    for _ in 0..ROUNDS {
+        cell = best(&cells, |&c| score(c, g, d));
+        g = best(&(0..GAMMAS.len()).collect::<Vec<_>>(), |&i| {
+            score(cell, i, d)
+        });
+        d = best(&(0..DENSITIES.len()).collect::<Vec<_>>(), |&i| {
+            score(cell, g, i)
+        });
+    }
+    Tuned {
+        options: with_tuning(options, cell, GAMMAS[g], DENSITIES[d]),
+        gamma: GAMMAS[g],
+        density: DENSITIES[d],
+        delta_e: score(cell, g, d),
+    }
+}
+
+// The value with the lowest score, the first of any ties.
+fn best<T: Copy>(values: &[T], mut score: impl FnMut(&T) -> f32) -> T {
+    let mut scored: Vec<(f32, T)> = values.iter().map(|v| (score(v), *v)).collect();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    scored[0].1
+}
+
+// The source scaled down to at most DRAFT_SIDE a side.
+fn draft_source(source: &RgbaImage) -> RgbaImage {
+    let (width, height) = source.dimensions();
+    let scale = (DRAFT_SIDE as f32 / width.max(height) as f32).min(1.0);
+    let (w, h) = (
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    );
+    imageops::resize(source, w, h, imageops::FilterType::Triangle)
+}
+
+// The options at `cell` with darkness raised to `gamma` and scaled by
+// `density`, before any tone curve they already have.
+fn with_tuning(options: &RenderOptions, cell: u32, gamma: f32, density: f32) -> RenderOptions {
+    let points = (0..CURVE_POINTS)
+        .map(|i| {
+            let t = i as f32 / (CURVE_POINTS - 1) as f32;
+            let tuned = (density * t.powf(gamma)).clamp(0.0, 1.0);
+            let drawn = options
+                .tone_curve
+                .as_ref()
+                .map_or(tuned, |curve| curve.apply(tuned));
+            [t, drawn]
+        })
+        .collect();
+    RenderOptions {
+        cell,
+        tone_curve: Some(ToneCurve { points }),
+        ..options.clone()
+    }
+}
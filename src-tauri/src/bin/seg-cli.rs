@@ -0,0 +1,146 @@
+// Render an image from the command line with the same options the app
+// sends, read from a json file and overridden by flags.
+//
+//   seg-cli INPUT OUTPUT [--options FILE] [--cell N] [--style NAME]
+//           [--seed N] [--secondary FILE] [--threads N]
+
+use image::RgbaImage;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use seg_core::blend;
+use seg_core::canvas_pool::CanvasPool;
+use seg_core::error::Error;
+use seg_core::migrate;
+use seg_core::planes::Planes;
+use seg_core::post;
+use seg_core::queue::{JobKind, Signal};
+use seg_core::render::{generate, Pools};
+use seg_core::slic;
+use seg_core::RenderOptions;
+
+const USAGE: &str = "Usage: seg-cli INPUT OUTPUT [--options FILE] [--cell N] [--style NAME] \
+[--seed N] [--secondary FILE] [--threads N]";
+
+struct Args {
+    input: String,
+    output: String,
+    options: RenderOptions,
+    secondary: Option<String>,
+    threads: usize,
+}
+
+fn main() -> ExitCode {
+    match parse(std::env::args().skip(1)).and_then(run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut paths = Vec::new();
+    let mut options = serde_json::Map::new();
+    let mut file = None;
+    let mut secondary = None;
+    let mut threads = 0;
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            paths.push(arg);
+            continue;
+        };
+        let value = args
+            .next()
+            .ok_or(format!("--{} needs a value\n{}", flag, USAGE))?;
+        let number = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("--{} must be a whole number, not {}", flag, value))
+        };
+        match flag {
+            "options" => file = Some(value),
+            "cell" | "seed" => {
+                options.insert(flag.to_string(), number(&value)?.into());
+            }
+            "style" => {
+                options.insert(flag.to_string(), value.into());
+            }
+            "secondary" => secondary = Some(value),
+            "threads" => threads = number(&value)? as usize,
+            _ => return Err(format!("Unknown option --{}\n{}", flag, USAGE)),
+        }
+    }
+    let [input, output] = <[String; 2]>::try_from(paths).map_err(|_| USAGE.to_string())?;
+    // Flags win over the file, fields in neither take their defaults.
+    let mut merged = match &file {
+        Some(path) => {
+            let json = std::fs::read_to_string(path)
+                .map_err(|err| format!("The file at {} could not be opened: {}", path, err))?;
+            match serde_json::from_str(&json) {
+                Ok(serde_json::Value::Object(map)) => map,
+                Ok(_) => return Err(format!("The file at {} is not a json object", path)),
+                Err(err) => return Err(format!("The file at {} is not valid json: {}", path, err)),
+            }
+        }
+        None => serde_json::Map::new(),
+    };
+    merged.extend(options);
+    // Files saved by older versions are upgraded before the flags apply.
+    let options = migrate::from_value(merged.into())?;
+    Ok(Args {
+        input,
+        output,
+        options: options.validate()?,
+        secondary,
+        threads,
+    })
+}
+
+fn open(path: &str) -> Result<RgbaImage, String> {
+    let img = image::open(path)
+        .map_err(|err| format!("The file at {} could not be opened: {}", path, err))?
+        .to_rgba8();
+    if img.width() == 0 || img.height() == 0 {
+        return Err(Error::EmptyImage(Some(path.to_string())).into());
+    }
+    Ok(img)
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let options = &args.options;
+    let source = open(&args.input)?;
+    let base = Planes::new(&source, options.needs_hue());
+    let base = match options.threshold_trace {
+        Some(level) => base.trace(level),
+        None => base,
+    };
+    let planes = match (options.blend, &args.secondary) {
+        (Some(mode), Some(path)) => {
+            let secondary = blend::secondary_planes(&open(path)?, base.width, base.height);
+            blend::blend(&base, &secondary, mode)
+        }
+        _ => base,
+    };
+    let planes = match &options.segments {
+        Some(slic) => planes.with_segments(Arc::new(slic::segment(&source, slic))),
+        None => planes,
+    };
+    let planes = match options.cmy {
+        Some(_) => planes.with_photo(Arc::new(source.clone())),
+        None => planes,
+    };
+    let pool = Pools::new(args.threads, false)?.for_kind(JobKind::Export);
+    let img = generate(
+        &planes,
+        options,
+        &Signal::default(),
+        &pool,
+        &CanvasPool::default(),
+    )
+    .expect("An unsignalled render can not be interrupted");
+    let img = post::finish(&img, &source, &planes, options, true)?.unwrap_or(img);
+    img.save(&args.output)
+        .map_err(|err| format!("The file at {} could not be saved: {}", args.output, err))
+}
@@ -0,0 +1,47 @@
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::planes::Planes;
+
+// How a second image takes part in a render.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Blend {
+    // Darkness is a mix of the two images, an `amount` of 1 is all the
+    // secondary image.
+    Mix { amount: f32 },
+    // The base image sets darkness and the secondary image sets hue, which
+    // picks the style in Multi and turns hue rotated dots.
+    Hue,
+}
+
+// The planes of the secondary image stretched to the size of the base image
+// so the two line up pixel for pixel.
+pub fn secondary_planes(secondary: &RgbaImage, width: u32, height: u32) -> Planes {
+    if secondary.dimensions() == (width, height) {
+        return Planes::new(secondary, true);
+    }
+    let resized = imageops::resize(secondary, width, height, imageops::FilterType::Triangle);
+    Planes::new(&resized, true)
+}
+
+// Combine the base and secondary planes, which must be the same size.
+pub fn blend(base: &Planes, secondary: &Planes, blend: Blend) -> Planes {
+    match blend {
+        Blend::Mix { amount } => {
+            let amount = amount.clamp(0.0, 1.0);
+            let luma = base
+                .luma
+                .iter()
+                .zip(&secondary.luma)
+                .map(|(a, b)| a + (b - a) * amount)
+                .collect();
+            Planes::from_parts(base.width, base.height, luma, base.hue.clone())
+        }
+        Blend::Hue => Planes::from_parts(
+            base.width,
+            base.height,
+            base.luma.clone(),
+            secondary.hue.clone(),
+        ),
+    }
+}
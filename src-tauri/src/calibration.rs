@@ -0,0 +1,103 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Problem};
+
+// A curve applied to the darkness of every mark, so the tones a plotter or
+// printer lays down match the tones on screen. Made by `measure` from a
+// scan of a printed step wedge.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ToneCurve {
+    // (wanted, drawn) darkness pairs in [0, 1], in increasing order.
+    // Darkness between them is interpolated.
+    pub points: Vec<[f32; 2]>,
+}
+
+impl ToneCurve {
+    // The darkness to draw for a wanted darkness `t`.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        let Some(i) = self.points.iter().position(|p| p[0] >= t) else {
+            return self.points.last().map_or(t, |p| p[1]);
+        };
+        if i == 0 {
+            return self.points[0][1];
+        }
+        let ([x0, y0], [x1, y1]) = (self.points[i - 1], self.points[i]);
+        if x1 == x0 {
+            y1
+        } else {
+            y0 + (y1 - y0) * (t - x0) / (x1 - x0)
+        }
+    }
+
+    pub fn problem(&self) -> Option<Problem> {
+        if self.points.iter().flatten().any(|v| !v.is_finite()) {
+            Some(Problem::PointsNotNumbers)
+        } else if self.points.windows(2).any(|w| w[1][0] < w[0][0]) {
+            Some(Problem::PointsOutOfOrder)
+        } else {
+            None
+        }
+    }
+}
+
+// The tone curve that undoes how a device printed the step wedge test
+// pattern with `steps` steps. `scan` is a scan or photo of the print
+// cropped to the wedge; it may be upside down. Each band is measured over
+// its middle, away from the edges and the marks bleeding across them, and
+// the darkness is taken relative to the lightest and darkest bands so
+// paper white and solid ink are the ends of the scale.
+pub fn measure(scan: &RgbaImage, steps: u32) -> Result<ToneCurve, Error> {
+    let steps = steps.max(2);
+    let (width, height) = (scan.width(), scan.height());
+    if width < 2 * steps || height < 2 {
+        return Err(Error::ScanTooSmall {
+            width,
+            height,
+            steps,
+        });
+    }
+    let band = width as f32 / steps as f32;
+    let mut lightness: Vec<f32> = (0..steps)
+        .map(|i| {
+            let x0 = (band * (i as f32 + 0.25)) as u32;
+            let x1 = ((band * (i as f32 + 0.75)) as u32).max(x0 + 1);
+            let (y0, y1) = (height / 4, (3 * height / 4).max(height / 4 + 1));
+            let mut sum = 0.0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let px = scan.get_pixel(x, y);
+                    sum += 0.2989 * px[0] as f32 + 0.5870 * px[1] as f32 + 0.1140 * px[2] as f32;
+                }
+            }
+            sum / ((x1 - x0) * (y1 - y0)) as f32 / 255.0
+        })
+        .collect();
+    // The wedge runs from black to white, a scan that runs the other way
+    // was turned around.
+    if lightness[0] > lightness[lightness.len() - 1] {
+        lightness.reverse();
+    }
+    let (darkest, lightest) = (lightness[0], lightness[lightness.len() - 1]);
+    if lightest - darkest < 0.05 {
+        return Err("The scan has too little contrast to measure".to_string());
+    }
+    // Step i wants darkness 1 - i / (steps - 1), it came out as `printed`.
+    // Noise can make a lighter step read darker than the one before, so
+    // the printed darkness is kept falling.
+    let mut points = Vec::with_capacity(steps as usize);
+    let mut floor = f32::INFINITY;
+    for (i, l) in lightness.iter().enumerate() {
+        let wanted = 1.0 - i as f32 / (steps - 1) as f32;
+        let printed = ((lightest - l) / (lightest - darkest))
+            .clamp(0.0, 1.0)
+            .min(floor);
+        floor = printed;
+        points.push([printed, wanted]);
+    }
+    // Inverted, the printed darkness is what is wanted and the wanted
+    // darkness is what to draw to get it.
+    points.reverse();
+    Ok(ToneCurve { points })
+}
@@ -0,0 +1,84 @@
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use wassily::prelude::*;
+
+// Most band canvases of a single size kept for reuse.
+const MAX_CANVASES: usize = 256;
+
+// Buffers left over from earlier renders, keyed by their dimensions. A
+// render of the same source at the same cell size reuses them instead of
+// allocating a fresh multi-hundred-megabyte canvas and output image.
+#[derive(Default)]
+pub struct CanvasPool {
+    canvases: Mutex<HashMap<(u32, u32), Vec<Canvas>>>,
+    images: Mutex<HashMap<(u32, u32), Vec<u8>>>,
+}
+
+impl CanvasPool {
+    // A canvas of the given size filled with `color`.
+    pub fn canvas(&self, width: u32, height: u32, color: Color) -> Canvas {
+        let reused = self
+            .canvases
+            .lock()
+            .expect("Could not lock canvas pool mutex")
+            .get_mut(&(width, height))
+            .and_then(|free| free.pop());
+        let mut canvas = reused.unwrap_or_else(|| Canvas::new(width, height));
+        canvas.fill(color);
+        canvas
+    }
+
+    pub fn recycle_canvas(&self, canvas: Canvas) {
+        let mut canvases = self
+            .canvases
+            .lock()
+            .expect("Could not lock canvas pool mutex");
+        let free = canvases
+            .entry((canvas.width(), canvas.height()))
+            .or_default();
+        if free.len() < MAX_CANVASES {
+            free.push(canvas);
+        }
+    }
+
+    // An output image of the given size filled with `color`. Only the most
+    // recently used size is kept, so switching cell sizes does not hold on
+    // to buffers that will not be used again.
+    pub fn image(&self, width: u32, height: u32, color: Rgba<u8>) -> RgbaImage {
+        let reused = self
+            .images
+            .lock()
+            .expect("Could not lock canvas pool mutex")
+            .remove(&(width, height));
+        match reused.and_then(|buf| RgbaImage::from_raw(width, height, buf)) {
+            Some(mut img) => {
+                img.pixels_mut().for_each(|px| *px = color);
+                img
+            }
+            None => RgbaImage::from_pixel(width, height, color),
+        }
+    }
+
+    pub fn recycle_image(&self, img: RgbaImage) {
+        let mut images = self
+            .images
+            .lock()
+            .expect("Could not lock canvas pool mutex");
+        images.clear();
+        images.insert((img.width(), img.height()), img.into_raw());
+    }
+
+    // Drop every pooled buffer, used when a new source changes the sizes
+    // future renders will need.
+    pub fn clear(&self) {
+        self.canvases
+            .lock()
+            .expect("Could not lock canvas pool mutex")
+            .clear();
+        self.images
+            .lock()
+            .expect("Could not lock canvas pool mutex")
+            .clear();
+    }
+}
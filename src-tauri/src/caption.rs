@@ -0,0 +1,149 @@
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{imageops, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::error::Error;
+
+// Where the platforms keep their fonts. Captions only use fonts from
+// these, by file name, so options from the webview can't read other files.
+const FONT_FOLDERS: [&str; 6] = [
+    "/System/Library/Fonts",
+    "/Library/Fonts",
+    "C:\\Windows\\Fonts",
+    "/usr/share/fonts/truetype",
+    "/usr/share/fonts/truetype/dejavu",
+    "/usr/share/fonts/TTF",
+];
+// Fonts tried in turn when the caption names none, one that comes with
+// each platform.
+const DEFAULT_FONTS: [&str; 3] = ["Helvetica.ttc", "arial.ttf", "DejaVuSans.ttf"];
+// Largest text height, in output pixels.
+pub const MAX_SIZE: f32 = 1000.0;
+
+// A line of text under the artwork, on a margin added for it, for prints
+// ready to hang.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Caption {
+    pub title: String,
+    pub date: Option<String>,
+    // Like "3/50".
+    pub edition: Option<String>,
+    // The file name of a TrueType or OpenType font in a font folder, like
+    // "Georgia.ttf", or a platform font if there is none.
+    pub font: Option<String>,
+    // Height of the text in output pixels, which is also the space kept
+    // above and below it.
+    pub size: f32,
+    pub color: [u8; 3],
+    pub background: [u8; 3],
+    #[serde(default)]
+    pub align: Align,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl Caption {
+    // The parts that are set, in order, with a dot between each.
+    pub fn text(&self) -> String {
+        [Some(&self.title), self.date.as_ref(), self.edition.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("  \u{b7}  ")
+    }
+}
+
+// The image with the caption written on a margin added below it.
+pub fn apply(img: &RgbaImage, caption: &Caption) -> Result<RgbaImage, Error> {
+    let font = load(caption.font.as_deref())?;
+    let scale = PxScale::from(caption.size);
+    let scaled = font.as_scaled(scale);
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    let mut previous = None;
+    for c in caption.text().chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            x += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(x, scaled.ascent())));
+        x += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    let text_width = x.ceil() as u32;
+    let line_height = (scaled.ascent() - scaled.descent()).ceil() as u32;
+    let pad = caption.size.round() as u32;
+    let [r, g, b] = caption.background;
+    let mut out = RgbaImage::from_pixel(
+        img.width(),
+        img.height() + line_height + 2 * pad,
+        Rgba([r, g, b, 255]),
+    );
+    imageops::overlay(&mut out, img, 0, 0);
+    let left = match caption.align {
+        Align::Left => pad,
+        Align::Center => img.width().saturating_sub(text_width) / 2,
+        Align::Right => img.width().saturating_sub(text_width + pad),
+    } as f32;
+    let top = (img.height() + pad) as f32;
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = left + bounds.min.x + gx as f32;
+            let py = top + bounds.min.y + gy as f32;
+            if px < 0.0 || py < 0.0 || px >= out.width() as f32 || py >= out.height() as f32 {
+                return;
+            }
+            let pixel = out.get_pixel_mut(px as u32, py as u32);
+            for (c, ink) in pixel.0.iter_mut().zip(caption.color) {
+                *c = (*c as f32 * (1.0 - coverage) + ink as f32 * coverage).round() as u8;
+            }
+        });
+    }
+    Ok(out)
+}
+
+// Whether a font name is a bare file name, as captions need.
+pub fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+fn load(name: Option<&str>) -> Result<FontVec, Error> {
+    let names = match name {
+        Some(name) if is_file_name(name) => vec![name],
+        Some(name) => return Err(Error::FontNotFileName(name.to_string())),
+        None => DEFAULT_FONTS.to_vec(),
+    };
+    let path = names
+        .iter()
+        .flat_map(|name| {
+            FONT_FOLDERS
+                .iter()
+                .map(move |folder| Path::new(folder).join(name))
+        })
+        .find(|path| path.is_file())
+        .ok_or_else(|| Error::NoFont(names.join(" or ")))?;
+    let unopened = |reason: String| Error::Open {
+        path: path.display().to_string(),
+        reason,
+    };
+    let data = fs::read(&path).map_err(|err| unopened(err.to_string()))?;
+    FontVec::try_from_vec_and_index(data, 0).map_err(|err| unopened(err.to_string()))
+}
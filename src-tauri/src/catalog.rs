@@ -0,0 +1,108 @@
+use serde::Serialize;
+
+use crate::canvas_pool::CanvasPool;
+use crate::naming::style_name;
+use crate::options::DEFAULT_CELL;
+use crate::patterns::{self, TestPattern};
+use crate::planes::Planes;
+use crate::queue::Signal;
+use crate::render::generate;
+use crate::{RenderOptions, Style};
+
+// Size in source pixels of the pattern the samples are rendered from, and
+// the cell size used for them.
+const SAMPLE_SOURCE: u32 = 12;
+const SAMPLE_CELL: u32 = 6;
+
+// A small render of a style, as raw rgba.
+#[derive(Serialize)]
+pub struct Sample {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+// What the js side needs to offer a style.
+#[derive(Serialize)]
+pub struct StyleInfo {
+    // The value to send back in `RenderOptions::style`.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub defaults: RenderOptions,
+    pub sample: Sample,
+}
+
+fn describe(style: Style) -> (&'static str, &'static str) {
+    match style {
+        Style::Dots => ("Dots", "A dot in each cell sized by darkness."),
+        Style::VLines => (
+            "Vertical Lines",
+            "Vertical lines, more of them where it is dark.",
+        ),
+        Style::HLines => (
+            "Horizontal Lines",
+            "Horizontal lines, more of them where it is dark.",
+        ),
+        Style::Cross => (
+            "Crosshatch",
+            "Vertical and horizontal lines laid over each other.",
+        ),
+        Style::Stipple => ("Stipple", "Scattered points, denser where it is dark."),
+        Style::Grid => (
+            "Grid",
+            "Points on a regular grid that tightens where it is dark.",
+        ),
+        Style::Multi => ("Multi", "A different style for each hue of the source."),
+        Style::Tonal => (
+            "Tonal",
+            "A different style for the shadows, midtones and highlights.",
+        ),
+        Style::Patches => (
+            "Patches",
+            "Organic patches of different styles laid out by noise.",
+        ),
+    }
+}
+
+// Every style with a sample rendered from a gradient, or a color wheel for
+// Multi, which picks styles by hue.
+pub fn styles(pool: &rayon::ThreadPool, canvases: &CanvasPool) -> Vec<StyleInfo> {
+    Style::ALL
+        .iter()
+        .map(|&style| {
+            let (name, description) = describe(style);
+            let pattern = match style {
+                Style::Multi => TestPattern::ColorWheel,
+                _ => TestPattern::Gradient,
+            };
+            let options = RenderOptions {
+                cell: SAMPLE_CELL,
+                style,
+                ..Default::default()
+            };
+            let planes = Planes::new(
+                &patterns::generate(pattern, SAMPLE_SOURCE),
+                options.needs_hue(),
+            );
+            let img = generate(&planes, &options, &Signal::default(), pool, canvases)
+                .expect("An unsignalled render can not be interrupted");
+            let sample = Sample {
+                width: img.width(),
+                height: img.height(),
+                data: img.as_raw().clone(),
+            };
+            canvases.recycle_image(img);
+            StyleInfo {
+                id: style_name(style),
+                name,
+                description,
+                defaults: RenderOptions {
+                    cell: DEFAULT_CELL,
+                    ..options
+                },
+                sample,
+            }
+        })
+        .collect()
+}
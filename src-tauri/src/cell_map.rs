@@ -0,0 +1,82 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Problem;
+use crate::mask;
+
+// Largest cell, in source pixels, a region may ask for.
+pub const MAX_SIZE: u32 = 64;
+
+// Cell sizes by region, a coarse grid stretched over the source so the
+// subject can be fine and the background coarse. Each value is how many
+// source pixels a cell spans there, 1 is the usual grid.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CellMap {
+    pub width: u32,
+    pub height: u32,
+    pub sizes: Vec<u32>,
+}
+
+impl CellMap {
+    // The cell size over source pixel (x, y) of a `width` by `height`
+    // source.
+    pub fn size_at(&self, x: u32, y: u32, width: u32, height: u32) -> u32 {
+        self.sizes[mask::stretched(self.width, self.height, x, y, width, height)].max(1)
+    }
+
+    // Every size used, smallest first.
+    pub fn distinct(&self) -> Vec<u32> {
+        let mut sizes: Vec<u32> = self.sizes.iter().map(|&size| size.max(1)).collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes
+    }
+
+    // Why the map can't be used, if it can't.
+    pub fn problem(&self) -> Option<Problem> {
+        let len = self.width as usize * self.height as usize;
+        if self.width == 0 || self.height == 0 || self.sizes.len() != len {
+            return Some(Problem::MapSize {
+                width: self.width,
+                height: self.height,
+                sizes: self.sizes.len(),
+            });
+        }
+        self.sizes
+            .iter()
+            .find(|&&size| size > MAX_SIZE)
+            .map(|&size| Problem::SizeTooLarge {
+                max: MAX_SIZE,
+                size,
+            })
+    }
+}
+
+// Copy the pixels of `region`, a render at cell size `size`, into `out`
+// wherever the map asks for that size. `cell` is output pixels per source
+// pixel and `width` by `height` the size of the source.
+pub fn composite(
+    out: &mut RgbaImage,
+    region: &RgbaImage,
+    map: &CellMap,
+    size: u32,
+    cell: u32,
+    (width, height): (u32, u32),
+) {
+    let row = 4 * out.width() as usize;
+    let region_row = 4 * region.width() as usize;
+    out.as_mut()
+        .par_chunks_exact_mut(row)
+        .enumerate()
+        .for_each(|(oy, dst)| {
+            let sy = (oy as u32 / cell).min(height - 1);
+            let src = &region.as_raw()[oy * region_row..oy * region_row + row];
+            for (ox, (d, s)) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)).enumerate() {
+                let sx = (ox as u32 / cell).min(width - 1);
+                if map.size_at(sx, sy, width, height) == size {
+                    d.copy_from_slice(s);
+                }
+            }
+        });
+}
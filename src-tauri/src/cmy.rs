@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::{GradientMap, Stop};
+use crate::layout::Layout;
+use crate::planes::Planes;
+use crate::riso::Channel;
+use crate::{RenderOptions, Style};
+
+// The process inks and the angles of their screens in degrees, set apart
+// so the dots of the three screens don't beat against each other.
+const INKS: [(Channel, [u8; 3], f32); 3] = [
+    (Channel::Cyan, [0, 174, 239], 15.0),
+    (Channel::Magenta, [236, 0, 140], 45.0),
+    (Channel::Yellow, [255, 242, 0], 75.0),
+];
+
+// A faux color halftone: three passes of dots in cyan, magenta and
+// yellow, each on its own turned screen and multiplied together. Each ink
+// gets its share of the photo scaled by its density.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Cmy {
+    pub cyan: f32,
+    pub magenta: f32,
+    pub yellow: f32,
+}
+
+// The planes and options of each ink's pass, `None` when the planes can't
+// see the photo to separate. The passes draw plain dots on their screen
+// in the ink, positive and opaque so they multiply cleanly, and leave the
+// rest of the options as they are.
+pub fn passes(planes: &Planes, options: &RenderOptions) -> Option<Vec<(Planes, RenderOptions)>> {
+    let cmy = options.cmy?;
+    let photo = planes.photo.as_ref()?;
+    let densities = [cmy.cyan, cmy.magenta, cmy.yellow];
+    let passes = INKS
+        .iter()
+        .zip(densities)
+        .map(|(&(channel, ink, angle), density)| {
+            let luma = photo
+                .pixels()
+                .map(|px| (channel.darkness(px) * density).clamp(0.0, 1.0))
+                .collect();
+            let options = RenderOptions {
+                style: Style::Dots,
+                layout: Layout::Screen { angle },
+                layers: Vec::new(),
+                gradient_map: Some(GradientMap {
+                    stops: vec![Stop {
+                        at: 0.0,
+                        color: ink,
+                    }],
+                }),
+                cmy: None,
+                invert_output: false,
+                transparent: false,
+                ..options.clone()
+            };
+            (planes.with_luma(luma), options)
+        })
+        .collect();
+    Some(passes)
+}
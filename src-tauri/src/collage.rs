@@ -0,0 +1,135 @@
+use image::{imageops, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, FieldError, Problem};
+use crate::RenderOptions;
+
+// Bounds on a collage, so its canvas fits in memory.
+pub const MAX_PANELS: usize = 16;
+pub const MAX_SIDE: u32 = 20_000;
+
+// Several images, each rendered in its own style into its own rectangle
+// of one canvas, for diptychs, triptychs and the like.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Collage {
+    pub width: u32,
+    pub height: u32,
+    // Shows between and around the panels.
+    pub background: [u8; 3],
+    // Drawn in order, so later panels cover earlier ones where they meet.
+    pub panels: Vec<Panel>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Panel {
+    // The image file to render.
+    pub path: String,
+    pub rect: Rect,
+    pub options: RenderOptions,
+}
+
+// A rectangle of the canvas in output pixels.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Collage {
+    // The collage with every panel's options validated.
+    pub fn validate(mut self) -> Result<Self, Error> {
+        let mut errors = Vec::new();
+        let mut problem =
+            |field: String, problem: Problem| errors.push(FieldError { field, problem });
+        if !(1..=MAX_SIDE).contains(&self.width) || !(1..=MAX_SIDE).contains(&self.height) {
+            problem(
+                "collage".to_string(),
+                Problem::CanvasSize {
+                    max: MAX_SIDE,
+                    width: self.width,
+                    height: self.height,
+                },
+            );
+        }
+        if !(1..=MAX_PANELS).contains(&self.panels.len()) {
+            problem(
+                "collage.panels".to_string(),
+                Problem::Count {
+                    min: 1,
+                    max: MAX_PANELS,
+                    count: self.panels.len(),
+                },
+            );
+        }
+        let (width, height) = (self.width, self.height);
+        let mut panels = Vec::with_capacity(self.panels.len());
+        for (i, panel) in self.panels.into_iter().enumerate() {
+            let Rect {
+                x,
+                y,
+                width: w,
+                height: h,
+            } = panel.rect;
+            if w == 0 || h == 0 || x.saturating_add(w) > width || y.saturating_add(h) > height {
+                problem(
+                    format!("collage.panels[{}].rect", i),
+                    Problem::OffCanvas {
+                        x,
+                        y,
+                        width: w,
+                        height: h,
+                    },
+                );
+            }
+            match panel.options.validate() {
+                Ok(options) => panels.push(Panel { options, ..panel }),
+                Err(Error::InvalidOptions(fields)) => {
+                    for field in fields {
+                        problem(
+                            format!("collage.panels[{}].options.{}", i, field.field),
+                            field.problem,
+                        );
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        self.panels = panels;
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::InvalidOptions(errors))
+        }
+    }
+
+    pub fn canvas(&self) -> RgbaImage {
+        let [r, g, b] = self.background;
+        RgbaImage::from_pixel(self.width, self.height, Rgba([r, g, b, 255]))
+    }
+}
+
+// The image scaled so that rendered at `cell` it just covers `rect`.
+pub fn fit(img: &RgbaImage, rect: Rect, cell: u32) -> RgbaImage {
+    let cell = cell.max(1) as f32;
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let scale = (rect.width as f32 / cell / w).max(rect.height as f32 / cell / h);
+    let (sw, sh) = (
+        ((w * scale).ceil() as u32).max(1),
+        ((h * scale).ceil() as u32).max(1),
+    );
+    imageops::resize(img, sw, sh, imageops::FilterType::Lanczos3)
+}
+
+// Draw the middle of a render over `rect` of the canvas.
+pub fn place(canvas: &mut RgbaImage, render: &RgbaImage, rect: Rect) {
+    let (w, h) = (
+        rect.width.min(render.width()),
+        rect.height.min(render.height()),
+    );
+    let x0 = (render.width() - w) / 2;
+    let y0 = (render.height() - h) / 2;
+    let middle = imageops::crop_imm(render, x0, y0, w, h).to_image();
+    imageops::overlay(canvas, &middle, rect.x as i64, rect.y as i64);
+}
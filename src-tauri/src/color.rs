@@ -0,0 +1,213 @@
+use image::RgbaImage;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// A color at a position along a gradient.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Stop {
+    // Position in [0, 1], 0 is the darkest part of the source.
+    pub at: f32,
+    pub color: [u8; 3],
+}
+
+// Colors the marks by looking up the luminance of the source in a
+// gradient, so one style can carry a monochrome with an accent look.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GradientMap {
+    pub stops: Vec<Stop>,
+}
+
+impl GradientMap {
+    // The colors at 256 evenly spaced levels of lightness, looked up once
+    // per render rather than once per mark.
+    pub fn table(&self) -> Vec<[u8; 3]> {
+        let mut stops = self.stops.clone();
+        stops.sort_by(|a, b| a.at.total_cmp(&b.at));
+        (0..256)
+            .map(|i| color_at(&stops, i as f32 / 255.0))
+            .collect()
+    }
+
+    // The gradient `t` of the way from this one to `other`. Both are
+    // sampled at every stop either has, so maps with different stops still
+    // blend smoothly.
+    pub fn lerp(&self, other: &GradientMap, t: f32) -> GradientMap {
+        let mut a = self.stops.clone();
+        let mut b = other.stops.clone();
+        a.sort_by(|x, y| x.at.total_cmp(&y.at));
+        b.sort_by(|x, y| x.at.total_cmp(&y.at));
+        let mut positions: Vec<f32> = a.iter().chain(&b).map(|stop| stop.at).collect();
+        positions.sort_by(f32::total_cmp);
+        positions.dedup();
+        let stops = positions
+            .into_iter()
+            .map(|at| {
+                let (ca, cb) = (color_at(&a, at), color_at(&b, at));
+                Stop {
+                    at,
+                    color: std::array::from_fn(|c| {
+                        (ca[c] as f32 + (cb[c] as f32 - ca[c] as f32) * t).round() as u8
+                    }),
+                }
+            })
+            .collect();
+        GradientMap { stops }
+    }
+}
+
+// The color at `lightness` in [0, 1] along sorted stops, interpolated
+// between the nearest two and held flat beyond the first and last. Black
+// without stops.
+fn color_at(stops: &[Stop], lightness: f32) -> [u8; 3] {
+    let Some(first) = stops.first() else {
+        return [0, 0, 0];
+    };
+    if lightness <= first.at {
+        return first.color;
+    }
+    for pair in stops.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        if lightness <= b.at {
+            let k = if b.at > a.at {
+                (lightness - a.at) / (b.at - a.at)
+            } else {
+                1.0
+            };
+            return std::array::from_fn(|c| {
+                let (ca, cb) = (a.color[c] as f32, b.color[c] as f32);
+                (ca + (cb - ca) * k).round() as u8
+            });
+        }
+    }
+    stops[stops.len() - 1].color
+}
+
+// A named set of colors offered to the frontend, ordered dark to light so
+// they can be spread along a gradient map.
+#[derive(Clone, Serialize)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    // A gradient map with the colors spread evenly from dark to light.
+    pub fn gradient_map(&self) -> GradientMap {
+        let last = (self.colors.len().max(2) - 1) as f32;
+        GradientMap {
+            stops: self
+                .colors
+                .iter()
+                .enumerate()
+                .map(|(i, &color)| Stop {
+                    at: i as f32 / last,
+                    color,
+                })
+                .collect(),
+        }
+    }
+}
+
+const PALETTES: [(&str, [u32; 5]); 8] = [
+    ("Ink", [0x0b0c10, 0x1f2833, 0x45525e, 0x8d99a6, 0xe5e5e5]),
+    ("Sepia", [0x2b1d0e, 0x5c3d1e, 0x8b6b3d, 0xc8a97e, 0xf3e6cc]),
+    ("Ocean", [0x03045e, 0x023e8a, 0x0077b6, 0x48cae4, 0xcaf0f8]),
+    ("Sunset", [0x3d0c45, 0x8e1c4a, 0xd9534f, 0xf39c4a, 0xfde3a7]),
+    ("Forest", [0x102216, 0x2d4a2b, 0x55733c, 0x9bb069, 0xe3edc2]),
+    ("Riso", [0x1b1f5e, 0x0078bf, 0xff48b0, 0xffe800, 0xf7f2e8]),
+    (
+        "Bauhaus",
+        [0x111111, 0x1d4e89, 0xd7263d, 0xf4c430, 0xf2efe4],
+    ),
+    ("Ember", [0x1a0500, 0x6a1400, 0xc0392b, 0xf5820b, 0xffe8b0]),
+];
+
+pub fn palettes() -> Vec<Palette> {
+    PALETTES
+        .iter()
+        .map(|(name, colors)| Palette {
+            name,
+            colors: colors
+                .iter()
+                .map(|c| [(c >> 16) as u8, (c >> 8) as u8, *c as u8])
+                .collect(),
+        })
+        .collect()
+}
+
+// One of the built in palettes picked by `seed`, the same seed always picks
+// the same palette.
+pub fn random_palette(seed: u64) -> Palette {
+    let mut palettes = palettes();
+    let i = SmallRng::seed_from_u64(seed).gen_range(0..palettes.len());
+    palettes.swap_remove(i)
+}
+
+// Fixed so extracting from the same image always gives the same palette.
+const KMEANS_SEED: u64 = 4181;
+// Pixels sampled from the image for clustering.
+const KMEANS_SAMPLES: usize = 20_000;
+const KMEANS_ROUNDS: usize = 24;
+
+// The `n` dominant colors of an image by k-means clustering, ordered dark
+// to light. Fewer colors are returned if the image has fewer distinct ones.
+pub fn extract_palette(img: &RgbaImage, n: usize) -> Vec<[u8; 3]> {
+    let stride = (img.pixels().len() / KMEANS_SAMPLES).max(1);
+    let samples: Vec<[f32; 3]> = img
+        .pixels()
+        .step_by(stride)
+        .map(|px| [px[0] as f32, px[1] as f32, px[2] as f32])
+        .collect();
+    if samples.is_empty() || n == 0 {
+        return Vec::new();
+    }
+    let mut rng = SmallRng::seed_from_u64(KMEANS_SEED);
+    // k-means++: each new center is picked with probability proportional
+    // to its squared distance from the nearest center so far.
+    let mut centers = vec![samples[rng.gen_range(0..samples.len())]];
+    while centers.len() < n {
+        let weights: Vec<f32> = samples.iter().map(|s| nearest(&centers, s).1).collect();
+        let total: f32 = weights.iter().sum();
+        if total == 0.0 {
+            break;
+        }
+        let mut pick = rng.gen_range(0.0..total);
+        let i = weights
+            .iter()
+            .position(|w| {
+                pick -= w;
+                pick <= 0.0
+            })
+            .unwrap_or(samples.len() - 1);
+        centers.push(samples[i]);
+    }
+    for _ in 0..KMEANS_ROUNDS {
+        let mut sums = vec![([0.0; 3], 0); centers.len()];
+        for s in &samples {
+            let (i, _) = nearest(&centers, s);
+            let (sum, count) = &mut sums[i];
+            sum.iter_mut().zip(s).for_each(|(a, b)| *a += b);
+            *count += 1;
+        }
+        for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                *center = sum.map(|v| v / count as f32);
+            }
+        }
+    }
+    let mut colors: Vec<[u8; 3]> = centers.iter().map(|c| c.map(|v| v.round() as u8)).collect();
+    colors.sort_by_key(|[r, g, b]| 299 * *r as u32 + 587 * *g as u32 + 114 * *b as u32);
+    colors
+}
+
+// The index of the center closest to `s` and its squared distance.
+fn nearest(centers: &[[f32; 3]], s: &[f32; 3]) -> (usize, f32) {
+    centers
+        .iter()
+        .map(|c| (0..3).map(|i| (c[i] - s[i]).powi(2)).sum::<f32>())
+        .enumerate()
+        .fold(
+            (0, f32::INFINITY),
+            |best, (i, d)| if d < best.1 { (i, d) } else { best },
+        )
+}
@@ -0,0 +1,122 @@
+use image::{imageops, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::slic::to_lab;
+use crate::RenderOptions;
+
+// Side of the blocks SSIM is measured over.
+const WINDOW: u32 = 8;
+// Constants of SSIM for values from 0 to 1, which keep flat blocks from
+// dividing by nearly 0.
+const C1: f32 = 0.01 * 0.01;
+const C2: f32 = 0.03 * 0.03;
+// A color difference that shows at full heat, far past a just noticeable
+// one of about 2.3.
+const DELTA_E_FULL: f32 = 50.0;
+
+// One side of a comparison.
+#[derive(Clone, Deserialize)]
+pub enum Subject {
+    // The base image.
+    Source,
+    Render(RenderOptions),
+}
+
+// Which difference the heat map shows.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Metric {
+    // Structural dissimilarity of the lightness, blocks that lost their
+    // edges and texture.
+    Ssim,
+    // CIE76 color difference of each pixel.
+    DeltaE,
+}
+
+// How alike two images are.
+#[derive(Clone, Copy, Serialize)]
+pub struct Scores {
+    // Mean structural similarity, 1 for the same lightness.
+    pub ssim: f32,
+    // Mean color difference, 0 for the same colors.
+    pub delta_e: f32,
+}
+
+// The scores of `b` against `a` and a heat map of where they differ, black
+// where they match through red and yellow to white. `b` is scaled to the
+// size of `a`, and both are seen over white.
+pub fn compare(a: &RgbaImage, b: &RgbaImage, metric: Metric) -> (Scores, RgbaImage) {
+    let (width, height) = a.dimensions();
+    let scaled;
+    let b = if b.dimensions() == (width, height) {
+        b
+    } else {
+        scaled = imageops::resize(b, width, height, imageops::FilterType::Triangle);
+        &scaled
+    };
+    let lab = |img: &RgbaImage| -> Vec<[f32; 3]> {
+        img.pixels()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|px| to_lab(&over_white(px)))
+            .collect()
+    };
+    let (lab_a, lab_b) = (lab(a), lab(b));
+    let delta_e: Vec<f32> = lab_a
+        .par_iter()
+        .zip(&lab_b)
+        .map(|(p, q)| {
+            ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2) + (p[2] - q[2]).powi(2)).sqrt()
+        })
+        .collect();
+    let (columns, rows) = (width.div_ceil(WINDOW), height.div_ceil(WINDOW));
+    let blocks: Vec<f32> = (0..columns * rows)
+        .into_par_iter()
+        .map(|i| block_ssim(&lab_a, &lab_b, width, height, i % columns, i / columns))
+        .collect();
+    let count = delta_e.len().max(1) as f32;
+    let scores = Scores {
+        ssim: blocks.iter().sum::<f32>() / blocks.len().max(1) as f32,
+        delta_e: delta_e.iter().sum::<f32>() / count,
+    };
+    let map = RgbaImage::from_fn(width, height, |x, y| {
+        let t = match metric {
+            Metric::Ssim => 1.0 - blocks[((y / WINDOW) * columns + x / WINDOW) as usize],
+            Metric::DeltaE => delta_e[(y * width + x) as usize] / DELTA_E_FULL,
+        };
+        heat(t)
+    });
+    (scores, map)
+}
+
+// SSIM of the lightness of one block of the images.
+fn block_ssim(a: &[[f32; 3]], b: &[[f32; 3]], width: u32, height: u32, bx: u32, by: u32) -> f32 {
+    let (mut sa, mut sb, mut saa, mut sbb, mut sab, mut n) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for y in by * WINDOW..((by + 1) * WINDOW).min(height) {
+        for x in bx * WINDOW..((bx + 1) * WINDOW).min(width) {
+            let i = (y * width + x) as usize;
+            let (p, q) = (a[i][0] / 100.0, b[i][0] / 100.0);
+            sa += p;
+            sb += q;
+            saa += p * p;
+            sbb += q * q;
+            sab += p * q;
+            n += 1.0;
+        }
+    }
+    let (ma, mb) = (sa / n, sb / n);
+    let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+    ((2.0 * ma * mb + C1) * (2.0 * cov + C2)) / ((ma * ma + mb * mb + C1) * (va + vb + C2))
+}
+
+fn over_white(px: &Rgba<u8>) -> Rgba<u8> {
+    let a = px[3] as f32 / 255.0;
+    let c = |v: u8| (v as f32 * a + 255.0 * (1.0 - a)).round() as u8;
+    Rgba([c(px[0]), c(px[1]), c(px[2]), 255])
+}
+
+fn heat(t: f32) -> Rgba<u8> {
+    let t = 3.0 * t.clamp(0.0, 1.0);
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgba([c(t), c(t - 1.0), c(t - 2.0), 255])
+}
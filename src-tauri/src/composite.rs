@@ -0,0 +1,73 @@
+use image::{imageops, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Ways of combining an image with the one under it, as in image editors.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum BlendMode {
+    Multiply,
+    Screen,
+    // Multiply the darks and screen the lights of the bottom image.
+    Overlay,
+    // The darker of the two, how marks on white naturally stack.
+    #[default]
+    Darken,
+    Lighten,
+    Difference,
+}
+
+impl BlendMode {
+    // One channel of `top` blended onto `bottom`.
+    pub fn channel(self, bottom: u8, top: u8) -> u8 {
+        let (b, t) = (bottom as u32, top as u32);
+        let blended = match self {
+            BlendMode::Multiply => b * t / 255,
+            BlendMode::Screen => 255 - (255 - b) * (255 - t) / 255,
+            BlendMode::Overlay if b < 128 => 2 * b * t / 255,
+            BlendMode::Overlay => 255 - 2 * (255 - b) * (255 - t) / 255,
+            BlendMode::Darken => b.min(t),
+            BlendMode::Lighten => b.max(t),
+            BlendMode::Difference => b.abs_diff(t),
+        };
+        blended as u8
+    }
+}
+
+// How the source photo shows through a render.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SourceBlend {
+    pub mode: BlendMode,
+    // How much of the photo shows, in [0, 1].
+    pub opacity: f32,
+}
+
+// Blend the color channels of `top` onto `bottom` with `mode`, mixed back
+// with `bottom` by `opacity` in [0, 1]. The images must be the same size,
+// the alpha of `bottom` is kept.
+pub fn composite(bottom: &mut RgbaImage, top: &RgbaImage, mode: BlendMode, opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    bottom
+        .as_mut()
+        .par_chunks_exact_mut(4)
+        .zip(top.as_raw().par_chunks_exact(4))
+        .for_each(|(b, t)| {
+            for c in 0..3 {
+                let blended = mode.channel(b[c], t[c]) as f32;
+                b[c] = (b[c] as f32 + (blended - b[c] as f32) * opacity).round() as u8;
+            }
+        });
+}
+
+// Blend the source photo, stretched to the size of the render, onto it.
+pub fn blend_source(img: &mut RgbaImage, source: &RgbaImage, blend: SourceBlend) {
+    if blend.opacity <= 0.0 || source.width() == 0 || source.height() == 0 {
+        return;
+    }
+    let photo = imageops::resize(
+        source,
+        img.width(),
+        img.height(),
+        imageops::FilterType::Triangle,
+    );
+    composite(img, &photo, blend.mode, blend.opacity);
+}
@@ -0,0 +1,97 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::planes::Planes;
+
+// How much of a render is ink, to judge how long a pen lasts or a plot
+// takes before committing to it. Percentages in [0, 100], where a pixel
+// of solid ink counts fully and a light one in part.
+#[derive(Serialize)]
+pub struct CoverageStats {
+    pub total: f32,
+    // One entry per color of the label mask, empty without one.
+    pub regions: Vec<RegionCoverage>,
+}
+
+#[derive(Serialize)]
+pub struct RegionCoverage {
+    pub color: [u8; 3],
+    pub coverage: f32,
+    // How much of the canvas the region takes up.
+    pub area: f32,
+}
+
+// The coverage of a render of `planes` at `cell` output pixels per source
+// pixel. Ink is dark on white, or light on black for a negative.
+pub fn stats(img: &RgbaImage, planes: &Planes, cell: u32, negative: bool) -> CoverageStats {
+    let width = img.width() as usize;
+    let regions = planes
+        .labels
+        .as_ref()
+        .map_or(0, |labels| labels.colors.len());
+    // Ink and pixel count for each region, and for the whole at the end.
+    let sums = if width == 0 {
+        vec![(0.0, 0u64); regions + 1]
+    } else {
+        img.as_raw()
+            .par_chunks_exact(4 * width)
+            .enumerate()
+            .fold(
+                || vec![(0.0f64, 0u64); regions + 1],
+                |mut sums, (y, row)| {
+                    let sy = (y as u32 / cell).min(planes.height - 1);
+                    for (x, px) in row.chunks_exact(4).enumerate() {
+                        let lightest = px[0].max(px[1]).max(px[2]);
+                        let darkest = px[0].min(px[1]).min(px[2]);
+                        let ink = if negative {
+                            lightest as f64 / 255.0
+                        } else {
+                            1.0 - darkest as f64 / 255.0
+                        };
+                        if let Some(labels) = &planes.labels {
+                            let sx = (x as u32 / cell).min(planes.width - 1);
+                            let label = labels.label_at(sx, sy, planes.width, planes.height);
+                            let sum = &mut sums[label as usize];
+                            sum.0 += ink;
+                            sum.1 += 1;
+                        }
+                        sums[regions].0 += ink;
+                        sums[regions].1 += 1;
+                    }
+                    sums
+                },
+            )
+            .reduce(
+                || vec![(0.0, 0); regions + 1],
+                |mut a, b| {
+                    for (a, b) in a.iter_mut().zip(b) {
+                        a.0 += b.0;
+                        a.1 += b.1;
+                    }
+                    a
+                },
+            )
+    };
+    let percent = |(ink, count): (f64, u64)| {
+        if count == 0 {
+            0.0
+        } else {
+            (100.0 * ink / count as f64) as f32
+        }
+    };
+    let pixels = sums[regions].1.max(1) as f32;
+    CoverageStats {
+        total: percent(sums[regions]),
+        regions: planes
+            .labels
+            .iter()
+            .flat_map(|labels| labels.colors.iter().zip(&sums))
+            .map(|(color, &sum)| RegionCoverage {
+                color: *color,
+                coverage: percent(sum),
+                area: 100.0 * sum.1 as f32 / pixels,
+            })
+            .collect(),
+    }
+}
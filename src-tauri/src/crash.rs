@@ -0,0 +1,96 @@
+use serde::Serialize;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use seg_core::RenderOptions;
+
+// The options of the render last started, for the report of a panic in it.
+static LAST_OPTIONS: Mutex<Option<RenderOptions>> = Mutex::new(None);
+
+// Sent to the js side as a "backend-panic" event, to tell the user where
+// the report is instead of failing silently.
+#[derive(Clone, Serialize)]
+struct Crash {
+    message: String,
+    report: Option<String>,
+}
+
+// What is written to the crash reports folder of the app data dir.
+#[derive(Serialize)]
+struct Report<'a> {
+    message: &'a str,
+    location: Option<String>,
+    thread: Option<String>,
+    app_version: String,
+    tauri_version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    options: Option<RenderOptions>,
+    backtrace: String,
+}
+
+// Report panics on any thread, then carry on to the default hook so they
+// are still printed.
+pub(crate) fn install(app: tauri::AppHandle, dir: PathBuf) {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|text| text.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Unknown panic".to_string());
+        let location = info
+            .location()
+            .map(|at| format!("{}:{}:{}", at.file(), at.line(), at.column()));
+        let report = match write_report(&app, &dir, &message, location) {
+            Ok(path) => Some(path.to_string_lossy().into_owned()),
+            Err(err) => {
+                eprintln!("The crash report could not be saved: {}", err);
+                None
+            }
+        };
+        crate::emit(&app, "backend-panic", Crash { message, report });
+        default(info);
+    }));
+}
+
+// Keep the options of a render about to start for the report.
+pub(crate) fn note(options: &RenderOptions) {
+    // A panic while the lock was held leaves old options, good enough.
+    if let Ok(mut last) = LAST_OPTIONS.lock() {
+        *last = Some(options.clone());
+    }
+}
+
+fn write_report(
+    app: &tauri::AppHandle,
+    dir: &Path,
+    message: &str,
+    location: Option<String>,
+) -> Result<PathBuf, String> {
+    let dir = dir.join("crash-reports");
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let report = Report {
+        message,
+        location,
+        thread: std::thread::current().name().map(str::to_string),
+        app_version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        options: LAST_OPTIONS.lock().ok().and_then(|last| last.clone()),
+        backtrace: Backtrace::force_capture().to_string(),
+    };
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let path = dir.join(format!("crash-{}.json", seconds));
+    let json = serde_json::to_string_pretty(&report).map_err(|err| err.to_string())?;
+    fs::write(&path, json).map_err(|err| err.to_string())?;
+    Ok(path)
+}
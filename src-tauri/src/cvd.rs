@@ -0,0 +1,75 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Kinds of color vision deficiency, each missing one of the three cones.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Cvd {
+    // No red cones.
+    Protanopia,
+    // No green cones, the most common.
+    Deuteranopia,
+    // No blue cones.
+    Tritanopia,
+}
+
+impl Cvd {
+    // From Machado, Oliveira and Fernandes 2009 at full severity, on
+    // linear RGB.
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Cvd::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Cvd::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Cvd::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+// Change the colors of the image to how they look to someone with `cvd`.
+// Alpha is kept.
+pub fn simulate(img: &mut RgbaImage, cvd: Cvd) {
+    let m = cvd.matrix();
+    let linear: Vec<f32> = (0..=255u8).map(to_linear).collect();
+    img.par_chunks_mut(4).for_each(|px| {
+        let rgb = [
+            linear[px[0] as usize],
+            linear[px[1] as usize],
+            linear[px[2] as usize],
+        ];
+        for (c, row) in px.iter_mut().zip(m) {
+            let v = row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2];
+            *c = to_srgb(v);
+        }
+    });
+}
+
+fn to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let c = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
@@ -0,0 +1,96 @@
+use image::{Rgba, RgbaImage};
+
+use crate::canvas_pool::CanvasPool;
+use crate::planes::Planes;
+use crate::queue::{Interrupt, Signal};
+use crate::render::{hue_bucket, BACKGROUND};
+use crate::RenderOptions;
+
+// Tints for the Multi hue buckets, red through purple.
+const BUCKET_COLORS: [[u8; 3]; 6] = [
+    [220, 50, 50],
+    [240, 140, 40],
+    [230, 210, 40],
+    [60, 170, 70],
+    [50, 100, 220],
+    [150, 60, 200],
+];
+
+const GRID_COLOR: [u8; 3] = [90, 90, 90];
+
+// Digits 0 to 9 on a 3 by 5 grid, one row of 3 bits per entry.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// Draw what the renderer sees in each cell of the grid: the source dimmed
+// and tinted by its Multi hue bucket, the cell outlines and, when the
+// cells are big enough to read, darkness as a percentage from 0 to 99.
+pub fn overlay(
+    planes: &Planes,
+    options: &RenderOptions,
+    signal: &Signal,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    let cell = options.cell;
+    let mut img = canvases.image(cell * planes.width, cell * planes.height, BACKGROUND);
+    // Two digits and a one digit gap, each 3 units wide, plus a margin.
+    let scale = (cell / 12).max(1);
+    let labels = 7 * scale + 2 <= cell;
+    for y in 0..planes.height {
+        if let Err(err) = signal.check() {
+            canvases.recycle_image(img);
+            return Err(err);
+        }
+        for x in 0..planes.width {
+            let t = planes.t(x, y);
+            // Halfway to white so the grid and labels stand out.
+            let gray = 255.0 - 127.5 * t;
+            let tint = BUCKET_COLORS[hue_bucket(planes.hue(x, y))];
+            let fill = tint.map(|c| (0.65 * gray + 0.35 * c as f32).round() as u8);
+            let (x0, y0) = (x * cell, y * cell);
+            for py in y0..y0 + cell {
+                for px in x0..x0 + cell {
+                    let edge = px == x0 || py == y0;
+                    let [r, g, b] = if edge && cell > 2 { GRID_COLOR } else { fill };
+                    img.put_pixel(px, py, Rgba([r, g, b, 255]));
+                }
+            }
+            if labels {
+                let percent = ((t * 100.0) as u32).min(99);
+                for (i, digit) in [percent / 10, percent % 10].into_iter().enumerate() {
+                    let left = x0 + 2 + 4 * scale * i as u32;
+                    draw_digit(&mut img, digit as usize, left, y0 + 2, scale);
+                }
+            }
+        }
+    }
+    Ok(img)
+}
+
+fn draw_digit(img: &mut RgbaImage, digit: usize, left: u32, top: u32, scale: u32) {
+    for (row, bits) in DIGITS[digit].iter().enumerate() {
+        for col in 0..3 {
+            if bits & (0b100 >> col) == 0 {
+                continue;
+            }
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let (px, py) = (left + col * scale + dx, top + row as u32 * scale + dy);
+                    if px < img.width() && py < img.height() {
+                        img.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+        }
+    }
+}
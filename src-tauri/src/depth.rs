@@ -0,0 +1,162 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::cell_map::CellMap;
+use crate::mask::{self, DensityMap};
+use crate::planes::Planes;
+use crate::Style;
+
+// Source pixels per value of the cell map made from a depth map.
+const MAP_STEP: u32 = 4;
+
+// How near each part of the source is, as saved by the portrait mode of a
+// phone or by a depth estimator like MiDaS: white is nearest, black is
+// farthest. Stretched over the source like a mask.
+pub struct DepthMap {
+    pub width: u32,
+    pub height: u32,
+    data: Vec<u8>,
+}
+
+impl DepthMap {
+    pub fn from_image(img: &RgbaImage) -> DepthMap {
+        let data = img
+            .pixels()
+            .map(|px| {
+                (0.2989 * px[0] as f32 + 0.5870 * px[1] as f32 + 0.1140 * px[2] as f32).round()
+                    as u8
+            })
+            .collect();
+        DepthMap {
+            width: img.width(),
+            height: img.height(),
+            data,
+        }
+    }
+
+    // The nearness in [0, 1] over source pixel (x, y) of a `width` by
+    // `height` source, 1 is nearest.
+    pub fn near_at(&self, x: u32, y: u32, width: u32, height: u32) -> f32 {
+        self.data[mask::stretched(self.width, self.height, x, y, width, height)] as f32 / 255.0
+    }
+
+    // The nearness of each pixel from 0 to 255, row by row.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+// Treat the foreground and background of the source differently, like
+// crisp stipple on the subject and sparse dots behind it. Densities and
+// cell sizes go smoothly from the nearest to the farthest depth.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DepthOptions {
+    // Darkness multipliers at the nearest and farthest depth.
+    #[serde(default = "one")]
+    pub near_density: f32,
+    #[serde(default = "one")]
+    pub far_density: f32,
+    // Cell sizes at the nearest and farthest depth, in source pixels like
+    // the sizes of a cell map.
+    #[serde(default = "one_cell")]
+    pub near_cell: u32,
+    #[serde(default = "one_cell")]
+    pub far_cell: u32,
+    // Styles in place of `style` nearer and farther than `split`.
+    pub near_style: Option<Style>,
+    pub far_style: Option<Style>,
+    // Nearness in [0, 1] that divides the foreground from the background.
+    #[serde(default = "half")]
+    pub split: f32,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn one_cell() -> u32 {
+    1
+}
+
+fn half() -> f32 {
+    0.5
+}
+
+impl DepthOptions {
+    // Whether the densities or cell sizes change with depth, which is done
+    // to the planes and options before drawing.
+    pub fn modulates(&self) -> bool {
+        self.near_density != 1.0
+            || self.far_density != 1.0
+            || self.near_cell > 1
+            || self.far_cell > 1
+    }
+
+    // The options with only the styles left, for once the densities and
+    // cell sizes have been applied.
+    pub fn styles_only(&self) -> DepthOptions {
+        DepthOptions {
+            near_density: 1.0,
+            far_density: 1.0,
+            near_cell: 1,
+            far_cell: 1,
+            ..self.clone()
+        }
+    }
+
+    // The style for a cell at nearness `near`, if the depth picks one.
+    pub fn style(&self, near: f32) -> Option<Style> {
+        if near >= self.split {
+            self.near_style
+        } else {
+            self.far_style
+        }
+    }
+}
+
+// The planes with their darkness multiplied by the density at each depth.
+pub fn apply_density(
+    planes: &Planes,
+    depth: &DepthMap,
+    options: &DepthOptions,
+    invert_output: bool,
+) -> Planes {
+    let data = depth
+        .data
+        .iter()
+        .map(|&d| {
+            let near = d as f32 / 255.0;
+            let density = options.far_density + (options.near_density - options.far_density) * near;
+            density.max(0.0)
+        })
+        .collect();
+    let map = DensityMap {
+        width: depth.width,
+        height: depth.height,
+        data,
+    };
+    mask::apply_density(planes, &map, invert_output)
+}
+
+// Cell sizes going from `near_cell` to `far_cell` with depth over a
+// `width` by `height` source.
+pub fn cell_map(depth: &DepthMap, options: &DepthOptions, width: u32, height: u32) -> CellMap {
+    let (map_width, map_height) = (width.div_ceil(MAP_STEP), height.div_ceil(MAP_STEP));
+    let (near_cell, far_cell) = (options.near_cell as f32, options.far_cell as f32);
+    let sizes = (0..map_height)
+        .flat_map(|y| (0..map_width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (sx, sy) = (
+                (x * MAP_STEP + MAP_STEP / 2).min(width - 1),
+                (y * MAP_STEP + MAP_STEP / 2).min(height - 1),
+            );
+            let near = depth.near_at(sx, sy, width, height);
+            (far_cell + (near_cell - far_cell) * near).round().max(1.0) as u32
+        })
+        .collect();
+    CellMap {
+        width: map_width,
+        height: map_height,
+        sizes,
+    }
+}
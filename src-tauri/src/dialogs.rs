@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+use tauri_plugin_dialog::{DialogExt, FileDialogBuilder, FilePath};
+
+use seg_core::error::Error;
+use seg_core::messages::Message;
+
+use crate::{scope, State};
+
+// Image types that can be opened as a base, secondary or paper image.
+const INPUT_EXTENSIONS: [&str; 5] = ["png", "jpeg", "jpg", "tiff", "webp"];
+
+// Ask for an image to open, starting in the folder of the last file picked.
+// `None` if the dialog was cancelled. The dialogs block until closed, so
+// the commands are async to keep them off the main thread. The file picked
+// is added to the scope for the commands that open or save it.
+#[tauri::command]
+pub(crate) async fn pick_input_file(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<Option<String>, Message> {
+    let Some(path) = builder(&app, &state)
+        .add_filter("Images", &INPUT_EXTENSIONS)
+        .blocking_pick_file()
+        .and_then(local_path)
+    else {
+        return Ok(None);
+    };
+    if !path.is_file() {
+        return Err(Error::Open {
+            path: path.display().to_string(),
+            reason: "it is not a file".to_string(),
+        }
+        .into());
+    }
+    check_extension(&path, &INPUT_EXTENSIONS)?;
+    remember(&state, &path);
+    scope::allow(&app, &path);
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+// Ask where to save a render. Formats without alpha are not offered for a
+// transparent render, and a name without an extension is saved as png.
+#[tauri::command]
+pub(crate) async fn pick_save_path(
+    default_name: Option<String>,
+    transparent: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<Option<String>, Message> {
+    let extensions: &[&str] = if transparent.unwrap_or(false) {
+        &["png"]
+    } else {
+        &["png", "jpeg", "jpg"]
+    };
+    let Some(mut path) = builder(&app, &state)
+        .set_file_name(default_name.as_deref().unwrap_or("seg.png"))
+        .add_filter("Images", extensions)
+        .blocking_save_file()
+        .and_then(local_path)
+    else {
+        return Ok(None);
+    };
+    if path.extension().is_none() {
+        path.set_extension("png");
+    }
+    check_extension(&path, extensions)?;
+    if let Some(parent) = path.parent().filter(|p| !p.is_dir()) {
+        return Err(Error::NoFolder(parent.display().to_string()).into());
+    }
+    remember(&state, &path);
+    scope::allow(&app, &path);
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+fn builder(app: &tauri::AppHandle, state: &State) -> FileDialogBuilder<tauri::Wry> {
+    // Before a file has been picked, the export folder from the settings.
+    let dir = state
+        .last_dir
+        .lock()
+        .expect("Could not lock state mutex")
+        .clone()
+        .or_else(|| {
+            state
+                .settings
+                .read()
+                .expect("Could not lock state mutex")
+                .export_folder
+                .clone()
+        });
+    match dir {
+        Some(dir) => app.dialog().file().set_directory(dir),
+        None => app.dialog().file(),
+    }
+}
+
+// Desktop dialogs always pick a path, only mobile ones give a url.
+fn local_path(picked: FilePath) -> Option<PathBuf> {
+    picked.into_path().ok()
+}
+
+// The folder of a picked file is where the next dialog opens, and one the
+// user has approved for the sandbox.
+fn remember(state: &State, path: &Path) {
+    if let Some(parent) = path.parent() {
+        *state.last_dir.lock().expect("Could not lock state mutex") = Some(parent.to_path_buf());
+        state
+            .sandbox
+            .write()
+            .expect("Could not lock state mutex")
+            .approve(parent);
+    }
+}
+
+fn check_extension(path: &Path, allowed: &[&str]) -> Result<(), Error> {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if allowed.contains(&ext.as_str()) {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedFormat(path.display().to_string()))
+    }
+}
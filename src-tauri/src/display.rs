@@ -0,0 +1,466 @@
+use serde::Serialize;
+use std::fmt::Write;
+use wassily::prelude::*;
+
+use crate::units::Page;
+use crate::{dxf, gcode, pdf, svg};
+
+// A shape drawn by a style, in output pixels, with its color as RGBA.
+// Styles add these to a display list, which the backends then draw as
+// pixels, SVG, PDF or G-code.
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Primitive {
+    // A filled disc.
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+        color: [u8; 4],
+    },
+    // A stroked circle, `weight` wide.
+    Ring {
+        x: f32,
+        y: f32,
+        radius: f32,
+        weight: f32,
+        color: [u8; 4],
+    },
+    // A filled polygon.
+    Polygon {
+        points: Vec<[f32; 2]>,
+        color: [u8; 4],
+    },
+    Line {
+        from: [f32; 2],
+        to: [f32; 2],
+        weight: f32,
+        color: [u8; 4],
+    },
+    // A stroked path of cubic Béziers from `start`, each segment its two
+    // control points and end point, made by smoothing joined lines.
+    Curve {
+        start: [f32; 2],
+        segments: Vec<[[f32; 2]; 3]>,
+        weight: f32,
+        color: [u8; 4],
+    },
+    // A single pixel.
+    Dot {
+        x: f32,
+        y: f32,
+        color: [u8; 4],
+    },
+}
+
+// Points along a Bézier path, for backends that only draw straight
+// lines.
+pub fn flatten(start: [f32; 2], segments: &[[[f32; 2]; 3]]) -> Vec<[f32; 2]> {
+    // Enough for the short curves the smoothing makes to look smooth.
+    const STEPS: usize = 8;
+    let mut points = vec![start];
+    let mut p0 = start;
+    for [p1, p2, p3] in segments {
+        for i in 1..=STEPS {
+            let t = i as f32 / STEPS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            points.push([
+                a * p0[0] + b * p1[0] + c * p2[0] + d * p3[0],
+                a * p0[1] + b * p1[1] + c * p2[1] + d * p3[1],
+            ]);
+        }
+        p0 = *p3;
+    }
+    points
+}
+
+// The RGBA bytes of a color.
+pub fn rgba(color: Color) -> [u8; 4] {
+    let c = color.to_color_u8();
+    [c.red(), c.green(), c.blue(), c.alpha()]
+}
+
+impl Primitive {
+    pub fn color(&self) -> [u8; 4] {
+        match self {
+            Primitive::Circle { color, .. }
+            | Primitive::Ring { color, .. }
+            | Primitive::Polygon { color, .. }
+            | Primitive::Line { color, .. }
+            | Primitive::Curve { color, .. }
+            | Primitive::Dot { color, .. } => *color,
+        }
+    }
+
+    // The primitive moved by (dx, dy).
+    pub fn translated(self, dx: f32, dy: f32) -> Primitive {
+        let at = |[x, y]: [f32; 2]| [x + dx, y + dy];
+        match self {
+            Primitive::Circle {
+                x,
+                y,
+                radius,
+                color,
+            } => Primitive::Circle {
+                x: x + dx,
+                y: y + dy,
+                radius,
+                color,
+            },
+            Primitive::Ring {
+                x,
+                y,
+                radius,
+                weight,
+                color,
+            } => Primitive::Ring {
+                x: x + dx,
+                y: y + dy,
+                radius,
+                weight,
+                color,
+            },
+            Primitive::Polygon { points, color } => Primitive::Polygon {
+                points: points.into_iter().map(at).collect(),
+                color,
+            },
+            Primitive::Line {
+                from,
+                to,
+                weight,
+                color,
+            } => Primitive::Line {
+                from: at(from),
+                to: at(to),
+                weight,
+                color,
+            },
+            Primitive::Curve {
+                start,
+                segments,
+                weight,
+                color,
+            } => Primitive::Curve {
+                start: at(start),
+                segments: segments.into_iter().map(|s| s.map(at)).collect(),
+                weight,
+                color,
+            },
+            Primitive::Dot { x, y, color } => Primitive::Dot {
+                x: x + dx,
+                y: y + dy,
+                color,
+            },
+        }
+    }
+
+    // The primitive with each of its points moved by `f`, which has to keep
+    // distances, like a turn or a mirror, for radii and weights to stay
+    // right.
+    pub fn mapped(self, f: impl Fn([f32; 2]) -> [f32; 2]) -> Primitive {
+        match self {
+            Primitive::Circle {
+                x,
+                y,
+                radius,
+                color,
+            } => {
+                let [x, y] = f([x, y]);
+                Primitive::Circle {
+                    x,
+                    y,
+                    radius,
+                    color,
+                }
+            }
+            Primitive::Ring {
+                x,
+                y,
+                radius,
+                weight,
+                color,
+            } => {
+                let [x, y] = f([x, y]);
+                Primitive::Ring {
+                    x,
+                    y,
+                    radius,
+                    weight,
+                    color,
+                }
+            }
+            Primitive::Polygon { points, color } => Primitive::Polygon {
+                points: points.into_iter().map(&f).collect(),
+                color,
+            },
+            Primitive::Line {
+                from,
+                to,
+                weight,
+                color,
+            } => Primitive::Line {
+                from: f(from),
+                to: f(to),
+                weight,
+                color,
+            },
+            Primitive::Curve {
+                start,
+                segments,
+                weight,
+                color,
+            } => Primitive::Curve {
+                start: f(start),
+                segments: segments.into_iter().map(|s| s.map(&f)).collect(),
+                weight,
+                color,
+            },
+            Primitive::Dot { x, y, color } => {
+                let [x, y] = f([x, y]);
+                Primitive::Dot { x, y, color }
+            }
+        }
+    }
+
+    // The left, top, right and bottom of the area the primitive draws on.
+    pub fn bounds(&self) -> [f32; 4] {
+        let around = |points: &mut dyn Iterator<Item = [f32; 2]>, pad: f32| {
+            let [x0, y0, x1, y1] = points.fold(
+                [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+                |[x0, y0, x1, y1], [x, y]| [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+            );
+            [x0 - pad, y0 - pad, x1 + pad, y1 + pad]
+        };
+        match self {
+            Primitive::Circle { x, y, radius, .. } => {
+                [x - radius, y - radius, x + radius, y + radius]
+            }
+            Primitive::Ring {
+                x,
+                y,
+                radius,
+                weight,
+                ..
+            } => {
+                let r = radius + weight / 2.0;
+                [x - r, y - r, x + r, y + r]
+            }
+            Primitive::Polygon { points, .. } => around(&mut points.iter().copied(), 0.0),
+            Primitive::Line {
+                from, to, weight, ..
+            } => around(&mut [*from, *to].into_iter(), weight / 2.0),
+            // A Bézier stays inside the hull of its control points.
+            Primitive::Curve {
+                start,
+                segments,
+                weight,
+                ..
+            } => around(
+                &mut std::iter::once(*start).chain(segments.iter().flatten().copied()),
+                weight / 2.0,
+            ),
+            Primitive::Dot { x, y, .. } => [*x, *y, x + 1.0, y + 1.0],
+        }
+    }
+
+    // Where the primitive is, for deciding which side of a line it is on:
+    // the center of a circle or ring, the mean of a polygon's points, the
+    // middle of a line and the start of a curve.
+    pub fn anchor(&self) -> [f32; 2] {
+        match self {
+            Primitive::Circle { x, y, .. }
+            | Primitive::Ring { x, y, .. }
+            | Primitive::Dot { x, y, .. } => [*x, *y],
+            Primitive::Polygon { points, .. } => {
+                let n = points.len().max(1) as f32;
+                let [sx, sy] = points
+                    .iter()
+                    .fold([0.0, 0.0], |[sx, sy], [x, y]| [sx + x, sy + y]);
+                [sx / n, sy / n]
+            }
+            Primitive::Line { from, to, .. } => [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0],
+            Primitive::Curve { start, .. } => *start,
+        }
+    }
+
+    fn color_mut(&mut self) -> &mut [u8; 4] {
+        match self {
+            Primitive::Circle { color, .. }
+            | Primitive::Ring { color, .. }
+            | Primitive::Polygon { color, .. }
+            | Primitive::Line { color, .. }
+            | Primitive::Curve { color, .. }
+            | Primitive::Dot { color, .. } => color,
+        }
+    }
+
+    // Flip the color, for the marks of a negative which are drawn for
+    // lightness and flipped once rendered.
+    pub fn invert(&mut self) {
+        let color = self.color_mut();
+        for c in color.iter_mut().take(3) {
+            *c = 255 - *c;
+        }
+    }
+}
+
+// The marks of a render and the size of the output they were drawn for.
+#[derive(Serialize)]
+pub struct DisplayList {
+    pub width: u32,
+    pub height: u32,
+    #[serde(rename = "marks")]
+    pub primitives: Vec<Primitive>,
+}
+
+// File types a display list can be saved as.
+#[derive(Clone, Copy)]
+pub enum VectorFormat {
+    // The primitives as data, for p5.js, Blender and the like.
+    Json,
+    Csv,
+    // Drawings for other apps and machines.
+    Svg,
+    Pdf,
+    Gcode,
+    // Line drawings for laser cutters.
+    Dxf,
+}
+
+impl VectorFormat {
+    // The format for a file extension, `None` if it is not one.
+    pub fn from_extension(extension: &str) -> Option<VectorFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(VectorFormat::Json),
+            "csv" => Some(VectorFormat::Csv),
+            "svg" => Some(VectorFormat::Svg),
+            "pdf" => Some(VectorFormat::Pdf),
+            "gcode" | "nc" => Some(VectorFormat::Gcode),
+            "dxf" => Some(VectorFormat::Dxf),
+            _ => None,
+        }
+    }
+}
+
+impl DisplayList {
+    // The file contents of the list in `format`. A `background` of `None`
+    // leaves the drawing formats transparent. The drawing formats are laid
+    // out on `page` when there is one, the data formats stay in output
+    // pixels.
+    pub fn encode(
+        &self,
+        format: VectorFormat,
+        background: Option<[u8; 4]>,
+        page: Option<&Page>,
+    ) -> Vec<u8> {
+        match format {
+            VectorFormat::Json => self.to_json().into_bytes(),
+            VectorFormat::Csv => self.to_csv().into_bytes(),
+            VectorFormat::Svg => svg::write(self, background, page).into_bytes(),
+            VectorFormat::Pdf => pdf::write(self, background, page),
+            VectorFormat::Gcode => gcode::write(self, page).into_bytes(),
+            VectorFormat::Dxf => dxf::write(self, page).into_bytes(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("A display list is always serializable")
+    }
+
+    // One row per mark. Columns a kind does not use are left empty, the
+    // points of a polygon are space separated x;y pairs, as are the start
+    // and then the control and end points of each segment of a curve.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,x,y,x2,y2,radius,weight,red,green,blue,alpha,points\n");
+        for mark in &self.primitives {
+            let (kind, numbers, color, points) = match mark {
+                Primitive::Circle {
+                    x,
+                    y,
+                    radius,
+                    color,
+                } => (
+                    "circle",
+                    [Some(*x), Some(*y), None, None, Some(*radius), None],
+                    color,
+                    String::new(),
+                ),
+                Primitive::Ring {
+                    x,
+                    y,
+                    radius,
+                    weight,
+                    color,
+                } => (
+                    "ring",
+                    [Some(*x), Some(*y), None, None, Some(*radius), Some(*weight)],
+                    color,
+                    String::new(),
+                ),
+                Primitive::Polygon { points, color } => (
+                    "polygon",
+                    [None; 6],
+                    color,
+                    points
+                        .iter()
+                        .map(|[x, y]| format!("{};{}", x, y))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                Primitive::Line {
+                    from,
+                    to,
+                    weight,
+                    color,
+                } => (
+                    "line",
+                    [
+                        Some(from[0]),
+                        Some(from[1]),
+                        Some(to[0]),
+                        Some(to[1]),
+                        None,
+                        Some(*weight),
+                    ],
+                    color,
+                    String::new(),
+                ),
+                Primitive::Curve {
+                    start,
+                    segments,
+                    weight,
+                    color,
+                } => (
+                    "curve",
+                    [None, None, None, None, None, Some(*weight)],
+                    color,
+                    std::iter::once(start)
+                        .chain(segments.iter().flatten())
+                        .map(|[x, y]| format!("{};{}", x, y))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                Primitive::Dot { x, y, color } => (
+                    "dot",
+                    [Some(*x), Some(*y), None, None, None, None],
+                    color,
+                    String::new(),
+                ),
+            };
+            let numbers = numbers.map(|n| n.map(|n| n.to_string()).unwrap_or_default());
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                kind,
+                numbers.join(","),
+                color[0],
+                color[1],
+                color[2],
+                color[3],
+                points
+            );
+        }
+        csv
+    }
+}
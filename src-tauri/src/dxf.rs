@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::display::{self, DisplayList, Primitive};
+use crate::units::{Page, Unit};
+
+// A display list as an R12 DXF drawing for laser cutters and engravers, in
+// output pixels with y flipped so the drawing is upright, or in the unit
+// of `page` laid out on its paper. Each stroke
+// weight gets a layer of its own, like "WEIGHT_1.5", so the job software
+// can give each its own power and speed. Fills don't exist in a cut file,
+// so discs and polygons are drawn as outlines, on a layer named for the
+// weight 0.
+pub fn write(list: &DisplayList, page: Option<&Page>) -> String {
+    let frame = match page {
+        Some(page) => Frame {
+            scale: page.scale,
+            left: page.offset[0],
+            bottom: page.height - page.offset[1],
+        },
+        None => Frame {
+            scale: 1.0,
+            left: 0.0,
+            bottom: list.height as f32,
+        },
+    };
+    let layers: BTreeSet<String> = list.primitives.iter().map(layer).collect();
+    let mut dxf = String::new();
+    if let Some(page) = page {
+        // Read by later versions, so the drawing imports at its size.
+        pair(&mut dxf, 0, "SECTION");
+        pair(&mut dxf, 2, "HEADER");
+        pair(&mut dxf, 9, "$INSUNITS");
+        pair(
+            &mut dxf,
+            70,
+            match page.unit {
+                Unit::Inches => 1,
+                Unit::Millimeters => 4,
+            },
+        );
+        pair(&mut dxf, 0, "ENDSEC");
+    }
+    pair(&mut dxf, 0, "SECTION");
+    pair(&mut dxf, 2, "TABLES");
+    pair(&mut dxf, 0, "TABLE");
+    pair(&mut dxf, 2, "LAYER");
+    pair(&mut dxf, 70, layers.len());
+    for name in &layers {
+        pair(&mut dxf, 0, "LAYER");
+        pair(&mut dxf, 2, name);
+        pair(&mut dxf, 70, 0);
+        pair(&mut dxf, 62, 7);
+        pair(&mut dxf, 6, "CONTINUOUS");
+    }
+    pair(&mut dxf, 0, "ENDTAB");
+    pair(&mut dxf, 0, "ENDSEC");
+    pair(&mut dxf, 0, "SECTION");
+    pair(&mut dxf, 2, "ENTITIES");
+    for primitive in &list.primitives {
+        let name = layer(primitive);
+        match primitive {
+            Primitive::Circle { x, y, radius, .. } | Primitive::Ring { x, y, radius, .. } => {
+                pair(&mut dxf, 0, "CIRCLE");
+                pair(&mut dxf, 8, &name);
+                let [x, y] = frame.place([*x, *y]);
+                pair(&mut dxf, 10, x);
+                pair(&mut dxf, 20, y);
+                pair(&mut dxf, 40, radius * frame.scale);
+            }
+            Primitive::Polygon { points, .. } => {
+                polyline(&mut dxf, &name, points, true, &frame);
+            }
+            // R12 has no Béziers, the curve is drawn as short lines.
+            Primitive::Curve {
+                start, segments, ..
+            } => {
+                let points = display::flatten(*start, segments);
+                polyline(&mut dxf, &name, &points, false, &frame);
+            }
+            Primitive::Line { from, to, .. } => {
+                pair(&mut dxf, 0, "LINE");
+                pair(&mut dxf, 8, &name);
+                let (from, to) = (frame.place(*from), frame.place(*to));
+                pair(&mut dxf, 10, from[0]);
+                pair(&mut dxf, 20, from[1]);
+                pair(&mut dxf, 11, to[0]);
+                pair(&mut dxf, 21, to[1]);
+            }
+            Primitive::Dot { x, y, .. } => {
+                pair(&mut dxf, 0, "POINT");
+                let [x, y] = frame.place([*x, *y]);
+                pair(&mut dxf, 8, &name);
+                pair(&mut dxf, 10, x);
+                pair(&mut dxf, 20, y);
+            }
+        }
+    }
+    pair(&mut dxf, 0, "ENDSEC");
+    pair(&mut dxf, 0, "EOF");
+    dxf
+}
+
+// The layer for a primitive, by its stroke weight to a tenth of a pixel.
+fn layer(primitive: &Primitive) -> String {
+    let weight = match primitive {
+        Primitive::Ring { weight, .. }
+        | Primitive::Line { weight, .. }
+        | Primitive::Curve { weight, .. } => *weight,
+        Primitive::Circle { .. } | Primitive::Polygon { .. } | Primitive::Dot { .. } => 0.0,
+    };
+    format!("WEIGHT_{:.1}", weight)
+}
+
+// How output pixels map to drawing units: their length and where the
+// top left of the drawing is, with y running up from the bottom.
+struct Frame {
+    scale: f32,
+    left: f32,
+    bottom: f32,
+}
+
+impl Frame {
+    fn place(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [self.left + x * self.scale, self.bottom - y * self.scale]
+    }
+}
+
+fn polyline(dxf: &mut String, layer: &str, points: &[[f32; 2]], closed: bool, frame: &Frame) {
+    pair(dxf, 0, "POLYLINE");
+    pair(dxf, 8, layer);
+    pair(dxf, 66, 1);
+    pair(dxf, 70, u8::from(closed));
+    for p in points {
+        let [x, y] = frame.place(*p);
+        pair(dxf, 0, "VERTEX");
+        pair(dxf, 8, layer);
+        pair(dxf, 10, x);
+        pair(dxf, 20, y);
+    }
+    pair(dxf, 0, "SEQEND");
+}
+
+// A DXF group: its code and value each on a line.
+fn pair(dxf: &mut String, code: u16, value: impl std::fmt::Display) {
+    let _ = writeln!(dxf, "{:>3}\n{}", code, value);
+}
@@ -0,0 +1,146 @@
+use image::{ImageFormat, RgbaImage};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tiff::encoder::{colortype, TiffEncoder};
+
+use crate::queue::Signal;
+
+// Rows written between progress reports and checks for a cancel.
+const ROWS_PER_STEP: u32 = 64;
+// Likewise for files written as bytes.
+const BYTES_PER_STEP: usize = 1 << 20;
+
+// Why an image was not saved.
+pub enum Unsaved {
+    Cancelled,
+    Failed(String),
+}
+
+// Save the image at `path` in the format of its extension, reporting the
+// fraction written so far to `progress`. PNG and TIFF are written a band
+// of rows at a time, so a large image shows its progress and stops if
+// `signal` is cancelled. Other formats are written in one go. The image is
+// written beside `path` and moved there once complete, so a cancelled or
+// failed save leaves no partial file and any file already there intact.
+pub fn save(
+    img: &RgbaImage,
+    path: &Path,
+    signal: &Signal,
+    mut progress: impl FnMut(f32),
+) -> Result<(), Unsaved> {
+    let format = ImageFormat::from_path(path).map_err(failed)?;
+    through_partial(path, &mut progress, |partial, progress| match format {
+        ImageFormat::Png => png(img, partial, signal, progress),
+        ImageFormat::Tiff => tiff(img, partial, signal, progress),
+        _ => img.save_with_format(partial, format).map_err(failed),
+    })
+}
+
+// Write `contents`, like a vector drawing, at `path` a chunk at a time,
+// reporting progress and stopping on a cancel like `save`, with the same
+// care to leave no partial file.
+pub fn write(
+    contents: &[u8],
+    path: &Path,
+    signal: &Signal,
+    mut progress: impl FnMut(f32),
+) -> Result<(), Unsaved> {
+    through_partial(path, &mut progress, |partial, progress| {
+        let mut file = BufWriter::new(File::create(partial).map_err(failed)?);
+        let mut written = 0;
+        for chunk in contents.chunks(BYTES_PER_STEP) {
+            if signal.is_cancelled() {
+                return Err(Unsaved::Cancelled);
+            }
+            file.write_all(chunk).map_err(failed)?;
+            written += chunk.len();
+            progress(written as f32 / contents.len() as f32);
+        }
+        file.flush().map_err(failed)
+    })
+}
+
+// Run `write` on the partial file beside `path`, then move it to `path`,
+// or remove it if anything went wrong.
+fn through_partial<P: FnMut(f32)>(
+    path: &Path,
+    progress: &mut P,
+    write: impl FnOnce(&Path, &mut P) -> Result<(), Unsaved>,
+) -> Result<(), Unsaved> {
+    let partial = partial_path(path);
+    let result =
+        write(&partial, progress).and_then(|()| fs::rename(&partial, path).map_err(failed));
+    match result {
+        Ok(()) => progress(1.0),
+        Err(_) => {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+    result
+}
+
+// Where an image is written until it is complete, "a.png.part" for
+// "a.png", which no image folder takes for an image.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+fn failed(err: impl ToString) -> Unsaved {
+    Unsaved::Failed(err.to_string())
+}
+
+fn png(
+    img: &RgbaImage,
+    path: &Path,
+    signal: &Signal,
+    progress: &mut impl FnMut(f32),
+) -> Result<(), Unsaved> {
+    let file = BufWriter::new(File::create(path).map_err(failed)?);
+    let mut encoder = png::Encoder::new(file, img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(failed)?;
+    let mut stream = writer.stream_writer().map_err(failed)?;
+    let row = img.width() as usize * 4;
+    let mut written = 0;
+    for band in img.as_raw().chunks(row * ROWS_PER_STEP as usize) {
+        if signal.is_cancelled() {
+            return Err(Unsaved::Cancelled);
+        }
+        stream.write_all(band).map_err(failed)?;
+        written += band.len() / row;
+        progress(written as f32 / img.height() as f32);
+    }
+    stream.finish().map_err(failed)?;
+    writer.finish().map_err(failed)
+}
+
+fn tiff(
+    img: &RgbaImage,
+    path: &Path,
+    signal: &Signal,
+    progress: &mut impl FnMut(f32),
+) -> Result<(), Unsaved> {
+    let file = BufWriter::new(File::create(path).map_err(failed)?);
+    let mut encoder = TiffEncoder::new(file).map_err(failed)?;
+    let mut image = encoder
+        .new_image::<colortype::RGBA8>(img.width(), img.height())
+        .map_err(failed)?;
+    image.rows_per_strip(ROWS_PER_STEP).map_err(failed)?;
+    let mut start = 0;
+    while image.next_strip_sample_count() > 0 {
+        if signal.is_cancelled() {
+            return Err(Unsaved::Cancelled);
+        }
+        let end = start + image.next_strip_sample_count() as usize;
+        image
+            .write_strip(&img.as_raw()[start..end])
+            .map_err(failed)?;
+        start = end;
+        progress(start as f32 / img.as_raw().len() as f32);
+    }
+    image.finish().map_err(failed)
+}
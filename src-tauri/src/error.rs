@@ -0,0 +1,208 @@
+use std::fmt;
+
+// Problems with the input of a command, sent to the frontend as a
+// `Message` in the current locale.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // A render or query needs the base image and none has been loaded.
+    ImageNotLoaded,
+    // An image with no pixels, from the file at the path if there is one.
+    EmptyImage(Option<String>),
+    // Render options that can't be used, one entry per offending field.
+    InvalidOptions(Vec<FieldError>),
+    // A file that could not be read or decoded.
+    Open {
+        path: String,
+        reason: String,
+    },
+    Save {
+        path: String,
+        reason: String,
+    },
+    // A save to the path that was cancelled before it was done.
+    SaveCancelled(String),
+    // A helper app, like the file manager, that could not be started.
+    Launch {
+        path: String,
+        reason: String,
+    },
+    // A file of a type that can't be used where it was given.
+    UnsupportedFormat(String),
+    // A job id that is not in the queue.
+    NoJob(u64),
+    // A path outside the files and folders the user has picked.
+    NotAllowed(String),
+    // An export or batch job without a path to save to.
+    NoExportPath,
+    // A command on the latest render before there is one.
+    NoRender,
+    // A folder that should exist, or hold images, and doesn't.
+    NoFolder(String),
+    NoImages(String),
+    // Breeding without a favorite candidate to breed from.
+    NoFavorites,
+    // A hot folder whose renders would be saved into itself.
+    OutputInHotFolder,
+    // An export while only the quick preview of the image is loaded.
+    StillLoading,
+    // A request to the http api from another machine or another site.
+    ForeignRequest,
+    // A folder that could not be created to save into, and a file that
+    // could not be removed.
+    CreateFolder {
+        path: String,
+        reason: String,
+    },
+    Remove {
+        path: String,
+        reason: String,
+    },
+    // A save over an existing file when conflicts are errors.
+    FileExists(String),
+    // The folder for the app's own files, which the system didn't give.
+    NoDataFolder(String),
+    // A file name template with an unclosed `{`, a field it doesn't know,
+    // or that makes no file name, like one with a folder in it.
+    UnclosedTemplate(String),
+    UnknownTemplateField {
+        template: String,
+        field: String,
+    },
+    TemplateNotFileName(String),
+    // A mask or density map whose values don't fill its size.
+    MaskSize {
+        width: u32,
+        height: u32,
+        values: usize,
+    },
+    DensityMapSize {
+        width: u32,
+        height: u32,
+        values: usize,
+    },
+    // A density multiplier below 0 or not a number.
+    BadDensity(f32),
+    // A label mask with more colors than there can be labels.
+    TooManyLabels(usize),
+    // A scan of the step wedge with too few pixels for its steps.
+    ScanTooSmall {
+        width: u32,
+        height: u32,
+        steps: u32,
+    },
+    // A caption font that is a path rather than a file name, or that is
+    // in none of the font folders, with the names looked for.
+    FontNotFileName(String),
+    NoFont(String),
+    // Text with too many characters for a QR code, and a QR code with too
+    // many pixels a side for the image.
+    QrTooLong(usize),
+    QrTooBig(u32),
+}
+
+// A field of the render options and what is wrong with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub problem: Problem,
+}
+
+// What can be wrong with a field, worded in the current locale like the
+// errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    NotANumber(f32),
+    Negative(f32),
+    NotPositive(f32),
+    // A whole number outside `min..=max`.
+    OutOfRange {
+        min: u64,
+        max: u64,
+        value: u64,
+    },
+    TooSmall {
+        min: u64,
+        value: u64,
+    },
+    TooLarge {
+        max: u64,
+        value: u64,
+    },
+    // Scales below 1 would shrink what they scale.
+    BelowOne(f32),
+    // Sizes that must be more than 0 and at most `max`.
+    PositiveUpTo {
+        max: f32,
+        value: f32,
+    },
+    // A value above that of the `other` field, `limit`.
+    Above {
+        other: String,
+        value: f32,
+        limit: f32,
+    },
+    // A list with fewer or more entries than allowed.
+    Count {
+        min: usize,
+        max: usize,
+        count: usize,
+    },
+    Empty,
+    // Multi, Tonal or Patches where a single style is needed.
+    NotSingleStyle,
+    NotAFolder(String),
+    NotAFontName(String),
+    NoLayer(usize),
+    // Sheet margins that leave no room on a `width` by `height` sheet.
+    MarginTooWide {
+        margin: f32,
+        width: f32,
+        height: f32,
+    },
+    // A panel rect that is empty or reaches past the canvas.
+    OffCanvas {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    CanvasSize {
+        max: u32,
+        width: u32,
+        height: u32,
+    },
+    // A cell map without a size for each of its cells, or with a size
+    // over the largest.
+    MapSize {
+        width: u32,
+        height: u32,
+        sizes: usize,
+    },
+    SizeTooLarge {
+        max: u32,
+        size: u32,
+    },
+    // Tone curve points that aren't numbers or aren't in order.
+    PointsNotNumbers,
+    PointsOutOfOrder,
+    // A swept setting that isn't one, is off, or isn't a number, and a
+    // value the options won't take, with why.
+    NotASetting,
+    SettingOff,
+    NotNumeric,
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.english())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for String {
+    fn from(err: Error) -> String {
+        err.to_string()
+    }
+}
@@ -0,0 +1,113 @@
+use image::{imageops, RgbaImage};
+use serde::Serialize;
+
+use seg_core::error::Error;
+use seg_core::explore::{self, Variant};
+use seg_core::messages::Message;
+use seg_core::planes::Planes;
+use seg_core::queue::JobKind;
+use seg_core::Style;
+
+use crate::State;
+
+// Candidates are drawn about this wide, from the base image shrunk to
+// match their cell size, so a whole population renders quickly.
+const CANDIDATE_WIDTH: u32 = 320;
+
+// The candidates of the current generation, with their renders for the seg
+// protocol. Ids count up across generations and are never reused.
+#[derive(Default)]
+pub(crate) struct Evolution {
+    next_id: u64,
+    styles: Vec<Style>,
+    pub(crate) candidates: Vec<(u64, Variant, RgbaImage)>,
+}
+
+// A candidate as the js side sees it, its render is at `png` on the seg
+// protocol.
+#[derive(Serialize)]
+pub(crate) struct Candidate {
+    id: u64,
+    variant: Variant,
+    png: String,
+}
+
+// Start over with `n` random candidates, from any style or only `styles`.
+#[tauri::command]
+pub(crate) fn start_evolution(
+    n: usize,
+    styles: Option<Vec<Style>>,
+    state: tauri::State<State>,
+) -> Result<Vec<Candidate>, Message> {
+    let mut evolution = state.evolution.lock().expect("Could not lock state mutex");
+    evolution.styles = styles.unwrap_or_default();
+    breed(&state, &mut evolution, &[], n)
+}
+
+// Replace the candidates with `n` children of the favorites.
+#[tauri::command]
+pub(crate) fn next_generation(
+    favorite_ids: Vec<u64>,
+    n: usize,
+    state: tauri::State<State>,
+) -> Result<Vec<Candidate>, Message> {
+    let mut evolution = state.evolution.lock().expect("Could not lock state mutex");
+    let parents: Vec<Variant> = evolution
+        .candidates
+        .iter()
+        .filter(|(id, _, _)| favorite_ids.contains(id))
+        .map(|(_, variant, _)| variant.clone())
+        .collect();
+    if parents.is_empty() {
+        return Err(Error::NoFavorites.into());
+    }
+    breed(&state, &mut evolution, &parents, n)
+}
+
+fn breed(
+    state: &State,
+    evolution: &mut Evolution,
+    parents: &[Variant],
+    n: usize,
+) -> Result<Vec<Candidate>, Message> {
+    let source = crate::source(state);
+    crate::check_image(&source)?;
+    let base = &source.base_image;
+    let variants = explore::next_generation(
+        parents,
+        n,
+        rand::random(),
+        &evolution.styles,
+        base.width(),
+        base.height(),
+    );
+    let mut candidates = Vec::new();
+    for variant in variants {
+        let img = render(state, base, &variant)?;
+        evolution.next_id += 1;
+        candidates.push((evolution.next_id, variant, img));
+    }
+    let sent = candidates
+        .iter()
+        .map(|(id, variant, _)| Candidate {
+            id: *id,
+            variant: variant.clone(),
+            png: format!("candidate/{}.png", id),
+        })
+        .collect();
+    for (_, _, old) in std::mem::replace(&mut evolution.candidates, candidates) {
+        state.canvases.recycle_image(old);
+    }
+    Ok(sent)
+}
+
+// A small render of the variant. The base image is shrunk so the output is
+// about `CANDIDATE_WIDTH` wide whatever the cell size.
+fn render(state: &State, base: &RgbaImage, variant: &Variant) -> Result<RgbaImage, Message> {
+    let options = variant.options.clone().validate()?;
+    let width = (CANDIDATE_WIDTH / options.cell).clamp(1, base.width());
+    let height = ((base.height() as u64 * width as u64 / base.width() as u64) as u32).max(1);
+    let small = imageops::resize(base, width, height, imageops::FilterType::Triangle);
+    let planes = Planes::new(&small, options.needs_hue());
+    crate::render_planes(state, &small, &planes, &options, JobKind::Preview)
+}
@@ -0,0 +1,153 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::color;
+use crate::{RenderOptions, Style};
+
+// Memory a random render may use, and a rough cost per output pixel: the
+// output buffer plus the band canvases it is drawn on.
+const MEMORY_BUDGET: u64 = 1 << 30;
+const BYTES_PER_PIXEL: u64 = 8;
+// Cell sizes picked at random, smaller cells lose the marks and larger
+// ones lose the picture.
+const MIN_CELL: u32 = 3;
+const MAX_CELL: u32 = 40;
+// Chance each setting changes when a variant is mutated.
+const MUTATION_RATE: f64 = 0.3;
+
+// Options and the palette their gradient map came from, so the js side can
+// show it. The settings explored at random are the style, cell size,
+// palette and seed.
+#[derive(Clone, Serialize)]
+pub struct Variant {
+    pub options: RenderOptions,
+    pub palette: Option<&'static str>,
+}
+
+// The largest cell size whose output for a `width` by `height` source fits
+// in the memory budget.
+pub fn max_cell(width: u32, height: u32) -> u32 {
+    let pixels = (width as u64 * height as u64).max(1);
+    ((MEMORY_BUDGET / BYTES_PER_PIXEL / pixels) as f64).sqrt() as u32
+}
+
+// Random but sane options for a `width` by `height` source: a style from
+// `styles`, or any style if it is empty, a cell size the output has memory
+// for, a palette half the time and a seed. The same seed gives the same
+// options.
+pub fn randomize(seed: u64, styles: &[Style], width: u32, height: u32) -> Variant {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let cell = random_cell(&mut rng, width, height);
+    let palette = random_palette(&mut rng);
+    Variant {
+        options: RenderOptions {
+            cell,
+            style: random_style(&mut rng, styles),
+            seed: rng.gen(),
+            gradient_map: palette.map(gradient_map),
+            ..Default::default()
+        },
+        palette,
+    }
+}
+
+// `n` children of the `parents`, each a crossover of two parents picked at
+// random, then mutated. Without parents the children are random.
+pub fn next_generation(
+    parents: &[Variant],
+    n: usize,
+    seed: u64,
+    styles: &[Style],
+    width: u32,
+    height: u32,
+) -> Vec<Variant> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| {
+            if parents.is_empty() {
+                return randomize(rng.gen(), styles, width, height);
+            }
+            let a = &parents[rng.gen_range(0..parents.len())];
+            let b = &parents[rng.gen_range(0..parents.len())];
+            let child = crossover(a, b, &mut rng);
+            mutate(&child, &mut rng, styles, width, height)
+        })
+        .collect()
+}
+
+// Each explored setting taken from one parent or the other, the rest from
+// `a`.
+pub fn crossover(a: &Variant, b: &Variant, rng: &mut SmallRng) -> Variant {
+    let mut child = a.clone();
+    if rng.gen_bool(0.5) {
+        child.options.style = b.options.style;
+    }
+    if rng.gen_bool(0.5) {
+        child.options.cell = b.options.cell;
+    }
+    if rng.gen_bool(0.5) {
+        child.options.seed = b.options.seed;
+    }
+    if rng.gen_bool(0.5) {
+        child.palette = b.palette;
+        child.options.gradient_map = b.options.gradient_map.clone();
+    }
+    child
+}
+
+// A copy with some explored settings changed. The cell size moves by up to
+// a third rather than jumping, so a liked scale is kept roughly.
+pub fn mutate(
+    variant: &Variant,
+    rng: &mut SmallRng,
+    styles: &[Style],
+    width: u32,
+    height: u32,
+) -> Variant {
+    let mut child = variant.clone();
+    let options = &mut child.options;
+    if rng.gen_bool(MUTATION_RATE) {
+        options.style = random_style(rng, styles);
+    }
+    if rng.gen_bool(MUTATION_RATE) {
+        let largest = max_cell(width, height).min(MAX_CELL).max(1);
+        let scaled = options.cell as f32 * rng.gen_range(0.67..1.33);
+        options.cell = (scaled.round() as u32).clamp(MIN_CELL.min(largest), largest);
+    }
+    if rng.gen_bool(MUTATION_RATE) {
+        options.seed = rng.gen();
+    }
+    if rng.gen_bool(MUTATION_RATE) {
+        child.palette = random_palette(rng);
+        child.options.gradient_map = child.palette.map(gradient_map);
+    }
+    child
+}
+
+fn random_style(rng: &mut SmallRng, styles: &[Style]) -> Style {
+    let styles = if styles.is_empty() {
+        &Style::ALL[..]
+    } else {
+        styles
+    };
+    styles[rng.gen_range(0..styles.len())]
+}
+
+fn random_cell(rng: &mut SmallRng, width: u32, height: u32) -> u32 {
+    let largest = max_cell(width, height).min(MAX_CELL).max(1);
+    rng.gen_range(MIN_CELL.min(largest)..=largest)
+}
+
+// A built in palette half the time, otherwise plain black marks.
+fn random_palette(rng: &mut SmallRng) -> Option<&'static str> {
+    rng.gen_bool(0.5)
+        .then(|| color::random_palette(rng.gen()).name)
+}
+
+fn gradient_map(name: &'static str) -> color::GradientMap {
+    color::palettes()
+        .into_iter()
+        .find(|palette| palette.name == name)
+        .map(|palette| palette.gradient_map())
+        .unwrap_or(color::GradientMap { stops: Vec::new() })
+}
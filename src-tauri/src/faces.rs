@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cell_map::CellMap;
+use crate::mask::{self, DensityMap};
+use crate::planes::Planes;
+
+// Source pixels per value of the cell map made around the faces.
+const MAP_STEP: u32 = 4;
+
+// A face found in the source, in source pixels.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Face {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Face {
+    fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+// More detail in faces than in the rest of a portrait.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct FaceBoost {
+    // Darkness multiplier inside faces.
+    pub density: f32,
+    // Cell size of everything outside the faces, in source pixels, so the
+    // faces are drawn finer than the background. 1 keeps one grid.
+    pub background_cell: u32,
+}
+
+// The planes with the darkness inside the faces multiplied by the boost.
+pub fn boost(planes: &Planes, faces: &[Face], boost: &FaceBoost, invert_output: bool) -> Planes {
+    let (width, height) = (planes.width, planes.height);
+    let data = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            if faces.iter().any(|face| face.contains(x, y)) {
+                boost.density.max(0.0)
+            } else {
+                1.0
+            }
+        })
+        .collect();
+    let map = DensityMap {
+        width,
+        height,
+        data,
+    };
+    mask::apply_density(planes, &map, invert_output)
+}
+
+// Cells of `background` source pixels everywhere but the faces of a
+// `width` by `height` source.
+pub fn cell_map(faces: &[Face], width: u32, height: u32, background: u32) -> CellMap {
+    let (map_width, map_height) = (width.div_ceil(MAP_STEP), height.div_ceil(MAP_STEP));
+    let sizes = (0..map_height)
+        .flat_map(|y| (0..map_width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (sx, sy) = (x * MAP_STEP + MAP_STEP / 2, y * MAP_STEP + MAP_STEP / 2);
+            if faces.iter().any(|face| face.contains(sx, sy)) {
+                1
+            } else {
+                background
+            }
+        })
+        .collect();
+    CellMap {
+        width: map_width,
+        height: map_height,
+        sizes,
+    }
+}
+
+// Find the faces in an image with the SeetaFace detector. The model file
+// is not bundled, its path is taken from SEG_FACE_MODEL.
+#[cfg(feature = "faces")]
+pub fn detect(img: &image::RgbaImage) -> Result<Vec<Face>, String> {
+    let path = std::env::var("SEG_FACE_MODEL").map_err(|_| {
+        "Finding faces needs the SeetaFace model, set SEG_FACE_MODEL to its path".to_string()
+    })?;
+    let mut detector = rustface::create_detector(&path)
+        .map_err(|err| format!("The file at {} could not be opened: {}", path, err))?;
+    detector.set_min_face_size(20);
+    detector.set_score_thresh(2.0);
+    detector.set_pyramid_scale_factor(0.8);
+    detector.set_slide_window_step(4, 4);
+    let gray = image::imageops::grayscale(img);
+    let mut data = rustface::ImageData::new(gray.as_raw(), gray.width(), gray.height());
+    let faces = detector
+        .detect(&mut data)
+        .into_iter()
+        .map(|info| {
+            let bbox = info.bbox();
+            let x = bbox.x().max(0) as u32;
+            let y = bbox.y().max(0) as u32;
+            Face {
+                x,
+                y,
+                width: bbox.width().min(img.width().saturating_sub(x)),
+                height: bbox.height().min(img.height().saturating_sub(y)),
+            }
+        })
+        .collect();
+    Ok(faces)
+}
@@ -0,0 +1,141 @@
+use std::fmt::Write;
+
+use crate::display::{self, DisplayList, Primitive};
+use crate::units::Page;
+
+// Millimeters on the machine per output pixel without a page.
+pub const MM_PER_PIXEL: f32 = 0.1;
+// Pen heights in millimeters and speeds in millimeters per minute.
+const PEN_UP: f32 = 5.0;
+const PEN_DOWN: f32 = 0.0;
+const DRAW_FEED: f32 = 3000.0;
+
+// A display list as G-code for a pen plotter, origin at the bottom left
+// of the paper of `page`, or of the drawing without one. A pen can't fill,
+// so discs and polygons are drawn as outlines, and every mark is drawn in
+// the one pen.
+pub fn write(list: &DisplayList, page: Option<&Page>) -> String {
+    let (mm_per_pixel, origin) = match page {
+        Some(page) => {
+            let mm = page.unit.millimeters();
+            (
+                page.mm_per_pixel(),
+                [page.offset[0] * mm, (page.height - page.offset[1]) * mm],
+            )
+        }
+        None => (MM_PER_PIXEL, [0.0, list.height as f32 * MM_PER_PIXEL]),
+    };
+    let mut plotter = Plotter {
+        gcode: String::new(),
+        mm_per_pixel,
+        origin,
+        at: None,
+        down: false,
+    };
+    plotter.gcode.push_str("G21\nG90\n");
+    plotter.lift();
+    for primitive in &list.primitives {
+        match primitive {
+            Primitive::Circle { x, y, radius, .. } | Primitive::Ring { x, y, radius, .. } => {
+                plotter.circle(*x, *y, *radius)
+            }
+            Primitive::Polygon { points, .. } => {
+                if let (Some(first), Some(rest)) = (points.first(), points.get(1..)) {
+                    plotter.move_to(*first);
+                    rest.iter().for_each(|p| plotter.line_to(*p));
+                    plotter.line_to(*first);
+                }
+            }
+            Primitive::Line { from, to, .. } => {
+                plotter.move_to(*from);
+                plotter.line_to(*to);
+            }
+            // Few controllers take Béziers, the curve is drawn as short
+            // lines.
+            Primitive::Curve {
+                start, segments, ..
+            } => {
+                plotter.move_to(*start);
+                for p in display::flatten(*start, segments).into_iter().skip(1) {
+                    plotter.line_to(p);
+                }
+            }
+            Primitive::Dot { x, y, .. } => {
+                plotter.move_to([*x, *y]);
+                plotter.lower();
+            }
+        }
+    }
+    plotter.lift();
+    plotter.gcode.push_str("G0 X0 Y0\n");
+    plotter.gcode
+}
+
+// Where the pen is, so consecutive marks that join are drawn without
+// lifting it.
+struct Plotter {
+    gcode: String,
+    mm_per_pixel: f32,
+    // Where the top left of the drawing is on the machine, in millimeters.
+    origin: [f32; 2],
+    at: Option<[f32; 2]>,
+    down: bool,
+}
+
+impl Plotter {
+    // Output pixels to machine millimeters, flipping y.
+    fn mm(&self, [x, y]: [f32; 2]) -> (f32, f32) {
+        (
+            self.origin[0] + x * self.mm_per_pixel,
+            self.origin[1] - y * self.mm_per_pixel,
+        )
+    }
+
+    fn lift(&mut self) {
+        let _ = writeln!(self.gcode, "G0 Z{}", PEN_UP);
+        self.down = false;
+    }
+
+    fn lower(&mut self) {
+        if !self.down {
+            let _ = writeln!(self.gcode, "G1 Z{} F{}", PEN_DOWN, DRAW_FEED);
+            self.down = true;
+        }
+    }
+
+    // Travel to `p` unless the pen is already there.
+    fn move_to(&mut self, p: [f32; 2]) {
+        if self.at == Some(p) {
+            return;
+        }
+        if self.down {
+            self.lift();
+        }
+        let (x, y) = self.mm(p);
+        let _ = writeln!(self.gcode, "G0 X{:.3} Y{:.3}", x, y);
+        self.at = Some(p);
+    }
+
+    fn line_to(&mut self, p: [f32; 2]) {
+        self.lower();
+        let (x, y) = self.mm(p);
+        let _ = writeln!(self.gcode, "G1 X{:.3} Y{:.3} F{}", x, y, DRAW_FEED);
+        self.at = Some(p);
+    }
+
+    // A full circle as one arc from its rightmost point.
+    fn circle(&mut self, x: f32, y: f32, r: f32) {
+        let start = [x + r, y];
+        self.move_to(start);
+        self.lower();
+        let (sx, sy) = self.mm(start);
+        let _ = writeln!(
+            self.gcode,
+            "G2 X{:.3} Y{:.3} I{:.3} J0 F{}",
+            sx,
+            sy,
+            -r * self.mm_per_pixel,
+            DRAW_FEED
+        );
+    }
+}
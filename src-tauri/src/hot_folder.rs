@@ -0,0 +1,221 @@
+use image::RgbaImage;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tauri::Manager;
+
+use seg_core::encode::{self, Unsaved};
+use seg_core::error::Error;
+use seg_core::manifest;
+use seg_core::messages::Message;
+use seg_core::naming::{self, OnConflict};
+use seg_core::planes;
+use seg_core::queue::{JobKind, Signal};
+use seg_core::RenderOptions;
+
+use crate::slideshow::images;
+use crate::State;
+
+// How often the folder is looked in. A new file is only taken once its
+// size and modified time are the same two looks running, so one still
+// being copied in is left until it is whole.
+const POLL: Duration = Duration::from_secs(2);
+// How often a waiting hot folder checks whether it was stopped.
+const STOP_POLL: Duration = Duration::from_millis(100);
+// Looks at which a file that holds still but can't be opened, as when the
+// app saving it pauses before the end, is tried before it is reported.
+const OPEN_ATTEMPTS: u32 = 5;
+
+// The modified time and size of a file, which change when it is written
+// to or replaced.
+type Stamp = (SystemTime, u64);
+
+// What became of an image dropped in the hot folder, sent as a
+// "hot-folder" event.
+#[derive(Clone, Serialize)]
+struct Processed {
+    source: String,
+    // Where the render was saved, if it was.
+    output: Option<String>,
+    error: Option<Message>,
+}
+
+// The hot folder being watched in the background, if any.
+#[derive(Default)]
+pub(crate) struct HotFolder {
+    stop: Option<Arc<AtomicBool>>,
+}
+
+// Watch `input` and render each image that appears in it with `options`
+// into `output`, as a PNG named after it, until stopped. Images already in
+// the folder are left alone until they change, and existing renders are
+// never overwritten.
+// A "hot-folder" event is sent as each image is done or fails.
+#[tauri::command]
+pub(crate) fn start_hot_folder(
+    input: String,
+    output: String,
+    options: RenderOptions,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<(), Message> {
+    let options = options.validate()?;
+    crate::scope::check(&app, &input)?;
+    crate::scope::check(&app, &output)?;
+    let input = crate::sandbox(&state).check_read(&input)?;
+    let output = crate::sandbox(&state).check_write(&output)?;
+    // Renders saved where they are watched for would be rendered again.
+    if output == input {
+        return Err(Error::OutputInHotFolder.into());
+    }
+    let seen: HashMap<PathBuf, Stamp> = images(&input)?
+        .into_iter()
+        .filter_map(|path| Some((path.clone(), stamp(&path)?)))
+        .collect();
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let mut hot_folder = state.hot_folder.lock().expect("Could not lock state mutex");
+        if let Some(old) = hot_folder.stop.replace(stop.clone()) {
+            old.store(true, Ordering::Relaxed);
+        }
+    }
+    std::thread::spawn(move || run(app, input, output, options, seen, stop));
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn stop_hot_folder(state: tauri::State<State>) {
+    let mut hot_folder = state.hot_folder.lock().expect("Could not lock state mutex");
+    if let Some(stop) = hot_folder.stop.take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// Watch the folder until stopped. `seen` has the files already done, as
+// they were then, a file is done again once it changes.
+fn run(
+    app: tauri::AppHandle,
+    input: PathBuf,
+    output: PathBuf,
+    options: RenderOptions,
+    mut seen: HashMap<PathBuf, Stamp>,
+    stop: Arc<AtomicBool>,
+) {
+    let state = app.state::<State>();
+    // Stamps of the new files at the last look.
+    let mut stamps: HashMap<PathBuf, Stamp> = HashMap::new();
+    // Looks at which each file that held still could not be opened.
+    let mut attempts: HashMap<PathBuf, u32> = HashMap::new();
+    loop {
+        let started = Instant::now();
+        // A folder that can't be read, as while it is renamed, is looked
+        // in again next time.
+        let listed = images(&input).ok();
+        if let Some(listed) = &listed {
+            // Files that are gone are forgotten, so one dropped in again
+            // is rendered again.
+            let listed: HashSet<&PathBuf> = listed.iter().collect();
+            seen.retain(|path, _| listed.contains(path));
+            stamps.retain(|path, _| listed.contains(path));
+            attempts.retain(|path, _| listed.contains(path));
+        }
+        for path in listed.unwrap_or_default() {
+            let Some(current) = stamp(&path) else {
+                continue;
+            };
+            if seen.get(&path) == Some(&current)
+                || stamps.insert(path.clone(), current) != Some(current)
+            {
+                continue;
+            }
+            let source = path.to_string_lossy().into_owned();
+            let rendered = match crate::open_image(&source) {
+                Ok(img) => render_into(&app, &state, &path, img, &output, &options),
+                Err(err) => {
+                    let tries = attempts.entry(path.clone()).or_default();
+                    *tries += 1;
+                    if *tries < OPEN_ATTEMPTS {
+                        continue;
+                    }
+                    Err(err.into())
+                }
+            };
+            stamps.remove(&path);
+            attempts.remove(&path);
+            seen.insert(path, current);
+            let processed = match rendered {
+                Ok(saved) => Processed {
+                    source,
+                    output: Some(saved.to_string_lossy().into_owned()),
+                    error: None,
+                },
+                Err(message) => Processed {
+                    source,
+                    output: None,
+                    error: Some(message),
+                },
+            };
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            crate::emit(&app, "hot-folder", processed);
+        }
+        while started.elapsed() < POLL {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(STOP_POLL);
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+// Render `img`, opened from `path`, into the `output` folder, returning
+// where it was saved.
+fn render_into(
+    app: &tauri::AppHandle,
+    state: &State,
+    path: &Path,
+    img: RgbaImage,
+    output: &Path,
+    options: &RenderOptions,
+) -> Result<PathBuf, Message> {
+    let source = path.to_string_lossy();
+    // Limited and built like the loaded image, so the render matches its
+    // preview.
+    let img = Arc::new(crate::limit_source(state, img).0);
+    let planes = planes::build(&img, options, planes::Inputs::default());
+    let rendered = crate::render_planes(state, &img, &planes, options, JobKind::Batch)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let target = naming::resolve(
+        &output.join(format!("{}.png", stem)).to_string_lossy(),
+        OnConflict::Rename,
+        true,
+    )?;
+    let saved = encode::save(&rendered, &target, &Signal::default(), |_| {});
+    state.canvases.recycle_image(rendered);
+    if let Err(Unsaved::Failed(reason)) = saved {
+        return Err(Error::Save {
+            path: target.to_string_lossy().into_owned(),
+            reason,
+        }
+        .into());
+    }
+    crate::scope::allow(app, &target);
+    crate::write_manifest(
+        app,
+        state,
+        &target,
+        Some(source.as_ref()),
+        manifest::Inputs::default(),
+        options,
+    )?;
+    Ok(target)
+}
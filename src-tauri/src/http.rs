@@ -0,0 +1,156 @@
+use serde::Deserialize;
+use std::io::Read;
+use tauri::Manager;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use seg_core::catalog;
+use seg_core::error::Error;
+use seg_core::messages::Message;
+use seg_core::migrate;
+use seg_core::planes;
+use seg_core::queue::JobKind;
+
+use crate::State;
+
+// Port used when SEG_HTTP_PORT is not set.
+const DEFAULT_PORT: u16 = 7878;
+
+// The body of `POST /render`. Without an `image` the image loaded in the
+// app is rendered. Options are read like saved options, so any version is
+// accepted and missing fields take their defaults.
+#[derive(Deserialize)]
+struct RenderRequest {
+    image: Option<String>,
+    #[serde(default = "empty_options")]
+    options: serde_json::Value,
+}
+
+fn empty_options() -> serde_json::Value {
+    serde_json::Value::Object(Default::default())
+}
+
+// Serve the render pipeline on localhost so scripts and other apps can
+// drive Seg:
+//
+//   GET  /styles  the styles as json, as list_styles returns them
+//   POST /render  a png of the render described by the json body
+//
+// Errors are sent as a json message with a 400 status. Requests must be
+// addressed to localhost and, when a browser sends them, come from a page
+// on localhost, or they are refused with a 403.
+pub(crate) fn start(app: tauri::AppHandle) {
+    let port = std::env::var("SEG_HTTP_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let server = match Server::http(("127.0.0.1", port)) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("The http api could not start on port {}: {}", port, err);
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(&app, request);
+        }
+    });
+}
+
+fn handle(app: &tauri::AppHandle, mut request: Request) {
+    let state = app.state::<State>();
+    let (method, url) = (request.method().clone(), request.url().to_string());
+    let trusted = is_trusted(
+        header_value(&request, "Host"),
+        header_value(&request, "Origin"),
+    );
+    let response = match (method, url.as_str()) {
+        _ if !trusted => json(403, &Message::from(Error::ForeignRequest)),
+        (Method::Get, "/styles") => {
+            let pool = state
+                .pools
+                .lock()
+                .expect("Could not lock state mutex")
+                .for_kind(JobKind::Preview);
+            json(200, &catalog::styles(&pool, &state.canvases))
+        }
+        (Method::Post, "/render") => {
+            let mut body = String::new();
+            match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => match render(&state, &body) {
+                    Ok(png) => Response::from_data(png).with_header(header("image/png")),
+                    Err(message) => json(400, &message),
+                },
+                Err(err) => json(400, &Message::from(err.to_string())),
+            }
+        }
+        _ => Response::from_data(Vec::new()).with_status_code(404),
+    };
+    let _ = request.respond(response);
+}
+
+fn render(state: &State, body: &str) -> Result<Vec<u8>, Message> {
+    let request: RenderRequest =
+        serde_json::from_str(body).map_err(|err| format!("The request is not valid: {}", err))?;
+    let options = migrate::from_value(request.options)?.validate()?;
+    let img = match &request.image {
+        Some(path) => {
+            let path = crate::sandbox(state).check_read(path)?;
+            let source = crate::open_image(&path.to_string_lossy())?;
+            // Held to the same size limit as an image opened in the app.
+            let source = std::sync::Arc::new(crate::limit_source(state, source).0);
+            let planes = planes::build(&source, &options, planes::Inputs::default());
+            crate::render_planes(state, &source, &planes, &options, JobKind::Export)?
+        }
+        None => crate::render_now(state, &options, JobKind::Export)?,
+    };
+    let png = crate::encode_png(&img).map_err(|err| err.to_string());
+    state.canvases.recycle_image(img);
+    Ok(png?)
+}
+
+fn header_value<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+// Whether a request, by its Host and Origin headers, comes from this
+// machine. Checking the host stops pages that rebind their domain to
+// 127.0.0.1, and a browser sends the origin of the page making the
+// request, which must be local too. Scripts usually send no origin.
+pub(crate) fn is_trusted(host: Option<&str>, origin: Option<&str>) -> bool {
+    let local_origin = match origin {
+        Some(origin) => origin
+            .split_once("://")
+            .filter(|(scheme, _)| matches!(*scheme, "http" | "https"))
+            .is_some_and(|(_, rest)| is_loopback(rest.split('/').next().unwrap_or_default())),
+        None => true,
+    };
+    host.is_some_and(is_loopback) && local_origin
+}
+
+// Whether a host, with or without a port, names this machine.
+fn is_loopback(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn json(status: u16, value: &impl serde::Serialize) -> Response<std::io::Cursor<Vec<u8>>> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    Response::from_data(body)
+        .with_status_code(status)
+        .with_header(header("application/json"))
+}
+
+fn header(content_type: &str) -> Header {
+    Header::from_bytes("Content-Type", content_type).expect("A content type is a valid header")
+}
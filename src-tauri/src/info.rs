@@ -0,0 +1,182 @@
+use image::codecs::{jpeg::JpegDecoder, png::PngDecoder, tiff::TiffDecoder, webp::WebPDecoder};
+use image::io::Reader;
+use image::{imageops, DynamicImage, ImageDecoder, ImageFormat, RgbaImage};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::error::Error;
+
+// Cell sizes to estimate the render size of when none are asked for,
+// spanning the cell size slider.
+pub const DEFAULT_CELLS: [u32; 5] = [1, 5, 10, 20, 50];
+
+// What can be learned about an image file from its header, without
+// decoding the pixels, to decide whether to load it.
+#[derive(Serialize)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    // Like "Rgb8" or "La16", `None` for formats read without a decoder here.
+    pub color_type: Option<String>,
+    pub file_size: u64,
+    pub exif: Option<Exif>,
+    pub renders: Vec<RenderSize>,
+}
+
+// The EXIF fields worth showing, as the camera wrote them.
+#[derive(Serialize)]
+pub struct Exif {
+    pub camera: Option<String>,
+    pub date: Option<String>,
+    // 1 to 8, where 1 is upright.
+    pub orientation: Option<u32>,
+}
+
+// How big a render at a cell size would be.
+#[derive(Serialize)]
+pub struct RenderSize {
+    pub cell: u32,
+    pub width: u64,
+    pub height: u64,
+    // Of the RGBA pixels while rendering.
+    pub bytes: u64,
+}
+
+// Inspect the image at `path`, estimating render sizes at `cells`.
+pub fn inspect(path: &str, cells: &[u32]) -> Result<ImageInfo, Error> {
+    let open_failed = |reason: String| Error::Open {
+        path: path.to_string(),
+        reason,
+    };
+    let file_size = std::fs::metadata(path)
+        .map_err(|err| open_failed(err.to_string()))?
+        .len();
+    let reader = Reader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| open_failed(err.to_string()))?;
+    let format = reader
+        .format()
+        .ok_or_else(|| Error::UnsupportedFormat(path.to_string()))?;
+    let (width, height) = reader
+        .into_dimensions()
+        .map_err(|err| open_failed(err.to_string()))?;
+    Ok(ImageInfo {
+        width,
+        height,
+        format: format!("{:?}", format),
+        color_type: color_type(path, format),
+        file_size,
+        exif: read_exif(path),
+        renders: cells
+            .iter()
+            .map(|&cell| {
+                let (width, height) = (width as u64 * cell as u64, height as u64 * cell as u64);
+                RenderSize {
+                    cell,
+                    width,
+                    height,
+                    bytes: 4 * width * height,
+                }
+            })
+            .collect(),
+    })
+}
+
+// The color type from the decoder of the formats Seg opens, which read
+// only the header until asked for pixels.
+fn color_type(path: &str, format: ImageFormat) -> Option<String> {
+    let file = BufReader::new(File::open(path).ok()?);
+    let color = match format {
+        ImageFormat::Png => PngDecoder::new(file).ok()?.color_type(),
+        ImageFormat::Jpeg => JpegDecoder::new(file).ok()?.color_type(),
+        ImageFormat::Tiff => TiffDecoder::new(file).ok()?.color_type(),
+        ImageFormat::WebP => WebPDecoder::new(file).ok()?.color_type(),
+        _ => return None,
+    };
+    Some(format!("{:?}", color))
+}
+
+// A version of the image at `path` about `width` wide, read without
+// decoding all of it, to show while the full image loads: a JPEG decoded
+// at a fraction of its size, or the thumbnail EXIF keeps in other formats.
+// `None` if there is neither.
+pub fn quick_preview(path: &str, width: u32) -> Option<RgbaImage> {
+    let format = Reader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .format()?;
+    let img = match format {
+        ImageFormat::Jpeg => {
+            let mut decoder = JpegDecoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+            let (w, h) = decoder.dimensions();
+            let height = (h as u64 * width as u64 / w.max(1) as u64) as u32;
+            decoder.scale(clamp_u16(width), clamp_u16(height)).ok()?;
+            DynamicImage::from_decoder(decoder).ok()?.to_rgba8()
+        }
+        _ => exif_thumbnail(path)?,
+    };
+    if img.width() == 0 || img.height() == 0 {
+        return None;
+    }
+    if img.width() <= width {
+        return Some(img);
+    }
+    let height = (img.height() as u64 * width as u64 / img.width() as u64).max(1) as u32;
+    Some(imageops::resize(
+        &img,
+        width,
+        height,
+        imageops::FilterType::Triangle,
+    ))
+}
+
+fn clamp_u16(n: u32) -> u16 {
+    n.clamp(1, u16::MAX as u32) as u16
+}
+
+// The JPEG thumbnail in the EXIF of a TIFF or other file, if it has one.
+fn exif_thumbnail(path: &str) -> Option<RgbaImage> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    let data = exif::Reader::new().read_from_container(&mut file).ok()?;
+    let field = |tag| {
+        data.get_field(tag, exif::In::THUMBNAIL)
+            .and_then(|field| field.value.get_uint(0))
+    };
+    let offset = field(exif::Tag::JPEGInterchangeFormat)? as usize;
+    let length = field(exif::Tag::JPEGInterchangeFormatLength)? as usize;
+    let bytes = data.buf().get(offset..offset.checked_add(length)?)?;
+    Some(image::load_from_memory(bytes).ok()?.to_rgba8())
+}
+
+fn read_exif(path: &str) -> Option<Exif> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    let data = exif::Reader::new().read_from_container(&mut file).ok()?;
+    let text = |tag| {
+        data.get_field(tag, exif::In::PRIMARY)
+            .map(|field| {
+                field
+                    .display_value()
+                    .to_string()
+                    .trim_matches('"')
+                    .trim()
+                    .to_string()
+            })
+            .filter(|text| !text.is_empty())
+    };
+    let camera = match (text(exif::Tag::Make), text(exif::Tag::Model)) {
+        // Models often repeat the make, like "Canon" "Canon EOS R5".
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    Some(Exif {
+        camera,
+        date: text(exif::Tag::DateTimeOriginal).or_else(|| text(exif::Tag::DateTime)),
+        orientation: data
+            .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0)),
+    })
+}
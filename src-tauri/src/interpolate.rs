@@ -0,0 +1,152 @@
+use crate::blend::Blend;
+use crate::composite::SourceBlend;
+use crate::layout::Layout;
+use crate::paper::Paper;
+use crate::pen::{Stroke, Wobble};
+use crate::post::{Border, Effects};
+use crate::quadtree::Quadtree;
+use crate::render::HueDensity;
+use crate::RenderOptions;
+
+// The options `t` of the way from `a` to `b`, for scrubbing between two
+// looks and for morphs. Numbers are blended, everything else, like the
+// style or seed, switches from `a` to `b` at 0.5. Settings that are off
+// at 0, like the effects or the paper, fade in from nothing when only one
+// side has them.
+pub fn interpolate(a: &RenderOptions, b: &RenderOptions, t: f32) -> RenderOptions {
+    let t = if t.is_finite() {
+        t.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut options = if t < 0.5 { a.clone() } else { b.clone() };
+    options.cell = lerp_u32(a.cell, b.cell, t).max(1);
+    options.jitter = lerp(a.jitter, b.jitter, t);
+    options.underlay_source = lerp(a.underlay_source, b.underlay_source, t);
+    options.multi.density = density(&a.multi.density, &b.multi.density, t);
+    if let (Layout::Polar { center: ca }, Layout::Polar { center: cb }) = (a.layout, b.layout) {
+        options.layout = Layout::Polar {
+            center: [lerp(ca[0], cb[0], t), lerp(ca[1], cb[1], t)],
+        };
+    }
+    if let (Some(ga), Some(gb)) = (&a.gradient_map, &b.gradient_map) {
+        options.gradient_map = Some(ga.lerp(gb, t));
+    }
+    if let (Some(Blend::Mix { amount: ma }), Some(Blend::Mix { amount: mb })) = (a.blend, b.blend) {
+        options.blend = Some(Blend::Mix {
+            amount: lerp(ma, mb, t),
+        });
+    }
+    if let (Some(qa), Some(qb)) = (a.quadtree, b.quadtree) {
+        options.quadtree = Some(Quadtree {
+            max_block: lerp_u32(qa.max_block, qb.max_block, t),
+            min_block: lerp_u32(qa.min_block, qb.min_block, t),
+            threshold: lerp(qa.threshold, qb.threshold, t),
+        });
+    }
+    if let (Some(wa), Some(wb)) = (a.hand_drawn, b.hand_drawn) {
+        options.hand_drawn = Some(Wobble {
+            amplitude: lerp(wa.amplitude, wb.amplitude, t),
+            frequency: lerp(wa.frequency, wb.frequency, t),
+        });
+    }
+    if let (Some(sa), Some(sb)) = (a.stroke, b.stroke) {
+        options.stroke = Some(Stroke {
+            weight: lerp(sa.weight, sb.weight, t),
+            ..options.stroke.unwrap_or(sb)
+        });
+    }
+    options.paper = paper(a.paper.as_ref(), b.paper.as_ref(), t);
+    options.effects = effects(a.effects, b.effects, t);
+    options.source_blend = source_blend(a.source_blend, b.source_blend, t);
+    if let (Some(ba), Some(bb)) = (a.border, b.border) {
+        options.border = Some(Border {
+            margin: lerp_u32(ba.margin, bb.margin, t),
+            color: std::array::from_fn(|c| lerp_u8(ba.color[c], bb.color[c], t)),
+            corner_radius: lerp_u32(ba.corner_radius, bb.corner_radius, t),
+            ..options.border.unwrap_or(bb)
+        });
+    }
+    options
+}
+
+fn density(a: &HueDensity, b: &HueDensity, t: f32) -> HueDensity {
+    HueDensity {
+        red: lerp(a.red, b.red, t),
+        orange: lerp(a.orange, b.orange, t),
+        yellow: lerp(a.yellow, b.yellow, t),
+        green: lerp(a.green, b.green, t),
+        blue: lerp(a.blue, b.blue, t),
+        purple: lerp(a.purple, b.purple, t),
+    }
+}
+
+// Missing paper is the same texture at no intensity.
+fn paper(a: Option<&Paper>, b: Option<&Paper>, t: f32) -> Option<Paper> {
+    let texture = match (a, b) {
+        (None, None) => return None,
+        (Some(pa), Some(pb)) => {
+            if t < 0.5 {
+                pa.texture.clone()
+            } else {
+                pb.texture.clone()
+            }
+        }
+        (Some(p), None) | (None, Some(p)) => p.texture.clone(),
+    };
+    let intensity = |paper: Option<&Paper>| paper.map_or(0.0, |paper| paper.intensity);
+    Some(Paper {
+        texture,
+        intensity: lerp(intensity(a), intensity(b), t),
+    })
+}
+
+// A missing source blend is the same mode at no opacity.
+fn source_blend(a: Option<SourceBlend>, b: Option<SourceBlend>, t: f32) -> Option<SourceBlend> {
+    let mode = match (a, b) {
+        (None, None) => return None,
+        (Some(ba), Some(bb)) => {
+            if t < 0.5 {
+                ba.mode
+            } else {
+                bb.mode
+            }
+        }
+        (Some(blend), None) | (None, Some(blend)) => blend.mode,
+    };
+    let opacity = |blend: Option<SourceBlend>| blend.map_or(0.0, |blend| blend.opacity);
+    Some(SourceBlend {
+        mode,
+        opacity: lerp(opacity(a), opacity(b), t),
+    })
+}
+
+// Missing effects are all off, which is every amount at 0.
+fn effects(a: Option<Effects>, b: Option<Effects>, t: f32) -> Option<Effects> {
+    if a.is_none() && b.is_none() {
+        return None;
+    }
+    let off = Effects {
+        vignette: 0.0,
+        grain: 0.0,
+        blur: 0.0,
+    };
+    let (ea, eb) = (a.unwrap_or(off), b.unwrap_or(off));
+    Some(Effects {
+        vignette: lerp(ea.vignette, eb.vignette, t),
+        grain: lerp(ea.grain, eb.grain, t),
+        blur: lerp(ea.blur, eb.blur, t),
+    })
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_u32(a: u32, b: u32, t: f32) -> u32 {
+    lerp(a as f32, b as f32, t).round() as u32
+}
+
+fn lerp_u8(a: u8, b: u8, t: f32) -> u8 {
+    lerp(a as f32, b as f32, t).round() as u8
+}
@@ -0,0 +1,76 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::mask;
+use crate::Style;
+
+// Colors a label mask may use, one byte indexes them.
+pub const MAX_LABELS: usize = 256;
+
+// A flat color image painted in any editor, each color marking a region of
+// the source like "sky", "building" or "person". Stretched over the
+// source like a mask.
+pub struct Labels {
+    pub width: u32,
+    pub height: u32,
+    // The colors in the order first seen, reading left to right.
+    pub colors: Vec<[u8; 3]>,
+    index: Vec<u8>,
+}
+
+impl Labels {
+    pub fn from_image(img: &RgbaImage) -> Result<Labels, Error> {
+        let mut colors = Vec::new();
+        let mut seen: HashMap<[u8; 3], u8> = HashMap::new();
+        let mut index = Vec::with_capacity(img.as_raw().len() / 4);
+        for px in img.pixels() {
+            let color = [px[0], px[1], px[2]];
+            let label = match seen.get(&color) {
+                Some(&label) => label,
+                None => {
+                    if colors.len() == MAX_LABELS {
+                        return Err(Error::TooManyLabels(MAX_LABELS));
+                    }
+                    let label = colors.len() as u8;
+                    colors.push(color);
+                    seen.insert(color, label);
+                    label
+                }
+            };
+            index.push(label);
+        }
+        Ok(Labels {
+            width: img.width(),
+            height: img.height(),
+            colors,
+            index,
+        })
+    }
+
+    // The label over source pixel (x, y) of a `width` by `height` source,
+    // an index into `colors`.
+    pub fn label_at(&self, x: u32, y: u32, width: u32, height: u32) -> u8 {
+        self.index[mask::stretched(self.width, self.height, x, y, width, height)]
+    }
+
+    // The label of each pixel, row by row.
+    pub fn index(&self) -> &[u8] {
+        &self.index
+    }
+}
+
+// The treatment of the cells under one label color.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Region {
+    pub color: [u8; 3],
+    pub style: Style,
+    // Darkness multiplier, like the densities of Multi.
+    #[serde(default = "full")]
+    pub density: f32,
+}
+
+fn full() -> f32 {
+    1.0
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::composite::BlendMode;
+use crate::{RenderOptions, Style};
+
+// A named style drawn over the others. Layers share the cell size and
+// every other option, so their marks line up cell for cell.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    pub style: Style,
+    // A muted layer is kept in the stack but not drawn.
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    // While any layer is soloed only the soloed layers are drawn, muted or
+    // not, like the tracks of a mixing desk.
+    #[serde(default)]
+    pub solo: bool,
+    // How the layer combines with the layers under it.
+    #[serde(default)]
+    pub mode: BlendMode,
+    // In [0, 1].
+    #[serde(default = "opaque")]
+    pub opacity: f32,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+// The layers that are drawn, bottom first.
+pub fn active(layers: &[Layer]) -> impl Iterator<Item = &Layer> {
+    let soloing = layers.iter().any(|layer| layer.solo);
+    layers
+        .iter()
+        .filter(move |layer| if soloing { layer.solo } else { layer.enabled })
+}
+
+// The options with layer `i` alone in the stack and drawn whatever its
+// flags, to see what it contributes. `None` if there is no such layer.
+pub fn only(options: &RenderOptions, i: usize) -> Option<RenderOptions> {
+    let layer = options.layers.get(i)?;
+    Some(RenderOptions {
+        layers: vec![Layer {
+            enabled: true,
+            solo: false,
+            mode: BlendMode::Darken,
+            opacity: 1.0,
+            ..layer.clone()
+        }],
+        ..options.clone()
+    })
+}
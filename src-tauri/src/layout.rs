@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::planes::Planes;
+use crate::styles::Cell;
+
+// How cells are arranged on the canvas.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Layout {
+    #[default]
+    Grid,
+    // Rings of cells around `center`, given as fractions of the width and
+    // height. Marks are turned to follow the rings.
+    Polar {
+        center: [f32; 2],
+    },
+    // Every other row, or column, shifted by half a cell.
+    Brick {
+        columns: bool,
+    },
+    // A grid turned by `angle` degrees, like the screen of a halftone.
+    Screen {
+        angle: f32,
+    },
+}
+
+// A cell placed on the canvas with the source pixel it samples.
+pub struct Placed {
+    pub cell: Cell,
+    pub at: (u32, u32),
+    pub t: f32,
+}
+
+// Visit the cells for grid position (x, y) of a brick layout, `by` is the
+// row of the cell on the canvas being drawn. A shifted row or column also
+// gets a half cell hanging off its start, and each shifted cell samples
+// the source at its own center.
+pub fn brick(
+    planes: &Planes,
+    cell: u32,
+    columns: bool,
+    (x, y): (u32, u32),
+    by: u32,
+    mut visit: impl FnMut(Placed),
+) {
+    let shifted = if columns { x % 2 == 1 } else { y % 2 == 1 };
+    if !shifted {
+        visit(Placed {
+            cell: Cell::grid(cell, x, by),
+            at: (x, y),
+            t: planes.t(x, y),
+        });
+        return;
+    }
+    let (dx, dy) = if columns { (0.0, 0.5) } else { (0.5, 0.0) };
+    let first = if columns { y == 0 } else { x == 0 };
+    let starts = if first {
+        [-1.0, 0.0].as_slice()
+    } else {
+        [0.0].as_slice()
+    };
+    for k in starts {
+        let (kx, ky) = if columns { (0.0, *k) } else { (*k, 0.0) };
+        let (fx, fy) = (x as f32 + dx + kx, y as f32 + dy + ky);
+        let s = cell as f32;
+        // Center of the shifted cell in source pixels.
+        let (sx, sy) = (fx + 0.5, fy + 0.5);
+        visit(Placed {
+            cell: Cell {
+                x0: fx * s,
+                y0: (by as f32 + dy + ky) * s,
+                size: cell,
+                angle: 0.0,
+            },
+            at: (
+                (sx as u32).min(planes.width - 1),
+                (sy as u32).min(planes.height - 1),
+            ),
+            t: planes.sample(sx, sy),
+        });
+    }
+}
+
+// Cells on rings one cell wide, each ring split into as many sectors as
+// keep the cells roughly square.
+pub fn polar(planes: &Planes, cell: u32, center: [f32; 2]) -> Vec<Placed> {
+    if planes.width == 0 || planes.height == 0 {
+        return Vec::new();
+    }
+    let (width, height) = ((cell * planes.width) as f32, (cell * planes.height) as f32);
+    let (cx, cy) = (center[0] * width, center[1] * height);
+    let s = cell as f32;
+    // Distance to the farthest corner.
+    let reach = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+        .iter()
+        .map(|(x, y)| (x - cx).hypot(y - cy))
+        .fold(0.0, f32::max);
+    let mut placed = Vec::new();
+    let mut ring = 0;
+    loop {
+        let r = (ring as f32 + 0.5) * s;
+        if r - s > reach {
+            break;
+        }
+        let sectors = ((TAU * r / s).round() as u32).max(1);
+        for j in 0..sectors {
+            let theta = TAU * (j as f32 + 0.5) / sectors as f32;
+            let (px, py) = (cx + r * theta.cos(), cy + r * theta.sin());
+            if px < -s / 2.0 || py < -s / 2.0 || px > width + s / 2.0 || py > height + s / 2.0 {
+                continue;
+            }
+            let at = (
+                ((px / s).max(0.0) as u32).min(planes.width - 1),
+                ((py / s).max(0.0) as u32).min(planes.height - 1),
+            );
+            placed.push(Placed {
+                cell: Cell {
+                    x0: px - s / 2.0,
+                    y0: py - s / 2.0,
+                    size: cell,
+                    angle: theta + FRAC_PI_2,
+                },
+                at,
+                t: planes.t(at.0, at.1),
+            });
+        }
+        ring += 1;
+    }
+    placed
+}
+
+// Cells on a grid turned by `angle` degrees, covering the canvas. Each
+// cell samples the source at its center and is turned with the grid.
+pub fn screen(planes: &Planes, cell: u32, angle: f32) -> Vec<Placed> {
+    if planes.width == 0 || planes.height == 0 {
+        return Vec::new();
+    }
+    let (width, height) = ((cell * planes.width) as f32, (cell * planes.height) as f32);
+    let s = cell as f32;
+    let angle = angle.to_radians();
+    let (sin, cos) = angle.sin_cos();
+    // The corners of the canvas in grid steps along each axis of the grid.
+    let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+        .map(|(x, y)| ((x * cos + y * sin) / s, (y * cos - x * sin) / s));
+    let range = |along: fn(&(f32, f32)) -> f32| {
+        let lo = corners.iter().map(along).fold(f32::INFINITY, f32::min);
+        let hi = corners.iter().map(along).fold(f32::NEG_INFINITY, f32::max);
+        (lo.floor() as i64 - 1)..=(hi.ceil() as i64)
+    };
+    let mut placed = Vec::new();
+    for j in range(|c| c.1) {
+        for i in range(|c| c.0) {
+            let (u, v) = ((i as f32 + 0.5) * s, (j as f32 + 0.5) * s);
+            let (px, py) = (u * cos - v * sin, u * sin + v * cos);
+            if px < -s / 2.0 || py < -s / 2.0 || px > width + s / 2.0 || py > height + s / 2.0 {
+                continue;
+            }
+            placed.push(Placed {
+                cell: Cell {
+                    x0: px - s / 2.0,
+                    y0: py - s / 2.0,
+                    size: cell,
+                    angle,
+                },
+                at: (
+                    ((px / s).max(0.0) as u32).min(planes.width - 1),
+                    ((py / s).max(0.0) as u32).min(planes.height - 1),
+                ),
+                t: planes.sample(px / s, py / s),
+            });
+        }
+    }
+    placed
+}
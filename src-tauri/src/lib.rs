@@ -0,0 +1,64 @@
+// The rendering core of Seg, shared by the app and its tests.
+
+pub mod autotune;
+pub mod blend;
+pub mod calibration;
+pub mod canvas_pool;
+pub mod caption;
+pub mod catalog;
+pub mod cell_map;
+pub mod cmy;
+pub mod collage;
+pub mod color;
+pub mod compare;
+pub mod composite;
+pub mod coverage;
+pub mod cvd;
+pub mod debug;
+pub mod depth;
+pub mod display;
+pub mod dxf;
+pub mod encode;
+pub mod error;
+pub mod explore;
+pub mod faces;
+pub mod gcode;
+pub mod info;
+pub mod interpolate;
+pub mod labels;
+pub mod layers;
+pub mod layout;
+pub mod manifest;
+pub mod mask;
+pub mod messages;
+pub mod migrate;
+pub mod naming;
+pub mod optimize;
+mod options;
+pub mod paper;
+pub mod patches;
+pub mod patterns;
+pub mod pdf;
+pub mod pen;
+pub mod pens;
+pub mod planes;
+pub mod plot;
+pub mod post;
+pub mod quadtree;
+pub mod queue;
+pub mod raster;
+pub mod render;
+pub mod riso;
+pub mod sampling;
+pub mod sandbox;
+pub mod slic;
+pub mod smooth;
+pub mod stamp;
+pub mod styles;
+pub mod svg;
+pub mod sweep;
+pub mod symmetry;
+pub mod tiling;
+pub mod units;
+
+pub use options::{RenderOptions, Style};
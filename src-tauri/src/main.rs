@@ -1,288 +1,2256 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use image::{imageops, RgbaImage};
-use rand::{rngs::SmallRng, SeedableRng};
-use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use wassily::prelude::*;
+use image::{imageops, GrayImage, RgbaImage};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::http::Response;
+use tauri::{Emitter, Manager};
+
+mod crash;
+mod dialogs;
+mod evolve;
+mod hot_folder;
+#[cfg(feature = "http-api")]
+mod http;
+mod scope;
+mod session;
+mod settings;
+mod shell;
+mod slideshow;
+mod usage;
+#[cfg(feature = "http-api")]
+mod ws;
+
+use evolve::Evolution;
+use hot_folder::HotFolder;
+use seg_core::autotune::{self, Tuned};
+use seg_core::blend;
+use seg_core::calibration::{self, ToneCurve};
+use seg_core::canvas_pool::CanvasPool;
+use seg_core::catalog::{self, StyleInfo};
+use seg_core::collage::{self, Collage};
+use seg_core::color::{self, Palette};
+use seg_core::compare::{self, Metric, Scores, Subject};
+use seg_core::coverage::{self, CoverageStats};
+use seg_core::cvd::{self, Cvd};
+use seg_core::depth::DepthMap;
+use seg_core::display::{DisplayList, VectorFormat};
+use seg_core::encode::{self, Unsaved};
+use seg_core::error::{Error, FieldError, Problem};
+use seg_core::explore::{self, Variant};
+use seg_core::faces::Face;
+use seg_core::gcode;
+use seg_core::info::{self, ImageInfo};
+use seg_core::interpolate;
+use seg_core::labels::Labels;
+use seg_core::layers;
+use seg_core::manifest;
+use seg_core::mask::{DensityMap, Mask};
+use seg_core::messages::{self, Locale, Message};
+use seg_core::naming::{self, OnConflict};
+use seg_core::optimize::{self, Optimize};
+use seg_core::patterns::{self, TestPattern};
+use seg_core::pens::{self, PenSplit};
+use seg_core::planes::{Planes, Tones};
+use seg_core::plot::{self, PlotEstimate, PlotSpeed};
+use seg_core::post;
+use seg_core::queue::{Interrupt, JobInfo, JobKind, Queue, Signal, Task};
+use seg_core::render::{self, generate, Pools};
+use seg_core::riso::{self, Riso};
+use seg_core::sandbox::Sandbox;
+use seg_core::slic::{self, Segments, Slic};
+use seg_core::sweep;
+use seg_core::units::{Page, Sheet};
+use seg_core::{RenderOptions, Style};
+use session::{Saved, Session};
+use settings::Settings;
+use slideshow::Slideshow;
+use usage::{Usage, UsageStats};
 
 const W: f32 = 1024.0;
+// Width of the history thumbnails and how many of them are kept.
+const THUMB: u32 = 160;
+const MAX_HISTORY: usize = 24;
+// Width of the preview saved beside an export, enough for galleries and
+// the web.
+const EXPORT_PREVIEW: u32 = 1024;
 
 // Shared state for the tauri app.
 struct State {
-    base_image: Mutex<RgbaImage>,
+    // Replaced as a whole when an image is loaded, the lock is only held
+    // long enough to swap or clone the `Arc` so loading never waits on a
+    // render.
+    source: RwLock<Arc<Source>>,
+    // Counts the images opened, so the decode of one that was replaced
+    // while it loaded is dropped.
+    opening: Mutex<u64>,
+    queue: Queue<Source>,
+    // The latest preview at full size and thumbnails of recent ones, newest
+    // last, served by the seg protocol.
+    latest_preview: Mutex<Option<(u64, Arc<RgbaImage>)>>,
+    history: Mutex<VecDeque<(u64, RgbaImage)>>,
+    pools: Mutex<Pools>,
+    canvases: CanvasPool,
+    // Autosave of the controls, started once the app data dir is known.
+    session: Mutex<Option<Session>>,
+    // Folder of the last file picked in a dialog, where the next one opens.
+    last_dir: Mutex<Option<PathBuf>>,
+    // Where images may be opened from and renders saved to: the folders of
+    // the allowlist and those picked in a dialog.
+    sandbox: RwLock<Sandbox>,
+    settings: RwLock<Settings>,
+    // Started once the app data dir is known, like the session.
+    usage: Mutex<Option<Usage>>,
+    slideshow: Mutex<Slideshow>,
+    hot_folder: Mutex<HotFolder>,
+    evolution: Mutex<Evolution>,
+}
+
+// The loaded images and the planes derived from them. Renders work from the
+// snapshot they started with even if a new image is loaded meanwhile.
+#[derive(Default)]
+struct Source {
+    base_image: Arc<RgbaImage>,
+    // Where the base image was loaded from, if it came from a file.
+    path: Option<String>,
+    // Whether the base image is only the small preview embedded in the
+    // file at `path`, shown while the full image loads.
+    preview: bool,
+    // Whether the base image is gray or black and white, and its levels if
+    // it is, to compute the planes from without the luminance math.
+    tones: Tones,
+    gray: Option<Arc<GrayImage>>,
+    // Luminance and hue of the base image, computed on first use.
+    planes: Mutex<Option<Arc<Planes>>>,
+    // A second image that can be blended with the base image.
+    secondary_image: Option<Arc<RgbaImage>>,
+    // Planes of the secondary image at the size of the base image.
+    secondary_planes: Mutex<Option<Arc<Planes>>>,
+    // Density painted over the base image, dropped with it.
+    mask: Option<Arc<Mask>>,
+    density_map: Option<Arc<DensityMap>>,
+    // Regions to draw in their own styles, dropped with the base image.
+    labels: Option<Arc<Labels>>,
+    // How near each part of the base image is, dropped with it.
+    depth: Option<Arc<DepthMap>>,
+    // Superpixels of the base image and the settings they were cut with,
+    // computed on first use.
+    segments: Mutex<Option<(Slic, Arc<Segments>)>>,
+    // Faces found in the base image, looked for on first use.
+    faces: Mutex<Option<Arc<Vec<Face>>>>,
 }
 
 // Data to send to the js side for rendering the image.
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 struct Picture {
     width: u32,
     height: u32,
     data: Vec<u8>,
 }
 
-#[derive(Deserialize)]
-enum Style {
-    Dots,
-    VLines,
-    HLines,
-    Cross,
-    Stipple,
-    Grid,
-    Multi,
+// How alike two renders are, with a heat map of where they differ.
+#[derive(Clone, Serialize)]
+struct Comparison {
+    #[serde(flatten)]
+    scores: Scores,
+    heat_map: Picture,
+}
+
+// How much of an export's file has been written, sent while it saves.
+#[derive(Clone, Serialize)]
+struct JobProgress {
+    id: u64,
+    // From 0 to 1.
+    saved: f32,
+}
+
+// An image being opened, from its header, and a small version of it to
+// show and render from until the full image is decoded. The full image
+// follows in an `image-loaded` event with the same id.
+#[derive(Serialize)]
+struct Opening {
+    id: u64,
+    width: u32,
+    height: u32,
+    preview: Option<Picture>,
+}
+
+// The end of opening an image, the image or why it could not be read.
+#[derive(Clone, Serialize)]
+struct ImageLoaded {
+    id: u64,
+    loaded: Option<Loaded>,
+    error: Option<Message>,
+}
+
+// A base image as loaded, with a warning if it had to be scaled down.
+#[derive(Clone, Serialize)]
+struct Loaded {
+    #[serde(flatten)]
+    picture: Picture,
+    warning: Option<Message>,
+    // Gray and black and white sources render faster, and black and white
+    // ones are best traced.
+    tones: Tones,
+}
+
+// Where a vector export was saved and how long it would take to plot.
+#[derive(Serialize)]
+struct SaveReport {
+    path: String,
+    plot: PlotEstimate,
+}
+
+// Where an export and the preview beside it were saved.
+#[derive(Serialize)]
+struct SavedWithPreview {
+    path: String,
+    preview: String,
 }
 
 fn main() {
     tauri::Builder::default()
         .manage(State {
-            base_image: Mutex::new(RgbaImage::new(0, 0)),
+            source: RwLock::default(),
+            opening: Mutex::new(0),
+            queue: Queue::default(),
+            latest_preview: Mutex::new(None),
+            history: Mutex::new(VecDeque::new()),
+            pools: Mutex::new(Pools::new(0, true).expect("Could not start the render threads")),
+            canvases: CanvasPool::default(),
+            session: Mutex::new(None),
+            last_dir: Mutex::new(None),
+            sandbox: RwLock::default(),
+            settings: RwLock::default(),
+            usage: Mutex::new(None),
+            slideshow: Mutex::default(),
+            hot_folder: Mutex::default(),
+            evolution: Mutex::default(),
+        })
+        // The scope of the files picked in dialogs is kept between sessions,
+        // so a recovered session can open its image again.
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_persisted_scope::init())
+        .plugin(tauri_plugin_dialog::init())
+        .register_uri_scheme_protocol("seg", |ctx, request| {
+            serve(ctx.app_handle(), &request.uri().to_string()).unwrap_or_else(|err| {
+                Response::builder()
+                    .status(500)
+                    .body(err.to_string().into_bytes())
+                    .expect("A plain response can be built")
+            })
         })
-        .invoke_handler(tauri::generate_handler![get_image, gen_image, save_image])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .setup(|app| {
+            let handle = app.handle().clone();
+            if let Ok(dir) = handle.path().app_data_dir() {
+                crash::install(handle.clone(), dir.clone());
+                load_sandbox(&handle, &dir);
+                if let Err(err) = apply_settings(&handle.state::<State>(), Settings::load(&dir)) {
+                    eprintln!("{}", err);
+                }
+                *handle
+                    .state::<State>()
+                    .usage
+                    .lock()
+                    .expect("Could not lock state mutex") = Some(Usage::load(&dir));
+                match Session::start(dir) {
+                    Ok(session) => {
+                        *handle
+                            .state::<State>()
+                            .session
+                            .lock()
+                            .expect("Could not lock state mutex") = Some(session)
+                    }
+                    Err(err) => eprintln!("Autosave is off: {}", err),
+                }
+            }
+            restore_queue(&handle.state::<State>());
+            #[cfg(feature = "http-api")]
+            {
+                http::start(handle.clone());
+                ws::start();
+            }
+            std::thread::spawn(move || render_worker(handle));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_image,
+            get_image_info,
+            has_image,
+            load_secondary_image,
+            set_mask,
+            clear_mask,
+            set_density_map,
+            clear_density_map,
+            load_label_mask,
+            clear_label_mask,
+            set_depth_map,
+            clear_depth_map,
+            load_test_pattern,
+            measure_tone,
+            gen_image,
+            get_coverage_stats,
+            render_layer,
+            save_image,
+            save_with_preview,
+            save_marks,
+            save_pen_layers,
+            save_riso,
+            enqueue_render,
+            cancel_job,
+            get_queue,
+            pause_queue,
+            resume_queue,
+            abort_exports,
+            set_render_threads,
+            list_styles,
+            randomize_params,
+            interpolate_params,
+            sweep_parameter,
+            render_collage,
+            list_palettes,
+            random_palette,
+            extract_palette,
+            simulate_cvd,
+            compare_renders,
+            auto_tune,
+            autosave,
+            recover_session,
+            set_locale,
+            get_settings,
+            update_settings,
+            get_usage_stats,
+            export_usage_stats,
+            delete_usage_stats,
+            dialogs::pick_input_file,
+            dialogs::pick_save_path,
+            shell::reveal_file,
+            shell::open_with_default_app,
+            slideshow::start_slideshow,
+            slideshow::stop_slideshow,
+            hot_folder::start_hot_folder,
+            hot_folder::stop_hot_folder,
+            evolve::start_evolution,
+            evolve::next_generation
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                let state = app.state::<State>();
+                let session = state.session.lock().expect("Could not lock state mutex");
+                if let Some(session) = session.as_ref() {
+                    session.end();
+                }
+                let mut usage = state.usage.lock().expect("Could not lock state mutex");
+                if let Some(Err(err)) = usage.as_mut().map(Usage::save) {
+                    eprintln!("{}", err);
+                }
+            }
+        });
+}
+
+// Open the image and store it in the global state. Returns as soon as the
+// header is read, with a quick preview when the file has one that is
+// cheap to read; large files are decoded on a thread of their own and sent
+// to the js side, scaled to the canvas size, when they are ready. Until
+// then renders use the preview, or fail as if no image were loaded.
+#[tauri::command]
+fn get_image(
+    path: &str,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<Opening, Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state)
+        .check_read(path)?
+        .to_string_lossy()
+        .into_owned();
+    let (width, height) = image::image_dimensions(&path).map_err(|err| Error::Open {
+        path: path.clone(),
+        reason: err.to_string(),
+    })?;
+    let id = {
+        let mut opening = state.opening.lock().expect("Could not lock state mutex");
+        *opening += 1;
+        *opening
+    };
+    let preview = match info::quick_preview(&path, preview_width(&state)) {
+        Some(small) => {
+            let picture = picture(&small, preview_width(&state));
+            let mut source = state.source.write().expect("Could not lock state mutex");
+            let secondary_image = source.secondary_image.clone();
+            *source = Arc::new(Source {
+                preview: true,
+                ..new_source(small, Some(path.clone()), secondary_image)
+            });
+            drop(source);
+            state.canvases.clear();
+            Some(picture)
+        }
+        None => {
+            let mut source = state.source.write().expect("Could not lock state mutex");
+            *source = Arc::new(Source {
+                secondary_image: source.secondary_image.clone(),
+                ..Default::default()
+            });
+            None
+        }
+    };
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state = app.state::<State>();
+        let result = open_image(&path).map(|img| limit_source(&state, img));
+        // Held while the image is stored, so a newer one can't be opened
+        // in between.
+        let opening = state.opening.lock().expect("Could not lock state mutex");
+        if *opening != id {
+            return;
+        }
+        let done = match result {
+            Ok((img, warning)) => {
+                let picture = set_base_image(&state, img, Some(path));
+                ImageLoaded {
+                    id,
+                    loaded: Some(Loaded {
+                        picture,
+                        warning,
+                        tones: source(&state).tones,
+                    }),
+                    error: None,
+                }
+            }
+            Err(err) => ImageLoaded {
+                id,
+                loaded: None,
+                error: Some(err.into()),
+            },
+        };
+        drop(opening);
+        emit(&app, "image-loaded", done);
+    });
+    Ok(Opening {
+        id,
+        width,
+        height,
+        preview,
+    })
+}
+
+// Scale an image down to the most pixels the settings allow a source,
+// with a warning saying so.
+fn limit_source(state: &State, img: RgbaImage) -> (RgbaImage, Option<Message>) {
+    let max = state
+        .settings
+        .read()
+        .expect("Could not lock state mutex")
+        .max_source_pixels;
+    let (width, height) = img.dimensions();
+    let pixels = width as u64 * height as u64;
+    let Some(max) = max.filter(|&max| pixels > max) else {
+        return (img, None);
+    };
+    let scale = (max as f64 / pixels as f64).sqrt();
+    let (new_width, new_height) = (
+        ((width as f64 * scale) as u32).max(1),
+        ((height as f64 * scale) as u32).max(1),
+    );
+    let scaled = imageops::resize(&img, new_width, new_height, imageops::FilterType::Triangle);
+    let warning = Message::new(
+        "downscaled",
+        BTreeMap::from([
+            ("from", format!("{} × {}", width, height)),
+            ("to", format!("{} × {}", new_width, new_height)),
+        ]),
+    );
+    (scaled, Some(warning))
+}
+
+fn sandbox(state: &State) -> std::sync::RwLockReadGuard<'_, Sandbox> {
+    state.sandbox.read().expect("Could not lock state mutex")
 }
 
-// Open the image and store it in the global state.
-// Scale it to the canvas size before sending it to the js side.
+// Read the allowlist of the sandbox from the app data dir, starting it
+// with the usual folders for pictures the first time.
+fn load_sandbox(app: &tauri::AppHandle, dir: &Path) {
+    let paths = app.path();
+    let defaults = [
+        paths.picture_dir(),
+        paths.document_dir(),
+        paths.desktop_dir(),
+        paths.download_dir(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        eprintln!(
+            "The folder at {} could not be created: {}",
+            dir.display(),
+            err
+        );
+    }
+    match Sandbox::load(&dir.join("allowlist.json"), defaults) {
+        Ok(sandbox) => {
+            *app.state::<State>()
+                .sandbox
+                .write()
+                .expect("Could not lock state mutex") = sandbox
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+}
+
+// What an image file holds, read from its header without loading it: its
+// size, format, EXIF basics and how big renders of it would be at `cells`,
+// or a spread of cell sizes.
 #[tauri::command]
-fn get_image(path: &str, state: tauri::State<State>) -> Result<Picture, String> {
+fn get_image_info(
+    path: &str,
+    cells: Option<Vec<u32>>,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<ImageInfo, Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_read(path)?;
+    let cells = cells.unwrap_or_else(|| info::DEFAULT_CELLS.to_vec());
+    Ok(info::inspect(&path.to_string_lossy(), &cells)?)
+}
+
+fn open_image(path: &str) -> Result<RgbaImage, Error> {
     let img = image::open(path)
-        .map_err(|err| format!("The file at {} could not be opened: {}", path, err))?;
-    let mut state_base_image = state.base_image.lock().expect("Could not lock state mutex");
-    *state_base_image = img.to_rgba8();
-    let scale = W / img.width() as f32;
+        .map_err(|err| Error::Open {
+            path: path.to_string(),
+            reason: err.to_string(),
+        })?
+        .to_rgba8();
+    if img.width() == 0 || img.height() == 0 {
+        return Err(Error::EmptyImage(Some(path.to_string())));
+    }
+    Ok(img)
+}
+
+// Whether a base image has been loaded to render.
+#[tauri::command]
+fn has_image(state: tauri::State<State>) -> bool {
+    check_image(&source(&state)).is_ok()
+}
+
+// Use a synthetic image of known tones as the base image, `size` pixels
+// square.
+#[tauri::command]
+fn load_test_pattern(
+    kind: TestPattern,
+    size: Option<u32>,
+    state: tauri::State<State>,
+) -> Result<Picture, Message> {
+    let size = size.unwrap_or(256);
+    if size == 0 {
+        return Err(Error::EmptyImage(None).into());
+    }
+    Ok(set_base_image(&state, patterns::generate(kind, size), None))
+}
+
+// The tone curve for an output device from a scan of the step wedge test
+// pattern with `steps` steps as it printed, to send back in the render
+// options.
+#[tauri::command]
+fn measure_tone(
+    path: &str,
+    steps: u32,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<ToneCurve, Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_read(path)?;
+    Ok(calibration::measure(
+        &open_image(&path.to_string_lossy())?,
+        steps,
+    )?)
+}
+
+// The current source, a cheap snapshot that stays valid while it is used.
+fn source(state: &State) -> Arc<Source> {
+    state
+        .source
+        .read()
+        .expect("Could not lock state mutex")
+        .clone()
+}
+
+// Fail unless there is a base image with pixels in it.
+fn check_image(source: &Source) -> Result<(), Error> {
+    if source.base_image.width() == 0 || source.base_image.height() == 0 {
+        return Err(Error::ImageNotLoaded);
+    }
+    Ok(())
+}
+
+// Fail unless the full base image is loaded, as exports need, rather than
+// the quick preview shown while it loads.
+fn check_full_image(source: &Source) -> Result<(), Error> {
+    check_image(source)?;
+    if source.preview {
+        return Err(Error::StillLoading);
+    }
+    Ok(())
+}
+
+// Replace the base image, dropping everything derived from the old one.
+// The secondary image is kept.
+fn set_base_image(state: &State, img: RgbaImage, path: Option<String>) -> Picture {
+    let picture = picture(&img, preview_width(state));
+    let mut source = state.source.write().expect("Could not lock state mutex");
+    let secondary_image = source.secondary_image.clone();
+    *source = Arc::new(new_source(img, path, secondary_image));
+    drop(source);
+    state.canvases.clear();
+    picture
+}
+
+// A source with nothing derived from `img` yet but its tones.
+fn new_source(
+    img: RgbaImage,
+    path: Option<String>,
+    secondary_image: Option<Arc<RgbaImage>>,
+) -> Source {
+    let tones = seg_core::planes::tones(&img);
+    let gray = (tones != Tones::Color).then(|| Arc::new(seg_core::planes::gray(&img)));
+    Source {
+        base_image: Arc::new(img),
+        path,
+        tones,
+        gray,
+        secondary_image,
+        ..Default::default()
+    }
+}
+
+// Open an image to blend with the base image.
+#[tauri::command]
+fn load_secondary_image(
+    path: &str,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<Picture, Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_read(path)?;
+    let img = open_image(&path.to_string_lossy())?;
+    let picture = picture(&img, preview_width(&state));
+    update_source(&state, |source| {
+        source.secondary_image = Some(Arc::new(img));
+        source.secondary_planes = Mutex::default();
+    });
+    Ok(picture)
+}
+
+// Scale the density of the marks by a grayscale mask painted over the base
+// image, one byte per pixel from 0, no marks, to 255, full darkness. The
+// mask is stretched to the image if their sizes differ.
+#[tauri::command]
+fn set_mask(
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    state: tauri::State<State>,
+) -> Result<(), Message> {
+    let mask = Mask::new(width, height, data)?;
+    update_source(&state, |source| source.mask = Some(Arc::new(mask)));
+    Ok(())
+}
+
+#[tauri::command]
+fn clear_mask(state: tauri::State<State>) {
+    update_source(&state, |source| source.mask = None);
+}
+
+// Multiply the darkness of the base image by a map of factors, one per
+// pixel, to boost or reduce the marks locally. Stretched like a mask.
+#[tauri::command]
+fn set_density_map(
+    width: u32,
+    height: u32,
+    data: Vec<f32>,
+    state: tauri::State<State>,
+) -> Result<(), Message> {
+    let map = DensityMap::new(width, height, data)?;
+    update_source(&state, |source| source.density_map = Some(Arc::new(map)));
+    Ok(())
+}
+
+#[tauri::command]
+fn clear_density_map(state: tauri::State<State>) {
+    update_source(&state, |source| source.density_map = None);
+}
+
+// Open a flat color image marking the regions of the base image, to draw
+// each region in its own style. Returns the label colors found.
+#[tauri::command]
+fn load_label_mask(
+    path: &str,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<Vec<[u8; 3]>, Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_read(path)?;
+    let labels = Labels::from_image(&open_image(&path.to_string_lossy())?)?;
+    let colors = labels.colors.clone();
+    update_source(&state, |source| source.labels = Some(Arc::new(labels)));
+    Ok(colors)
+}
+
+#[tauri::command]
+fn clear_label_mask(state: tauri::State<State>) {
+    update_source(&state, |source| source.labels = None);
+}
+
+// Open a depth map of the base image, white nearest, for the depth options
+// to work from.
+#[tauri::command]
+fn set_depth_map(
+    path: &str,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<(), Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_read(path)?;
+    let depth = DepthMap::from_image(&open_image(&path.to_string_lossy())?);
+    update_source(&state, |source| source.depth = Some(Arc::new(depth)));
+    Ok(())
+}
+
+#[tauri::command]
+fn clear_depth_map(state: tauri::State<State>) {
+    update_source(&state, |source| source.depth = None);
+}
+
+// Replace the source with a changed copy. The copy shares the images and
+// keeps the cached planes unless a render is busy computing them.
+fn update_source(state: &State, change: impl FnOnce(&mut Source)) {
+    let mut source = state.source.write().expect("Could not lock state mutex");
+    let cached = |planes: &Mutex<Option<Arc<Planes>>>| {
+        Mutex::new(planes.try_lock().ok().and_then(|planes| planes.clone()))
+    };
+    let mut updated = Source {
+        base_image: source.base_image.clone(),
+        path: source.path.clone(),
+        preview: source.preview,
+        tones: source.tones,
+        gray: source.gray.clone(),
+        planes: cached(&source.planes),
+        secondary_image: source.secondary_image.clone(),
+        secondary_planes: cached(&source.secondary_planes),
+        mask: source.mask.clone(),
+        density_map: source.density_map.clone(),
+        labels: source.labels.clone(),
+        depth: source.depth.clone(),
+        segments: Mutex::new(
+            source
+                .segments
+                .try_lock()
+                .ok()
+                .and_then(|segments| segments.clone()),
+        ),
+        faces: Mutex::new(source.faces.try_lock().ok().and_then(|faces| faces.clone())),
+    };
+    change(&mut updated);
+    *source = Arc::new(updated);
+}
+
+// The planes of the base image, computed on first use and kept until a new
+// image is loaded, built for `options` with the extras loaded over it, see
+// `planes::build`. The hue plane is only computed once a style needs it,
+// and the secondary planes, superpixels and faces are cached alongside.
+fn planes(source: &Source, options: &RenderOptions) -> Arc<Planes> {
+    let base = base_planes(source, options);
+    let secondary = options.blend.and_then(|_| secondary_planes(source, &base));
+    seg_core::planes::build(
+        &source.base_image,
+        options,
+        seg_core::planes::Inputs {
+            base: Some(base),
+            secondary,
+            density_map: source.density_map.clone(),
+            mask: source.mask.clone(),
+            labels: source.labels.clone(),
+            depth: source.depth.clone(),
+            segments: options.segments.as_ref().map(|slic| segments(source, slic)),
+            faces: options.faces.as_ref().and_then(|_| faces(source)),
+        },
+    )
+}
+
+fn base_planes(source: &Source, options: &RenderOptions) -> Arc<Planes> {
+    let mut planes = source.planes.lock().expect("Could not lock state mutex");
+    let with_hue = options.needs_hue();
+    match planes.as_ref() {
+        Some(cached) if cached.hue.is_some() || !with_hue => cached.clone(),
+        _ => {
+            let computed = Arc::new(match &source.gray {
+                Some(gray) => Planes::from_gray(gray, with_hue),
+                None => Planes::new(&source.base_image, with_hue),
+            });
+            *planes = Some(computed.clone());
+            computed
+        }
+    }
+}
+
+// The faces in the base image, looked for once.
+fn faces(source: &Source) -> Option<Arc<Vec<Face>>> {
+    let mut faces = source.faces.lock().expect("Could not lock state mutex");
+    if faces.is_none() {
+        *faces = seg_core::planes::faces(&source.base_image);
+    }
+    faces.clone()
+}
+
+// The superpixels of the base image, cut again only when the settings
+// change.
+fn segments(source: &Source, slic: &Slic) -> Arc<Segments> {
+    let mut segments = source.segments.lock().expect("Could not lock state mutex");
+    match segments.as_ref() {
+        Some((cut_with, cached)) if cut_with == slic => cached.clone(),
+        _ => {
+            let computed = Arc::new(slic::segment(&source.base_image, slic));
+            *segments = Some((*slic, computed.clone()));
+            computed
+        }
+    }
+}
+
+// The planes of the secondary image sized to match `base`, if one is loaded.
+fn secondary_planes(source: &Source, base: &Planes) -> Option<Arc<Planes>> {
+    let mut planes = source
+        .secondary_planes
+        .lock()
+        .expect("Could not lock state mutex");
+    if let Some(cached) = planes.as_ref() {
+        return Some(cached.clone());
+    }
+    let computed = Arc::new(blend::secondary_planes(
+        source.secondary_image.as_ref()?,
+        base.width,
+        base.height,
+    ));
+    *planes = Some(computed.clone());
+    Some(computed)
+}
+
+// Scale an image to the canvas width for display on the js side.
+fn picture(img: &RgbaImage, width: u32) -> Picture {
+    let scale = width as f32 / img.width() as f32;
     let nwidth = (img.width() as f32 * scale) as u32;
     let nhight = (img.height() as f32 * scale) as u32;
-    let new_img = imageops::resize(&img, nwidth, nhight, imageops::FilterType::Lanczos3);
-    Ok(Picture {
+    let new_img = imageops::resize(img, nwidth, nhight, imageops::FilterType::Lanczos3);
+    Picture {
         width: nwidth,
         height: nhight,
         data: new_img.into_vec(),
+    }
+}
+
+// Render right away, outside the queue, with the same finishing as a
+// queued job of `kind`.
+fn render_now(state: &State, options: &RenderOptions, kind: JobKind) -> Result<RgbaImage, Message> {
+    render_source(state, &source(state), options, kind)
+}
+
+// Render right away from `source` rather than whatever image is current
+// by the time the render starts.
+fn render_source(
+    state: &State,
+    source: &Source,
+    options: &RenderOptions,
+    kind: JobKind,
+) -> Result<RgbaImage, Message> {
+    check_image(source)?;
+    crash::note(options);
+    record_usage(state, options);
+    render_planes(
+        state,
+        &source.base_image,
+        &planes(source, options),
+        options,
+        kind,
+    )
+}
+
+// Render `planes`, which were made from the `source` photo.
+fn render_planes(
+    state: &State,
+    source: &RgbaImage,
+    planes: &Planes,
+    options: &RenderOptions,
+    kind: JobKind,
+) -> Result<RgbaImage, Message> {
+    let pool = state
+        .pools
+        .lock()
+        .expect("Could not lock state mutex")
+        .for_kind(kind);
+    let img = generate(planes, options, &Signal::default(), &pool, &state.canvases)
+        .expect("An unsignalled render can not be interrupted");
+    match post::finish(&img, source, planes, options, kind != JobKind::Preview) {
+        Ok(Some(finished)) => {
+            state.canvases.recycle_image(img);
+            Ok(finished)
+        }
+        Ok(None) => Ok(img),
+        Err(err) => {
+            state.canvases.recycle_image(img);
+            Err(err.into())
+        }
+    }
+}
+
+#[tauri::command]
+fn gen_image(options: RenderOptions, state: tauri::State<State>) -> Result<Picture, Message> {
+    let options = options.validate()?;
+    let img = render_now(&state, &options, JobKind::Preview)?;
+    let picture = picture(&img, preview_width(&state));
+    state.canvases.recycle_image(img);
+    Ok(picture)
+}
+
+// How much of the render is ink, in all and in each region of the label
+// mask. The marks are measured bare, without the paper or effects.
+#[tauri::command]
+fn get_coverage_stats(
+    options: RenderOptions,
+    state: tauri::State<State>,
+) -> Result<CoverageStats, Message> {
+    let options = RenderOptions {
+        transparent: false,
+        ..options.validate()?
+    };
+    let source = source(&state);
+    check_image(&source)?;
+    let planes = planes(&source, &options);
+    let pool = state
+        .pools
+        .lock()
+        .expect("Could not lock state mutex")
+        .for_kind(JobKind::Preview);
+    let img = generate(
+        &planes,
+        &options,
+        &Signal::default(),
+        &pool,
+        &state.canvases,
+    )
+    .expect("An unsignalled render can not be interrupted");
+    let stats = coverage::stats(&img, &planes, options.cell, options.invert_output);
+    state.canvases.recycle_image(img);
+    Ok(stats)
+}
+
+// Preview what layer `i` of the stack contributes on its own.
+#[tauri::command]
+fn render_layer(
+    i: usize,
+    options: RenderOptions,
+    state: tauri::State<State>,
+) -> Result<Picture, Message> {
+    let options = layers::only(&options, i).ok_or_else(|| {
+        Error::InvalidOptions(vec![FieldError {
+            field: "layers".to_string(),
+            problem: Problem::NoLayer(i),
+        }])
+    })?;
+    gen_image(options, state)
+}
+
+// Render and save to `path`, returning the path actually written, which
+// differs when an existing file made `on_conflict` pick a new name. The
+// save reports its progress and can be cancelled like a queued export.
+#[tauri::command]
+async fn save_image(
+    path: &str,
+    options: RenderOptions,
+    on_conflict: Option<OnConflict>,
+    create_dirs: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<String, Message> {
+    let options = options.validate()?;
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_write(path)?;
+    let source = source(&state);
+    check_full_image(&source)?;
+    let path = naming::resolve(
+        &path.to_string_lossy(),
+        on_conflict.unwrap_or_default(),
+        create_dirs.unwrap_or(false),
+    )?;
+    let img = render_source(&state, &source, &options, JobKind::Export)?;
+    let saved = save_export(&app, &state, &img, &path).map(|_| {
+        scope::allow(&app, &path);
+        path.to_string_lossy().into_owned()
+    });
+    state.canvases.recycle_image(img);
+    let saved = saved?;
+    write_manifest(
+        &app,
+        &state,
+        &path,
+        source.path.as_deref(),
+        manifest_inputs(&source),
+        &options,
+    )?;
+    Ok(saved)
+}
+
+// Save an image rendered outside the queue with `encode::save`.
+fn save_export(
+    app: &tauri::AppHandle,
+    state: &State,
+    img: &RgbaImage,
+    path: &Path,
+) -> Result<(), Message> {
+    save_tracked(app, state, path, |signal, progress| {
+        encode::save(img, path, signal, progress)
     })
 }
 
-pub fn halton_seq(width: f32, height: f32, n: u32, seed: u64) -> Vec<Point> {
-    let mut rng = SmallRng::seed_from_u64(seed);
-    let k: u32 = rng.gen();
-    let xs = (k..n + k).map(|i| halton(i, 2));
-    let ys = (k..n + k).map(|i| halton(i, 3));
-    xs.zip(ys)
-        .map(|p| {
-            Point::from_xy(
-                (p.0 * (width as f32)).clamp(0.0, width as f32 - 1.0),
-                (p.1 * (height as f32)).clamp(0.0, width as f32 - 1.0),
-            )
+// Save a file outside the queue with `save`, sending its progress as
+// "job-progress" events under an id of its own, by which `cancel_job`
+// can stop it, as can `abort_exports`. The commands that save this way
+// are async, so they run off the main thread and leave it free for the UI
+// and the cancel meanwhile.
+fn save_tracked(
+    app: &tauri::AppHandle,
+    state: &State,
+    path: &Path,
+    save: impl FnOnce(&Signal, &mut dyn FnMut(f32)) -> Result<(), Unsaved>,
+) -> Result<(), Message> {
+    let (id, signal) = state.queue.start_save();
+    // Sent each time another percent is written.
+    let mut percent = 0;
+    let saved = save(&signal, &mut |saved: f32| {
+        if (saved * 100.0) as u32 > percent {
+            percent = (saved * 100.0) as u32;
+            emit(app, "job-progress", JobProgress { id, saved });
+        }
+    });
+    state.queue.end_save(id);
+    saved.map_err(|unsaved| {
+        let path = path.to_string_lossy().into_owned();
+        match unsaved {
+            Unsaved::Cancelled => Error::SaveCancelled(path).into(),
+            Unsaved::Failed(reason) => Error::Save { path, reason }.into(),
+        }
+    })
+}
+
+// Write the manifest of an export, rendered from the image at `source`
+// and `inputs`, beside it if the settings ask for one.
+fn write_manifest(
+    app: &tauri::AppHandle,
+    state: &State,
+    path: &Path,
+    source: Option<&str>,
+    inputs: manifest::Inputs,
+    options: &RenderOptions,
+) -> Result<(), Message> {
+    if !state
+        .settings
+        .read()
+        .expect("Could not lock state mutex")
+        .manifests
+    {
+        return Ok(());
+    }
+    let written = manifest::write(path, source, inputs, options)?;
+    scope::allow(app, &written);
+    Ok(())
+}
+
+// The inputs of a render from `source` besides the base image.
+fn manifest_inputs(source: &Source) -> manifest::Inputs<'_> {
+    manifest::Inputs {
+        secondary_image: source.secondary_image.as_deref(),
+        mask: source.mask.as_deref(),
+        density_map: source.density_map.as_deref(),
+        labels: source.labels.as_deref(),
+        depth: source.depth.as_deref(),
+    }
+}
+
+// Render and save to `path` like `save_image`, and save a JPEG of it
+// scaled to EXPORT_PREVIEW wide beside it, from the same render. The
+// preview is named after the export as saved and replaces any earlier
+// one.
+#[tauri::command]
+async fn save_with_preview(
+    path: &str,
+    options: RenderOptions,
+    on_conflict: Option<OnConflict>,
+    create_dirs: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<SavedWithPreview, Message> {
+    let options = options.validate()?;
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_write(path)?;
+    let source = source(&state);
+    check_full_image(&source)?;
+    let path = naming::resolve(
+        &path.to_string_lossy(),
+        on_conflict.unwrap_or_default(),
+        create_dirs.unwrap_or(false),
+    )?;
+    let preview = naming::preview_path(&path);
+    let img = render_source(&state, &source, &options, JobKind::Export)?;
+    let saved = save_export(&app, &state, &img, &path).and_then(|_| {
+        save_preview(&img, &preview).map_err(|err| {
+            Message::from(Error::Save {
+                path: preview.to_string_lossy().into_owned(),
+                reason: err.to_string(),
+            })
         })
-        .collect()
+    });
+    state.canvases.recycle_image(img);
+    saved?;
+    scope::allow(&app, &path);
+    scope::allow(&app, &preview);
+    write_manifest(
+        &app,
+        &state,
+        &path,
+        source.path.as_deref(),
+        manifest_inputs(&source),
+        &options,
+    )?;
+    Ok(SavedWithPreview {
+        path: path.to_string_lossy().into_owned(),
+        preview: preview.to_string_lossy().into_owned(),
+    })
+}
+
+// Save `img` as a JPEG at most EXPORT_PREVIEW wide, over white since JPEG
+// has no alpha.
+fn save_preview(img: &RgbaImage, path: &Path) -> image::ImageResult<()> {
+    let width = img.width().min(EXPORT_PREVIEW);
+    let height = (img.height() as u64 * width as u64 / img.width().max(1) as u64).max(1);
+    let scaled = imageops::resize(img, width, height as u32, imageops::FilterType::Lanczos3);
+    let flat = image::RgbImage::from_fn(width, height as u32, |x, y| {
+        let [r, g, b, a] = scaled.get_pixel(x, y).0;
+        let over = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        image::Rgb([over(r), over(g), over(b)])
+    });
+    flat.save_with_format(path, image::ImageFormat::Jpeg)
 }
 
-fn dots(cell: u32, x: u32, y: u32, t: f32, canvas: &mut Canvas) {
-    Shape::new()
-        .circle(
-            pt(x * cell + cell / 2, y * cell + cell / 2),
-            t * cell as f32 * 0.6036, // mid way between sqrt(2)/2 and 1/2.
-        )
-        .fill_color(*BLACK)
-        .no_stroke()
-        .draw(canvas);
-}
-
-fn vline(cell: u32, x: u32, y: u32, t: f32, canvas: &mut Canvas) {
-    let g = (t * cell as f32).round() as u32;
-    let gs = bool_vec(cell as usize, g as usize);
-    for l in 0..cell {
-        if gs[l as usize] {
-            Shape::new()
-                .line(
-                    pt(x * cell + l, y * cell),
-                    pt(x * cell + l, y * cell + cell),
-                )
-                .no_fill()
-                .stroke_color(*BLACK)
-                .stroke_weight(1.0)
-                .draw(canvas);
+// Save the marks of a render as vectors, by the extension of `path`: a
+// JSON or CSV list of primitives for other tools, SVG, PDF, G-code or
+// DXF. The paper, effects and border are left out. With `optimize` the
+// marks are merged and ordered for plotting first. With a `sheet` the
+// drawings are laid out on paper of that size in physical units. The
+// plot time is estimated at `speed`, or a typical plotter's.
+#[tauri::command]
+async fn save_marks(
+    path: &str,
+    options: RenderOptions,
+    optimize: Option<Optimize>,
+    sheet: Option<Sheet>,
+    speed: Option<PlotSpeed>,
+    on_conflict: Option<OnConflict>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<SaveReport, Message> {
+    let options = options.validate()?;
+    scope::check(&app, path)?;
+    let path = sandbox(&state)
+        .check_write(path)?
+        .to_string_lossy()
+        .into_owned();
+    let path = path.as_str();
+    let sheet = sheet.map(Sheet::validate).transpose()?;
+    let format = vector_format(path)?;
+    let source = source(&state);
+    check_full_image(&source)?;
+    let mut list = render::display_list(&planes(&source, &options), &options);
+    if let Some(optimize) = &optimize {
+        list = optimize::optimize(list, optimize);
+    }
+    let background = Some(render::background(&options).0).filter(|color| color[3] > 0);
+    let page = sheet.map(|sheet| Page::new(&list, &sheet));
+    let path = write_vectors(
+        &app,
+        &state,
+        path,
+        &list.encode(format, background, page.as_ref()),
+        on_conflict,
+    )?;
+    write_manifest(
+        &app,
+        &state,
+        Path::new(&path),
+        source.path.as_deref(),
+        manifest_inputs(&source),
+        &options,
+    )?;
+    Ok(SaveReport {
+        path,
+        plot: estimate(&list, page.as_ref(), speed),
+    })
+}
+
+// Save a drawing split between the pens of a plotter, one file per pen
+// named after `path` with the pen added, like "plot-dark.svg". Each file
+// has the same registration marks so the passes line up. Returns where
+// each was saved and how long it takes to plot, in the order to plot them.
+#[tauri::command]
+async fn save_pen_layers(
+    path: &str,
+    options: RenderOptions,
+    split: PenSplit,
+    optimize: Option<Optimize>,
+    sheet: Option<Sheet>,
+    speed: Option<PlotSpeed>,
+    on_conflict: Option<OnConflict>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<SaveReport>, Message> {
+    let options = options.validate()?;
+    scope::check(&app, path)?;
+    let path = sandbox(&state)
+        .check_write(path)?
+        .to_string_lossy()
+        .into_owned();
+    let path = path.as_str();
+    let sheet = sheet.map(Sheet::validate).transpose()?;
+    let format = vector_format(path)?;
+    let source = source(&state);
+    check_full_image(&source)?;
+    let planes = planes(&source, &options);
+    let layers = match split {
+        PenSplit::Color => pens::by_color(render::display_list(&planes, &options)),
+        PenSplit::Luminance { pens: n } => {
+            let passes = pens::passes(&planes, n);
+            let count = passes.len();
+            passes
+                .iter()
+                .enumerate()
+                .map(|(k, pass)| {
+                    (
+                        pens::pass_name(k, count),
+                        render::display_list(pass, &options),
+                    )
+                })
+                .collect()
         }
+    };
+    let (names, lists): (Vec<String>, Vec<_>) = layers
+        .into_iter()
+        .map(|(name, list)| {
+            let list = match &optimize {
+                Some(optimize) => optimize::optimize(list, optimize),
+                None => list,
+            };
+            (name, pens::with_registration(list))
+        })
+        .unzip();
+    // Shared so the registration marks land in the same place on every
+    // sheet.
+    let page = sheet.map(|sheet| Page::shared(&lists, &sheet));
+    names
+        .iter()
+        .zip(&lists)
+        .map(|(name, list)| {
+            // A plotter draws on the paper it is given, there is no
+            // background to draw.
+            let encoded = list.encode(format, None, page.as_ref());
+            let path = write_vectors(
+                &app,
+                &state,
+                &naming::suffixed(path, name),
+                &encoded,
+                on_conflict,
+            )?;
+            write_manifest(
+                &app,
+                &state,
+                Path::new(&path),
+                source.path.as_deref(),
+                manifest_inputs(&source),
+                &options,
+            )?;
+            Ok(SaveReport {
+                path,
+                plot: estimate(list, page.as_ref(), speed),
+            })
+        })
+        .collect()
+}
+
+// Save a risograph separation: a master per ink, black marks on white to
+// print from its drum, named after `path` with the layer added, and at
+// `path` itself a preview of the print in the inks. Each master is drawn
+// in its layer's style from its channel of the photo. Returns the paths
+// saved to, the masters in printing order and then the preview.
+#[tauri::command]
+async fn save_riso(
+    path: &str,
+    options: RenderOptions,
+    riso: Riso,
+    on_conflict: Option<OnConflict>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<String>, Message> {
+    let options = options.validate()?;
+    scope::check(&app, path)?;
+    let path = sandbox(&state)
+        .check_write(path)?
+        .to_string_lossy()
+        .into_owned();
+    let path = path.as_str();
+    let riso = riso.validate()?;
+    let source = source(&state);
+    check_full_image(&source)?;
+    let planes = planes(&source, &options);
+    let pool = state
+        .pools
+        .lock()
+        .expect("Could not lock state mutex")
+        .for_kind(JobKind::Export);
+    let masters: Vec<RgbaImage> = riso
+        .layers
+        .iter()
+        .map(|layer| {
+            // A master is one ink, plain black marks in a single style.
+            let options = RenderOptions {
+                style: layer.style,
+                layers: Vec::new(),
+                gradient_map: None,
+                invert_output: false,
+                transparent: false,
+                ..options.clone()
+            };
+            let planes = riso::separate(&source.base_image, &planes, layer);
+            generate(
+                &planes,
+                &options,
+                &Signal::default(),
+                &pool,
+                &state.canvases,
+            )
+            .expect("An unsignalled render can not be interrupted")
+        })
+        .collect();
+    let mut saved = Vec::with_capacity(masters.len() + 1);
+    for (master, layer) in masters.iter().zip(&riso.layers) {
+        saved.push(save_png(
+            &app,
+            &state,
+            &naming::suffixed(path, &layer.name),
+            master,
+            on_conflict,
+        )?);
     }
+    saved.push(save_png(
+        &app,
+        &state,
+        path,
+        &riso::print(&masters, &riso),
+        on_conflict,
+    )?);
+    masters
+        .into_iter()
+        .for_each(|master| state.canvases.recycle_image(master));
+    for path in &saved {
+        write_manifest(
+            &app,
+            &state,
+            Path::new(path),
+            source.path.as_deref(),
+            manifest_inputs(&source),
+            &options,
+        )?;
+    }
+    Ok(saved)
+}
+
+// Files derived from a path the user picked, by a suffix or a new name on
+// conflict, are written next to it and added to the scope.
+fn save_png(
+    app: &tauri::AppHandle,
+    state: &State,
+    path: &str,
+    img: &RgbaImage,
+    on_conflict: Option<OnConflict>,
+) -> Result<String, Message> {
+    let path = sandbox(state).check_write(path)?;
+    let path = naming::resolve(
+        &path.to_string_lossy(),
+        on_conflict.unwrap_or_default(),
+        false,
+    )?;
+    save_export(app, state, img, &path)?;
+    scope::allow(app, &path);
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn estimate(list: &DisplayList, page: Option<&Page>, speed: Option<PlotSpeed>) -> PlotEstimate {
+    let mm_per_pixel = page.map_or(gcode::MM_PER_PIXEL, Page::mm_per_pixel);
+    plot::estimate(list, mm_per_pixel, &speed.unwrap_or_default())
+}
+
+fn vector_format(path: &str) -> Result<VectorFormat, Error> {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(VectorFormat::from_extension)
+        .ok_or_else(|| Error::UnsupportedFormat(path.to_string()))
+}
+
+fn write_vectors(
+    app: &tauri::AppHandle,
+    state: &State,
+    path: &str,
+    contents: &[u8],
+    on_conflict: Option<OnConflict>,
+) -> Result<String, Message> {
+    let path = sandbox(state).check_write(path)?;
+    let path = naming::resolve(
+        &path.to_string_lossy(),
+        on_conflict.unwrap_or_default(),
+        false,
+    )?;
+    save_tracked(app, state, &path, |signal, progress| {
+        encode::write(contents, &path, signal, progress)
+    })?;
+    scope::allow(app, &path);
+    Ok(path.to_string_lossy().into_owned())
 }
 
-fn hline(cell: u32, x: u32, y: u32, t: f32, canvas: &mut Canvas) {
-    let g = (t * cell as f32).round() as u32;
-    let gs = bool_vec(cell as usize, g as usize);
-    for l in 0..cell {
-        if gs[l as usize] {
-            Shape::new()
-                .line(
-                    pt(x * cell, y * cell + l),
-                    pt(x * cell + cell, y * cell + l),
-                )
-                .no_fill()
-                .stroke_color(*BLACK)
-                .stroke_weight(1.0)
-                .draw(canvas);
+// Add a render to the job queue. Previews are sent back with a
+// "render-complete" event, exports are written to `path`. With a file name
+// `template` the path is a folder and the file is named from the template.
+// The path is resolved against existing files with `on_conflict` when the
+// job is queued, like `save_image` does.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn enqueue_render(
+    options: RenderOptions,
+    kind: JobKind,
+    path: Option<String>,
+    template: Option<String>,
+    on_conflict: Option<OnConflict>,
+    create_dirs: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<u64, Message> {
+    let options = options.validate()?;
+    let source = source(&state);
+    check_image(&source)?;
+    if kind != JobKind::Preview {
+        if path.is_none() {
+            return Err(Error::NoExportPath.into());
+        }
+        check_full_image(&source)?;
+    }
+    let path = match (path, template) {
+        (Some(dir), Some(template)) => {
+            let name = naming::file_name(&template, source.path.as_deref(), &options, "png")?;
+            Some(Path::new(&dir).join(name).to_string_lossy().into_owned())
+        }
+        (path, _) => path,
+    };
+    let path = match path {
+        Some(path) => {
+            scope::check(&app, &path)?;
+            let path = sandbox(&state).check_write(&path)?;
+            let path = naming::resolve(
+                &path.to_string_lossy(),
+                on_conflict.unwrap_or_default(),
+                create_dirs.unwrap_or(false),
+            )?;
+            scope::allow(&app, &path);
+            Some(path.to_string_lossy().into_owned())
         }
+        None => None,
+    };
+    let changed = state.queue.push(kind, options, path, source.clone());
+    let id = changed.last().map(|info| info.id).unwrap_or_default();
+    for info in changed {
+        emit(&app, "job-state", info);
+    }
+    if kind != JobKind::Preview {
+        save_queue(&state);
     }
+    Ok(id)
 }
 
-fn cross(cell: u32, x: u32, y: u32, t: f32, canvas: &mut Canvas) {
-    let c = Color::from_rgba8(0, 0, 0, 127);
-    let g = (t * cell as f32).round() as u32;
-    let gs = bool_vec(cell as usize, g as usize);
-    for l in 0..cell {
-        if gs[l as usize] {
-            Shape::new()
-                .line(
-                    pt(x * cell + l, y * cell),
-                    pt(x * cell + l, y * cell + cell),
-                )
-                .no_fill()
-                .stroke_color(c)
-                .stroke_weight(1.0)
-                .draw(canvas);
+#[tauri::command]
+async fn cancel_job(
+    id: u64,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<(), Message> {
+    if state.queue.cancel_save(id) {
+        return Ok(());
+    }
+    let info = state.queue.cancel(id).ok_or(Error::NoJob(id))?;
+    emit(&app, "job-state", info);
+    save_queue(&state);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_queue(state: tauri::State<State>) -> Vec<JobInfo> {
+    state.queue.snapshot()
+}
+
+// Hold exports and batch jobs until `resume_queue`, previews still run.
+#[tauri::command]
+fn pause_queue(app: tauri::AppHandle, state: tauri::State<State>) {
+    state.queue.pause();
+    emit(&app, "queue-paused", true);
+}
+
+#[tauri::command]
+fn resume_queue(app: tauri::AppHandle, state: tauri::State<State>) {
+    state.queue.resume();
+    emit(&app, "queue-paused", false);
+}
+
+// Cancel every export and batch job that has not finished.
+#[tauri::command]
+fn abort_exports(app: tauri::AppHandle, state: tauri::State<State>) {
+    for info in state.queue.abort() {
+        emit(&app, "job-state", info);
+    }
+    save_queue(&state);
+}
+
+// Keep the pending exports and batch jobs on disk so a long batch can
+// carry on after a restart.
+fn save_queue(state: &State) {
+    let session = state.session.lock().expect("Could not lock state mutex");
+    if let Some(session) = session.as_ref() {
+        let jobs = state.queue.pending(|source| source.path.clone());
+        if let Err(err) = session.save_queue(jobs) {
+            eprintln!("The queue was not saved: {}", err);
         }
     }
-    let gs = bool_vec(cell as usize, g as usize);
-    for l in 0..cell {
-        if gs[l as usize] {
-            Shape::new()
-                .line(
-                    pt(x * cell, y * cell + l),
-                    pt(x * cell + cell, y * cell + l),
-                )
-                .no_fill()
-                .stroke_color(c)
-                .stroke_weight(1.0)
-                .draw(canvas);
+}
+
+// Queue the jobs left over from the last run, paused until the js side
+// resumes them, each with the image it renders from. The image of the
+// last job becomes the base image. Jobs whose image can't be opened, or
+// whose image or export path is outside the sandbox, are dropped.
+fn restore_queue(state: &State) {
+    let saved = {
+        let session = state.session.lock().expect("Could not lock state mutex");
+        match session.as_ref().and_then(|session| session.load_queue()) {
+            Some(saved) => saved,
+            None => return,
+        }
+    };
+    let mut sources: HashMap<String, Option<Arc<Source>>> = HashMap::new();
+    let mut last = None;
+    state.queue.pause();
+    for job in saved.jobs {
+        let image_path = match job.image_path.or_else(|| saved.image_path.clone()) {
+            Some(image_path) => image_path,
+            None => continue,
+        };
+        let path = match job.path.map(|path| sandbox(state).check_write(&path)) {
+            Some(Ok(path)) => Some(path.to_string_lossy().into_owned()),
+            Some(Err(err)) => {
+                eprintln!("{}", err);
+                continue;
+            }
+            None => None,
+        };
+        let source = sources.entry(image_path.clone()).or_insert_with(|| {
+            match sandbox(state)
+                .check_read(&image_path)
+                .and_then(|checked| open_image(&checked.to_string_lossy()))
+            {
+                Ok(img) => {
+                    let img = limit_source(state, img).0;
+                    Some(Arc::new(new_source(img, Some(image_path), None)))
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    None
+                }
+            }
+        });
+        if let Some(source) = source {
+            state
+                .queue
+                .push(job.kind, job.options, path, source.clone());
+            last = Some(source.clone());
         }
     }
+    if let Some(source) = last {
+        *state.source.write().expect("Could not lock state mutex") = source;
+        state.canvases.clear();
+    }
 }
 
-fn stipple(cell: u32, x: u32, y: u32, t: f32, rng: &mut SmallRng, canvas: &mut Canvas) {
-    let n = t * (cell * cell) as f32;
-    let ps = halton_seq(cell as f32, cell as f32, n as u32, rng.gen());
-    let qs = ps
-        .into_iter()
-        .map(|p| pt((x * cell) as f32 + p.x, (y * cell) as f32 + p.y));
-    for p in qs {
-        canvas.dot(p.x, p.y, *BLACK)
-    }
-}
-
-fn grid(cell: u32, x: u32, y: u32, t: f32, canvas: &mut Canvas) {
-    let s = (1.0 / t).clamp(1.0, cell as f32);
-    let x0 = (cell * x) as f32;
-    let y0 = (cell * y) as f32;
-    let mut i = x0;
-    while i < x0 + cell as f32 {
-        let mut j = y0;
-        while j < y0 + cell as f32 {
-            canvas.dot(i, j, *BLACK);
-            j += s;
+// Set the number of render threads, 0 uses one per core. Exports and
+// batch jobs can optionally run at below normal priority so the UI and
+// other apps stay responsive. Jobs already running keep their threads.
+#[tauri::command]
+fn set_render_threads(
+    n: usize,
+    low_priority_exports: Option<bool>,
+    state: tauri::State<State>,
+) -> Result<(), Message> {
+    let mut pools = state.pools.lock().expect("Could not lock state mutex");
+    let low_priority_exports = low_priority_exports.unwrap_or(pools.low_priority_exports);
+    *pools = Pools::new(n, low_priority_exports)?;
+    Ok(())
+}
+
+// The styles with names, descriptions and a small sample of each, so the
+// js side can offer whatever styles this build has.
+#[tauri::command]
+fn list_styles(state: tauri::State<State>) -> Vec<StyleInfo> {
+    let pool = state
+        .pools
+        .lock()
+        .expect("Could not lock state mutex")
+        .for_kind(JobKind::Preview);
+    catalog::styles(&pool, &state.canvases)
+}
+
+// Random options for a "surprise me" button, from any style or only from
+// `styles`, with a cell size the loaded image has memory for.
+#[tauri::command]
+fn randomize_params(
+    seed: Option<u64>,
+    styles: Option<Vec<Style>>,
+    state: tauri::State<State>,
+) -> Variant {
+    let source = source(&state);
+    let (width, height) = match check_image(&source) {
+        Ok(()) => source.base_image.dimensions(),
+        Err(_) => (W as u32, W as u32),
+    };
+    explore::randomize(
+        seed.unwrap_or_else(rand::random),
+        &styles.unwrap_or_default(),
+        width,
+        height,
+    )
+}
+
+// Options `t` of the way between two looks, for scrubbing from one to the
+// other.
+#[tauri::command]
+fn interpolate_params(a: RenderOptions, b: RenderOptions, t: f32) -> RenderOptions {
+    interpolate::interpolate(&a, &b, t)
+}
+
+// Render `steps` variants with the number at `name`, like "cell" or
+// "stroke.weight", going from `from` to `to`, side by side and labeled
+// with their values, to find the best value at a glance. The strip is
+// saved to `path` if there is one.
+#[tauri::command]
+async fn sweep_parameter(
+    name: &str,
+    from: f32,
+    to: f32,
+    steps: u32,
+    options: RenderOptions,
+    path: Option<&str>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<Picture, Message> {
+    if !(2..=sweep::MAX_STEPS).contains(&steps) {
+        return Err(Error::InvalidOptions(vec![FieldError {
+            field: "steps".to_string(),
+            problem: Problem::OutOfRange {
+                min: 2,
+                max: sweep::MAX_STEPS.into(),
+                value: steps.into(),
+            },
+        }])
+        .into());
+    }
+    let path = match path {
+        Some(path) => {
+            scope::check(&app, path)?;
+            Some(sandbox(&state).check_write(path)?)
         }
-        i += s;
+        None => None,
+    };
+    let mut tiles = Vec::with_capacity(steps as usize);
+    for value in sweep::values(from, to, steps) {
+        let (options, value) = sweep::with_value(&options, name, value)?;
+        let img = render_now(&state, &options.validate()?, JobKind::Preview)?;
+        tiles.push((sweep::label(value), img));
+    }
+    let strip = sweep::strip(&tiles);
+    tiles
+        .into_iter()
+        .for_each(|(_, img)| state.canvases.recycle_image(img));
+    if let Some(path) = path {
+        save_export(&app, &state, &strip, &path)?;
+        scope::allow(&app, &path);
     }
+    Ok(picture(&strip, preview_width(&state)))
 }
 
+// Render each panel of a collage from its own image in its own style into
+// its rectangle of one canvas. The collage is saved to `path` if there is
+// one.
 #[tauri::command]
-fn gen_image(cell: u32, style: Style, state: tauri::State<State>) -> Picture {
-    let img = generate(cell, style, state);
-    let scale = W / img.width() as f32;
-    let nwidth = (img.width() as f32 * scale) as u32;
-    let nhight = (img.height() as f32 * scale) as u32;
-    let new_img = imageops::resize(&img, nwidth, nhight, imageops::FilterType::Lanczos3);
-    Picture {
-        width: nwidth,
-        height: nhight,
-        data: new_img.into_vec(),
+async fn render_collage(
+    collage: Collage,
+    path: Option<&str>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, State>,
+) -> Result<Picture, Message> {
+    let collage = collage.validate()?;
+    let path = match path {
+        Some(path) => {
+            scope::check(&app, path)?;
+            Some(sandbox(&state).check_write(path)?)
+        }
+        None => None,
+    };
+    let mut canvas = collage.canvas();
+    for panel in &collage.panels {
+        scope::check(&app, &panel.path)?;
+        let file = sandbox(&state).check_read(&panel.path)?;
+        let img = Arc::new(collage::fit(
+            &open_image(&file.to_string_lossy())?,
+            panel.rect,
+            panel.options.cell,
+        ));
+        let planes =
+            seg_core::planes::build(&img, &panel.options, seg_core::planes::Inputs::default());
+        let render = render_planes(&state, &img, &planes, &panel.options, JobKind::Export)?;
+        collage::place(&mut canvas, &render, panel.rect);
+        state.canvases.recycle_image(render);
+    }
+    if let Some(path) = path {
+        save_export(&app, &state, &canvas, &path)?;
+        scope::allow(&app, &path);
     }
+    Ok(picture(&canvas, preview_width(&state)))
+}
+
+#[tauri::command]
+fn list_palettes() -> Vec<Palette> {
+    color::palettes()
 }
 
-fn generate(cell: u32, style: Style, state: tauri::State<State>) -> RgbaImage {
-    let mut rng = SmallRng::from_entropy();
-    let in_img = state
-        .base_image
+#[tauri::command]
+fn random_palette(seed: u64) -> Palette {
+    color::random_palette(seed)
+}
+
+// The dominant colors of the base image, dark to light.
+#[tauri::command]
+fn extract_palette(n: usize, state: tauri::State<State>) -> Result<Palette, Message> {
+    let source = source(&state);
+    check_image(&source)?;
+    Ok(Palette {
+        name: "Extracted",
+        colors: color::extract_palette(&source.base_image, n),
+    })
+}
+
+// The latest preview as someone with a color vision deficiency would see
+// it, to check that a palette still reads.
+#[tauri::command]
+fn simulate_cvd(kind: Cvd, state: tauri::State<State>) -> Result<Picture, Message> {
+    let latest = state
+        .latest_preview
         .lock()
         .expect("Could not lock state mutex")
         .clone();
-    let width = cell * in_img.width();
-    let height = cell * in_img.height();
-    let mut canvas = Canvas::new(width, height);
-    canvas.fill(*WHITE);
-    for x in 0..in_img.width() {
-        for y in 0..in_img.height() {
-            let pixel = in_img.get_pixel(x, y);
-            let color =
-                (0.2989 * pixel[0] as f32 + 0.5870 * pixel[1] as f32 + 0.1140 * pixel[2] as f32)
-                    / 255.0;
-            let t = 1.0 - color;
-            match style {
-                Style::Dots => dots(cell, x, y, t, &mut canvas),
-                Style::VLines => vline(cell, x, y, t, &mut canvas),
-                Style::HLines => hline(cell, x, y, t, &mut canvas),
-                Style::Cross => cross(cell, x, y, t, &mut canvas),
-                Style::Stipple => stipple(cell, x, y, t, &mut rng, &mut canvas),
-                Style::Grid => grid(cell, x, y, t, &mut canvas),
-                Style::Multi => {
-                    let hue = pixel_to_hue(pixel);
-                    match hue {
-                        15..=45 => cross(cell, x, y, t, &mut canvas), // orange
-                        46..=75 => stipple(cell, x, y, t, &mut rng, &mut canvas), // yellow
-                        76..=165 => vline(cell, x, y, t, &mut canvas), // green
-                        166..=255 => dots(cell, x, y, t, &mut canvas), // blue
-                        256..=345 => grid(cell, x, y, t, &mut canvas), // purple
-                        _ => hline(cell, x, y, t, &mut canvas),       // red
-                    }
-                }
+    let Some((_, img)) = latest else {
+        return Err(Error::NoRender.into());
+    };
+    let mut simulated = (*img).clone();
+    cvd::simulate(&mut simulated, kind);
+    let picture = picture(&simulated, preview_width(&state));
+    state.canvases.recycle_image(simulated);
+    Ok(picture)
+}
+
+// Compare two renders, or a render and the source, to judge which
+// parameters keep the most of the subject. The heat map is the size of `a`.
+#[tauri::command]
+fn compare_renders(
+    a: Subject,
+    b: Subject,
+    metric: Metric,
+    state: tauri::State<State>,
+) -> Result<Comparison, Message> {
+    let image = |subject: Subject| -> Result<RgbaImage, Message> {
+        match subject {
+            Subject::Source => {
+                let source = source(&state);
+                check_image(&source)?;
+                Ok(source.base_image.clone())
             }
+            Subject::Render(options) => render_now(&state, &options.validate()?, JobKind::Preview),
+        }
+    };
+    let (a, b) = (image(a)?, image(b)?);
+    let (scores, map) = compare::compare(&a, &b, metric);
+    state.canvases.recycle_image(a);
+    state.canvases.recycle_image(b);
+    Ok(Comparison {
+        scores,
+        heat_map: picture(&map, preview_width(&state)),
+    })
+}
+
+// The cell size and tone of the marks that draw the base image most like
+// the photo, searched from `options`.
+#[tauri::command]
+fn auto_tune(options: RenderOptions, state: tauri::State<State>) -> Result<Tuned, Message> {
+    let options = options.validate()?;
+    let source = source(&state);
+    check_image(&source)?;
+    let pool = state
+        .pools
+        .lock()
+        .expect("Could not lock state mutex")
+        .for_kind(JobKind::Preview);
+    Ok(autotune::tune(
+        &source.base_image,
+        &options,
+        &pool,
+        &state.canvases,
+    ))
+}
+
+// Save the state of the controls with the current image and a proof of
+// the latest preview, to recover them if the app crashes.
+#[tauri::command]
+fn autosave(controls: serde_json::Value, state: tauri::State<State>) -> Result<(), Message> {
+    let image_path = source(&state).path.clone();
+    let latest = state
+        .latest_preview
+        .lock()
+        .expect("Could not lock state mutex")
+        .clone();
+    let session = state.session.lock().expect("Could not lock state mutex");
+    match session.as_ref() {
+        Some(session) => {
+            Ok(session.save(controls, image_path, latest.as_ref().map(|(_, img)| &**img))?)
         }
+        None => Ok(()),
     }
-    let out_img = canvas.into();
-    out_img
 }
 
+// The app settings, as saved in the app data dir.
 #[tauri::command]
-fn save_image(path: &str, cell: u32, style: Style, state: tauri::State<State>) {
-    let gen = generate(cell, style, state);
-    let _ = gen.save(path);
+fn get_settings(state: tauri::State<State>) -> Settings {
+    state
+        .settings
+        .read()
+        .expect("Could not lock state mutex")
+        .clone()
 }
 
-fn bool_vec(n: usize, k: usize) -> Vec<bool> {
-    let mut rng = SmallRng::from_entropy();
-    let mut vec = vec![true; k];
-    vec.extend(vec![false; n - k]);
-    vec.shuffle(&mut rng);
-    vec
+// Change the settings, saving them for the next run and applying them now.
+// A "settings-changed" event carries them to the js side.
+#[tauri::command]
+fn update_settings(
+    settings: Settings,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<Settings, Message> {
+    let settings = settings.validate()?;
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| Error::NoDataFolder(err.to_string()))?;
+    settings.save(&dir)?;
+    apply_settings(&state, settings.clone())?;
+    emit(&app, "settings-changed", settings.clone());
+    Ok(settings)
 }
 
-fn pixel_to_hue(pixel: &Rgba<u8>) -> i32 {
-    let r = pixel[0] as f32 / 255.0;
-    let g = pixel[1] as f32 / 255.0;
-    let b = pixel[2] as f32 / 255.0;
+// Make the settings current, rebuilding the render threads if their
+// number changed.
+fn apply_settings(state: &State, settings: Settings) -> Result<(), String> {
+    {
+        let mut pools = state.pools.lock().expect("Could not lock state mutex");
+        if pools.threads != settings.threads {
+            *pools = Pools::new(settings.threads, pools.low_priority_exports)?;
+        }
+    }
+    *state.settings.write().expect("Could not lock state mutex") = settings;
+    Ok(())
+}
 
-    let max = r.max(g.max(b));
-    let min = r.min(g.min(b));
-    let delta = max - min;
+// Count a render in the usage stats, if the user has turned them on.
+fn record_usage(state: &State, options: &RenderOptions) {
+    if !state
+        .settings
+        .read()
+        .expect("Could not lock state mutex")
+        .usage_stats
+    {
+        return;
+    }
+    if let Some(usage) = state
+        .usage
+        .lock()
+        .expect("Could not lock state mutex")
+        .as_mut()
+    {
+        usage.record(options);
+    }
+}
 
-    if delta == 0.0 {
-        // Achromatic case (grey scale), hue is undefined
-        0
-    } else {
-        let hue = if max == r {
-            // Red is max
-            60.0 * (((g - b) / delta) % 6.0)
-        } else if max == g {
-            // Green is max
-            60.0 * (((b - r) / delta) + 2.0)
-        } else {
-            // Blue is max
-            60.0 * (((r - g) / delta) + 4.0)
+// How often each style and setting has been rendered, empty unless the
+// usage stats are on.
+#[tauri::command]
+fn get_usage_stats(state: tauri::State<State>) -> UsageStats {
+    state
+        .usage
+        .lock()
+        .expect("Could not lock state mutex")
+        .as_ref()
+        .map(|usage| usage.stats.clone())
+        .unwrap_or_default()
+}
+
+// Save a copy of the usage stats as JSON to `path`.
+#[tauri::command]
+fn export_usage_stats(
+    path: &str,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<String, Message> {
+    scope::check(&app, path)?;
+    let path = sandbox(&state).check_write(path)?;
+    let json =
+        serde_json::to_string_pretty(&get_usage_stats(state)).map_err(|err| err.to_string())?;
+    std::fs::write(&path, json)
+        .map(|_| path.to_string_lossy().into_owned())
+        .map_err(|err| {
+            Message::from(Error::Save {
+                path: path.to_string_lossy().into_owned(),
+                reason: err.to_string(),
+            })
+        })
+}
+
+// Forget the usage stats, removing them from disk.
+#[tauri::command]
+fn delete_usage_stats(state: tauri::State<State>) -> Result<(), Message> {
+    let mut usage = state.usage.lock().expect("Could not lock state mutex");
+    match usage.as_mut() {
+        Some(usage) => Ok(usage.delete()?),
+        None => Ok(()),
+    }
+}
+
+fn preview_width(state: &State) -> u32 {
+    state
+        .settings
+        .read()
+        .expect("Could not lock state mutex")
+        .preview_width
+}
+
+// The language of messages sent from here on.
+#[tauri::command]
+fn set_locale(locale: Locale) {
+    messages::set_locale(locale);
+}
+
+// The session autosaved before the last run crashed, if it did.
+#[tauri::command]
+fn recover_session(state: tauri::State<State>) -> Option<Saved> {
+    let session = state.session.lock().expect("Could not lock state mutex");
+    session.as_ref().and_then(|session| session.recover())
+}
+
+// Data sent to the js side when a preview job finishes. Either the scaled
+// picture itself or, for shared previews, where to fetch the pixels. The
+// render and its thumbnail can also be loaded as PNGs from the seg protocol
+// at `png` and `thumb`.
+#[derive(Clone, Serialize)]
+struct RenderComplete {
+    id: u64,
+    picture: Option<Picture>,
+    shared: Option<SharedPicture>,
+    png: String,
+    thumb: String,
+}
+
+// A full size preview served as raw RGBA by the seg protocol at `path`.
+#[derive(Clone, Serialize)]
+struct SharedPicture {
+    width: u32,
+    height: u32,
+    path: String,
+}
+
+// Answer a request on the seg protocol:
+//   preview/{id}       raw RGBA of the latest preview, so large previews
+//                      skip the JSON encoding of an event and the copy
+//                      into it,
+//   render/{id}.png    the latest preview as a PNG,
+//   thumb/{id}.png     the thumbnail of a recent preview,
+//   slide/{id}.png     the slide on screen in a slideshow,
+//   candidate/{id}.png a candidate of the current evolve generation.
+// Ids are never reused so responses can be cached for good.
+fn serve(app: &tauri::AppHandle, uri: &str) -> Served {
+    let state = app.state::<State>();
+    let path = protocol_path(uri);
+    let Some((route, id)) = path.split_once('/') else {
+        return not_found();
+    };
+    let Ok(id) = id.trim_end_matches(".png").parse::<u64>() else {
+        return not_found();
+    };
+    let latest = || {
+        state
+            .latest_preview
+            .lock()
+            .expect("Could not lock state mutex")
+            .clone()
+            .filter(|(latest_id, _)| *latest_id == id)
+    };
+    match route {
+        "preview" => match latest() {
+            Some((_, img)) => Ok(Response::builder()
+                .header("Content-Type", "application/octet-stream")
+                .header("Access-Control-Allow-Origin", "*")
+                .body(img.as_raw().clone())?),
+            None => not_found(),
+        },
+        "render" => match latest() {
+            Some((_, img)) => png(&img),
+            None => not_found(),
+        },
+        "slide" => {
+            let slideshow = state.slideshow.lock().expect("Could not lock state mutex");
+            match &slideshow.current {
+                Some((slide_id, img)) if *slide_id == id => png(img),
+                _ => not_found(),
+            }
+        }
+        "candidate" => {
+            let evolution = state.evolution.lock().expect("Could not lock state mutex");
+            match evolution.candidates.iter().find(|(c, _, _)| *c == id) {
+                Some((_, _, img)) => png(img),
+                None => not_found(),
+            }
+        }
+        "thumb" => {
+            let history = state.history.lock().expect("Could not lock state mutex");
+            match history.iter().find(|(thumb_id, _)| *thumb_id == id) {
+                Some((_, thumb)) => png(thumb),
+                None => not_found(),
+            }
+        }
+        _ => not_found(),
+    }
+}
+
+type Served = Result<Response<Vec<u8>>, Box<dyn std::error::Error>>;
+
+fn png(img: &RgbaImage) -> Served {
+    let bytes = encode_png(img)?;
+    Ok(Response::builder()
+        .header("Content-Type", "image/png")
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Cache-Control", "max-age=31536000, immutable")
+        .body(bytes)?)
+}
+
+fn encode_png(img: &RgbaImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+    Ok(bytes)
+}
+
+fn not_found() -> Served {
+    Ok(Response::builder().status(404).body(Vec::new())?)
+}
+
+// The path of a protocol uri without the scheme and host, which differ
+// between platforms.
+fn protocol_path(uri: &str) -> &str {
+    let rest = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    let path = rest.split_once('/').map_or("", |(_, path)| path);
+    path.split(['?', '#']).next().unwrap_or_default()
+}
+
+// Keep a finished preview and a thumbnail of it for the seg protocol, then
+// send it, or for shared previews where to find it, to the js side.
+fn keep_preview(app: &tauri::AppHandle, state: &State, task: &Task<Source>, img: RgbaImage) {
+    let id = task.id;
+    let thumb_height = (img.height() as u64 * THUMB as u64 / img.width().max(1) as u64).max(1);
+    let thumb = imageops::resize(
+        &img,
+        THUMB,
+        thumb_height as u32,
+        imageops::FilterType::Triangle,
+    );
+    {
+        let mut history = state.history.lock().expect("Could not lock state mutex");
+        history.push_back((id, thumb));
+        while history.len() > MAX_HISTORY {
+            history.pop_front();
+        }
+    }
+    let (picture, shared) = if task.options.shared_preview {
+        let shared = SharedPicture {
+            width: img.width(),
+            height: img.height(),
+            path: format!("preview/{}", id),
         };
+        (None, Some(shared))
+    } else {
+        (Some(picture(&img, preview_width(state))), None)
+    };
+    let replaced = state
+        .latest_preview
+        .lock()
+        .expect("Could not lock state mutex")
+        .replace((id, Arc::new(img)));
+    // Reuse the buffer of the old preview unless a request still holds it.
+    if let Some(old) = replaced.and_then(|(_, old)| Arc::try_unwrap(old).ok()) {
+        state.canvases.recycle_image(old);
+    }
+    let complete = RenderComplete {
+        id,
+        picture,
+        shared,
+        png: format!("render/{}.png", id),
+        thumb: format!("thumb/{}.png", id),
+    };
+    // Websocket clients get the event without the pixels.
+    #[cfg(feature = "http-api")]
+    ws::broadcast(
+        "render-complete",
+        &RenderComplete {
+            id,
+            picture: None,
+            shared: complete.shared.clone(),
+            png: complete.png.clone(),
+            thumb: complete.thumb.clone(),
+        },
+    );
+    let _ = app.emit("render-complete", complete);
+}
 
-        let hue = hue.round() as i32;
-        if hue < 0 {
-            hue + 360
-        } else {
-            hue
+// Send an event to the js side, and with the http api to the websocket
+// clients too.
+fn emit<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    #[cfg(feature = "http-api")]
+    ws::broadcast(event, &payload);
+    let _ = app.emit(event, payload);
+}
+
+// Runs queued jobs one at a time for the lifetime of the app.
+fn render_worker(app: tauri::AppHandle) {
+    let state = app.state::<State>();
+    loop {
+        let (task, info) = state.queue.next();
+        emit(&app, "job-state", info);
+        let (result, error) = run_task(&app, &state, &task);
+        if let Some(info) = state.queue.finish(task.id, result, error) {
+            emit(&app, "job-state", info);
+        }
+        if task.kind != JobKind::Preview {
+            save_queue(&state);
         }
     }
 }
+
+fn run_task(
+    app: &tauri::AppHandle,
+    state: &State,
+    task: &Task<Source>,
+) -> (Result<(), Interrupt>, Option<Message>) {
+    let source = task.source.clone();
+    crash::note(&task.options);
+    let planes = planes(&source, &task.options);
+    let pool = state
+        .pools
+        .lock()
+        .expect("Could not lock state mutex")
+        .for_kind(task.kind);
+    let img = match generate(&planes, &task.options, &task.signal, &pool, &state.canvases) {
+        Ok(img) => img,
+        Err(interrupt) => return (Err(interrupt), None),
+    };
+    record_usage(state, &task.options);
+    let finished = match post::finish(
+        &img,
+        &source.base_image,
+        &planes,
+        &task.options,
+        task.kind != JobKind::Preview,
+    ) {
+        Ok(finished) => finished,
+        Err(err) => {
+            state.canvases.recycle_image(img);
+            return (Ok(()), Some(err.into()));
+        }
+    };
+    if task.kind == JobKind::Preview {
+        let out_img = match finished {
+            Some(finished) => {
+                state.canvases.recycle_image(img);
+                finished
+            }
+            None => img,
+        };
+        keep_preview(app, state, task, out_img);
+        return (Ok(()), None);
+    }
+    let out_img = finished.as_ref().unwrap_or(&img);
+    let result = match &task.path {
+        Some(path) => {
+            // Sent each time another percent is written.
+            let mut percent = 0;
+            let saved = encode::save(out_img, Path::new(path), &task.signal, |saved| {
+                if (saved * 100.0) as u32 > percent {
+                    percent = (saved * 100.0) as u32;
+                    emit(app, "job-progress", JobProgress { id: task.id, saved });
+                }
+            });
+            match saved {
+                Ok(()) => (
+                    Ok(()),
+                    write_manifest(
+                        app,
+                        state,
+                        Path::new(path),
+                        source.path.as_deref(),
+                        manifest_inputs(&source),
+                        &task.options,
+                    )
+                    .err(),
+                ),
+                Err(Unsaved::Cancelled) => (Err(Interrupt::Cancelled), None),
+                Err(Unsaved::Failed(reason)) => (
+                    Ok(()),
+                    Some(Message::from(Error::Save {
+                        path: path.clone(),
+                        reason,
+                    })),
+                ),
+            }
+        }
+        None => (Ok(()), Some(Error::NoExportPath.into())),
+    };
+    state.canvases.recycle_image(img);
+    result
+}
@@ -0,0 +1,142 @@
+use image::RgbaImage;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::depth::DepthMap;
+use crate::error::Error;
+use crate::labels::Labels;
+use crate::mask::{DensityMap, Mask};
+use crate::naming;
+use crate::RenderOptions;
+
+// What made an export, saved beside it so a print can be traced back to
+// its source and settings and the file checked against its checksum.
+#[derive(Serialize)]
+pub struct Manifest<'a> {
+    pub app_version: &'static str,
+    // The file name of the export, without its folder.
+    pub file: String,
+    // Of the bytes of the export, in hex.
+    pub sha256: String,
+    // The image the export was rendered from, if it came from a file.
+    pub source: Option<SourceFile>,
+    pub inputs: InputHashes,
+    pub options: &'a RenderOptions,
+}
+
+#[derive(Serialize)]
+pub struct SourceFile {
+    pub path: String,
+    // `None` if the file can no longer be read, as when it was moved.
+    pub sha256: Option<String>,
+}
+
+// What else the export was rendered with besides the source image and the
+// options. Masks are painted in the app and have no file, so each input is
+// recorded by the SHA-256 of its contents as they were used.
+#[derive(Clone, Copy, Default)]
+pub struct Inputs<'a> {
+    pub secondary_image: Option<&'a RgbaImage>,
+    pub mask: Option<&'a Mask>,
+    pub density_map: Option<&'a DensityMap>,
+    pub labels: Option<&'a Labels>,
+    pub depth: Option<&'a DepthMap>,
+}
+
+// The hashes of the inputs, in hex, `None` for those not used.
+#[derive(Serialize)]
+pub struct InputHashes {
+    pub secondary_image: Option<String>,
+    pub mask: Option<String>,
+    pub density_map: Option<String>,
+    pub labels: Option<String>,
+    pub depth: Option<String>,
+}
+
+impl Inputs<'_> {
+    // Each input is hashed with its size, so the same values laid out
+    // differently hash differently.
+    pub fn hashes(&self) -> InputHashes {
+        InputHashes {
+            secondary_image: self
+                .secondary_image
+                .map(|img| digest(img.width(), img.height(), img.as_raw())),
+            mask: self
+                .mask
+                .map(|mask| digest(mask.width, mask.height, &mask.data)),
+            density_map: self.density_map.map(|map| {
+                let bytes: Vec<u8> = map.data.iter().flat_map(|v| v.to_le_bytes()).collect();
+                digest(map.width, map.height, &bytes)
+            }),
+            labels: self.labels.map(|labels| {
+                let colors: Vec<u8> = labels.colors.iter().flatten().copied().collect();
+                digest(
+                    labels.width,
+                    labels.height,
+                    &[colors.as_slice(), labels.index()].concat(),
+                )
+            }),
+            depth: self
+                .depth
+                .map(|depth| digest(depth.width, depth.height, depth.data())),
+        }
+    }
+}
+
+// Write the manifest of the export at `path`, rendered with `options` from
+// the image at `source` and `inputs`, returning where it was written.
+pub fn write(
+    path: &Path,
+    source: Option<&str>,
+    inputs: Inputs,
+    options: &RenderOptions,
+) -> Result<PathBuf, Error> {
+    let manifest = Manifest {
+        app_version: env!("CARGO_PKG_VERSION"),
+        file: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        sha256: sha256(path).map_err(|err| Error::Open {
+            path: path.display().to_string(),
+            reason: err.to_string(),
+        })?,
+        source: source.map(|source| SourceFile {
+            path: source.to_string(),
+            sha256: sha256(Path::new(source)).ok(),
+        }),
+        inputs: inputs.hashes(),
+        options,
+    };
+    let manifest_path = naming::manifest_path(path);
+    let unsaved = |reason: String| Error::Save {
+        path: manifest_path.display().to_string(),
+        reason,
+    };
+    let file = File::create(&manifest_path).map_err(|err| unsaved(err.to_string()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &manifest)
+        .map_err(|err| unsaved(err.to_string()))?;
+    Ok(manifest_path)
+}
+
+fn digest(width: u32, height: u32, data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(width.to_le_bytes());
+    hasher.update(height.to_le_bytes());
+    hasher.update(data);
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The SHA-256 of a file in lowercase hex.
+pub fn sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
@@ -0,0 +1,140 @@
+use crate::error::Error;
+use crate::planes::Planes;
+
+// Lightness over which a luminance mask fades from full to none, so the
+// edge of the subject is not cut with a hard line.
+const FEATHER: f32 = 0.05;
+
+// How strongly marks are drawn at each source pixel, 0 skips them and 255
+// draws them at full darkness. A mask of a different size than the source
+// is stretched over it.
+#[derive(Clone)]
+pub struct Mask {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Mask {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Result<Mask, Error> {
+        if width == 0 || height == 0 || data.len() != width as usize * height as usize {
+            return Err(Error::MaskSize {
+                width,
+                height,
+                values: data.len(),
+            });
+        }
+        Ok(Mask {
+            width,
+            height,
+            data,
+        })
+    }
+
+    // The mask in [0, 1] at source pixel (x, y) of a `width` by `height`
+    // source.
+    fn at(&self, x: u32, y: u32, width: u32, height: u32) -> f32 {
+        self.data[stretched(self.width, self.height, x, y, width, height)] as f32 / 255.0
+    }
+}
+
+// Multipliers for the darkness of the source, to locally boost or reduce
+// the marks, like darkening the eyes of a portrait or quieting a busy
+// background. 1 leaves the source as it is. Stretched over the source like
+// a mask.
+#[derive(Clone)]
+pub struct DensityMap {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl DensityMap {
+    pub fn new(width: u32, height: u32, data: Vec<f32>) -> Result<DensityMap, Error> {
+        if width == 0 || height == 0 || data.len() != width as usize * height as usize {
+            return Err(Error::DensityMapSize {
+                width,
+                height,
+                values: data.len(),
+            });
+        }
+        if let Some(&bad) = data.iter().find(|m| !m.is_finite() || **m < 0.0) {
+            return Err(Error::BadDensity(bad));
+        }
+        Ok(DensityMap {
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+// The index of the value of a `map_width` by `map_height` map over source
+// pixel (x, y) of a `width` by `height` source, nearest neighbor.
+pub(crate) fn stretched(
+    map_width: u32,
+    map_height: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> usize {
+    let mx = (x as u64 * map_width as u64 / width as u64) as u32;
+    let my = (y as u64 * map_height as u64 / height as u64) as u32;
+    (my * map_width + mx) as usize
+}
+
+// A mask that leaves out the near white background of a product shot or
+// studio portrait: pixels lighter than `threshold` in [0, 1] get no marks.
+pub fn mask_from_luminance(planes: &Planes, threshold: f32) -> Mask {
+    let threshold = threshold.clamp(0.0, 1.0);
+    let data = planes
+        .luma
+        .iter()
+        .map(|t| {
+            let lightness = 1.0 - t;
+            let k = ((threshold - lightness) / FEATHER).clamp(0.0, 1.0);
+            (k * 255.0).round() as u8
+        })
+        .collect();
+    Mask {
+        width: planes.width,
+        height: planes.height,
+        data,
+    }
+}
+
+// The planes with the density of the marks scaled by the mask. For a
+// negative the marks are drawn for lightness, so it is lightness that is
+// scaled.
+pub fn apply(planes: &Planes, mask: &Mask, invert_output: bool) -> Planes {
+    scale(planes, invert_output, |x, y| {
+        mask.at(x, y, planes.width, planes.height)
+    })
+}
+
+// The planes with the density of the marks multiplied by the map and
+// clamped to full darkness, before any style reads them.
+pub fn apply_density(planes: &Planes, map: &DensityMap, invert_output: bool) -> Planes {
+    scale(planes, invert_output, |x, y| {
+        map.data[stretched(map.width, map.height, x, y, planes.width, planes.height)]
+    })
+}
+
+fn scale(planes: &Planes, invert_output: bool, factor: impl Fn(u32, u32) -> f32) -> Planes {
+    let width = planes.width;
+    let luma = planes
+        .luma
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let m = factor(i as u32 % width, i as u32 / width);
+            if invert_output {
+                1.0 - ((1.0 - t) * m).min(1.0)
+            } else {
+                (t * m).min(1.0)
+            }
+        })
+        .collect();
+    planes.with_luma(luma)
+}
@@ -0,0 +1,537 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::error::{Error, Problem};
+
+// Languages the messages are written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+// The locale for every message the backend sends, set from the js side.
+static LOCALE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::Fr,
+        _ => Locale::En,
+    }
+}
+
+// Message templates keyed by code, with `{param}` filled in from the
+// params of the message.
+fn template(locale: Locale, code: &str) -> &'static str {
+    match (locale, code) {
+        (Locale::En, "image_not_loaded") => "No image has been loaded",
+        (Locale::Fr, "image_not_loaded") => "Aucune image n'a été chargée",
+        (Locale::En, "empty_image") => "The image at {path} is empty",
+        (Locale::Fr, "empty_image") => "L'image {path} est vide",
+        (Locale::En, "empty_pattern") => "The image is empty",
+        (Locale::Fr, "empty_pattern") => "L'image est vide",
+        (Locale::En, "invalid_options") => "The render options are invalid:\n{fields}",
+        (Locale::Fr, "invalid_options") => "Les options de rendu ne sont pas valides :\n{fields}",
+        (Locale::En, "open_failed") => "The file at {path} could not be opened: {reason}",
+        (Locale::Fr, "open_failed") => "Le fichier {path} n'a pas pu être ouvert : {reason}",
+        (Locale::En, "save_failed") => "The file at {path} could not be saved: {reason}",
+        (Locale::Fr, "save_failed") => "Le fichier {path} n'a pas pu être enregistré : {reason}",
+        (Locale::En, "launch_failed") => "The file at {path} could not be shown: {reason}",
+        (Locale::Fr, "launch_failed") => "Le fichier {path} n'a pas pu être affiché : {reason}",
+        (Locale::En, "unsupported_format") => "The file at {path} is not a supported type",
+        (Locale::Fr, "unsupported_format") => "Le type du fichier {path} n'est pas pris en charge",
+        (Locale::En, "no_job") => "There is no job with id {id}",
+        (Locale::Fr, "no_job") => "Il n'y a pas de tâche numéro {id}",
+        (Locale::En, "not_allowed") => "Seg has not been given access to {path}",
+        (Locale::Fr, "not_allowed") => "Seg n'a pas reçu l'accès à {path}",
+        (Locale::En, "no_export_path") => "Export and batch jobs need a path to save to",
+        (Locale::Fr, "no_export_path") => {
+            "Les exports et les lots ont besoin d'un chemin où enregistrer"
+        }
+        (Locale::En, "still_loading") => "The image is still loading, export once it has",
+        (Locale::Fr, "still_loading") => {
+            "L'image est encore en cours de chargement, exportez une fois chargée"
+        }
+        (Locale::En, "foreign_request") => "Only requests from apps on this computer are accepted",
+        (Locale::Fr, "foreign_request") => {
+            "Seules les requêtes des applications de cet ordinateur sont acceptées"
+        }
+        (Locale::En, "save_cancelled") => "The save to {path} was cancelled",
+        (Locale::Fr, "save_cancelled") => "L'enregistrement dans {path} a été annulé",
+        (Locale::En, "no_render") => "There is no render yet, generate one first",
+        (Locale::Fr, "no_render") => "Il n'y a pas encore de rendu, générez-en un d'abord",
+        (Locale::En, "no_folder") => "There is no folder at {path}",
+        (Locale::Fr, "no_folder") => "Il n'y a pas de dossier {path}",
+        (Locale::En, "no_images") => "There are no images in {path}",
+        (Locale::Fr, "no_images") => "Il n'y a pas d'images dans {path}",
+        (Locale::En, "no_favorites") => "Pick at least one candidate to breed from",
+        (Locale::Fr, "no_favorites") => "Choisissez au moins un candidat à croiser",
+        (Locale::En, "output_in_hot_folder") => "The output folder must not be the hot folder",
+        (Locale::Fr, "output_in_hot_folder") => {
+            "Le dossier de sortie ne doit pas être le dossier surveillé"
+        }
+        (Locale::En, "downscaled") => {
+            "The image was scaled down from {from} to {to} to stay under the source size limit"
+        }
+        (Locale::Fr, "downscaled") => {
+            "L'image a été réduite de {from} à {to} pour rester sous la taille limite des sources"
+        }
+        (Locale::En, "create_folder_failed") => {
+            "The folder at {path} could not be created: {reason}"
+        }
+        (Locale::Fr, "create_folder_failed") => "Le dossier {path} n'a pas pu être créé : {reason}",
+        (Locale::En, "remove_failed") => "The file at {path} could not be removed: {reason}",
+        (Locale::Fr, "remove_failed") => "Le fichier {path} n'a pas pu être supprimé : {reason}",
+        (Locale::En, "file_exists") => "The file at {path} already exists",
+        (Locale::Fr, "file_exists") => "Le fichier {path} existe déjà",
+        (Locale::En, "no_data_folder") => "The folder for Seg's files could not be found: {reason}",
+        (Locale::Fr, "no_data_folder") => {
+            "Le dossier des fichiers de Seg est introuvable : {reason}"
+        }
+        (Locale::En, "unclosed_template") => "The file name template {template} has an unclosed {",
+        (Locale::Fr, "unclosed_template") => {
+            "Le modèle de nom de fichier {template} a une { non fermée"
+        }
+        (Locale::En, "unknown_template_field") => {
+            "The file name template {template} has an unknown field {{field}}"
+        }
+        (Locale::Fr, "unknown_template_field") => {
+            "Le modèle de nom de fichier {template} a un champ inconnu {{field}}"
+        }
+        (Locale::En, "template_not_file_name") => {
+            "The file name template {template} does not make a file name"
+        }
+        (Locale::Fr, "template_not_file_name") => {
+            "Le modèle de nom de fichier {template} ne donne pas un nom de fichier"
+        }
+        (Locale::En, "mask_size") => "A {width} by {height} mask needs {needed} values, not {values}",
+        (Locale::Fr, "mask_size") => {
+            "Un masque de {width} sur {height} a besoin de {needed} valeurs, pas {values}"
+        }
+        (Locale::En, "density_map_size") => {
+            "A {width} by {height} density map needs {needed} values, not {values}"
+        }
+        (Locale::Fr, "density_map_size") => {
+            "Une carte de densité de {width} sur {height} a besoin de {needed} valeurs, pas {values}"
+        }
+        (Locale::En, "bad_density") => "Density multipliers must be numbers from 0 up, not {value}",
+        (Locale::Fr, "bad_density") => {
+            "Les multiplicateurs de densité doivent être des nombres à partir de 0, pas {value}"
+        }
+        (Locale::En, "too_many_labels") => {
+            "A label mask can have at most {max} colors, save it without anti-aliasing so each \
+             region is one flat color"
+        }
+        (Locale::Fr, "too_many_labels") => {
+            "Un masque d'étiquettes peut avoir au plus {max} couleurs, enregistrez-le sans \
+             anticrénelage pour que chaque région soit d'une seule couleur"
+        }
+        (Locale::En, "scan_too_small") => {
+            "A scan {width} by {height} pixels is too small to measure {steps} steps"
+        }
+        (Locale::Fr, "scan_too_small") => {
+            "Un scan de {width} sur {height} pixels est trop petit pour mesurer {steps} paliers"
+        }
+        (Locale::En, "font_not_file_name") => "The font {name} is not a file name",
+        (Locale::Fr, "font_not_file_name") => "La police {name} n'est pas un nom de fichier",
+        (Locale::En, "no_font") => "No font named {names} was found",
+        (Locale::Fr, "no_font") => "Aucune police nommée {names} n'a été trouvée",
+        (Locale::En, "qr_too_long") => {
+            "{length} characters are too many for a QR code, stamp a URL instead"
+        }
+        (Locale::Fr, "qr_too_long") => {
+            "{length} caractères sont trop pour un code QR, apposez plutôt une URL"
+        }
+        (Locale::En, "qr_too_big") => {
+            "The QR code needs {side} pixels a side and does not fit the image, use smaller modules"
+        }
+        (Locale::Fr, "qr_too_big") => {
+            "Le code QR a besoin de {side} pixels de côté et ne tient pas dans l'image, \
+             utilisez des modules plus petits"
+        }
+        (Locale::En, "not_a_number") => "must be a number, not {value}",
+        (Locale::Fr, "not_a_number") => "doit être un nombre, pas {value}",
+        (Locale::En, "negative") => "must not be negative, not {value}",
+        (Locale::Fr, "negative") => "ne doit pas être négatif, pas {value}",
+        (Locale::En, "not_positive") => "must be a positive number, not {value}",
+        (Locale::Fr, "not_positive") => "doit être un nombre positif, pas {value}",
+        (Locale::En, "out_of_range") => "must be from {min} to {max}, not {value}",
+        (Locale::Fr, "out_of_range") => "doit être entre {min} et {max}, pas {value}",
+        (Locale::En, "too_small") => "must be at least {min}, not {value}",
+        (Locale::Fr, "too_small") => "doit être au moins {min}, pas {value}",
+        (Locale::En, "too_large") => "must be at most {max}, not {value}",
+        (Locale::Fr, "too_large") => "doit être au plus {max}, pas {value}",
+        (Locale::En, "below_one") => "must be at least 1, not {value}",
+        (Locale::Fr, "below_one") => "doit être au moins 1, pas {value}",
+        (Locale::En, "positive_up_to") => "must be more than 0 and at most {max}, not {value}",
+        (Locale::Fr, "positive_up_to") => "doit être plus que 0 et au plus {max}, pas {value}",
+        (Locale::En, "above") => "must not be above {other}, {value} is above {limit}",
+        (Locale::Fr, "above") => "ne doit pas dépasser {other}, {value} dépasse {limit}",
+        (Locale::En, "empty") => "must not be empty",
+        (Locale::Fr, "empty") => "ne doit pas être vide",
+        (Locale::En, "not_a_font_name") => "must be the file name of a font, not {name}",
+        (Locale::Fr, "not_a_font_name") => "doit être le nom de fichier d'une police, pas {name}",
+        (Locale::En, "count") => "must have from {min} to {max} entries, not {count}",
+        (Locale::Fr, "count") => "doit avoir de {min} à {max} éléments, pas {count}",
+        (Locale::En, "not_single_style") => "must be a single style, not Multi, Tonal or Patches",
+        (Locale::Fr, "not_single_style") => {
+            "doit être un seul style, pas Multi, Tonal ou Patches"
+        }
+        (Locale::En, "not_a_folder") => "must be a folder, not {path}",
+        (Locale::Fr, "not_a_folder") => "doit être un dossier, pas {path}",
+        (Locale::En, "no_layer") => "has no layer {index}",
+        (Locale::Fr, "no_layer") => "n'a pas de calque {index}",
+        (Locale::En, "margin_too_wide") => {
+            "must leave room on the paper, not {margin} on {width} by {height}"
+        }
+        (Locale::Fr, "margin_too_wide") => {
+            "doit laisser de la place sur le papier, pas {margin} sur {width} par {height}"
+        }
+        (Locale::En, "off_canvas") => {
+            "must be a non empty part of the canvas, not {width} by {height} at {x}, {y}"
+        }
+        (Locale::Fr, "off_canvas") => {
+            "doit être une partie non vide du canevas, pas {width} sur {height} en {x}, {y}"
+        }
+        (Locale::En, "canvas_size") => {
+            "must be from 1 to {max} pixels a side, not {width} by {height}"
+        }
+        (Locale::Fr, "canvas_size") => {
+            "doit avoir de 1 à {max} pixels de côté, pas {width} sur {height}"
+        }
+        (Locale::En, "map_size") => "must have {needed} sizes for {width} by {height}, not {sizes}",
+        (Locale::Fr, "map_size") => {
+            "doit avoir {needed} tailles pour {width} sur {height}, pas {sizes}"
+        }
+        (Locale::En, "size_too_large") => "sizes must be at most {max}, not {size}",
+        (Locale::Fr, "size_too_large") => "les tailles doivent être au plus {max}, pas {size}",
+        (Locale::En, "points_not_numbers") => "must only have numbers for points",
+        (Locale::Fr, "points_not_numbers") => "ne doit avoir que des nombres comme points",
+        (Locale::En, "points_out_of_order") => "must have points in increasing order",
+        (Locale::Fr, "points_out_of_order") => "doit avoir des points dans l'ordre croissant",
+        (Locale::En, "not_a_setting") => "is not a setting",
+        (Locale::Fr, "not_a_setting") => "n'est pas un réglage",
+        (Locale::En, "setting_off") => "is off, turn it on to sweep it",
+        (Locale::Fr, "setting_off") => "est désactivé, activez-le pour le balayer",
+        (Locale::En, "not_numeric") => "is not a number",
+        (Locale::Fr, "not_numeric") => "n'est pas un nombre",
+        (Locale::En, "invalid") => "is invalid: {reason}",
+        (Locale::Fr, "invalid") => "n'est pas valide : {reason}",
+        // Errors without a code of their own are passed on as they are.
+        _ => "{text}",
+    }
+}
+
+fn fill(template: &str, params: &BTreeMap<&'static str, String>) -> String {
+    params
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+// An error as sent to the js side: a code and params to localize it with,
+// and the text in the current locale.
+#[derive(Clone, Debug, Serialize)]
+pub struct Message {
+    pub code: &'static str,
+    pub params: BTreeMap<&'static str, String>,
+    pub text: String,
+}
+
+impl Message {
+    pub fn new(code: &'static str, params: BTreeMap<&'static str, String>) -> Self {
+        Message {
+            code,
+            text: fill(template(locale(), code), &params),
+            params,
+        }
+    }
+}
+
+impl Error {
+    // The catalog code and params of the error, worded in the current
+    // locale where a param is itself a message.
+    pub fn code(&self) -> (&'static str, BTreeMap<&'static str, String>) {
+        self.code_in(locale())
+    }
+
+    fn code_in(&self, locale: Locale) -> (&'static str, BTreeMap<&'static str, String>) {
+        match self {
+            Error::ImageNotLoaded => ("image_not_loaded", BTreeMap::new()),
+            Error::EmptyImage(Some(path)) => {
+                ("empty_image", BTreeMap::from([("path", path.clone())]))
+            }
+            Error::EmptyImage(None) => ("empty_pattern", BTreeMap::new()),
+            Error::InvalidOptions(errors) => {
+                let fields = errors
+                    .iter()
+                    .map(|err| format!("{} {}", err.field, err.problem.text(locale)))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ("invalid_options", BTreeMap::from([("fields", fields)]))
+            }
+            Error::Open { path, reason } => (
+                "open_failed",
+                BTreeMap::from([("path", path.clone()), ("reason", reason.clone())]),
+            ),
+            Error::Save { path, reason } => (
+                "save_failed",
+                BTreeMap::from([("path", path.clone()), ("reason", reason.clone())]),
+            ),
+            Error::Launch { path, reason } => (
+                "launch_failed",
+                BTreeMap::from([("path", path.clone()), ("reason", reason.clone())]),
+            ),
+            Error::UnsupportedFormat(path) => (
+                "unsupported_format",
+                BTreeMap::from([("path", path.clone())]),
+            ),
+            Error::NoJob(id) => ("no_job", BTreeMap::from([("id", id.to_string())])),
+            Error::NotAllowed(path) => ("not_allowed", BTreeMap::from([("path", path.clone())])),
+            Error::NoExportPath => ("no_export_path", BTreeMap::new()),
+            Error::NoRender => ("no_render", BTreeMap::new()),
+            Error::SaveCancelled(path) => {
+                ("save_cancelled", BTreeMap::from([("path", path.clone())]))
+            }
+            Error::StillLoading => ("still_loading", BTreeMap::new()),
+            Error::ForeignRequest => ("foreign_request", BTreeMap::new()),
+            Error::NoFolder(path) => ("no_folder", BTreeMap::from([("path", path.clone())])),
+            Error::NoImages(path) => ("no_images", BTreeMap::from([("path", path.clone())])),
+            Error::NoFavorites => ("no_favorites", BTreeMap::new()),
+            Error::OutputInHotFolder => ("output_in_hot_folder", BTreeMap::new()),
+            Error::CreateFolder { path, reason } => (
+                "create_folder_failed",
+                BTreeMap::from([("path", path.clone()), ("reason", reason.clone())]),
+            ),
+            Error::Remove { path, reason } => (
+                "remove_failed",
+                BTreeMap::from([("path", path.clone()), ("reason", reason.clone())]),
+            ),
+            Error::FileExists(path) => ("file_exists", BTreeMap::from([("path", path.clone())])),
+            Error::NoDataFolder(reason) => (
+                "no_data_folder",
+                BTreeMap::from([("reason", reason.clone())]),
+            ),
+            Error::UnclosedTemplate(template) => (
+                "unclosed_template",
+                BTreeMap::from([("template", template.clone())]),
+            ),
+            Error::UnknownTemplateField { template, field } => (
+                "unknown_template_field",
+                BTreeMap::from([("template", template.clone()), ("field", field.clone())]),
+            ),
+            Error::TemplateNotFileName(template) => (
+                "template_not_file_name",
+                BTreeMap::from([("template", template.clone())]),
+            ),
+            Error::MaskSize {
+                width,
+                height,
+                values,
+            } => ("mask_size", size_params(*width, *height, *values)),
+            Error::DensityMapSize {
+                width,
+                height,
+                values,
+            } => ("density_map_size", size_params(*width, *height, *values)),
+            Error::BadDensity(value) => (
+                "bad_density",
+                BTreeMap::from([("value", value.to_string())]),
+            ),
+            Error::TooManyLabels(max) => (
+                "too_many_labels",
+                BTreeMap::from([("max", max.to_string())]),
+            ),
+            Error::ScanTooSmall {
+                width,
+                height,
+                steps,
+            } => (
+                "scan_too_small",
+                BTreeMap::from([
+                    ("width", width.to_string()),
+                    ("height", height.to_string()),
+                    ("steps", steps.to_string()),
+                ]),
+            ),
+            Error::FontNotFileName(name) => (
+                "font_not_file_name",
+                BTreeMap::from([("name", name.clone())]),
+            ),
+            Error::NoFont(names) => ("no_font", BTreeMap::from([("names", names.clone())])),
+            Error::QrTooLong(length) => (
+                "qr_too_long",
+                BTreeMap::from([("length", length.to_string())]),
+            ),
+            Error::QrTooBig(side) => ("qr_too_big", BTreeMap::from([("side", side.to_string())])),
+        }
+    }
+
+    // The message in English, whatever the locale, for logs.
+    pub fn english(&self) -> String {
+        let (code, params) = self.code_in(Locale::En);
+        fill(template(Locale::En, code), &params)
+    }
+}
+
+// The params of a `width` by `height` map given `values` values.
+fn size_params(width: u32, height: u32, values: usize) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        ("width", width.to_string()),
+        ("height", height.to_string()),
+        ("needed", (width as usize * height as usize).to_string()),
+        ("values", values.to_string()),
+    ])
+}
+
+impl Problem {
+    // The catalog code and params of the problem.
+    pub fn code(&self) -> (&'static str, BTreeMap<&'static str, String>) {
+        match self {
+            Problem::NotANumber(value) => (
+                "not_a_number",
+                BTreeMap::from([("value", value.to_string())]),
+            ),
+            Problem::Negative(value) => {
+                ("negative", BTreeMap::from([("value", value.to_string())]))
+            }
+            Problem::NotPositive(value) => (
+                "not_positive",
+                BTreeMap::from([("value", value.to_string())]),
+            ),
+            Problem::OutOfRange { min, max, value } => (
+                "out_of_range",
+                BTreeMap::from([
+                    ("min", min.to_string()),
+                    ("max", max.to_string()),
+                    ("value", value.to_string()),
+                ]),
+            ),
+            Problem::TooSmall { min, value } => (
+                "too_small",
+                BTreeMap::from([("min", min.to_string()), ("value", value.to_string())]),
+            ),
+            Problem::TooLarge { max, value } => (
+                "too_large",
+                BTreeMap::from([("max", max.to_string()), ("value", value.to_string())]),
+            ),
+            Problem::BelowOne(value) => {
+                ("below_one", BTreeMap::from([("value", value.to_string())]))
+            }
+            Problem::PositiveUpTo { max, value } => (
+                "positive_up_to",
+                BTreeMap::from([("max", max.to_string()), ("value", value.to_string())]),
+            ),
+            Problem::Above {
+                other,
+                value,
+                limit,
+            } => (
+                "above",
+                BTreeMap::from([
+                    ("other", other.clone()),
+                    ("value", value.to_string()),
+                    ("limit", limit.to_string()),
+                ]),
+            ),
+            Problem::Empty => ("empty", BTreeMap::new()),
+            Problem::NotAFontName(name) => {
+                ("not_a_font_name", BTreeMap::from([("name", name.clone())]))
+            }
+            Problem::Count { min, max, count } => (
+                "count",
+                BTreeMap::from([
+                    ("min", min.to_string()),
+                    ("max", max.to_string()),
+                    ("count", count.to_string()),
+                ]),
+            ),
+            Problem::NotSingleStyle => ("not_single_style", BTreeMap::new()),
+            Problem::NotAFolder(path) => ("not_a_folder", BTreeMap::from([("path", path.clone())])),
+            Problem::NoLayer(index) => ("no_layer", BTreeMap::from([("index", index.to_string())])),
+            Problem::MarginTooWide {
+                margin,
+                width,
+                height,
+            } => (
+                "margin_too_wide",
+                BTreeMap::from([
+                    ("margin", margin.to_string()),
+                    ("width", width.to_string()),
+                    ("height", height.to_string()),
+                ]),
+            ),
+            Problem::OffCanvas {
+                x,
+                y,
+                width,
+                height,
+            } => (
+                "off_canvas",
+                BTreeMap::from([
+                    ("x", x.to_string()),
+                    ("y", y.to_string()),
+                    ("width", width.to_string()),
+                    ("height", height.to_string()),
+                ]),
+            ),
+            Problem::CanvasSize { max, width, height } => (
+                "canvas_size",
+                BTreeMap::from([
+                    ("max", max.to_string()),
+                    ("width", width.to_string()),
+                    ("height", height.to_string()),
+                ]),
+            ),
+            Problem::MapSize {
+                width,
+                height,
+                sizes,
+            } => (
+                "map_size",
+                BTreeMap::from([
+                    ("width", width.to_string()),
+                    ("height", height.to_string()),
+                    ("needed", (*width as usize * *height as usize).to_string()),
+                    ("sizes", sizes.to_string()),
+                ]),
+            ),
+            Problem::SizeTooLarge { max, size } => (
+                "size_too_large",
+                BTreeMap::from([("max", max.to_string()), ("size", size.to_string())]),
+            ),
+            Problem::PointsNotNumbers => ("points_not_numbers", BTreeMap::new()),
+            Problem::PointsOutOfOrder => ("points_out_of_order", BTreeMap::new()),
+            Problem::NotASetting => ("not_a_setting", BTreeMap::new()),
+            Problem::SettingOff => ("setting_off", BTreeMap::new()),
+            Problem::NotNumeric => ("not_numeric", BTreeMap::new()),
+            Problem::Invalid(reason) => ("invalid", BTreeMap::from([("reason", reason.clone())])),
+        }
+    }
+
+    // The problem worded in `locale`.
+    pub fn text(&self, locale: Locale) -> String {
+        let (code, params) = self.code();
+        fill(template(locale, code), &params)
+    }
+}
+
+impl From<Error> for Message {
+    fn from(err: Error) -> Message {
+        let (code, params) = err.code();
+        Message::new(code, params)
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Message {
+        Message::new("other", BTreeMap::from([("text", text)]))
+    }
+}
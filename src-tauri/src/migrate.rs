@@ -0,0 +1,63 @@
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::RenderOptions;
+
+// The version written with saved options. Bump it when a change to
+// `RenderOptions` would misread older payloads, and add the step that
+// upgrades them to `STEPS`.
+pub const CURRENT_VERSION: u64 = 1;
+
+// Step `i` upgrades a payload from version `i` to `i + 1`.
+const STEPS: [fn(&mut Map<String, Value>); CURRENT_VERSION as usize] = [
+    // Payloads saved before versioning, from when only `cell` and `style`
+    // were sent onwards, have every later field defaulted by serde and
+    // read the same as version 1.
+    |_| {},
+];
+
+// Saved options with the version they were written in.
+pub fn to_value(options: &RenderOptions) -> Result<Value, String> {
+    let mut value = serde_json::to_value(options).map_err(|err| err.to_string())?;
+    if let Value::Object(map) = &mut value {
+        map.insert("version".to_string(), CURRENT_VERSION.into());
+    }
+    Ok(value)
+}
+
+// Read saved options of any version, upgrading them step by step. A payload
+// without a version is treated as version 0.
+pub fn from_value(value: Value) -> Result<RenderOptions, String> {
+    let Value::Object(mut map) = value else {
+        return Err("Saved render options must be a json object".to_string());
+    };
+    let version = match map.remove("version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or(format!(
+            "The options version {} is not a whole number",
+            version
+        ))?,
+    };
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "The options were saved by a newer version of Seg, version {} is newer than {}",
+            version, CURRENT_VERSION
+        ));
+    }
+    for step in &STEPS[version as usize..] {
+        step(&mut map);
+    }
+    serde_json::from_value(Value::Object(map))
+        .map_err(|err| format!("The saved render options are not valid: {}", err))
+}
+
+// For `#[serde(with = "seg_core::migrate")]` on saved options fields.
+pub fn serialize<S: Serializer>(options: &RenderOptions, serializer: S) -> Result<S::Ok, S::Error> {
+    to_value(options)
+        .map_err(ser::Error::custom)?
+        .serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RenderOptions, D::Error> {
+    from_value(Value::deserialize(deserializer)?).map_err(de::Error::custom)
+}
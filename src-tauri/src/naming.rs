@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::{RenderOptions, Style};
+
+// Exported variants of one source get different names, and the settings
+// can be read back from the name.
+pub const DEFAULT_TEMPLATE: &str = "{name}_{style}_{cell}_{seed}.{ext}";
+
+pub fn style_name(style: Style) -> &'static str {
+    match style {
+        Style::Dots => "Dots",
+        Style::VLines => "VLines",
+        Style::HLines => "HLines",
+        Style::Cross => "Cross",
+        Style::Stipple => "Stipple",
+        Style::Grid => "Grid",
+        Style::Multi => "Multi",
+        Style::Tonal => "Tonal",
+        Style::Patches => "Patches",
+    }
+}
+
+// Fill in a file name template. `{name}` is the stem of the source file,
+// or "seg" when the source did not come from a file, `{style}`, `{cell}`
+// and `{seed}` come from the options and `{ext}` is the file type.
+pub fn file_name(
+    template: &str,
+    source: Option<&str>,
+    options: &RenderOptions,
+    ext: &str,
+) -> Result<String, Error> {
+    let name = source
+        .and_then(|path| Path::new(path).file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "seg".to_string());
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::UnclosedTemplate(template.to_string()))?;
+        let field = &rest[start + 1..start + end];
+        match field {
+            "name" => out.push_str(&name),
+            "style" => out.push_str(style_name(options.style)),
+            "cell" => out.push_str(&options.cell.to_string()),
+            "seed" => out.push_str(&options.seed.to_string()),
+            "ext" => out.push_str(ext),
+            _ => {
+                return Err(Error::UnknownTemplateField {
+                    template: template.to_string(),
+                    field: field.to_string(),
+                })
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    if out.is_empty() || out.contains(['/', '\\']) {
+        return Err(Error::TemplateNotFileName(template.to_string()));
+    }
+    Ok(out)
+}
+
+// What to do when a file is about to be saved over an existing one.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum OnConflict {
+    #[default]
+    Overwrite,
+    // Add " (1)", " (2)", ... to the file stem until the name is free.
+    Rename,
+    Error,
+}
+
+// `path` with `-suffix` added to its file stem, "plot.svg" becomes
+// "plot-dark.svg".
+pub fn suffixed(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, suffix),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+// The path of the preview saved beside an export, "foo.png" gets
+// "foo.preview.jpg".
+pub fn preview_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.preview.jpg", stem))
+}
+
+// The path of the manifest saved beside an export, "foo.png" gets
+// "foo.png.manifest.json", so exports of the same name in different
+// formats keep a manifest each.
+pub fn manifest_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.manifest.json", name))
+}
+
+// The path to actually save to, creating missing parent folders first if
+// `create_dirs` is set.
+pub fn resolve(path: &str, on_conflict: OnConflict, create_dirs: bool) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if create_dirs {
+            fs::create_dir_all(parent).map_err(|err| Error::CreateFolder {
+                path: parent.display().to_string(),
+                reason: err.to_string(),
+            })?;
+        } else if !parent.is_dir() {
+            return Err(Error::NoFolder(parent.display().to_string()));
+        }
+    }
+    if !path.exists() {
+        return Ok(path);
+    }
+    match on_conflict {
+        OnConflict::Overwrite => Ok(path),
+        OnConflict::Error => Err(Error::FileExists(path.display().to_string())),
+        OnConflict::Rename => {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let ext = path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_default();
+            (1..)
+                .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+                .find(|candidate| !candidate.exists())
+                .ok_or_else(|| Error::FileExists(path.display().to_string()))
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::display::{DisplayList, Primitive};
+use crate::smooth;
+
+// Lines closer than this to the same infinite line, in output pixels,
+// count as collinear, and gaps up to this are bridged when fusing them.
+const TOLERANCE: f32 = 0.01;
+// How far along the plot order 2-opt looks for a better join.
+const TWO_OPT_WINDOW: usize = 32;
+const TWO_OPT_ROUNDS: usize = 4;
+
+// How a drawing is tidied up for a plotter or cutter before saving. The
+// line styles draw one short segment per cell, in reading order, which
+// leaves a pen lifting and travelling far more than it draws.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Optimize {
+    // Fuse collinear lines of the same pen that touch into one.
+    pub merge: bool,
+    // Draw each mark next from the end of the one before, greedily
+    // picking the nearest and turning lines around when that is shorter.
+    pub order: bool,
+    // Then undo crossings in the travel between marks.
+    pub two_opt: bool,
+    // Join lines that run on from each other into smooth curves, straying
+    // at most this many output pixels from them, like the wobbling lines
+    // of a hand drawn render.
+    #[serde(default)]
+    pub smooth: Option<f32>,
+}
+
+pub fn optimize(list: DisplayList, optimize: &Optimize) -> DisplayList {
+    let mut primitives = list.primitives;
+    if let Some(tolerance) = optimize.smooth {
+        primitives = smooth::smooth(primitives, tolerance);
+    }
+    if optimize.merge {
+        primitives = merge(primitives);
+    }
+    if optimize.order {
+        primitives = order(primitives);
+        if optimize.two_opt {
+            two_opt(&mut primitives);
+        }
+    }
+    DisplayList { primitives, ..list }
+}
+
+// The lines of the same weight and color on the same infinite line, keyed
+// by its direction and offset from the origin.
+type LineKey = (i64, i64, u32, [u8; 4]);
+
+// Fuse the collinear lines that touch or overlap. Other marks are kept as
+// they are, ahead of the lines.
+fn merge(primitives: Vec<Primitive>) -> Vec<Primitive> {
+    let mut others = Vec::new();
+    let mut lines: HashMap<LineKey, (f32, f32, Vec<(f32, f32)>)> = HashMap::new();
+    for primitive in primitives {
+        let Primitive::Line {
+            from,
+            to,
+            weight,
+            color,
+        } = primitive
+        else {
+            others.push(primitive);
+            continue;
+        };
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let len = dx.hypot(dy);
+        if len == 0.0 {
+            others.push(primitive);
+            continue;
+        }
+        // A direction pointing right, or down when vertical, so a line and
+        // its reverse get the same key.
+        let (ux, uy) = if dx > TOLERANCE || (dx.abs() <= TOLERANCE && dy > 0.0) {
+            (dx / len, dy / len)
+        } else {
+            (-dx / len, -dy / len)
+        };
+        let offset = from[0] * -uy + from[1] * ux;
+        let key = (
+            (ux / TOLERANCE).round() as i64 * 1_000_000 + (uy / TOLERANCE).round() as i64,
+            (offset / TOLERANCE).round() as i64,
+            weight.to_bits(),
+            color,
+        );
+        let (s0, s1) = (from[0] * ux + from[1] * uy, to[0] * ux + to[1] * uy);
+        lines
+            .entry(key)
+            .or_insert((ux, uy, Vec::new()))
+            .2
+            .push((s0.min(s1), s0.max(s1)));
+    }
+    let mut keys: Vec<&LineKey> = lines.keys().collect();
+    // Hash order would make the saved file differ run to run.
+    keys.sort_by_key(|(direction, offset, weight, color)| (*direction, *offset, *weight, *color));
+    let mut merged = others;
+    for key in keys {
+        let (ux, uy, spans) = &lines[key];
+        let (_, offset, weight, color) = *key;
+        let offset = offset as f32 * TOLERANCE;
+        // The point on the line nearest the origin, spans are measured from it.
+        let (ox, oy) = (offset * -uy, offset * ux);
+        let mut spans = spans.clone();
+        spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut push = |(s0, s1): (f32, f32)| {
+            merged.push(Primitive::Line {
+                from: [ox + s0 * ux, oy + s0 * uy],
+                to: [ox + s1 * ux, oy + s1 * uy],
+                weight: f32::from_bits(weight),
+                color,
+            })
+        };
+        let mut current = spans[0];
+        for &(s0, s1) in &spans[1..] {
+            if s0 <= current.1 + TOLERANCE {
+                current.1 = current.1.max(s1);
+            } else {
+                push(current);
+                current = (s0, s1);
+            }
+        }
+        push(current);
+    }
+    merged
+}
+
+// Where the pen goes down for a mark and where it comes up.
+pub fn ends(primitive: &Primitive) -> ([f32; 2], [f32; 2]) {
+    match primitive {
+        Primitive::Line { from, to, .. } => (*from, *to),
+        Primitive::Curve {
+            start, segments, ..
+        } => (*start, segments.last().map_or(*start, |segment| segment[2])),
+        Primitive::Polygon { points, .. } => {
+            let first = points.first().copied().unwrap_or([0.0, 0.0]);
+            (first, first)
+        }
+        // The backends start circles at their rightmost point.
+        Primitive::Circle { x, y, radius, .. } | Primitive::Ring { x, y, radius, .. } => {
+            ([x + radius, *y], [x + radius, *y])
+        }
+        Primitive::Dot { x, y, .. } => ([*x, *y], [*x, *y]),
+    }
+}
+
+// Turn a line or curve around, other marks are drawn the same either way.
+fn reverse(primitive: &mut Primitive) {
+    match primitive {
+        Primitive::Line { from, to, .. } => std::mem::swap(from, to),
+        Primitive::Curve {
+            start, segments, ..
+        } => {
+            // The points in order, read back to front.
+            let mut points: Vec<[f32; 2]> = std::iter::once(*start)
+                .chain(segments.iter().flatten().copied())
+                .collect();
+            points.reverse();
+            *start = points[0];
+            *segments = points[1..]
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+// Greedy nearest neighbor from the top left corner. The marks are put in
+// a grid of buckets searched in growing rings, so each step only looks at
+// the marks near the pen.
+fn order(primitives: Vec<Primitive>) -> Vec<Primitive> {
+    let n = primitives.len();
+    if n < 2 {
+        return primitives;
+    }
+    let points: Vec<([f32; 2], [f32; 2])> = primitives.iter().map(ends).collect();
+    let (mut max_x, mut max_y) = (1.0f32, 1.0f32);
+    for (a, b) in &points {
+        max_x = max_x.max(a[0]).max(b[0]);
+        max_y = max_y.max(a[1]).max(b[1]);
+    }
+    // About one mark per bucket.
+    let bucket = ((max_x * max_y / n as f32).sqrt()).max(1.0);
+    let columns = (max_x / bucket) as usize + 1;
+    let rows = (max_y / bucket) as usize + 1;
+    let slot = |p: [f32; 2]| {
+        let c = ((p[0].max(0.0) / bucket) as usize).min(columns - 1);
+        let r = ((p[1].max(0.0) / bucket) as usize).min(rows - 1);
+        (c, r)
+    };
+    let mut grid: Vec<Vec<usize>> = vec![Vec::new(); columns * rows];
+    for (i, (a, b)) in points.iter().enumerate() {
+        let (c, r) = slot(*a);
+        grid[r * columns + c].push(i);
+        if slot(*b) != (c, r) {
+            let (c, r) = slot(*b);
+            grid[r * columns + c].push(i);
+        }
+    }
+    let mut used = vec![false; n];
+    let mut slots: Vec<Option<Primitive>> = primitives.into_iter().map(Some).collect();
+    let mut ordered = Vec::with_capacity(n);
+    let mut pen = [0.0, 0.0];
+    for _ in 0..n {
+        let (pc, pr) = slot(pen);
+        let mut best: Option<(f32, usize, bool)> = None;
+        let mut ring = 0;
+        loop {
+            let (c0, c1) = (pc.saturating_sub(ring), (pc + ring).min(columns - 1));
+            let (r0, r1) = (pr.saturating_sub(ring), (pr + ring).min(rows - 1));
+            for r in r0..=r1 {
+                for c in c0..=c1 {
+                    // Only the edge of the ring is new.
+                    if r != r0 && r != r1 && c != c0 && c != c1 {
+                        continue;
+                    }
+                    grid[r * columns + c].retain(|&i| !used[i]);
+                    for &i in &grid[r * columns + c] {
+                        let (a, b) = points[i];
+                        for (d, flip) in [(distance(pen, a), false), (distance(pen, b), true)] {
+                            if d < best.map_or(f32::INFINITY, |(nearest, _, _)| nearest) {
+                                best = Some((d, i, flip));
+                            }
+                        }
+                    }
+                }
+            }
+            // Anything beyond this ring is at least `ring` buckets away.
+            let covered = c0 == 0 && r0 == 0 && c1 == columns - 1 && r1 == rows - 1;
+            if best.is_some_and(|(d, _, _)| d <= ring as f32 * bucket) || covered {
+                break;
+            }
+            ring += 1;
+        }
+        let (_, i, flip) = best.expect("An unused mark is always left");
+        used[i] = true;
+        let mut primitive = slots[i].take().expect("Each mark is taken once");
+        if flip {
+            reverse(&mut primitive);
+        }
+        pen = ends(&primitive).1;
+        ordered.push(primitive);
+    }
+    ordered
+}
+
+// Reverse runs of the plot order, and each line in them, wherever that
+// shortens the travel in and out of the run. Only runs up to the window
+// long are tried, which finds most of the gain at a fraction of the cost.
+fn two_opt(primitives: &mut [Primitive]) {
+    let n = primitives.len();
+    for _ in 0..TWO_OPT_ROUNDS {
+        let mut improved = false;
+        for i in 0..n.saturating_sub(2) {
+            for j in i + 2..(i + TWO_OPT_WINDOW).min(n) {
+                let a = ends(&primitives[i]).1;
+                let (b_start, _) = ends(&primitives[i + 1]);
+                let (_, c_end) = ends(&primitives[j]);
+                let before = distance(a, b_start);
+                let after = distance(a, c_end);
+                let (before, after) = match primitives.get(j + 1) {
+                    Some(next) => {
+                        let d = ends(next).0;
+                        (before + distance(c_end, d), after + distance(b_start, d))
+                    }
+                    None => (before, after),
+                };
+                if after + TOLERANCE < before {
+                    primitives[i + 1..=j].reverse();
+                    primitives[i + 1..=j].iter_mut().for_each(reverse);
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
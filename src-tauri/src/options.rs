@@ -0,0 +1,566 @@
+use serde::{Deserialize, Serialize};
+
+use crate::blend::Blend;
+use crate::calibration::ToneCurve;
+use crate::caption::{self, Caption, MAX_SIZE as MAX_CAPTION_SIZE};
+use crate::cell_map::{self, CellMap};
+use crate::cmy::Cmy;
+use crate::color::GradientMap;
+use crate::composite::SourceBlend;
+use crate::depth::DepthOptions;
+use crate::error::{Error, FieldError, Problem};
+use crate::faces::FaceBoost;
+use crate::labels::Region;
+use crate::layers::Layer;
+use crate::layout::Layout;
+use crate::paper::Paper;
+use crate::patches::{NoisePatches, MAX_OCTAVES};
+use crate::pen::{Stroke, Wobble};
+use crate::post::{Aspect, Backdrop, Border, Effects, Placement, Trim};
+use crate::quadtree::Quadtree;
+use crate::raster::Antialias;
+use crate::render::{HueMapping, ToneMapping};
+use crate::slic::Slic;
+use crate::stamp::{Stamp, StampContent, MAX_MODULE};
+use crate::styles::{DotFill, DotRotation, DotShape, HatchDirection};
+use crate::symmetry::{Symmetry, MAX_FOLDS};
+
+// Larger cells make outputs too big to hold in memory for most sources.
+pub const MAX_CELL: u32 = 512;
+// The cell size when none is given, the same as the app starts with.
+pub const DEFAULT_CELL: u32 = 10;
+// The background of a portrait is at most this many times coarser than
+// its faces, like the sizes of a cell map.
+const MAX_FACE_BACKGROUND_CELL: u32 = cell_map::MAX_SIZE;
+// More superpixels than this are no more coherent than single pixels.
+pub const MAX_SEGMENTS: u32 = 10_000;
+// Beyond this the band canvases grow large for little gain in smoothness.
+const MAX_SUPERSAMPLE: u8 = 4;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Style {
+    #[default]
+    Dots,
+    VLines,
+    HLines,
+    Cross,
+    Stipple,
+    Grid,
+    Multi,
+    // A different style for the shadows, midtones and highlights, see
+    // `ToneMapping`.
+    Tonal,
+    // Styles in organic patches laid out by noise, see `NoisePatches`.
+    Patches,
+}
+
+impl Style {
+    pub const ALL: [Style; 9] = [
+        Style::Dots,
+        Style::VLines,
+        Style::HLines,
+        Style::Cross,
+        Style::Stipple,
+        Style::Grid,
+        Style::Multi,
+        Style::Tonal,
+        Style::Patches,
+    ];
+}
+
+// The parameters of a single render, shared by every command that renders
+// and the command line. Every field has a default so callers that predate
+// a field keep working.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct RenderOptions {
+    #[serde(default = "default_cell")]
+    pub cell: u32,
+    #[serde(default)]
+    pub style: Style,
+    // Seeds the random choices of the marks, the same seed gives the same
+    // picture.
+    #[serde(default)]
+    pub seed: u64,
+    // Per hue settings for Multi.
+    #[serde(default)]
+    pub multi: HueMapping,
+    // Per luminance band settings for Tonal.
+    #[serde(default)]
+    pub tonal: ToneMapping,
+    // Noise field settings for Patches.
+    #[serde(default)]
+    pub patches: NoisePatches,
+    #[serde(default)]
+    pub layout: Layout,
+    // How far each mark strays from the center of its cell, in [0, 1] where
+    // 1 is up to half a cell.
+    #[serde(default)]
+    pub jitter: f32,
+    // Color the marks by the luminance of the source.
+    pub gradient_map: Option<GradientMap>,
+    // Draw the cell statistics instead of marks, to see why a region
+    // renders the way it does.
+    #[serde(default)]
+    pub debug_overlay: bool,
+    // Mix in the secondary image.
+    pub blend: Option<Blend>,
+    // Draw white marks on black, sized by lightness instead of darkness.
+    #[serde(default)]
+    pub invert_output: bool,
+    // Leave the background fully transparent, for formats with alpha.
+    #[serde(default)]
+    pub transparent: bool,
+    // Send previews at full size through the seg protocol rather than
+    // scaled down inside the completion event.
+    #[serde(default)]
+    pub shared_preview: bool,
+    // Use adaptive cell sizes instead of a regular grid.
+    pub quadtree: Option<Quadtree>,
+    // Margins to cut from exports, previews are never trimmed.
+    pub trim: Option<Trim>,
+    // Draw the line styles as if by hand.
+    pub hand_drawn: Option<Wobble>,
+    // The width of the line styles' lines.
+    pub stroke: Option<Stroke>,
+    #[serde(default)]
+    pub dot_shape: DotShape,
+    #[serde(default)]
+    pub dot_fill: DotFill,
+    #[serde(default)]
+    pub dot_rotation: DotRotation,
+    #[serde(default)]
+    pub hatch_direction: HatchDirection,
+    pub paper: Option<Paper>,
+    pub effects: Option<Effects>,
+    pub border: Option<Border>,
+    // Styles drawn over each other in place of `style`, bottom first.
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    // Opacity in [0, 1] of the source photo drawn under the marks, which
+    // keeps faces readable at large cell sizes. 0 leaves it out.
+    #[serde(default)]
+    pub underlay_source: f32,
+    // Leave out a near white background: no marks where the source is
+    // lighter than this, in [0, 1].
+    pub auto_mask: Option<f32>,
+    // Show the source photo through the marks.
+    pub source_blend: Option<SourceBlend>,
+    // Draw faces in more detail than the background. Needs a build with
+    // the faces feature, otherwise no faces are found.
+    pub faces: Option<FaceBoost>,
+    // Change density, cell size or style with the depth of the source, if
+    // a depth map is loaded.
+    pub depth: Option<DepthOptions>,
+    // Have Multi pick a style per superpixel, from its mean color and
+    // texture, rather than per pixel from its hue. Gives more coherent
+    // regions.
+    pub segments: Option<Slic>,
+    // Styles for the regions of a label mask, by label color. Regions
+    // without one are drawn in `style`.
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    // Coarser cells in some regions, drawn at their own size and pieced
+    // together. A simpler alternative to `quadtree`.
+    pub cell_map: Option<CellMap>,
+    // Correct the darkness of every mark for how the output device prints
+    // it.
+    pub tone_curve: Option<ToneCurve>,
+    // Draw the photo in color as cyan, magenta and yellow dots in place of
+    // the style.
+    pub cmy: Option<Cmy>,
+    // Treat the source as pure black and white, black where its darkness
+    // is at least this, in [0, 1]. Cleaner for scanned drawings.
+    pub threshold_trace: Option<f32>,
+    // Smooth the edges of the marks.
+    #[serde(default)]
+    pub antialias: Antialias,
+    // Make every pixel of the finished render black or white, black where
+    // its darkness is at least this, in [0, 1]. For thermal printers and
+    // screen printing separations, where gray edges are unwanted.
+    pub one_bit: Option<f32>,
+    // Draw the marks this many times larger, 1 to 4, and average them back
+    // down, for smoother small dots and thin lines than antialiasing
+    // gives. 0 is the same as 1.
+    #[serde(default)]
+    pub supersample: u8,
+    // Repeat part of the marks over the rest of the output.
+    pub symmetry: Option<Symmetry>,
+    // Wrap marks that cross an edge of the output around to the opposite
+    // edge, so it tiles seamlessly for wallpaper and fabric.
+    #[serde(default)]
+    pub tileable: bool,
+    // Place the finished render on a canvas of another aspect ratio.
+    pub aspect: Option<Aspect>,
+    // Text under the artwork, on a margin added for it.
+    pub caption: Option<Caption>,
+    // A QR code in a corner of the export with its parameters or a link.
+    pub stamp: Option<Stamp>,
+}
+
+fn default_cell() -> u32 {
+    DEFAULT_CELL
+}
+
+impl RenderOptions {
+    // How many canvas pixels across each output pixel is drawn with.
+    pub fn supersample(&self) -> u32 {
+        self.supersample.max(1) as u32
+    }
+
+    // Whether rendering reads the hue plane.
+    pub fn needs_hue(&self) -> bool {
+        matches!(self.style, Style::Multi)
+            || self
+                .layers
+                .iter()
+                .any(|layer| matches!(layer.style, Style::Multi))
+            || self
+                .regions
+                .iter()
+                .any(|region| matches!(region.style, Style::Multi))
+            || self.depth.as_ref().is_some_and(|depth| {
+                [depth.near_style, depth.far_style]
+                    .iter()
+                    .any(|style| matches!(style, Some(Style::Multi)))
+            })
+            || matches!(self.dot_rotation, DotRotation::Hue)
+            || self.debug_overlay
+    }
+
+    // Check options that arrived from outside. Fractions are clamped to
+    // [0, 1], anything that can't be rendered, like a cell size out of range,
+    // a negative size or a number that is not finite, is reported with every
+    // other offending field.
+    pub fn validate(mut self) -> Result<Self, Error> {
+        let mut errors = Vec::new();
+        if !(1..=MAX_CELL).contains(&self.cell) {
+            errors.push(field_error(
+                "cell",
+                Problem::OutOfRange {
+                    min: 1,
+                    max: MAX_CELL.into(),
+                    value: self.cell.into(),
+                },
+            ));
+        }
+        unit(&mut errors, "jitter", &mut self.jitter);
+        let d = &self.multi.density;
+        for (name, value) in [
+            ("red", d.red),
+            ("orange", d.orange),
+            ("yellow", d.yellow),
+            ("green", d.green),
+            ("blue", d.blue),
+            ("purple", d.purple),
+        ] {
+            non_negative(&mut errors, &format!("multi.density.{}", name), value);
+        }
+        let tonal = &mut self.tonal;
+        unit(&mut errors, "tonal.shadows_below", &mut tonal.shadows_below);
+        unit(
+            &mut errors,
+            "tonal.highlights_above",
+            &mut tonal.highlights_above,
+        );
+        if tonal.shadows_below > tonal.highlights_above {
+            errors.push(field_error(
+                "tonal.shadows_below",
+                Problem::Above {
+                    other: "tonal.highlights_above".to_string(),
+                    value: tonal.shadows_below,
+                    limit: tonal.highlights_above,
+                },
+            ));
+        }
+        for (name, style) in [
+            ("shadows", tonal.shadows),
+            ("midtones", tonal.midtones),
+            ("highlights", tonal.highlights),
+        ] {
+            single_style(&mut errors, &format!("tonal.{}", name), style);
+        }
+        let patches = &self.patches;
+        if !patches.scale.is_finite() || patches.scale < 1.0 {
+            errors.push(field_error(
+                "patches.scale",
+                Problem::BelowOne(patches.scale),
+            ));
+        }
+        if !(1..=MAX_OCTAVES).contains(&patches.octaves) {
+            errors.push(field_error(
+                "patches.octaves",
+                Problem::OutOfRange {
+                    min: 1,
+                    max: MAX_OCTAVES.into(),
+                    value: patches.octaves.into(),
+                },
+            ));
+        }
+        if patches.styles.is_empty() {
+            errors.push(field_error("patches.styles", Problem::Empty));
+        }
+        for (i, &style) in patches.styles.iter().enumerate() {
+            single_style(&mut errors, &format!("patches.styles[{}]", i), style);
+        }
+        if let Layout::Polar { center } = self.layout {
+            finite(&mut errors, "layout.center[0]", center[0]);
+            finite(&mut errors, "layout.center[1]", center[1]);
+        }
+        if let Layout::Screen { angle } = self.layout {
+            finite(&mut errors, "layout.angle", angle);
+        }
+        if let Some(map) = &mut self.gradient_map {
+            for (i, stop) in map.stops.iter_mut().enumerate() {
+                unit(
+                    &mut errors,
+                    &format!("gradient_map.stops[{}].at", i),
+                    &mut stop.at,
+                );
+            }
+        }
+        if let Some(Blend::Mix { amount }) = &mut self.blend {
+            unit(&mut errors, "blend.amount", amount);
+        }
+        if let Some(quadtree) = &self.quadtree {
+            non_negative(&mut errors, "quadtree.threshold", quadtree.threshold);
+        }
+        if let Some(wobble) = &self.hand_drawn {
+            non_negative(&mut errors, "hand_drawn.amplitude", wobble.amplitude);
+            non_negative(&mut errors, "hand_drawn.frequency", wobble.frequency);
+        }
+        if let Some(stroke) = &self.stroke {
+            if finite(&mut errors, "stroke.weight", stroke.weight) && stroke.weight <= 0.0 {
+                errors.push(field_error(
+                    "stroke.weight",
+                    Problem::NotPositive(stroke.weight),
+                ));
+            }
+        }
+        if let DotFill::Bleed { bleed } = self.dot_fill {
+            non_negative(&mut errors, "dot_fill.bleed", bleed);
+        }
+        if let DotShape::Polygon { vertices } = &self.dot_shape {
+            for (i, [x, y]) in vertices.iter().enumerate() {
+                finite(&mut errors, &format!("dot_shape.vertices[{}][0]", i), *x);
+                finite(&mut errors, &format!("dot_shape.vertices[{}][1]", i), *y);
+            }
+        }
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            unit(
+                &mut errors,
+                &format!("layers[{}].opacity", i),
+                &mut layer.opacity,
+            );
+        }
+        unit(&mut errors, "underlay_source", &mut self.underlay_source);
+        if let Some(threshold) = &mut self.auto_mask {
+            unit(&mut errors, "auto_mask", threshold);
+        }
+        if let Some(level) = &mut self.threshold_trace {
+            unit(&mut errors, "threshold_trace", level);
+        }
+        if self.supersample > MAX_SUPERSAMPLE {
+            errors.push(field_error(
+                "supersample",
+                Problem::TooLarge {
+                    max: MAX_SUPERSAMPLE.into(),
+                    value: self.supersample.into(),
+                },
+            ));
+        }
+        if let Some(Symmetry::Rotational { folds, center, .. }) = self.symmetry {
+            if !(2..=MAX_FOLDS).contains(&folds) {
+                errors.push(field_error(
+                    "symmetry.folds",
+                    Problem::OutOfRange {
+                        min: 2,
+                        max: MAX_FOLDS.into(),
+                        value: folds.into(),
+                    },
+                ));
+            }
+            finite(&mut errors, "symmetry.center[0]", center[0]);
+            finite(&mut errors, "symmetry.center[1]", center[1]);
+        }
+        if let Some(aspect) = &self.aspect {
+            for (i, side) in aspect.ratio.into_iter().enumerate() {
+                if !side.is_finite() || side <= 0.0 {
+                    errors.push(field_error(
+                        &format!("aspect.ratio[{}]", i),
+                        Problem::NotPositive(side),
+                    ));
+                }
+            }
+            if let Placement::Thirds { column, row } = aspect.placement {
+                for (name, third) in [("column", column), ("row", row)] {
+                    if third > 2 {
+                        errors.push(field_error(
+                            &format!("aspect.placement.{}", name),
+                            Problem::OutOfRange {
+                                min: 0,
+                                max: 2,
+                                value: third.into(),
+                            },
+                        ));
+                    }
+                }
+            }
+            if let Backdrop::Blur { sigma } = aspect.background {
+                non_negative(&mut errors, "aspect.background.sigma", sigma);
+            }
+        }
+        if let Some(c) = &self.caption {
+            if !c.size.is_finite() || c.size <= 0.0 || c.size > MAX_CAPTION_SIZE {
+                errors.push(field_error(
+                    "caption.size",
+                    Problem::PositiveUpTo {
+                        max: MAX_CAPTION_SIZE,
+                        value: c.size,
+                    },
+                ));
+            }
+            if let Some(font) = c
+                .font
+                .as_deref()
+                .filter(|font| !caption::is_file_name(font))
+            {
+                errors.push(field_error(
+                    "caption.font",
+                    Problem::NotAFontName(font.to_string()),
+                ));
+            }
+        }
+        if let Some(stamp) = &self.stamp {
+            if !(1..=MAX_MODULE).contains(&stamp.module) {
+                errors.push(field_error(
+                    "stamp.module",
+                    Problem::OutOfRange {
+                        min: 1,
+                        max: MAX_MODULE.into(),
+                        value: stamp.module.into(),
+                    },
+                ));
+            }
+            if matches!(&stamp.content, StampContent::Url(url) if url.trim().is_empty()) {
+                errors.push(field_error("stamp.content", Problem::Empty));
+            }
+        }
+        if let Some(level) = &mut self.one_bit {
+            unit(&mut errors, "one_bit", level);
+        }
+        if let Some(blend) = &mut self.source_blend {
+            unit(&mut errors, "source_blend.opacity", &mut blend.opacity);
+        }
+        for (i, region) in self.regions.iter().enumerate() {
+            non_negative(
+                &mut errors,
+                &format!("regions[{}].density", i),
+                region.density,
+            );
+        }
+        if let Some(boost) = &self.faces {
+            non_negative(&mut errors, "faces.density", boost.density);
+            if !(1..=MAX_FACE_BACKGROUND_CELL).contains(&boost.background_cell) {
+                errors.push(field_error(
+                    "faces.background_cell",
+                    Problem::OutOfRange {
+                        min: 1,
+                        max: MAX_FACE_BACKGROUND_CELL.into(),
+                        value: boost.background_cell.into(),
+                    },
+                ));
+            }
+        }
+        if let Some(depth) = &mut self.depth {
+            non_negative(&mut errors, "depth.near_density", depth.near_density);
+            non_negative(&mut errors, "depth.far_density", depth.far_density);
+            for (field, size) in [
+                ("depth.near_cell", depth.near_cell),
+                ("depth.far_cell", depth.far_cell),
+            ] {
+                if !(1..=cell_map::MAX_SIZE).contains(&size) {
+                    errors.push(field_error(
+                        field,
+                        Problem::OutOfRange {
+                            min: 1,
+                            max: cell_map::MAX_SIZE.into(),
+                            value: size.into(),
+                        },
+                    ));
+                }
+            }
+            unit(&mut errors, "depth.split", &mut depth.split);
+        }
+        if let Some(slic) = &self.segments {
+            if !(1..=MAX_SEGMENTS).contains(&slic.count) {
+                errors.push(field_error(
+                    "segments.count",
+                    Problem::OutOfRange {
+                        min: 1,
+                        max: MAX_SEGMENTS.into(),
+                        value: slic.count.into(),
+                    },
+                ));
+            }
+            non_negative(&mut errors, "segments.compactness", slic.compactness);
+        }
+        if let Some(problem) = self.cell_map.as_ref().and_then(CellMap::problem) {
+            errors.push(field_error("cell_map", problem));
+        }
+        if let Some(cmy) = &self.cmy {
+            non_negative(&mut errors, "cmy.cyan", cmy.cyan);
+            non_negative(&mut errors, "cmy.magenta", cmy.magenta);
+            non_negative(&mut errors, "cmy.yellow", cmy.yellow);
+        }
+        if let Some(problem) = self.tone_curve.as_ref().and_then(ToneCurve::problem) {
+            errors.push(field_error("tone_curve", problem));
+        }
+        if let Some(paper) = &mut self.paper {
+            unit(&mut errors, "paper.intensity", &mut paper.intensity);
+        }
+        if let Some(effects) = &mut self.effects {
+            unit(&mut errors, "effects.vignette", &mut effects.vignette);
+            unit(&mut errors, "effects.grain", &mut effects.grain);
+            non_negative(&mut errors, "effects.blur", effects.blur);
+        }
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::InvalidOptions(errors))
+        }
+    }
+}
+
+fn field_error(field: &str, problem: Problem) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        problem,
+    }
+}
+
+fn finite(errors: &mut Vec<FieldError>, field: &str, value: f32) -> bool {
+    if !value.is_finite() {
+        errors.push(field_error(field, Problem::NotANumber(value)));
+    }
+    value.is_finite()
+}
+
+fn non_negative(errors: &mut Vec<FieldError>, field: &str, value: f32) {
+    if finite(errors, field, value) && value < 0.0 {
+        errors.push(field_error(field, Problem::Negative(value)));
+    }
+}
+
+// Multi, Tonal and Patches pick among styles, so they can't be picked.
+fn single_style(errors: &mut Vec<FieldError>, field: &str, style: Style) {
+    if matches!(style, Style::Multi | Style::Tonal | Style::Patches) {
+        errors.push(field_error(field, Problem::NotSingleStyle));
+    }
+}
+
+// A fraction, clamped into [0, 1].
+fn unit(errors: &mut Vec<FieldError>, field: &str, value: &mut f32) {
+    if finite(errors, field, *value) {
+        *value = value.clamp(0.0, 1.0);
+    }
+}
@@ -0,0 +1,73 @@
+use image::RgbaImage;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+// Fixed so the paper looks the same on every render of a piece.
+const PAPER_SEED: u32 = 1729;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum PaperTexture {
+    // Procedural cloudy pulp with fine vertical fibers.
+    Fiber,
+    // An image file repeated across the canvas.
+    Tile { path: String },
+}
+
+// A textured stock the marks are printed on. `intensity` in [0, 1] scales
+// how far the darkest parts of the texture fall from white.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Paper {
+    pub texture: PaperTexture,
+    pub intensity: f32,
+}
+
+// Multiply the image by the paper texture, as if the marks had been
+// printed on it. Marks stay dark, white areas take on the texture.
+pub fn apply(img: &mut RgbaImage, paper: &Paper) -> Result<(), Error> {
+    let intensity = paper.intensity.clamp(0.0, 1.0);
+    let width = img.width() as usize;
+    if width == 0 || intensity == 0.0 {
+        return Ok(());
+    }
+    let shade: Box<dyn Fn(u32, u32) -> f32 + Sync> = match &paper.texture {
+        PaperTexture::Fiber => {
+            let pulp = Fbm::<Perlin>::new(PAPER_SEED).set_octaves(4);
+            let fibers = Perlin::new(PAPER_SEED + 1);
+            Box::new(move |x, y| {
+                let (x, y) = (x as f64, y as f64);
+                let cloud = pulp.get([x / 90.0, y / 90.0]);
+                let fiber = fibers.get([x / 2.5, y / 40.0]);
+                (0.85 + 0.1 * cloud + 0.05 * fiber).clamp(0.0, 1.0) as f32
+            })
+        }
+        PaperTexture::Tile { path } => {
+            let tile = image::open(path)
+                .map_err(|err| Error::Open {
+                    path: path.clone(),
+                    reason: err.to_string(),
+                })?
+                .to_luma8();
+            if tile.width() == 0 || tile.height() == 0 {
+                return Err(Error::EmptyImage(Some(path.clone())));
+            }
+            Box::new(move |x, y| {
+                tile.get_pixel(x % tile.width(), y % tile.height())[0] as f32 / 255.0
+            })
+        }
+    };
+    img.as_mut()
+        .par_chunks_exact_mut(4 * width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, px) in row.chunks_exact_mut(4).enumerate() {
+                let k = 1.0 - intensity * (1.0 - shade(x as u32, y as u32));
+                for c in px.iter_mut().take(3) {
+                    *c = (*c as f32 * k).round() as u8;
+                }
+            }
+        });
+    Ok(())
+}
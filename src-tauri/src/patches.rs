@@ -0,0 +1,56 @@
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+use crate::Style;
+
+// More octaves than this add detail finer than a cell.
+pub const MAX_OCTAVES: u32 = 8;
+
+// How `Patches` picks the style of each cell, from a noise field over the
+// source, so the styles lie in organic patches.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoisePatches {
+    // Roughly the size of a patch, in source pixels.
+    pub scale: f32,
+    // Layers of finer noise added to the field, more make raggeder edges.
+    pub octaves: u32,
+    // The styles the patches are drawn in, from the lowest noise to the
+    // highest.
+    pub styles: Vec<Style>,
+}
+
+impl Default for NoisePatches {
+    fn default() -> Self {
+        NoisePatches {
+            scale: 64.0,
+            octaves: 3,
+            styles: vec![Style::Dots, Style::Cross, Style::Stipple],
+        }
+    }
+}
+
+// The noise field for a render, seeded from its seed so a new seed moves
+// the patches.
+pub fn field(seed: u64, octaves: u32) -> Fbm<Perlin> {
+    Fbm::<Perlin>::new((seed ^ (seed >> 32)) as u32)
+        .set_octaves(octaves.clamp(1, MAX_OCTAVES) as usize)
+}
+
+impl NoisePatches {
+    // The style of the patch over a source pixel, `Dots` if there are no
+    // styles to pick from.
+    pub fn style(&self, field: &Fbm<Perlin>, sx: u32, sy: u32) -> Style {
+        if self.styles.is_empty() {
+            return Style::Dots;
+        }
+        // Sample off the integer lattice where Perlin noise is 0.
+        let f = 1.0 / self.scale.max(1.0) as f64;
+        let n = field.get([(sx as f64 + 0.37) * f, (sy as f64 + 0.37) * f]) as f32;
+        // fBm rarely strays far from 0, so it is stretched for the outer
+        // styles to get patches of their own.
+        let u = (0.5 + 0.75 * n).clamp(0.0, 1.0);
+        let i = (u * self.styles.len() as f32) as usize;
+        self.styles[i.min(self.styles.len() - 1)]
+    }
+}
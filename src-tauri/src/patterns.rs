@@ -0,0 +1,59 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+// Synthetic base images with known tones, for seeing how each style
+// responds without hunting for files.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum TestPattern {
+    // A smooth ramp from black on the left to white on the right.
+    Gradient,
+    // Concentric rings whose frequency rises away from the center, to show
+    // aliasing between the rings and the cell grid.
+    ZonePlate,
+    // Flat bands from black to white in `steps` equal steps.
+    StepWedge { steps: u32 },
+    // Hue around the center and saturation out from it, at full value.
+    ColorWheel,
+}
+
+// Draw a pattern into a new `size` by `size` image.
+pub fn generate(pattern: TestPattern, size: u32) -> RgbaImage {
+    let s = size.max(1) as f32;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let (u, v) = ((x as f32 + 0.5) / s, (y as f32 + 0.5) / s);
+        match pattern {
+            TestPattern::Gradient => gray(u),
+            TestPattern::ZonePlate => {
+                let r2 = (u - 0.5).powi(2) + (v - 0.5).powi(2);
+                gray(0.5 + 0.5 * (PI * s * r2).cos())
+            }
+            TestPattern::StepWedge { steps } => {
+                let steps = steps.max(2);
+                let step = ((u * steps as f32) as u32).min(steps - 1);
+                gray(step as f32 / (steps - 1) as f32)
+            }
+            TestPattern::ColorWheel => {
+                let (dx, dy) = (u - 0.5, v - 0.5);
+                let saturation = (2.0 * dx.hypot(dy)).min(1.0);
+                let hue = (dy.atan2(dx).to_degrees() + 360.0) % 360.0;
+                hsv(hue, saturation)
+            }
+        }
+    })
+}
+
+fn gray(value: f32) -> Rgba<u8> {
+    let c = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgba([c, c, c, 255])
+}
+
+// A color from hue in degrees and saturation in [0, 1] at full value.
+fn hsv(hue: f32, saturation: f32) -> Rgba<u8> {
+    let channel = |n: f32| {
+        let k = (n + hue / 60.0) % 6.0;
+        let c = 1.0 - saturation * k.min(4.0 - k).clamp(0.0, 1.0);
+        (c * 255.0).round() as u8
+    };
+    Rgba([channel(5.0), channel(3.0), channel(1.0), 255])
+}
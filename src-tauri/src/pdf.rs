@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::display::{DisplayList, Primitive};
+use crate::units::Page;
+
+// How far the control points of a Bézier quarter circle are from its ends,
+// as a fraction of the radius.
+const KAPPA: f32 = 0.552_284_8;
+
+// Points per millimeter.
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+// A display list as a one page PDF one point per output pixel, or on the
+// paper of `page`. The page is written by hand, it needs nothing beyond
+// paths, colors and opacity.
+pub fn write(list: &DisplayList, background: Option<[u8; 4]>, page: Option<&Page>) -> Vec<u8> {
+    // The paper size in points, and the points per output pixel and top
+    // left corner of the drawing on it.
+    let (paper_width, paper_height, scale, [left, top]) = match page {
+        Some(page) => {
+            let points = page.unit.millimeters() * POINTS_PER_MM;
+            (
+                page.width * points,
+                page.height * points,
+                page.scale * points,
+                page.offset.map(|o| o * points),
+            )
+        }
+        None => (list.width as f32, list.height as f32, 1.0, [0.0, 0.0]),
+    };
+    // Every opacity used gets a graphics state of its own.
+    let alphas: BTreeSet<u8> = list
+        .primitives
+        .iter()
+        .map(|primitive| primitive.color()[3])
+        .chain(background.map(|color| color[3]))
+        .collect();
+    let mut content = String::new();
+    // Pages start out opaque.
+    let mut alpha = 255;
+    // Flip the page so y runs down as it does on the canvas.
+    let _ = writeln!(
+        content,
+        "{} 0 0 {} {} {} cm",
+        scale,
+        -scale,
+        left,
+        paper_height - top
+    );
+    if let Some(color) = background {
+        set_color(&mut content, &mut alpha, color, true);
+        let _ = writeln!(
+            content,
+            "{} {} {} {} re f",
+            -left / scale,
+            -top / scale,
+            paper_width / scale,
+            paper_height / scale
+        );
+    }
+    for primitive in &list.primitives {
+        let color = primitive.color();
+        match primitive {
+            Primitive::Circle { x, y, radius, .. } => {
+                set_color(&mut content, &mut alpha, color, true);
+                circle(&mut content, *x, *y, *radius);
+                content.push_str("f\n");
+            }
+            Primitive::Ring {
+                x,
+                y,
+                radius,
+                weight,
+                ..
+            } => {
+                set_color(&mut content, &mut alpha, color, false);
+                let _ = writeln!(content, "{} w", weight);
+                circle(&mut content, *x, *y, *radius);
+                content.push_str("S\n");
+            }
+            Primitive::Polygon { points, .. } => {
+                set_color(&mut content, &mut alpha, color, true);
+                for (i, [x, y]) in points.iter().enumerate() {
+                    let op = if i == 0 { "m" } else { "l" };
+                    let _ = writeln!(content, "{} {} {}", x, y, op);
+                }
+                content.push_str("h f\n");
+            }
+            Primitive::Line {
+                from, to, weight, ..
+            } => {
+                set_color(&mut content, &mut alpha, color, false);
+                let _ = writeln!(
+                    content,
+                    "{} w {} {} m {} {} l S",
+                    weight, from[0], from[1], to[0], to[1]
+                );
+            }
+            Primitive::Curve {
+                start,
+                segments,
+                weight,
+                ..
+            } => {
+                set_color(&mut content, &mut alpha, color, false);
+                let _ = writeln!(content, "{} w {} {} m", weight, start[0], start[1]);
+                for [c1, c2, end] in segments {
+                    let _ = writeln!(
+                        content,
+                        "{} {} {} {} {} {} c",
+                        c1[0], c1[1], c2[0], c2[1], end[0], end[1]
+                    );
+                }
+                content.push_str("S\n");
+            }
+            Primitive::Dot { x, y, .. } => {
+                set_color(&mut content, &mut alpha, color, true);
+                let _ = writeln!(content, "{} {} 1 1 re f", x.floor(), y.floor());
+            }
+        }
+    }
+    let states: String = alphas
+        .iter()
+        .map(|a| {
+            let alpha = *a as f32 / 255.0;
+            format!("/A{} << /ca {:.3} /CA {:.3} >> ", a, alpha, alpha)
+        })
+        .collect();
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /ExtGState << {}>> >> /Contents 4 0 R >>",
+            paper_width, paper_height, states
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+    ];
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    pdf.extend_from_slice(trailer.as_bytes());
+    pdf
+}
+
+// Set the fill or stroke color, and the opacity when it is not `alpha`,
+// the opacity already set.
+fn set_color(content: &mut String, alpha: &mut u8, [r, g, b, a]: [u8; 4], fill: bool) {
+    let op = if fill { "rg" } else { "RG" };
+    let _ = writeln!(
+        content,
+        "{:.3} {:.3} {:.3} {}",
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0,
+        op
+    );
+    if a != *alpha {
+        let _ = writeln!(content, "/A{} gs", a);
+        *alpha = a;
+    }
+}
+
+// A circle as four Bézier quarters.
+fn circle(content: &mut String, x: f32, y: f32, r: f32) {
+    let k = KAPPA * r;
+    let _ = writeln!(content, "{} {} m", x + r, y);
+    let quarters = [
+        [x + r, y + k, x + k, y + r, x, y + r],
+        [x - k, y + r, x - r, y + k, x - r, y],
+        [x - r, y - k, x - k, y - r, x, y - r],
+        [x + k, y - r, x + r, y - k, x + r, y],
+    ];
+    for [x1, y1, x2, y2, x3, y3] in quarters {
+        let _ = writeln!(content, "{} {} {} {} {} {} c", x1, y1, x2, y2, x3, y3);
+    }
+}
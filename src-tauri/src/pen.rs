@@ -0,0 +1,111 @@
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+use wassily::prelude::*;
+
+use crate::display::{rgba, Primitive};
+
+// Fixed so a hand drawn line wobbles the same way on every render.
+const WOBBLE_SEED: u32 = 2718;
+
+// How far a hand drawn line strays from straight.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Wobble {
+    // Largest sideways offset in output pixels.
+    pub amplitude: f32,
+    // Wiggles per output pixel along the line.
+    pub frequency: f32,
+}
+
+// How wide the line styles draw.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Stroke {
+    // Width of a line in output pixels. Without a stroke lines are 1 wide.
+    pub weight: f32,
+    // Draw fewer lines as they get wider, so a thick pen gives the same
+    // tone as a thin one instead of overdrawing.
+    pub compensate: bool,
+}
+
+// Draws the line marks, either ruler straight or wobbling like ink.
+pub struct Pen {
+    wobble: Option<(Wobble, Perlin)>,
+    stroke: Option<Stroke>,
+    // Offset of the canvas being drawn on from the top of the full output,
+    // so the noise is continuous across render bands.
+    origin_y: f32,
+}
+
+impl Pen {
+    pub fn new(wobble: Option<Wobble>, stroke: Option<Stroke>, origin_y: f32) -> Self {
+        Pen {
+            wobble: wobble.map(|w| (w, Perlin::new(WOBBLE_SEED))),
+            stroke,
+            origin_y,
+        }
+    }
+
+    pub fn weight(&self) -> f32 {
+        self.stroke.map_or(1.0, |stroke| stroke.weight)
+    }
+
+    // The fraction of line positions to draw for darkness `t`. A line
+    // covers `weight` pixels across the cell, so compensating divides by
+    // it.
+    pub fn density(&self, t: f32) -> f32 {
+        match self.stroke {
+            Some(stroke) if stroke.compensate => (t / stroke.weight).clamp(0.0, 1.0),
+            _ => t,
+        }
+    }
+
+    pub fn line(&self, a: Point, b: Point, color: Color, weight: f32, list: &mut Vec<Primitive>) {
+        let Some((wobble, noise)) = &self.wobble else {
+            list.push(Primitive::Line {
+                from: [a.x, a.y],
+                to: [b.x, b.y],
+                weight,
+                color: rgba(color),
+            });
+            return;
+        };
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 {
+            return;
+        }
+        // Unit normal to the line.
+        let (nx, ny) = (-dy / len, dx / len);
+        let steps = (len * wobble.frequency * 8.0).ceil().clamp(1.0, 64.0) as u32;
+        // Sample the noise off the integer lattice where Perlin noise is 0.
+        let sample = |p: Point, offset: f64| {
+            let f = wobble.frequency as f64;
+            noise.get([
+                (p.x as f64 + 0.37) * f + offset,
+                (p.y as f64 + self.origin_y as f64 + 0.37) * f,
+            ]) as f32
+        };
+        let point = |i: u32| {
+            let s = i as f32 / steps as f32;
+            let p = pt(a.x + s * dx, a.y + s * dy);
+            let d = wobble.amplitude * sample(p, 0.0);
+            (pt(p.x + d * nx, p.y + d * ny), p)
+        };
+        let (mut prev, mut straight) = point(0);
+        for i in 1..=steps {
+            let (next, next_straight) = point(i);
+            let mid = pt(
+                (straight.x + next_straight.x) / 2.0,
+                (straight.y + next_straight.y) / 2.0,
+            );
+            let w = weight * (1.0 + 0.35 * sample(mid, 101.0));
+            list.push(Primitive::Line {
+                from: [prev.x, prev.y],
+                to: [next.x, next.y],
+                weight: w,
+                color: rgba(color),
+            });
+            prev = next;
+            straight = next_straight;
+        }
+    }
+}
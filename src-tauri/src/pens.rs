@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::display::{DisplayList, Primitive};
+use crate::planes::Planes;
+
+// The most pens a drawing can be split between.
+pub const MAX_PENS: u32 = 8;
+
+// Room around a split drawing for its registration marks, in output pixels.
+const MARGIN: f32 = 24.0;
+// Reach of each arm of a registration cross from its center.
+const ARM: f32 = 8.0;
+
+// How a drawing is split between the pens of a plotter, one file each.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum PenSplit {
+    // One pen per ink color.
+    Color,
+    // Passes that build up tone, like a light, medium and dark hatch: pass
+    // k of n draws where the source is darker than k / n, so the darkest
+    // parts get every pass.
+    Luminance { pens: u32 },
+}
+
+// The darkness each luminance pass draws from, in the order they are
+// plotted. Together the passes add up to the darkness of the source.
+pub fn passes(planes: &Planes, pens: u32) -> Vec<Planes> {
+    let n = pens.clamp(1, MAX_PENS);
+    (0..n)
+        .map(|k| {
+            let luma = planes
+                .luma
+                .iter()
+                .map(|t| (t * n as f32 - k as f32).clamp(0.0, 1.0))
+                .collect();
+            planes.with_luma(luma)
+        })
+        .collect()
+}
+
+// The name of luminance pass `k` of `n`, used in its file name.
+pub fn pass_name(k: usize, n: usize) -> String {
+    match (n, k) {
+        (2, 0) => "light".to_string(),
+        (2, 1) => "dark".to_string(),
+        (3, 0) => "light".to_string(),
+        (3, 1) => "medium".to_string(),
+        (3, 2) => "dark".to_string(),
+        _ => format!("pen{}", k + 1),
+    }
+}
+
+// A drawing split by ink color, named by the hex of the color.
+pub fn by_color(list: DisplayList) -> Vec<(String, DisplayList)> {
+    let mut pens: BTreeMap<[u8; 3], Vec<Primitive>> = BTreeMap::new();
+    for primitive in list.primitives {
+        let [r, g, b, _] = primitive.color();
+        pens.entry([r, g, b]).or_default().push(primitive);
+    }
+    pens.into_iter()
+        .map(|([r, g, b], primitives)| {
+            (
+                format!("{:02x}{:02x}{:02x}", r, g, b),
+                DisplayList {
+                    width: list.width,
+                    height: list.height,
+                    primitives,
+                },
+            )
+        })
+        .collect()
+}
+
+// The drawing moved in by a margin with a registration cross and circle
+// in each corner of the margin, in the same place on every pen's file so
+// the sheets can be lined up.
+pub fn with_registration(list: DisplayList) -> DisplayList {
+    let (width, height) = (
+        list.width as f32 + 2.0 * MARGIN,
+        list.height as f32 + 2.0 * MARGIN,
+    );
+    let mut primitives: Vec<Primitive> = list
+        .primitives
+        .into_iter()
+        .map(|primitive| primitive.translated(MARGIN, MARGIN))
+        .collect();
+    let black = [0, 0, 0, 255];
+    let h = MARGIN / 2.0;
+    for (x, y) in [
+        (h, h),
+        (width - h, h),
+        (h, height - h),
+        (width - h, height - h),
+    ] {
+        primitives.push(Primitive::Line {
+            from: [x - ARM, y],
+            to: [x + ARM, y],
+            weight: 1.0,
+            color: black,
+        });
+        primitives.push(Primitive::Line {
+            from: [x, y - ARM],
+            to: [x, y + ARM],
+            weight: 1.0,
+            color: black,
+        });
+        primitives.push(Primitive::Ring {
+            x,
+            y,
+            radius: ARM / 2.0,
+            weight: 1.0,
+            color: black,
+        });
+    }
+    DisplayList {
+        width: width as u32,
+        height: height as u32,
+        primitives,
+    }
+}
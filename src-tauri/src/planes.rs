@@ -0,0 +1,418 @@
+use image::{GrayImage, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::sync::{Arc, OnceLock};
+
+use crate::blend;
+use crate::depth::DepthMap;
+use crate::faces::Face;
+use crate::labels::Labels;
+use crate::mask::{self, DensityMap, Mask};
+use crate::slic::{self, Segments};
+use crate::RenderOptions;
+
+// Per pixel values derived from the base image, computed once per source
+// and shared by every render of it.
+pub struct Planes {
+    pub width: u32,
+    pub height: u32,
+    // Darkness in [0, 1], 1 is black.
+    pub luma: Vec<f32>,
+    // Hue in degrees, only computed when a style needs it.
+    pub hue: Option<Vec<i32>>,
+    // Regions marked by a label mask, if one is loaded.
+    pub labels: Option<Arc<Labels>>,
+    // Superpixels for Multi to pick styles by, if asked for.
+    pub segments: Option<Arc<Segments>>,
+    // Faces found in the source, if asked for.
+    pub faces: Option<Arc<Vec<Face>>>,
+    // How near each part of the source is, if a depth map is loaded.
+    pub depth: Option<Arc<DepthMap>>,
+    // The photo itself, for the modes that separate it by color.
+    pub photo: Option<Arc<RgbaImage>>,
+    // Direction of the luminance gradient in radians, NaN where the image
+    // is flat. Derived from `luma` the first time it is asked for.
+    gradient: OnceLock<Vec<f32>>,
+}
+
+// Gradients weaker than this are treated as flat, their direction is noise.
+const FLAT: f32 = 0.02;
+
+// What tones a source has, found once as it is loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum Tones {
+    #[default]
+    Color,
+    // Every pixel a gray, like a black and white photo.
+    Gray,
+    // Every pixel black or white, like scanned line art.
+    Bilevel,
+}
+
+// Whether every pixel of `img` is a gray, and whether only black or white.
+pub fn tones(img: &RgbaImage) -> Tones {
+    let pixels = img.as_raw().par_chunks_exact(4);
+    if !pixels.clone().all(|px| px[0] == px[1] && px[1] == px[2]) {
+        Tones::Color
+    } else if pixels.all(|px| px[0] == 0 || px[0] == 255) {
+        Tones::Bilevel
+    } else {
+        Tones::Gray
+    }
+}
+
+// The gray level of each pixel of an image known to be gray.
+pub fn gray(img: &RgbaImage) -> GrayImage {
+    let levels = img.as_raw().chunks_exact(4).map(|px| px[0]).collect();
+    GrayImage::from_raw(img.width(), img.height(), levels)
+        .expect("There is a level for every pixel")
+}
+
+impl Planes {
+    // Compute the planes in a single pass over the source buffer. Rows are
+    // processed in parallel and the inner loop is plain slice arithmetic
+    // so the compiler can vectorize it.
+    pub fn new(img: &RgbaImage, with_hue: bool) -> Self {
+        let width = img.width() as usize;
+        let len = width * img.height() as usize;
+        let mut luma = vec![0.0; len];
+        let mut hue = if with_hue { vec![0; len] } else { Vec::new() };
+        if width > 0 {
+            let src = img.as_raw().par_chunks_exact(4 * width);
+            if with_hue {
+                src.zip(luma.par_chunks_exact_mut(width))
+                    .zip(hue.par_chunks_exact_mut(width))
+                    .for_each(|((src, luma), hue)| {
+                        luma_row(src, luma);
+                        for (px, h) in src.chunks_exact(4).zip(hue.iter_mut()) {
+                            *h = pixel_to_hue(&Rgba([px[0], px[1], px[2], px[3]]));
+                        }
+                    });
+            } else {
+                src.zip(luma.par_chunks_exact_mut(width))
+                    .for_each(|(src, luma)| luma_row(src, luma));
+            }
+        }
+        Planes {
+            width: img.width(),
+            height: img.height(),
+            luma,
+            hue: with_hue.then_some(hue),
+            labels: None,
+            segments: None,
+            faces: None,
+            depth: None,
+            photo: None,
+            gradient: OnceLock::new(),
+        }
+    }
+
+    // The planes of a gray source, which skip the luminance weights. Gray
+    // has no hue, the hue plane is all 0 as `pixel_to_hue` would give.
+    pub fn from_gray(img: &GrayImage, with_hue: bool) -> Self {
+        let luma = img
+            .as_raw()
+            .par_iter()
+            .map(|&level| 1.0 - level as f32 / 255.0)
+            .collect::<Vec<_>>();
+        let hue = with_hue.then(|| vec![0; luma.len()]);
+        Planes::from_parts(img.width(), img.height(), luma, hue)
+    }
+
+    // Planes made from already computed values.
+    pub fn from_parts(width: u32, height: u32, luma: Vec<f32>, hue: Option<Vec<i32>>) -> Self {
+        Planes {
+            width,
+            height,
+            luma,
+            hue,
+            labels: None,
+            segments: None,
+            faces: None,
+            depth: None,
+            photo: None,
+            gradient: OnceLock::new(),
+        }
+    }
+
+    // A copy with new darkness values, keeping everything else.
+    pub fn with_luma(&self, luma: Vec<f32>) -> Planes {
+        Planes {
+            labels: self.labels.clone(),
+            segments: self.segments.clone(),
+            faces: self.faces.clone(),
+            depth: self.depth.clone(),
+            photo: self.photo.clone(),
+            ..Planes::from_parts(self.width, self.height, luma, self.hue.clone())
+        }
+    }
+
+    // A copy that knows the regions of a label mask.
+    pub fn with_labels(&self, labels: Arc<Labels>) -> Planes {
+        Planes {
+            labels: Some(labels),
+            ..self.with_luma(self.luma.clone())
+        }
+    }
+
+    // A copy that knows the superpixels of the source.
+    pub fn with_segments(&self, segments: Arc<Segments>) -> Planes {
+        Planes {
+            segments: Some(segments),
+            ..self.with_luma(self.luma.clone())
+        }
+    }
+
+    // A copy that knows where the faces are.
+    pub fn with_faces(&self, faces: Arc<Vec<Face>>) -> Planes {
+        Planes {
+            faces: Some(faces),
+            ..self.with_luma(self.luma.clone())
+        }
+    }
+
+    // A copy that knows how near each part of the source is.
+    pub fn with_depth(&self, depth: Arc<DepthMap>) -> Planes {
+        Planes {
+            depth: Some(depth),
+            ..self.with_luma(self.luma.clone())
+        }
+    }
+
+    // A copy that can see the photo the planes were made from.
+    pub fn with_photo(&self, photo: Arc<RgbaImage>) -> Planes {
+        Planes {
+            photo: Some(photo),
+            ..self.with_luma(self.luma.clone())
+        }
+    }
+
+    // A copy in pure black and white, black where the darkness is at least
+    // `level`, for tracing scanned drawings without their paper's tone.
+    pub fn trace(&self, level: f32) -> Planes {
+        let luma = self
+            .luma
+            .par_iter()
+            .map(|&t| if t >= level { 1.0 } else { 0.0 })
+            .collect();
+        self.with_luma(luma)
+    }
+
+    pub fn t(&self, x: u32, y: u32) -> f32 {
+        self.luma[(y * self.width + x) as usize]
+    }
+
+    // Darkness at a point between pixels, (0.5, 0.5) is the center of the
+    // top left pixel. Bilinear, clamped at the edges.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let fx = (x - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let fy = (y - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (fx as u32, fy as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (ax, ay) = (fx - x0 as f32, fy - y0 as f32);
+        let top = self.t(x0, y0) * (1.0 - ax) + self.t(x1, y0) * ax;
+        let bottom = self.t(x0, y1) * (1.0 - ax) + self.t(x1, y1) * ax;
+        top * (1.0 - ay) + bottom * ay
+    }
+
+    // The planes at 1 / `factor` the size, each pixel the mean darkness of
+    // a `factor` square block, cut short at the edges, and the hue at its
+    // top left.
+    pub fn shrink(&self, factor: u32) -> Planes {
+        let factor = factor.max(1);
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut luma = Vec::with_capacity((width * height) as usize);
+        let mut hue = self
+            .hue
+            .as_ref()
+            .map(|_| Vec::with_capacity(luma.capacity()));
+        for by in 0..height {
+            for bx in 0..width {
+                let (x0, y0) = (bx * factor, by * factor);
+                let (x1, y1) = (
+                    (x0 + factor).min(self.width),
+                    (y0 + factor).min(self.height),
+                );
+                let mut sum = 0.0;
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        sum += self.t(x, y);
+                    }
+                }
+                luma.push(sum / ((x1 - x0) * (y1 - y0)) as f32);
+                if let Some(hue) = &mut hue {
+                    hue.push(self.hue(x0, y0));
+                }
+            }
+        }
+        Planes {
+            labels: self.labels.clone(),
+            segments: self.segments.clone(),
+            faces: self.faces.clone(),
+            depth: self.depth.clone(),
+            ..Planes::from_parts(width, height, luma, hue)
+        }
+    }
+
+    pub fn hue(&self, x: u32, y: u32) -> i32 {
+        self.hue.as_ref().expect("The hue plane was not computed")[(y * self.width + x) as usize]
+    }
+
+    // The direction darkness increases fastest at a pixel, pointing from
+    // light to dark, or `None` if the neighborhood is flat.
+    pub fn gradient(&self, x: u32, y: u32) -> Option<f32> {
+        let gradient = self.gradient.get_or_init(|| self.sobel());
+        let angle = gradient[(y * self.width + x) as usize];
+        (!angle.is_nan()).then_some(angle)
+    }
+
+    // Sequential on purpose, it runs inside a render band and a nested
+    // parallel loop could steal another band that blocks on the OnceLock.
+    fn sobel(&self) -> Vec<f32> {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let at = |x: i64, y: i64| self.t(x.clamp(0, w - 1) as u32, y.clamp(0, h - 1) as u32);
+        let mut gradient = vec![f32::NAN; self.luma.len()];
+        gradient
+            .chunks_exact_mut(self.width.max(1) as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let y = y as i64;
+                for (x, angle) in row.iter_mut().enumerate() {
+                    let x = x as i64;
+                    let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                        - at(x - 1, y - 1)
+                        - 2.0 * at(x - 1, y)
+                        - at(x - 1, y + 1);
+                    let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                        - at(x - 1, y - 1)
+                        - 2.0 * at(x, y - 1)
+                        - at(x + 1, y - 1);
+                    if gx.hypot(gy) > FLAT {
+                        *angle = gy.atan2(gx);
+                    }
+                }
+            });
+        gradient
+    }
+}
+
+// What planes are built from besides the photo and the options, all
+// optional. The app passes what it has cached for the loaded image, a
+// render of a file on its own leaves them out.
+#[derive(Default)]
+pub struct Inputs {
+    // The planes of the photo itself, computed here when missing.
+    pub base: Option<Arc<Planes>>,
+    // The planes of a second image to blend in, at the size of the photo.
+    pub secondary: Option<Arc<Planes>>,
+    pub density_map: Option<Arc<DensityMap>>,
+    pub mask: Option<Arc<Mask>>,
+    pub labels: Option<Arc<Labels>>,
+    pub depth: Option<Arc<DepthMap>>,
+    // The superpixels and faces of the photo, found here when missing and
+    // the options ask for them.
+    pub segments: Option<Arc<Segments>>,
+    pub faces: Option<Arc<Vec<Face>>>,
+}
+
+// The planes a render of `photo` with `options` draws from: traced,
+// blended with the secondary image, shaped by the density map and then the
+// mask, and carrying the labels, depth, photo, superpixels and faces that
+// the options and `inputs` call for. Every render builds them here so a
+// file rendered on its own matches the preview of it.
+pub fn build(photo: &Arc<RgbaImage>, options: &RenderOptions, inputs: Inputs) -> Arc<Planes> {
+    let mut planes = inputs
+        .base
+        .unwrap_or_else(|| Arc::new(Planes::new(photo, options.needs_hue())));
+    if let Some(level) = options.threshold_trace {
+        planes = Arc::new(planes.trace(level));
+    }
+    if let (Some(mode), Some(secondary)) = (options.blend, &inputs.secondary) {
+        planes = Arc::new(blend::blend(&planes, secondary, mode));
+    }
+    if let Some(map) = &inputs.density_map {
+        planes = Arc::new(mask::apply_density(&planes, map, options.invert_output));
+    }
+    if let Some(mask) = &inputs.mask {
+        planes = Arc::new(mask::apply(&planes, mask, options.invert_output));
+    }
+    if let Some(labels) = inputs.labels {
+        planes = Arc::new(planes.with_labels(labels));
+    }
+    if let Some(depth) = inputs.depth {
+        planes = Arc::new(planes.with_depth(depth));
+    }
+    if options.cmy.is_some() {
+        planes = Arc::new(planes.with_photo(photo.clone()));
+    }
+    if let Some(slic) = &options.segments {
+        let segments = inputs
+            .segments
+            .unwrap_or_else(|| Arc::new(slic::segment(photo, slic)));
+        planes = Arc::new(planes.with_segments(segments));
+    }
+    if options.faces.is_some() {
+        if let Some(found) = inputs.faces.or_else(|| faces(photo)) {
+            planes = Arc::new(planes.with_faces(found));
+        }
+    }
+    planes
+}
+
+// The faces in `photo`. A failed search is logged and counts as finding
+// none, as do builds without the faces feature.
+#[cfg(feature = "faces")]
+pub fn faces(photo: &RgbaImage) -> Option<Arc<Vec<Face>>> {
+    Some(Arc::new(crate::faces::detect(photo).unwrap_or_else(
+        |err| {
+            eprintln!("No faces were looked for: {}", err);
+            Vec::new()
+        },
+    )))
+}
+
+#[cfg(not(feature = "faces"))]
+pub fn faces(_photo: &RgbaImage) -> Option<Arc<Vec<Face>>> {
+    None
+}
+
+fn luma_row(src: &[u8], luma: &mut [f32]) {
+    for (px, t) in src.chunks_exact(4).zip(luma.iter_mut()) {
+        let color = (0.2989 * px[0] as f32 + 0.5870 * px[1] as f32 + 0.1140 * px[2] as f32) / 255.0;
+        *t = 1.0 - color;
+    }
+}
+
+pub fn pixel_to_hue(pixel: &Rgba<u8>) -> i32 {
+    let r = pixel[0] as f32 / 255.0;
+    let g = pixel[1] as f32 / 255.0;
+    let b = pixel[2] as f32 / 255.0;
+
+    let max = r.max(g.max(b));
+    let min = r.min(g.min(b));
+    let delta = max - min;
+
+    if delta == 0.0 {
+        // Achromatic case (grey scale), hue is undefined
+        0
+    } else {
+        let hue = if max == r {
+            // Red is max
+            60.0 * (((g - b) / delta) % 6.0)
+        } else if max == g {
+            // Green is max
+            60.0 * (((b - r) / delta) + 2.0)
+        } else {
+            // Blue is max
+            60.0 * (((r - g) / delta) + 4.0)
+        };
+
+        let hue = hue.round() as i32;
+        if hue < 0 {
+            hue + 360
+        } else {
+            hue
+        }
+    }
+}
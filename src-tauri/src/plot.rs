@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+use crate::display::{self, DisplayList, Primitive};
+use crate::optimize;
+
+// How fast a plotter moves, in millimeters per minute, and how long it
+// takes to lift and lower the pen, in seconds.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PlotSpeed {
+    pub draw: f32,
+    pub travel: f32,
+    pub lift: f32,
+}
+
+impl Default for PlotSpeed {
+    // A hobby pen plotter like the AxiDraw at its default settings.
+    fn default() -> Self {
+        PlotSpeed {
+            draw: 3000.0,
+            travel: 6000.0,
+            lift: 0.25,
+        }
+    }
+}
+
+// How long a drawing takes to plot, to compare styles before starting one
+// that runs all night. Lengths in millimeters on the paper.
+#[derive(Clone, Copy, Serialize)]
+pub struct PlotEstimate {
+    // With the pen down.
+    pub draw: f32,
+    // With the pen up, between marks and back home at the end.
+    pub travel: f32,
+    pub lifts: u32,
+    pub seconds: f32,
+}
+
+// The estimate for plotting the marks in the order they are listed from
+// the top left corner, at `mm_per_pixel` millimeters per output pixel.
+// Marks that start where the one before ended are drawn without lifting
+// the pen, as the G-code backend does.
+pub fn estimate(list: &DisplayList, mm_per_pixel: f32, speed: &PlotSpeed) -> PlotEstimate {
+    let (mut draw, mut travel, mut lifts) = (0.0, 0.0, 0);
+    let mut pen = [0.0, 0.0];
+    for primitive in &list.primitives {
+        let (start, end) = optimize::ends(primitive);
+        if start != pen {
+            travel += distance(pen, start);
+            lifts += 1;
+        }
+        draw += length(primitive);
+        pen = end;
+    }
+    travel += distance(pen, [0.0, 0.0]);
+    let (draw, travel) = (draw * mm_per_pixel, travel * mm_per_pixel);
+    let seconds = 60.0 * draw / speed.draw.max(f32::EPSILON)
+        + 60.0 * travel / speed.travel.max(f32::EPSILON)
+        + lifts as f32 * speed.lift;
+    PlotEstimate {
+        draw,
+        travel,
+        lifts,
+        seconds,
+    }
+}
+
+// The distance the pen covers drawing a mark, in output pixels. A pen
+// outlines what the screen fills.
+fn length(primitive: &Primitive) -> f32 {
+    match primitive {
+        Primitive::Circle { radius, .. } | Primitive::Ring { radius, .. } => TAU * radius,
+        Primitive::Polygon { points, .. } => points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| distance(*a, *b))
+            .sum(),
+        Primitive::Line { from, to, .. } => distance(*from, *to),
+        Primitive::Curve {
+            start, segments, ..
+        } => display::flatten(*start, segments)
+            .windows(2)
+            .map(|w| distance(w[0], w[1]))
+            .sum(),
+        Primitive::Dot { .. } => 0.0,
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
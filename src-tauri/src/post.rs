@@ -0,0 +1,388 @@
+use image::{imageops, Rgba, RgbaImage};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::caption;
+use crate::composite::{self, BlendMode, SourceBlend};
+use crate::error::Error;
+use crate::paper;
+use crate::planes::Planes;
+use crate::render;
+use crate::stamp;
+use crate::RenderOptions;
+
+// How far a source pixel may differ from the corner and still count as
+// part of a uniform border.
+const BORDER_TOLERANCE: f32 = 0.02;
+
+// Ways to cut away empty margins before an export is saved. Padding is in
+// output pixels and is kept around whatever remains.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Trim {
+    // Remove rows and columns of the source that match its top left corner,
+    // for sources with large flat borders.
+    Background { padding: u32 },
+    // Crop to the bounding box of the drawn marks.
+    Marks { padding: u32 },
+}
+
+// A decorative mat around the artwork, sizes are in output pixels.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Border {
+    pub margin: u32,
+    pub color: [u8; 3],
+    // Draw a pair of thin black rules inside the mat.
+    pub double_rule: bool,
+    // Radius of the outer corners, which are cut out to transparent.
+    pub corner_radius: u32,
+}
+
+// A canvas of another shape than the render, which is placed on it, like
+// a square photo on a 2:3 poster.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Aspect {
+    // Width to height, like [2, 3]. The canvas is widened or heightened to
+    // it, never cropped.
+    pub ratio: [f32; 2],
+    pub placement: Placement,
+    pub background: Backdrop,
+}
+
+// Where the render sits on its canvas.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Placement {
+    Center,
+    // The center of the render on a third line of the canvas, 1 or 2
+    // counted from the left and from the top, or 0 for centered along
+    // that side.
+    Thirds { column: u8, row: u8 },
+}
+
+// What fills the canvas around the render.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Backdrop {
+    Color([u8; 3]),
+    Transparent,
+    // The render itself, scaled to cover the canvas and blurred by `sigma`
+    // output pixels.
+    Blur { sigma: f32 },
+}
+
+// Fixed so the grain is the same on every render of a piece.
+const GRAIN_SEED: u64 = 4104;
+
+// Photographic finishing touches, each is off at 0.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Effects {
+    // How dark the corners get, in [0, 1].
+    pub vignette: f32,
+    // Amount of film grain, in [0, 1].
+    pub grain: f32,
+    // Gaussian blur sigma in output pixels.
+    pub blur: f32,
+}
+
+// Apply the finishing steps that follow generation, in order. `source` is
+// the photo the planes were made from. Trimming is only done for exports.
+// Returns `None` if the image is unchanged.
+pub fn finish(
+    img: &RgbaImage,
+    source: &RgbaImage,
+    planes: &Planes,
+    options: &RenderOptions,
+    export: bool,
+) -> Result<Option<RgbaImage>, Error> {
+    let mut finished = None;
+    // The margins are found on the bare marks, before the photo fills them.
+    let trimmed = match (export, options.trim) {
+        (true, Some(t)) => trim_box(img, planes, options.cell, t, render::background(options)),
+        _ => None,
+    };
+    // Multiplying keeps the marks dark while the white between them takes
+    // on the photo, as if it were printed underneath.
+    let underlay = (options.underlay_source > 0.0).then_some(SourceBlend {
+        mode: BlendMode::Multiply,
+        opacity: options.underlay_source,
+    });
+    for blend in underlay.into_iter().chain(options.source_blend) {
+        let mut blended = finished.unwrap_or_else(|| img.clone());
+        composite::blend_source(&mut blended, source, blend);
+        finished = Some(blended);
+    }
+    if let Some((x0, y0, width, height)) = trimmed {
+        let uncut = finished.as_ref().unwrap_or(img);
+        finished = Some(imageops::crop_imm(uncut, x0, y0, width, height).to_image());
+    }
+    if let Some(p) = &options.paper {
+        let mut papered = finished.unwrap_or_else(|| img.clone());
+        paper::apply(&mut papered, p)?;
+        finished = Some(papered);
+    }
+    if let Some(e) = options.effects {
+        finished = Some(effects(finished.unwrap_or_else(|| img.clone()), &e));
+    }
+    if let Some(a) = &options.aspect {
+        if let Some(placed) = aspect(finished.as_ref().unwrap_or(img), a) {
+            finished = Some(placed);
+        }
+    }
+    if let Some(b) = options.border {
+        finished = Some(border(finished.as_ref().unwrap_or(img), &b));
+    }
+    if let Some(c) = &options.caption {
+        finished = Some(caption::apply(finished.as_ref().unwrap_or(img), c)?);
+    }
+    if let Some(s) = &options.stamp {
+        let mut stamped = finished.unwrap_or_else(|| img.clone());
+        stamp::apply(&mut stamped, s, &s.text(options))?;
+        finished = Some(stamped);
+    }
+    // Last, so nothing after it brings back gray.
+    if let Some(level) = options.one_bit {
+        let mut bilevel = finished.unwrap_or_else(|| img.clone());
+        one_bit(&mut bilevel, level);
+        finished = Some(bilevel);
+    }
+    Ok(finished)
+}
+
+// Make every pixel black or white, black where its darkness is at least
+// `level`, and fully opaque or fully clear.
+pub fn one_bit(img: &mut RgbaImage, level: f32) {
+    let cut = (1.0 - level) * 255.0;
+    img.par_chunks_exact_mut(4).for_each(|px| {
+        let luma = 0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32;
+        let c = if luma <= cut { 0 } else { 255 };
+        let a = if px[3] >= 128 { 255 } else { 0 };
+        px.copy_from_slice(&[c, c, c, a]);
+    });
+}
+
+// Crop the rendered image according to `trim`, `background` is the color
+// the canvas was filled with. Returns `None` if there is nothing to cut or
+// the trimmed area would be empty.
+pub fn trim(
+    img: &RgbaImage,
+    planes: &Planes,
+    cell: u32,
+    trim: Trim,
+    background: Rgba<u8>,
+) -> Option<RgbaImage> {
+    let (x0, y0, width, height) = trim_box(img, planes, cell, trim, background)?;
+    Some(imageops::crop_imm(img, x0, y0, width, height).to_image())
+}
+
+// The left, top, width and height `trim` keeps, `None` if there is
+// nothing to cut or nothing would be left.
+fn trim_box(
+    img: &RgbaImage,
+    planes: &Planes,
+    cell: u32,
+    trim: Trim,
+    background: Rgba<u8>,
+) -> Option<(u32, u32, u32, u32)> {
+    let (bounds, padding) = match trim {
+        Trim::Background { padding } => (
+            background_bounds(planes)
+                .map(|(x0, y0, x1, y1)| (x0 * cell, y0 * cell, x1 * cell, y1 * cell)),
+            padding,
+        ),
+        Trim::Marks { padding } => (marks_bounds(img, background), padding),
+    };
+    let (x0, y0, x1, y1) = bounds?;
+    let x0 = x0.saturating_sub(padding);
+    let y0 = y0.saturating_sub(padding);
+    let x1 = (x1 + padding).min(img.width());
+    let y1 = (y1 + padding).min(img.height());
+    if (x0, y0, x1, y1) == (0, 0, img.width(), img.height()) {
+        return None;
+    }
+    Some((x0, y0, x1 - x0, y1 - y0))
+}
+
+// The half open box of source cells that differ from the corner.
+fn background_bounds(planes: &Planes) -> Option<(u32, u32, u32, u32)> {
+    if planes.width == 0 || planes.height == 0 {
+        return None;
+    }
+    let corner = planes.t(0, 0);
+    let differs = |x, y| (planes.t(x, y) - corner).abs() > BORDER_TOLERANCE;
+    bounds(planes.width, planes.height, differs)
+}
+
+// The half open box of output pixels that are not the background color.
+fn marks_bounds(img: &RgbaImage, background: Rgba<u8>) -> Option<(u32, u32, u32, u32)> {
+    bounds(img.width(), img.height(), |x, y| {
+        *img.get_pixel(x, y) != background
+    })
+}
+
+fn bounds(
+    width: u32,
+    height: u32,
+    inside: impl Fn(u32, u32) -> bool,
+) -> Option<(u32, u32, u32, u32)> {
+    let mut x0 = width;
+    let mut y0 = height;
+    let mut x1 = 0;
+    let mut y1 = 0;
+    for y in 0..height {
+        for x in 0..width {
+            if inside(x, y) {
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x + 1);
+                y1 = y1.max(y + 1);
+            }
+        }
+    }
+    (x0 < x1 && y0 < y1).then_some((x0, y0, x1, y1))
+}
+
+// Blur, then darken toward the corners, then add grain.
+pub fn effects(img: RgbaImage, effects: &Effects) -> RgbaImage {
+    let mut img = if effects.blur > 0.0 {
+        imageops::blur(&img, effects.blur)
+    } else {
+        img
+    };
+    let width = img.width() as usize;
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let vignette = effects.vignette.clamp(0.0, 1.0);
+    let grain = effects.grain.clamp(0.0, 1.0);
+    if width == 0 || (vignette == 0.0 && grain == 0.0) {
+        return img;
+    }
+    img.as_mut()
+        .par_chunks_exact_mut(4 * width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let mut rng = SmallRng::seed_from_u64(GRAIN_SEED ^ y as u64);
+            let dy = (y as f32 + 0.5) / h - 0.5;
+            for (x, px) in row.chunks_exact_mut(4).enumerate() {
+                let dx = (x as f32 + 0.5) / w - 0.5;
+                // 0 at the center, 1 at the corners.
+                let r = (dx * dx + dy * dy).sqrt() * std::f32::consts::SQRT_2;
+                let falloff = r * r * (3.0 - 2.0 * r);
+                let k = 1.0 - vignette * falloff;
+                let noise = if grain > 0.0 {
+                    grain * 48.0 * (rng.gen::<f32>() + rng.gen::<f32>() - 1.0)
+                } else {
+                    0.0
+                };
+                for c in px.iter_mut().take(3) {
+                    *c = (*c as f32 * k + noise).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        });
+    img
+}
+
+// Place the image on a canvas of the aspect ratio, `None` if it already
+// has it.
+pub fn aspect(img: &RgbaImage, aspect: &Aspect) -> Option<RgbaImage> {
+    let (w, h) = img.dimensions();
+    let ratio = aspect.ratio[0] / aspect.ratio[1];
+    let (cw, ch) = if (w as f32) < h as f32 * ratio {
+        ((h as f32 * ratio).round() as u32, h)
+    } else {
+        (w, (w as f32 / ratio).round() as u32)
+    };
+    if (cw, ch) == (w, h) || w == 0 || h == 0 {
+        return None;
+    }
+    let mut canvas = match aspect.background {
+        Backdrop::Color([r, g, b]) => RgbaImage::from_pixel(cw, ch, Rgba([r, g, b, 255])),
+        Backdrop::Transparent => RgbaImage::new(cw, ch),
+        Backdrop::Blur { sigma } => {
+            let scale = (cw as f32 / w as f32).max(ch as f32 / h as f32);
+            let (sw, sh) = (
+                (w as f32 * scale).ceil() as u32,
+                (h as f32 * scale).ceil() as u32,
+            );
+            let cover = imageops::resize(img, sw, sh, imageops::FilterType::Triangle);
+            let cropped = imageops::crop_imm(&cover, (sw - cw) / 2, (sh - ch) / 2, cw, ch);
+            imageops::blur(&cropped.to_image(), sigma)
+        }
+    };
+    let (column, row) = match aspect.placement {
+        Placement::Center => (0, 0),
+        Placement::Thirds { column, row } => (column, row),
+    };
+    let at = |third: u8, size: u32, inner: u32| {
+        let free = (size - inner) as f32;
+        let center = match third {
+            1 | 2 => size as f32 * third as f32 / 3.0,
+            _ => size as f32 / 2.0,
+        };
+        (center - inner as f32 / 2.0).clamp(0.0, free).round() as i64
+    };
+    imageops::overlay(&mut canvas, img, at(column, cw, w), at(row, ch, h));
+    Some(canvas)
+}
+
+// Surround the image with a mat of `border.margin` pixels.
+pub fn border(img: &RgbaImage, border: &Border) -> RgbaImage {
+    let [r, g, b] = border.color;
+    let m = border.margin;
+    let mut out = RgbaImage::from_pixel(
+        img.width() + 2 * m,
+        img.height() + 2 * m,
+        Rgba([r, g, b, 255]),
+    );
+    imageops::replace(&mut out, img, m as i64, m as i64);
+    if border.double_rule && m >= 3 {
+        let rule = (m / 24).max(1);
+        let black = Rgba([0, 0, 0, 255]);
+        rule_rect(&mut out, m / 3, rule, black);
+        rule_rect(&mut out, 2 * m / 3, rule, black);
+    }
+    if border.corner_radius > 0 {
+        round_corners(&mut out, border.corner_radius);
+    }
+    out
+}
+
+// Draw the outline of a rectangle `inset` pixels in from the image edges.
+fn rule_rect(img: &mut RgbaImage, inset: u32, thickness: u32, color: Rgba<u8>) {
+    let (w, h) = (img.width(), img.height());
+    if 2 * (inset + thickness) >= w.min(h) {
+        return;
+    }
+    for y in inset..h - inset {
+        for x in inset..w - inset {
+            let on_rule = x < inset + thickness
+                || x >= w - inset - thickness
+                || y < inset + thickness
+                || y >= h - inset - thickness;
+            if on_rule {
+                img.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+// Make the pixels outside the rounded corners transparent, with a one
+// pixel ramp along the arc so the edge is antialiased.
+fn round_corners(img: &mut RgbaImage, radius: u32) {
+    let (w, h) = (img.width(), img.height());
+    let r = radius.min(w / 2).min(h / 2);
+    let rf = r as f32;
+    for y in 0..r {
+        for x in 0..r {
+            let dx = rf - x as f32 - 0.5;
+            let dy = rf - y as f32 - 0.5;
+            let coverage = (rf - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+            for (cx, cy) in [
+                (x, y),
+                (w - 1 - x, y),
+                (x, h - 1 - y),
+                (w - 1 - x, h - 1 - y),
+            ] {
+                let px = img.get_pixel_mut(cx, cy);
+                px[3] = (px[3] as f32 * coverage).round() as u8;
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+use crate::planes::Planes;
+
+// Settings for adaptive cells. Block sizes are in source pixels and are
+// rounded down to powers of two.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Quadtree {
+    pub max_block: u32,
+    pub min_block: u32,
+    // Blocks whose luminance variance exceeds this are split.
+    pub threshold: f32,
+}
+
+// A square block of the source drawn as a single cell.
+pub struct Leaf {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+    // Mean darkness over the block.
+    pub t: f32,
+}
+
+// Split the source into blocks, starting from `max_block` squares and
+// quartering any block with too much detail until `min_block` is reached.
+// Blocks hanging over the edge of the image are always split.
+pub fn leaves(planes: &Planes, quadtree: &Quadtree) -> Vec<Leaf> {
+    let max_block = power_of_two(quadtree.max_block);
+    let min_block = power_of_two(quadtree.min_block).min(max_block);
+    let table = SummedArea::new(planes);
+    let mut leaves = Vec::new();
+    let mut stack = Vec::new();
+    for y in (0..planes.height).step_by(max_block as usize) {
+        for x in (0..planes.width).step_by(max_block as usize) {
+            stack.push((x, y, max_block));
+        }
+    }
+    // Reverse so leaves come out roughly in reading order.
+    stack.reverse();
+    while let Some((x, y, size)) = stack.pop() {
+        if x >= planes.width || y >= planes.height {
+            continue;
+        }
+        let inside = x + size <= planes.width && y + size <= planes.height;
+        let (mean, variance) = table.stats(
+            x,
+            y,
+            size.min(planes.width - x),
+            size.min(planes.height - y),
+        );
+        if size > 1 && (!inside || (size > min_block && variance > quadtree.threshold as f64)) {
+            let h = size / 2;
+            stack.extend([(x + h, y + h, h), (x, y + h, h), (x + h, y, h), (x, y, h)]);
+        } else {
+            leaves.push(Leaf {
+                x,
+                y,
+                size,
+                t: mean as f32,
+            });
+        }
+    }
+    leaves
+}
+
+fn power_of_two(n: u32) -> u32 {
+    if n <= 1 {
+        1
+    } else {
+        1 << (31 - n.leading_zeros())
+    }
+}
+
+// Running sums of darkness and its square, for constant time block
+// statistics.
+struct SummedArea {
+    width: usize,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+}
+
+impl SummedArea {
+    fn new(planes: &Planes) -> Self {
+        let width = planes.width as usize + 1;
+        let len = width * (planes.height as usize + 1);
+        let mut sum = vec![0.0; len];
+        let mut sum_sq = vec![0.0; len];
+        for y in 0..planes.height as usize {
+            let mut row = 0.0;
+            let mut row_sq = 0.0;
+            for x in 0..planes.width as usize {
+                let t = planes.t(x as u32, y as u32) as f64;
+                row += t;
+                row_sq += t * t;
+                let i = (y + 1) * width + x + 1;
+                sum[i] = sum[i - width] + row;
+                sum_sq[i] = sum_sq[i - width] + row_sq;
+            }
+        }
+        SummedArea { width, sum, sum_sq }
+    }
+
+    // Mean and variance of darkness over a w by h block.
+    fn stats(&self, x: u32, y: u32, w: u32, h: u32) -> (f64, f64) {
+        let (x, y, w, h) = (x as usize, y as usize, w as usize, h as usize);
+        let area = |table: &[f64]| {
+            table[(y + h) * self.width + x + w]
+                - table[y * self.width + x + w]
+                - table[(y + h) * self.width + x]
+                + table[y * self.width + x]
+        };
+        let n = (w * h) as f64;
+        let mean = area(&self.sum) / n;
+        let variance = (area(&self.sum_sq) / n - mean * mean).max(0.0);
+        (mean, variance)
+    }
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::messages::Message;
+use crate::RenderOptions;
+
+// Kinds of render jobs. The order of the variants is the scheduling
+// priority: previews run before exports, and batch jobs only run when
+// nothing else is waiting.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JobKind {
+    Batch,
+    Export,
+    Preview,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+// What the js side sees of a job, sent with every "job-state" event.
+#[derive(Clone, Serialize)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub path: Option<String>,
+    pub error: Option<Message>,
+}
+
+// An export or batch job that has not finished, as saved between runs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pending {
+    pub kind: JobKind,
+    #[serde(with = "crate::migrate")]
+    pub options: RenderOptions,
+    pub path: Option<String>,
+    // The image the job renders. Missing from queues saved before jobs
+    // kept their own, which all rendered the one image of the queue.
+    #[serde(default)]
+    pub image_path: Option<String>,
+}
+
+// Why a running render stopped early.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    Cancelled,
+    Preempted,
+}
+
+// Flags the worker polls while a job is rendering.
+#[derive(Default)]
+pub struct Signal {
+    cancel: AtomicBool,
+    preempt: AtomicBool,
+}
+
+impl Signal {
+    pub fn check(&self) -> Result<(), Interrupt> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(Interrupt::Cancelled)
+        } else if self.preempt.load(Ordering::Relaxed) {
+            Err(Interrupt::Preempted)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Whether the job was cancelled, ignoring a preemption, for work that
+    // is cheaper to finish than to redo.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+struct Job<S> {
+    info: JobInfo,
+    options: RenderOptions,
+    source: Arc<S>,
+    signal: Arc<Signal>,
+}
+
+// A job handed to the worker.
+pub struct Task<S> {
+    pub id: u64,
+    pub kind: JobKind,
+    pub path: Option<String>,
+    pub options: RenderOptions,
+    // What the job renders from, as it was when the job was queued, so
+    // loading another image doesn't change the jobs already waiting.
+    pub source: Arc<S>,
+    pub signal: Arc<Signal>,
+}
+
+pub struct Queue<S> {
+    jobs: Mutex<Vec<Job<S>>>,
+    ready: Condvar,
+    next_id: AtomicU64,
+    // While set only previews are handed out, exports and batch jobs wait.
+    paused: AtomicBool,
+    // Exports being saved outside the queue, by their ids.
+    saves: Mutex<Vec<(u64, Arc<Signal>)>>,
+}
+
+impl<S> Default for Queue<S> {
+    fn default() -> Self {
+        Queue {
+            jobs: Mutex::default(),
+            ready: Condvar::new(),
+            next_id: AtomicU64::new(0),
+            paused: AtomicBool::new(false),
+            saves: Mutex::default(),
+        }
+    }
+}
+
+impl<S> Queue<S> {
+    // Add a job and return its info. A new preview supersedes any preview
+    // still waiting, and preempts a running export or batch job, which is
+    // put back in the queue to be restarted once the preview is done.
+    pub fn push(
+        &self,
+        kind: JobKind,
+        options: RenderOptions,
+        path: Option<String>,
+        source: Arc<S>,
+    ) -> Vec<JobInfo> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        let mut changed = Vec::new();
+        if kind == JobKind::Preview {
+            for job in jobs.iter_mut() {
+                match (job.info.kind, job.info.state) {
+                    (JobKind::Preview, JobState::Queued) => {
+                        job.info.state = JobState::Cancelled;
+                        changed.push(job.info.clone());
+                    }
+                    (JobKind::Preview, JobState::Running) => {
+                        job.signal.cancel.store(true, Ordering::Relaxed);
+                    }
+                    (_, JobState::Running) => {
+                        job.signal.preempt.store(true, Ordering::Relaxed);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let info = JobInfo {
+            id,
+            kind,
+            state: JobState::Queued,
+            path,
+            error: None,
+        };
+        jobs.push(Job {
+            info: info.clone(),
+            options,
+            source,
+            signal: Arc::new(Signal::default()),
+        });
+        changed.push(info);
+        self.ready.notify_one();
+        changed
+    }
+
+    // Cancel a job. Queued jobs are cancelled immediately, running jobs
+    // stop the next time the renderer checks its signal.
+    pub fn cancel(&self, id: u64) -> Option<JobInfo> {
+        let mut jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        let job = jobs.iter_mut().find(|job| job.info.id == id)?;
+        match job.info.state {
+            JobState::Queued => job.info.state = JobState::Cancelled,
+            JobState::Running => job.signal.cancel.store(true, Ordering::Relaxed),
+            _ => {}
+        }
+        Some(job.info.clone())
+    }
+
+    // Take an id and a signal for an export saved outside the queue, so its
+    // progress is reported like a job's and `cancel_save` and `abort` can
+    // stop it. Hand them back with `end_save` once it is saved.
+    pub fn start_save(&self) -> (u64, Arc<Signal>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let signal = Arc::new(Signal::default());
+        self.saves
+            .lock()
+            .expect("Could not lock queue mutex")
+            .push((id, signal.clone()));
+        (id, signal)
+    }
+
+    pub fn end_save(&self, id: u64) {
+        self.saves
+            .lock()
+            .expect("Could not lock queue mutex")
+            .retain(|(save, _)| *save != id);
+    }
+
+    // Cancel an export saved outside the queue, returning whether there
+    // was one with the id.
+    pub fn cancel_save(&self, id: u64) -> bool {
+        let saves = self.saves.lock().expect("Could not lock queue mutex");
+        let save = saves.iter().find(|(save, _)| *save == id);
+        if let Some((_, signal)) = save {
+            signal.cancel.store(true, Ordering::Relaxed);
+        }
+        save.is_some()
+    }
+
+    // Hold back exports and batch jobs, a running one is preempted and put
+    // back in the queue. Previews keep running.
+    pub fn pause(&self) {
+        let jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        self.paused.store(true, Ordering::Relaxed);
+        for job in jobs.iter() {
+            if job.info.kind != JobKind::Preview && job.info.state == JobState::Running {
+                job.signal.preempt.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn resume(&self) {
+        let _jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        self.paused.store(false, Ordering::Relaxed);
+        self.ready.notify_one();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Cancel every export and batch job that has not finished, and the
+    // exports being saved outside the queue.
+    pub fn abort(&self) -> Vec<JobInfo> {
+        let mut jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        let mut changed = Vec::new();
+        for job in jobs
+            .iter_mut()
+            .filter(|job| job.info.kind != JobKind::Preview)
+        {
+            match job.info.state {
+                JobState::Queued => {
+                    job.info.state = JobState::Cancelled;
+                    changed.push(job.info.clone());
+                }
+                JobState::Running => job.signal.cancel.store(true, Ordering::Relaxed),
+                _ => {}
+            }
+        }
+        for (_, signal) in self
+            .saves
+            .lock()
+            .expect("Could not lock queue mutex")
+            .iter()
+        {
+            signal.cancel.store(true, Ordering::Relaxed);
+        }
+        changed
+    }
+
+    // The exports and batch jobs still to do, oldest first, with the path
+    // of the image each renders from `image_path`.
+    pub fn pending(&self, image_path: impl Fn(&S) -> Option<String>) -> Vec<Pending> {
+        let jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        jobs.iter()
+            .filter(|job| job.info.kind != JobKind::Preview)
+            .filter(|job| matches!(job.info.state, JobState::Queued | JobState::Running))
+            .map(|job| Pending {
+                kind: job.info.kind,
+                options: job.options.clone(),
+                path: job.info.path.clone(),
+                image_path: image_path(&job.source),
+            })
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        jobs.iter().map(|job| job.info.clone()).collect()
+    }
+
+    // Block until a job is queued, then mark the highest priority one
+    // (oldest first within a kind) as running and hand it out. Only
+    // previews are handed out while the queue is paused.
+    pub fn next(&self) -> (Task<S>, JobInfo) {
+        let mut jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        loop {
+            let paused = self.is_paused();
+            let best = jobs
+                .iter_mut()
+                .filter(|job| job.info.state == JobState::Queued)
+                .filter(|job| !paused || job.info.kind == JobKind::Preview)
+                .max_by_key(|job| (job.info.kind, std::cmp::Reverse(job.info.id)));
+            if let Some(job) = best {
+                job.info.state = JobState::Running;
+                job.signal = Arc::new(Signal::default());
+                let task = Task {
+                    id: job.info.id,
+                    kind: job.info.kind,
+                    path: job.info.path.clone(),
+                    options: job.options.clone(),
+                    source: job.source.clone(),
+                    signal: job.signal.clone(),
+                };
+                return (task, job.info.clone());
+            }
+            jobs = self.ready.wait(jobs).expect("Could not lock queue mutex");
+        }
+    }
+
+    // Record how a job ended. Finished jobs are dropped from the queue
+    // once the js side has been told, only the most recent are kept.
+    pub fn finish(
+        &self,
+        id: u64,
+        result: Result<(), Interrupt>,
+        error: Option<Message>,
+    ) -> Option<JobInfo> {
+        let mut jobs = self.jobs.lock().expect("Could not lock queue mutex");
+        let job = jobs.iter_mut().find(|job| job.info.id == id)?;
+        job.info.state = match (result, &error) {
+            (Err(Interrupt::Preempted), _) => JobState::Queued,
+            (Err(Interrupt::Cancelled), _) => JobState::Cancelled,
+            (Ok(()), Some(_)) => JobState::Failed,
+            (Ok(()), None) => JobState::Done,
+        };
+        job.info.error = error;
+        let info = job.info.clone();
+        let finished =
+            |job: &Job<S>| !matches!(job.info.state, JobState::Queued | JobState::Running);
+        let excess = jobs
+            .iter()
+            .filter(|job| finished(job))
+            .count()
+            .saturating_sub(MAX_FINISHED);
+        let mut dropped = 0;
+        jobs.retain(|job| {
+            if dropped < excess && finished(job) {
+                dropped += 1;
+                false
+            } else {
+                true
+            }
+        });
+        Some(info)
+    }
+}
+
+// How many finished jobs `get_queue` keeps reporting.
+const MAX_FINISHED: usize = 32;
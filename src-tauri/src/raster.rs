@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use wassily::prelude::*;
+
+use crate::display::{self, Primitive};
+
+// Whether the edges of marks are smoothed, on unless turned off. Off,
+// every pixel is either a mark's color or the background.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Antialias(pub bool);
+
+impl Default for Antialias {
+    fn default() -> Self {
+        Antialias(true)
+    }
+}
+
+// Draw a display list onto a canvas, the way every preview and image
+// export is made. A canvas `scale` times the size of the output the list
+// was made for is drawn on with the marks scaled to match, see `shrink`.
+pub fn draw(canvas: &mut Canvas, primitives: &[Primitive], antialias: Antialias, scale: u32) {
+    let Antialias(antialias) = antialias;
+    let s = scale as f32;
+    let at = |[x, y]: [f32; 2]| pt(s * x, s * y);
+    for primitive in primitives {
+        let [r, g, b, a] = primitive.color();
+        let color = Color::from_rgba8(r, g, b, a);
+        match primitive {
+            Primitive::Circle { x, y, radius, .. } => Shape::new()
+                .anti_alias(antialias)
+                .circle(at([*x, *y]), s * radius)
+                .fill_color(color)
+                .no_stroke()
+                .draw(canvas),
+            Primitive::Ring {
+                x,
+                y,
+                radius,
+                weight,
+                ..
+            } => Shape::new()
+                .anti_alias(antialias)
+                .circle(at([*x, *y]), s * radius)
+                .no_fill()
+                .stroke_color(color)
+                .stroke_weight(s * weight)
+                .draw(canvas),
+            Primitive::Polygon { points, .. } => {
+                let points: Vec<Point> = points.iter().copied().map(at).collect();
+                Shape::new()
+                    .anti_alias(antialias)
+                    .points(&points)
+                    .fill_color(color)
+                    .no_stroke()
+                    .draw(canvas)
+            }
+            Primitive::Line {
+                from, to, weight, ..
+            } => Shape::new()
+                .anti_alias(antialias)
+                .line(at(*from), at(*to))
+                .no_fill()
+                .stroke_color(color)
+                .stroke_weight(s * weight)
+                .draw(canvas),
+            Primitive::Curve {
+                start,
+                segments,
+                weight,
+                ..
+            } => {
+                let points = display::flatten(*start, segments);
+                for pair in points.windows(2) {
+                    Shape::new()
+                        .anti_alias(antialias)
+                        .line(at(pair[0]), at(pair[1]))
+                        .no_fill()
+                        .stroke_color(color)
+                        .stroke_weight(s * weight)
+                        .draw(canvas)
+                }
+            }
+            // A pixel of the output, so a block of the canvas.
+            Primitive::Dot { x, y, .. } => {
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        canvas.dot(s * x + dx as f32, s * y + dy as f32, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The RGBA pixels of a canvas drawn at `scale` times the output size,
+// each output pixel the mean of its block.
+pub fn shrink(canvas: &Canvas, scale: u32) -> Cow<'_, [u8]> {
+    if scale <= 1 {
+        return Cow::Borrowed(canvas.data());
+    }
+    let (k, width) = (scale as usize, canvas.width() as usize);
+    let (out_width, out_height) = (width / k, canvas.height() as usize / k);
+    let data = canvas.data();
+    let mut out = vec![0; 4 * out_width * out_height];
+    for (i, px) in out.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % out_width * k, i / out_width * k);
+        let mut sum = [0u32; 4];
+        for row in y..y + k {
+            let start = 4 * (row * width + x);
+            for block in data[start..start + 4 * k].chunks_exact(4) {
+                for c in 0..4 {
+                    sum[c] += block[c] as u32;
+                }
+            }
+        }
+        for c in 0..4 {
+            px[c] = (sum[c] / (k * k) as u32) as u8;
+        }
+    }
+    Cow::Owned(out)
+}
@@ -0,0 +1,914 @@
+use image::{Rgba, RgbaImage};
+use noise::{Fbm, Perlin};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use wassily::prelude::*;
+
+use crate::canvas_pool::CanvasPool;
+use crate::cell_map::{self, CellMap};
+use crate::cmy;
+use crate::composite::{self, BlendMode};
+use crate::debug;
+use crate::depth;
+use crate::display::{DisplayList, Primitive};
+use crate::faces;
+use crate::labels::Region;
+use crate::layers;
+use crate::layout::{self, Layout, Placed};
+use crate::mask;
+use crate::patches;
+use crate::pen::Pen;
+use crate::planes::Planes;
+use crate::quadtree;
+use crate::queue::{Interrupt, JobKind, Signal};
+use crate::raster;
+use crate::slic::Segment;
+use crate::styles::{cross, dots, grid, hline, stipple, vline, Cell, DotRotation, HatchDirection};
+use crate::symmetry;
+use crate::tiling;
+use crate::{RenderOptions, Style};
+
+// The color the canvas is filled with before any marks are drawn.
+pub const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+// The color left where no marks are drawn once rendering is finished.
+pub fn background(options: &RenderOptions) -> Rgba<u8> {
+    match (options.transparent, options.invert_output) {
+        (true, false) => Rgba([0, 0, 0, 0]),
+        (true, true) => Rgba([255, 255, 255, 0]),
+        (false, true) => Rgba([0, 0, 0, 255]),
+        (false, false) => BACKGROUND,
+    }
+}
+
+// Marks may spill out of their cell, dots at full darkness reach about
+// a tenth of a cell into their neighbors. Each band is drawn with this
+// many extra rows of cells above and below so the spill is not clipped.
+const PAD: u32 = 1;
+
+// Mixed with the seed of the options for the jitter, so the offsets are
+// drawn apart from the marks' own randomness of the same cell.
+const JITTER_SEED: u64 = 1618;
+
+// Thread pools for the renderer. Previews get their own pool so they are
+// never starved by a long export running at low priority.
+pub struct Pools {
+    pub threads: usize,
+    pub low_priority_exports: bool,
+    preview: Arc<rayon::ThreadPool>,
+    export: Arc<rayon::ThreadPool>,
+}
+
+impl Pools {
+    // Build the pools, `threads` of 0 means one per core.
+    pub fn new(threads: usize, low_priority_exports: bool) -> Result<Self, String> {
+        let preview = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("seg-preview-{}", i))
+            .build()
+            .map_err(|err| format!("Could not start the render threads: {}", err))?;
+        let export = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("seg-export-{}", i))
+            .start_handler(move |_| {
+                if low_priority_exports {
+                    let _ = thread_priority::set_current_thread_priority(
+                        thread_priority::ThreadPriority::Min,
+                    );
+                }
+            })
+            .build()
+            .map_err(|err| format!("Could not start the render threads: {}", err))?;
+        Ok(Pools {
+            threads,
+            low_priority_exports,
+            preview: Arc::new(preview),
+            export: Arc::new(export),
+        })
+    }
+
+    // The pool a job of the given kind should run on.
+    pub fn for_kind(&self, kind: JobKind) -> Arc<rayon::ThreadPool> {
+        match kind {
+            JobKind::Preview => self.preview.clone(),
+            JobKind::Export | JobKind::Batch => self.export.clone(),
+        }
+    }
+}
+
+// Render the base image. The image is split into horizontal bands of cells
+// which are drawn in parallel on `pool` and then composited. The signal is
+// checked once per row of cells so that queued jobs can be cancelled or
+// preempted part way through. Band canvases and the output image come from
+// `canvases` and band canvases are returned to it once composited.
+pub fn generate(
+    planes: &Planes,
+    options: &RenderOptions,
+    signal: &Signal,
+    pool: &rayon::ThreadPool,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    if let Some(passes) = cmy::passes(planes, options) {
+        return generate_cmy(planes, options, &passes, signal, pool, canvases);
+    }
+    if let Some((planes, options)) = prepare(planes, options) {
+        return generate(&planes, &options, signal, pool, canvases);
+    }
+    if options.debug_overlay {
+        return debug::overlay(planes, options, signal, canvases);
+    }
+    let mut out_img = if options.layers.is_empty() {
+        marks(planes, options, signal, pool, canvases)?
+    } else {
+        generate_layers(planes, options, signal, pool, canvases)?
+    };
+    ink(&mut out_img, options);
+    Ok(out_img)
+}
+
+// One step of readying the planes and options to draw: the auto mask,
+// the face boost and the depth densities and cell sizes are each applied
+// once, after which the options no longer ask for them. `None` once there
+// is nothing left to do.
+fn prepare(planes: &Planes, options: &RenderOptions) -> Option<(Planes, RenderOptions)> {
+    if let Some(threshold) = options.auto_mask {
+        let mask = mask::mask_from_luminance(planes, threshold);
+        let masked = mask::apply(planes, &mask, options.invert_output);
+        let options = RenderOptions {
+            auto_mask: None,
+            ..options.clone()
+        };
+        return Some((masked, options));
+    }
+    if let (Some(boost), Some(found)) = (options.faces, &planes.faces) {
+        let boosted = faces::boost(planes, found, &boost, options.invert_output);
+        let cell_map = match (&options.cell_map, boost.background_cell) {
+            (None, background) if background > 1 && !found.is_empty() => Some(faces::cell_map(
+                found,
+                planes.width,
+                planes.height,
+                background,
+            )),
+            _ => options.cell_map.clone(),
+        };
+        let options = RenderOptions {
+            faces: None,
+            cell_map,
+            ..options.clone()
+        };
+        return Some((boosted, options));
+    }
+    if let (Some(by_depth), Some(map)) = (&options.depth, &planes.depth) {
+        if by_depth.modulates() {
+            let modulated = depth::apply_density(planes, map, by_depth, options.invert_output);
+            let cell_map = match &options.cell_map {
+                None if by_depth.near_cell > 1 || by_depth.far_cell > 1 => {
+                    Some(depth::cell_map(map, by_depth, planes.width, planes.height))
+                }
+                _ => options.cell_map.clone(),
+            };
+            let options = RenderOptions {
+                depth: Some(by_depth.styles_only()),
+                cell_map,
+                ..options.clone()
+            };
+            return Some((modulated, options));
+        }
+    }
+    None
+}
+
+// Draw the marks in black on white, before the output mode is applied.
+fn marks(
+    planes: &Planes,
+    options: &RenderOptions,
+    signal: &Signal,
+    pool: &rayon::ThreadPool,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    if options.symmetry.is_some() || options.tileable {
+        return generate_listed(planes, options, signal, canvases);
+    }
+    if let Some(map) = &options.cell_map {
+        return marks_by_cell_map(planes, options, map, signal, pool, canvases);
+    }
+    if let Some(placed) = placed_cells(planes, options) {
+        return generate_placed(planes, options, &placed, signal, canvases);
+    }
+    let cell = options.cell;
+    let rows = planes.height;
+    let band = (rows / (4 * pool.current_num_threads() as u32)).max(1);
+    let bands: Vec<(u32, u32)> = (0..rows)
+        .step_by(band as usize)
+        .map(|y0| (y0, (y0 + band).min(rows)))
+        .collect();
+    let rendered = pool.install(|| {
+        bands
+            .par_iter()
+            .map(|&(y0, y1)| render_band(planes, options, signal, canvases, y0, y1))
+            .collect::<Vec<_>>()
+    });
+    let mut bands_img = Vec::with_capacity(rendered.len());
+    let mut interrupt = None;
+    for band in rendered {
+        match band {
+            Ok(canvas) => bands_img.push(canvas),
+            Err(err) => interrupt = Some(err),
+        }
+    }
+    if let Some(err) = interrupt {
+        bands_img
+            .into_iter()
+            .for_each(|canvas| canvases.recycle_canvas(canvas));
+        return Err(err);
+    }
+    let mut out_img = canvases.image(cell * planes.width, cell * rows, BACKGROUND);
+    for (&(y0, _), canvas) in bands.iter().zip(bands_img) {
+        let pixels = raster::shrink(&canvas, options.supersample());
+        darken(&mut out_img, &pixels, y0.saturating_sub(PAD) * cell);
+        canvases.recycle_canvas(canvas);
+    }
+    Ok(out_img)
+}
+
+// Draw the cells in rows `y0..y1` onto a canvas that also covers the
+// padding rows.
+fn render_band(
+    planes: &Planes,
+    options: &RenderOptions,
+    signal: &Signal,
+    canvases: &CanvasPool,
+    y0: u32,
+    y1: u32,
+) -> Result<Canvas, Interrupt> {
+    let cell = options.cell;
+    let top = y0.saturating_sub(PAD);
+    let bottom = (y1 + PAD).min(planes.height);
+    let k = options.supersample();
+    let mut canvas = canvases.canvas(k * cell * planes.width, k * cell * (bottom - top), *WHITE);
+    let marker = Marker::new(planes, options, (top * cell) as f32);
+    // The display list of one row at a time, rasterized before the next.
+    let mut list = Vec::new();
+    for y in y0..y1 {
+        if let Err(err) = signal.check() {
+            canvases.recycle_canvas(canvas);
+            return Err(err);
+        }
+        // Row of the cell on the band canvas.
+        let by = y - top;
+        for x in 0..planes.width {
+            match options.layout {
+                Layout::Brick { columns } => {
+                    layout::brick(planes, cell, columns, (x, y), by, |p| {
+                        marker.mark(&p.cell, p.at, p.t, &mut list)
+                    })
+                }
+                _ => marker.mark(&Cell::grid(cell, x, by), (x, y), planes.t(x, y), &mut list),
+            }
+        }
+        raster::draw(&mut canvas, &list, options.antialias, k);
+        list.clear();
+    }
+    Ok(canvas)
+}
+
+// Cells for the layouts that do not fit in bands: adaptive cells can be
+// far taller than a band and polar and screen cells are scattered at
+// every angle.
+fn placed_cells(planes: &Planes, options: &RenderOptions) -> Option<Vec<Placed>> {
+    let cell = options.cell;
+    if let Some(quadtree) = &options.quadtree {
+        let placed = quadtree::leaves(planes, quadtree)
+            .into_iter()
+            .map(|leaf| Placed {
+                cell: Cell::grid(leaf.size * cell, leaf.x / leaf.size, leaf.y / leaf.size),
+                // Hue and gradient are read at the center of the leaf.
+                at: (
+                    (leaf.x + leaf.size / 2).min(planes.width - 1),
+                    (leaf.y + leaf.size / 2).min(planes.height - 1),
+                ),
+                t: leaf.t,
+            })
+            .collect();
+        return Some(placed);
+    }
+    match options.layout {
+        Layout::Grid | Layout::Brick { .. } => None,
+        Layout::Polar { center } => Some(layout::polar(planes, cell, center)),
+        Layout::Screen { angle } => Some(layout::screen(planes, cell, angle)),
+    }
+}
+
+// Render the whole source once per cell size in the map, from planes shrunk
+// to that size, and piece the renders together by region.
+fn marks_by_cell_map(
+    planes: &Planes,
+    options: &RenderOptions,
+    map: &CellMap,
+    signal: &Signal,
+    pool: &rayon::ThreadPool,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    let cell = options.cell;
+    let mut out_img = canvases.image(cell * planes.width, cell * planes.height, BACKGROUND);
+    for size in map.distinct() {
+        let region_options = RenderOptions {
+            cell: cell * size,
+            cell_map: None,
+            ..options.clone()
+        };
+        let rendered = if size == 1 {
+            marks(planes, &region_options, signal, pool, canvases)
+        } else {
+            marks(
+                &planes.shrink(size),
+                &region_options,
+                signal,
+                pool,
+                canvases,
+            )
+        };
+        let region = match rendered {
+            Ok(region) => region,
+            Err(err) => {
+                canvases.recycle_image(out_img);
+                return Err(err);
+            }
+        };
+        let source_size = (planes.width, planes.height);
+        cell_map::composite(&mut out_img, &region, map, size, cell, source_size);
+        canvases.recycle_image(region);
+    }
+    Ok(out_img)
+}
+
+// Draw each active layer on its own, in its style, and composite them in
+// their blend modes onto white, bottom first.
+fn generate_layers(
+    planes: &Planes,
+    options: &RenderOptions,
+    signal: &Signal,
+    pool: &rayon::ThreadPool,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    let cell = options.cell;
+    let mut out_img = canvases.image(cell * planes.width, cell * planes.height, BACKGROUND);
+    for layer in layers::active(&options.layers) {
+        let layer_options = RenderOptions {
+            style: layer.style,
+            layers: Vec::new(),
+            ..options.clone()
+        };
+        let img = match marks(planes, &layer_options, signal, pool, canvases) {
+            Ok(img) => img,
+            Err(err) => {
+                canvases.recycle_image(out_img);
+                return Err(err);
+            }
+        };
+        composite::composite(&mut out_img, &img, layer.mode, layer.opacity);
+        canvases.recycle_image(img);
+    }
+    Ok(out_img)
+}
+
+// Render each ink of a CMY halftone and multiply them together on white.
+fn generate_cmy(
+    planes: &Planes,
+    options: &RenderOptions,
+    passes: &[(Planes, RenderOptions)],
+    signal: &Signal,
+    pool: &rayon::ThreadPool,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    let cell = options.cell;
+    let mut out_img = canvases.image(cell * planes.width, cell * planes.height, BACKGROUND);
+    for (pass_planes, pass_options) in passes {
+        let img = match generate(pass_planes, pass_options, signal, pool, canvases) {
+            Ok(img) => img,
+            Err(err) => {
+                canvases.recycle_image(out_img);
+                return Err(err);
+            }
+        };
+        composite::composite(&mut out_img, &img, BlendMode::Multiply, 1.0);
+        canvases.recycle_image(img);
+    }
+    ink(&mut out_img, options);
+    Ok(out_img)
+}
+
+// The marks of a render as a display list for the vector backends, in
+// output pixels. The cells are visited as `generate` visits them but in a
+// single pass, so stipple may scatter differently. Layers are listed
+// bottom first and their blend modes left out.
+pub fn display_list(planes: &Planes, options: &RenderOptions) -> DisplayList {
+    if let Some(passes) = cmy::passes(planes, options) {
+        let mut primitives: Vec<Primitive> = passes
+            .iter()
+            .flat_map(|(planes, options)| display_list(planes, options).primitives)
+            .collect();
+        if options.invert_output {
+            primitives.iter_mut().for_each(Primitive::invert);
+        }
+        return DisplayList {
+            width: options.cell * planes.width,
+            height: options.cell * planes.height,
+            primitives,
+        };
+    }
+    if let Some((planes, options)) = prepare(planes, options) {
+        return display_list(&planes, &options);
+    }
+    let passes: Vec<RenderOptions> = if options.layers.is_empty() {
+        vec![options.clone()]
+    } else {
+        layers::active(&options.layers)
+            .map(|layer| RenderOptions {
+                style: layer.style,
+                layers: Vec::new(),
+                ..options.clone()
+            })
+            .collect()
+    };
+    let (width, height) = (planes.width, planes.height);
+    let mut marks = Vec::new();
+    for pass in &passes {
+        match &pass.cell_map {
+            Some(map) => {
+                for size in map.distinct() {
+                    let region_options = RenderOptions {
+                        cell: pass.cell * size,
+                        cell_map: None,
+                        ..pass.clone()
+                    };
+                    // Only the cells over the part of the source that asks
+                    // for this size.
+                    record_cells(&planes.shrink(size), &region_options, &mut marks, |x, y| {
+                        let (sx, sy) = ((x * size).min(width - 1), (y * size).min(height - 1));
+                        map.size_at(sx, sy, width, height) == size
+                    });
+                }
+            }
+            None => record_cells(planes, pass, &mut marks, |_, _| true),
+        }
+    }
+    if options.invert_output {
+        marks.iter_mut().for_each(Primitive::invert);
+    }
+    if let Some(symmetry) = options.symmetry {
+        marks = symmetry::apply(marks, options.cell * width, options.cell * height, symmetry);
+    }
+    if options.tileable {
+        marks = tiling::wrap(marks, options.cell * width, options.cell * height);
+    }
+    DisplayList {
+        width: options.cell * width,
+        height: options.cell * height,
+        primitives: marks,
+    }
+}
+
+// Record the marks of the cells whose source pixel is kept.
+fn record_cells(
+    planes: &Planes,
+    options: &RenderOptions,
+    marks: &mut Vec<Primitive>,
+    keep: impl Fn(u32, u32) -> bool,
+) {
+    let cell = options.cell;
+    let marker = Marker::new(planes, options, 0.0);
+    let mut mark = |p: Placed| {
+        if keep(p.at.0, p.at.1) {
+            marker.mark(&p.cell, p.at, p.t, marks);
+        }
+    };
+    if let Some(placed) = placed_cells(planes, options) {
+        placed.into_iter().for_each(mark);
+        return;
+    }
+    for y in 0..planes.height {
+        for x in 0..planes.width {
+            match options.layout {
+                Layout::Brick { columns } => {
+                    layout::brick(planes, cell, columns, (x, y), y, &mut mark)
+                }
+                _ => mark(Placed {
+                    cell: Cell::grid(cell, x, y),
+                    at: (x, y),
+                    t: planes.t(x, y),
+                }),
+            }
+        }
+    }
+}
+
+// Draw a list of placed cells on a single canvas.
+fn generate_placed(
+    planes: &Planes,
+    options: &RenderOptions,
+    placed: &[Placed],
+    signal: &Signal,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    let cell = options.cell;
+    let (width, height) = (cell * planes.width, cell * planes.height);
+    let k = options.supersample();
+    let mut canvas = canvases.canvas(k * width, k * height, *WHITE);
+    let marker = Marker::new(planes, options, 0.0);
+    let mut list = Vec::new();
+    for chunk in placed.chunks(1024) {
+        if let Err(err) = signal.check() {
+            canvases.recycle_canvas(canvas);
+            return Err(err);
+        }
+        for p in chunk {
+            marker.mark(&p.cell, p.at, p.t, &mut list);
+        }
+        raster::draw(&mut canvas, &list, options.antialias, k);
+        list.clear();
+    }
+    let mut out_img = canvases.image(width, height, BACKGROUND);
+    darken(&mut out_img, &raster::shrink(&canvas, k), 0);
+    canvases.recycle_canvas(canvas);
+    Ok(out_img)
+}
+
+// Draw the marks made symmetric or tileable on a single canvas. Marks are
+// moved across the whole output, so they are listed first rather than
+// drawn a band at a time.
+fn generate_listed(
+    planes: &Planes,
+    options: &RenderOptions,
+    signal: &Signal,
+    canvases: &CanvasPool,
+) -> Result<RgbaImage, Interrupt> {
+    let mut list = display_list(planes, options);
+    // The list has the colors of a negative flipped, which `ink` does for
+    // the whole render instead.
+    if options.invert_output {
+        list.primitives.iter_mut().for_each(Primitive::invert);
+    }
+    let k = options.supersample();
+    let mut canvas = canvases.canvas(k * list.width, k * list.height, *WHITE);
+    for chunk in list.primitives.chunks(1024) {
+        if let Err(err) = signal.check() {
+            canvases.recycle_canvas(canvas);
+            return Err(err);
+        }
+        raster::draw(&mut canvas, chunk, options.antialias, k);
+    }
+    let mut out_img = canvases.image(list.width, list.height, BACKGROUND);
+    darken(&mut out_img, &raster::shrink(&canvas, k), 0);
+    canvases.recycle_canvas(canvas);
+    Ok(out_img)
+}
+
+// Draws the mark for a cell in whichever style the options ask for.
+struct Marker<'a> {
+    planes: &'a Planes,
+    options: &'a RenderOptions,
+    pen: Pen,
+    origin_y: f32,
+    // The noise that lays out the patches of Patches.
+    field: Fbm<Perlin>,
+    // Mark colors at 256 levels of lightness from the gradient map, or
+    // `None` for black.
+    inks: Option<Vec<Color>>,
+    // The region options for each label of the label mask.
+    regions: Vec<Option<&'a Region>>,
+}
+
+impl<'a> Marker<'a> {
+    // `origin_y` is the offset of the canvas from the top of the output.
+    fn new(planes: &'a Planes, options: &'a RenderOptions, origin_y: f32) -> Self {
+        Marker {
+            planes,
+            options,
+            pen: Pen::new(options.hand_drawn, options.stroke, origin_y),
+            origin_y,
+            field: patches::field(options.seed, options.patches.octaves),
+            // A negative is flipped once rendered, so its colors are drawn
+            // flipped to come out right.
+            inks: options.gradient_map.as_ref().map(|map| {
+                map.table()
+                    .into_iter()
+                    .map(|[r, g, b]| {
+                        if options.invert_output {
+                            Color::from_rgba8(255 - r, 255 - g, 255 - b, 255)
+                        } else {
+                            Color::from_rgba8(r, g, b, 255)
+                        }
+                    })
+                    .collect()
+            }),
+            regions: planes
+                .labels
+                .as_ref()
+                .map(|labels| {
+                    labels
+                        .colors
+                        .iter()
+                        .map(|color| options.regions.iter().find(|r| r.color == *color))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    // The region options over a source pixel, if a label mask is loaded and
+    // its label there has any.
+    fn region(&self, sx: u32, sy: u32) -> Option<&'a Region> {
+        let labels = self.planes.labels.as_ref()?;
+        let label = labels.label_at(sx, sy, self.planes.width, self.planes.height);
+        self.regions[label as usize]
+    }
+
+    // The style the depth map picks over a source pixel, if any.
+    fn depth_style(&self, sx: u32, sy: u32) -> Option<Style> {
+        let map = self.planes.depth.as_ref()?;
+        let near = map.near_at(sx, sy, self.planes.width, self.planes.height);
+        self.options.depth.as_ref()?.style(near)
+    }
+
+    // Draw a mark in a cell on the list. The source pixel `at` supplies
+    // hue and gradient, `t` is the darkness.
+    fn mark(&self, cell: &Cell, at: (u32, u32), t: f32, list: &mut Vec<Primitive>) {
+        let mut rng = self.rng(cell);
+        let cell = &self.jitter(cell);
+        let color = self.ink(t);
+        // The band is picked by the luminance of the source, whatever the
+        // output mode.
+        let luminance = 1.0 - t;
+        // Inverted marks are drawn black for lightness and flipped at the
+        // end, which keeps their anti-aliased edges intact.
+        let t = if self.options.invert_output {
+            1.0 - t
+        } else {
+            t
+        };
+        let (sx, sy) = at;
+        let (style, t) = match self.region(sx, sy) {
+            Some(region) => (region.style, (t * region.density).clamp(0.0, 1.0)),
+            None => (self.depth_style(sx, sy).unwrap_or(self.options.style), t),
+        };
+        let (style, t) = match style {
+            Style::Multi => {
+                let (style, density) = match &self.planes.segments {
+                    Some(segments) => segment_style(
+                        segments.segment_at(sx, sy, self.planes.width, self.planes.height),
+                        &self.options.multi,
+                    ),
+                    None => multi_style(self.planes.hue(sx, sy), &self.options.multi),
+                };
+                (style, (t * density).clamp(0.0, 1.0))
+            }
+            Style::Tonal => (self.options.tonal.style(luminance), t),
+            Style::Patches => (self.options.patches.style(&self.field, sx, sy), t),
+            style => (style, t),
+        };
+        let t = match &self.options.tone_curve {
+            Some(curve) => curve.apply(t),
+            None => t,
+        };
+        match style {
+            Style::Dots => {
+                let angle = match self.options.dot_rotation {
+                    DotRotation::None => 0.0,
+                    DotRotation::Luminance => t * std::f32::consts::FRAC_PI_2,
+                    DotRotation::Hue => (self.planes.hue(sx, sy) as f32).to_radians(),
+                };
+                dots(
+                    cell,
+                    t,
+                    &self.options.dot_shape,
+                    self.options.dot_fill,
+                    angle,
+                    color,
+                    list,
+                )
+            }
+            Style::VLines => vline(cell, t, self.turn(at), color, &self.pen, &mut rng, list),
+            Style::HLines => hline(cell, t, self.turn(at), color, &self.pen, &mut rng, list),
+            Style::Cross => cross(cell, t, self.turn(at), color, &self.pen, &mut rng, list),
+            Style::Stipple => stipple(cell, t, color, &mut rng, list),
+            Style::Grid => grid(cell, t, color, list),
+            Style::Multi | Style::Tonal | Style::Patches => {
+                unreachable!("Multi, Tonal and Patches always resolve to a single style")
+            }
+        }
+    }
+
+    // The color of a mark for the darkness of its source.
+    fn ink(&self, t: f32) -> Color {
+        match &self.inks {
+            Some(inks) => inks[((1.0 - t).clamp(0.0, 1.0) * 255.0).round() as usize],
+            None => *BLACK,
+        }
+    }
+
+    // The random choices for a cell's mark, seeded from the seed and the
+    // cell's position in the full output. The marks are the same however
+    // the output is split into bands, whichever thread draws them and in
+    // whatever order, and when only part of it is drawn again.
+    fn rng(&self, cell: &Cell) -> SmallRng {
+        let seed = [cell.x0.to_bits(), (cell.y0 + self.origin_y).to_bits()]
+            .into_iter()
+            .fold(mix(self.options.seed), |hash, bits| mix(hash ^ bits as u64));
+        SmallRng::seed_from_u64(seed)
+    }
+
+    // Move a cell by a random offset of up to half a cell times the jitter.
+    // The offset is seeded from the seed of the options and the cell's
+    // position in the full output, like `rng`, so a new seed moves the
+    // marks and it does not depend on how the image was split into bands.
+    fn jitter(&self, cell: &Cell) -> Cell {
+        let jitter = self.options.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return *cell;
+        }
+        let seed = [cell.x0.to_bits(), (cell.y0 + self.origin_y).to_bits()]
+            .into_iter()
+            .fold(mix(self.options.seed ^ JITTER_SEED), |hash, bits| {
+                mix(hash ^ bits as u64)
+            });
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let reach = jitter * cell.size as f32 / 2.0;
+        Cell {
+            x0: cell.x0 + rng.gen_range(-reach..=reach),
+            y0: cell.y0 + rng.gen_range(-reach..=reach),
+            ..*cell
+        }
+    }
+
+    // The angle the line styles are turned to at a source pixel.
+    fn turn(&self, (sx, sy): (u32, u32)) -> Option<f32> {
+        match self.options.hatch_direction {
+            HatchDirection::Fixed => None,
+            direction => direction.angle(self.planes.gradient(sx, sy)),
+        }
+    }
+}
+
+// SplitMix64's finalizer, which spreads each bit of the input over the
+// whole output so nearby cells get unrelated seeds.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// How `Multi` treats each hue bucket.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct HueMapping {
+    #[serde(default)]
+    pub density: HueDensity,
+}
+
+// The styles `Tonal` draws each luminance band of the source in, and where
+// the bands meet, as luminance in [0, 1].
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneMapping {
+    pub shadows: Style,
+    pub midtones: Style,
+    pub highlights: Style,
+    pub shadows_below: f32,
+    pub highlights_above: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            shadows: Style::Cross,
+            midtones: Style::Stipple,
+            highlights: Style::Dots,
+            shadows_below: 1.0 / 3.0,
+            highlights_above: 2.0 / 3.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn style(&self, luminance: f32) -> Style {
+        if luminance < self.shadows_below {
+            self.shadows
+        } else if luminance > self.highlights_above {
+            self.highlights
+        } else {
+            self.midtones
+        }
+    }
+}
+
+// Darkness multipliers for the hue buckets, so mark types that read lighter
+// than others at the same darkness can be evened out.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HueDensity {
+    pub red: f32,
+    pub orange: f32,
+    pub yellow: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub purple: f32,
+}
+
+impl Default for HueDensity {
+    fn default() -> Self {
+        HueDensity {
+            red: 1.0,
+            orange: 1.0,
+            yellow: 1.0,
+            green: 1.0,
+            blue: 1.0,
+            purple: 1.0,
+        }
+    }
+}
+
+// The style `Multi` uses for a hue and the density multiplier for it.
+fn multi_style(hue: i32, mapping: &HueMapping) -> (Style, f32) {
+    let d = &mapping.density;
+    match hue_bucket(hue) {
+        1 => (Style::Cross, d.orange),
+        2 => (Style::Stipple, d.yellow),
+        3 => (Style::VLines, d.green),
+        4 => (Style::Dots, d.blue),
+        5 => (Style::Grid, d.purple),
+        _ => (Style::HLines, d.red),
+    }
+}
+
+// Grays are told apart by texture rather than by their meaningless hue.
+const GRAY: f32 = 0.12;
+const TEXTURED: f32 = 0.08;
+
+// The style `Multi` uses for a superpixel: by the hue of its mean color,
+// or for a gray segment stipple if it is busy and lines if it is smooth.
+fn segment_style(segment: &Segment, mapping: &HueMapping) -> (Style, f32) {
+    if segment.saturation >= GRAY {
+        multi_style(segment.hue, mapping)
+    } else if segment.texture > TEXTURED {
+        (Style::Stipple, 1.0)
+    } else {
+        (Style::HLines, 1.0)
+    }
+}
+
+// The bucket `Multi` puts a hue in: red, orange, yellow, green, blue and
+// purple in that order.
+pub fn hue_bucket(hue: i32) -> usize {
+    match hue {
+        15..=45 => 1,
+        46..=75 => 2,
+        76..=165 => 3,
+        166..=255 => 4,
+        256..=345 => 5,
+        _ => 0,
+    }
+}
+
+// Composite the pixels of a band, as wide as the output, onto it keeping
+// the darker of the two pixels, so marks spilling into the padding of
+// neighboring bands are kept.
+fn darken(out_img: &mut RgbaImage, band: &[u8], offset: u32) {
+    let row = 4 * out_img.width() as usize;
+    let height = (band.len() / row).min((out_img.height() - offset) as usize);
+    let start = offset as usize * row;
+    let dst = &mut out_img.as_mut()[start..start + height * row];
+    let src = &band[..height * row];
+    for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        for c in 0..3 {
+            dst[c] = dst[c].min(src[c]);
+        }
+    }
+}
+
+// Recolor the render on white for the output mode. A transparent
+// background turns the white into alpha, each pixel's coverage becomes its
+// opacity and its color is what was drawn over the white, so anti-aliased
+// edges composite cleanly. A negative then flips the color channels.
+fn ink(img: &mut RgbaImage, options: &RenderOptions) {
+    if !options.invert_output && !options.transparent {
+        return;
+    }
+    for px in img.as_mut().chunks_exact_mut(4) {
+        if options.transparent {
+            let alpha = 255 - px[0].min(px[1]).min(px[2]);
+            px[3] = alpha;
+            for c in px.iter_mut().take(3) {
+                *c = match alpha {
+                    0 => 0,
+                    a => ((*c as u32 + a as u32 - 255) * 255 / a as u32) as u8,
+                };
+            }
+        }
+        if options.invert_output {
+            px[..3].iter_mut().for_each(|c| *c = 255 - *c);
+        }
+    }
+}
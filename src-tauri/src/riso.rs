@@ -0,0 +1,156 @@
+use image::{Rgba, RgbaImage};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, FieldError, Problem};
+use crate::planes::Planes;
+use crate::Style;
+
+// A risograph has one drum per ink, two or three is usual.
+pub const MAX_LAYERS: usize = 3;
+// Fixed so the grain is the same on every print of a piece.
+const GRAIN_SEED: u64 = 6174;
+
+// The part of the photo an ink prints.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Channel {
+    // The process colors, with the gray they share taken out into Key.
+    Cyan,
+    Magenta,
+    Yellow,
+    Key,
+    // The darkness of the photo, for an ink that carries the drawing.
+    Luminance,
+}
+
+impl Channel {
+    // How much of this ink a color needs, in [0, 1].
+    pub fn darkness(self, px: &Rgba<u8>) -> f32 {
+        let [r, g, b] = [px[0], px[1], px[2]].map(|c| c as f32 / 255.0);
+        let k = 1.0 - r.max(g).max(b);
+        let process = |c: f32| {
+            if k >= 1.0 {
+                0.0
+            } else {
+                (1.0 - c - k) / (1.0 - k)
+            }
+        };
+        match self {
+            Channel::Cyan => process(r),
+            Channel::Magenta => process(g),
+            Channel::Yellow => process(b),
+            Channel::Key => k,
+            Channel::Luminance => 1.0 - (0.2989 * r + 0.5870 * g + 0.1140 * b),
+        }
+    }
+}
+
+// One drum: an ink drawn in a style of its own from a channel of the
+// photo.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RisoLayer {
+    // Names the master's file, like "pink".
+    pub name: String,
+    pub ink: [u8; 3],
+    pub channel: Channel,
+    pub style: Style,
+    // Scales the channel's darkness.
+    pub density: f32,
+    // How far off register the drum prints, in output pixels.
+    #[serde(default)]
+    pub offset: [i32; 2],
+}
+
+// A print made of one pass per ink, bottom first.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Riso {
+    pub layers: Vec<RisoLayer>,
+    // How much ink misses the paper in specks, in [0, 1].
+    pub grain: f32,
+}
+
+impl Riso {
+    pub fn validate(mut self) -> Result<Riso, Error> {
+        let mut errors = Vec::new();
+        if !(1..=MAX_LAYERS).contains(&self.layers.len()) {
+            errors.push(FieldError {
+                field: "riso.layers".to_string(),
+                problem: Problem::Count {
+                    min: 1,
+                    max: MAX_LAYERS,
+                    count: self.layers.len(),
+                },
+            });
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            if !(layer.density.is_finite() && layer.density >= 0.0) {
+                errors.push(FieldError {
+                    field: format!("riso.layers[{}].density", i),
+                    problem: Problem::Negative(layer.density),
+                });
+            }
+        }
+        if self.grain.is_finite() {
+            self.grain = self.grain.clamp(0.0, 1.0);
+        } else {
+            errors.push(FieldError {
+                field: "riso.grain".to_string(),
+                problem: Problem::NotANumber(self.grain),
+            });
+        }
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::InvalidOptions(errors))
+        }
+    }
+}
+
+// The planes to draw a layer's master from: its channel of the photo the
+// planes were made from, in place of the darkness.
+pub fn separate(photo: &RgbaImage, planes: &Planes, layer: &RisoLayer) -> Planes {
+    let luma = photo
+        .pixels()
+        .map(|px| (layer.channel.darkness(px) * layer.density).clamp(0.0, 1.0))
+        .collect();
+    planes.with_luma(luma)
+}
+
+// The print the masters would make: each master's marks in its ink,
+// shifted off register and speckled with grain, multiplied onto white
+// paper. `masters` are the renders of `riso.layers`, black on white.
+pub fn print(masters: &[RgbaImage], riso: &Riso) -> RgbaImage {
+    let (width, height) = masters.first().map_or((0, 0), |master| master.dimensions());
+    let mut img = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    if width == 0 {
+        return img;
+    }
+    img.as_mut()
+        .par_chunks_exact_mut(4 * width as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let mut rng = SmallRng::seed_from_u64(GRAIN_SEED ^ y as u64);
+            for (x, px) in row.chunks_exact_mut(4).enumerate() {
+                for (master, layer) in masters.iter().zip(&riso.layers) {
+                    let (mx, my) = (
+                        x as i64 - layer.offset[0] as i64,
+                        y as i64 - layer.offset[1] as i64,
+                    );
+                    if mx < 0 || my < 0 || mx >= width as i64 || my >= height as i64 {
+                        continue;
+                    }
+                    let m = master.get_pixel(mx as u32, my as u32);
+                    let mut coverage = 1.0 - m[0].min(m[1]).min(m[2]) as f32 / 255.0;
+                    if riso.grain > 0.0 {
+                        coverage *= 1.0 - riso.grain * rng.gen::<f32>();
+                    }
+                    for (c, ink) in px.iter_mut().zip(layer.ink) {
+                        let tint = 1.0 - coverage * (1.0 - ink as f32 / 255.0);
+                        *c = (*c as f32 * tint).round() as u8;
+                    }
+                }
+            }
+        });
+    img
+}
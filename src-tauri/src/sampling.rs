@@ -0,0 +1,39 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use wassily::prelude::*;
+
+// `n` points of a Halton sequence spread over a `width` by `height` area,
+// starting at a point picked by `seed`. Every point lies inside the area.
+pub fn halton_seq(width: f32, height: f32, n: u32, seed: u64) -> Vec<Point> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    // Start far enough from the end that the indices never overflow.
+    let k = rng.gen_range(0..=u32::MAX - n);
+    let (x_max, y_max) = ((width - 1.0).max(0.0), (height - 1.0).max(0.0));
+    (k..n + k)
+        .map(|i| {
+            Point::from_xy(
+                (halton(i, 2) * width).clamp(0.0, x_max),
+                (halton(i, 3) * height).clamp(0.0, y_max),
+            )
+        })
+        .collect()
+}
+
+// How many of `n` positions to fill for a darkness of `t`. Always in
+// `0..=n`, whatever `t` is.
+pub fn filled(n: usize, t: f32) -> usize {
+    let k = (t * n as f32).round();
+    if k.is_nan() || k <= 0.0 {
+        0
+    } else {
+        (k as usize).min(n)
+    }
+}
+
+// `n` flags with `k` of them set at random, all of them if `k > n`.
+pub fn bool_vec(n: usize, k: usize, rng: &mut SmallRng) -> Vec<bool> {
+    let k = k.min(n);
+    let mut vec = vec![true; k];
+    vec.extend(vec![false; n - k]);
+    vec.shuffle(rng);
+    vec
+}
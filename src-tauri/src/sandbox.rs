@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::Error;
+
+// The folders images may be read from and renders saved to, whatever path
+// the webview sends. Paths are canonicalized before they are checked, so
+// "..", symlinks and relative paths can't lead outside them.
+#[derive(Clone, Debug, Default)]
+pub struct Sandbox {
+    allowed: Vec<PathBuf>,
+}
+
+// The allowlist as it is kept on disk, a folder per entry, edited by hand
+// to let Seg reach more of the disk.
+#[derive(Serialize, Deserialize)]
+pub struct Allowlist {
+    pub folders: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    // Folders that don't exist are left out, there is nothing in them to
+    // reach.
+    pub fn new(folders: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut sandbox = Sandbox::default();
+        for folder in folders {
+            sandbox.approve(&folder);
+        }
+        sandbox
+    }
+
+    // The sandbox from the allowlist at `file`, or from `defaults` written
+    // there as a starting point when there is none yet.
+    pub fn load(file: &Path, defaults: Vec<PathBuf>) -> Result<Self, String> {
+        match fs::read_to_string(file) {
+            Ok(json) => {
+                let list: Allowlist = serde_json::from_str(&json).map_err(|err| {
+                    format!("The allowlist at {} is invalid: {}", file.display(), err)
+                })?;
+                Ok(Sandbox::new(list.folders))
+            }
+            Err(_) => {
+                let list = Allowlist { folders: defaults };
+                let json = serde_json::to_string_pretty(&list).map_err(|err| err.to_string())?;
+                if let Err(err) = fs::write(file, json) {
+                    eprintln!(
+                        "The allowlist at {} could not be saved: {}",
+                        file.display(),
+                        err
+                    );
+                }
+                Ok(Sandbox::new(list.folders))
+            }
+        }
+    }
+
+    // Allow a folder the user picked, for the rest of the session.
+    pub fn approve(&mut self, folder: &Path) {
+        if let Ok(folder) = folder.canonicalize() {
+            if !self.allowed.contains(&folder) {
+                self.allowed.push(folder);
+            }
+        }
+    }
+
+    // The canonical path of an existing file to read.
+    pub fn check_read(&self, path: &str) -> Result<PathBuf, Error> {
+        let canonical = Path::new(path).canonicalize().map_err(|err| Error::Open {
+            path: path.to_string(),
+            reason: err.to_string(),
+        })?;
+        self.inside(path, canonical)
+    }
+
+    // The canonical path of a file to write, which need not exist yet, nor
+    // need the folders above it. What exists is resolved and the rest may
+    // only name folders and the file, without "..".
+    pub fn check_write(&self, path: &str) -> Result<PathBuf, Error> {
+        let given = Path::new(path);
+        let not_allowed = || Error::NotAllowed(path.to_string());
+        if !given.is_absolute() || given.file_name().is_none() {
+            return Err(not_allowed());
+        }
+        let existing = given
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .ok_or_else(not_allowed)?;
+        let rest = given.strip_prefix(existing).map_err(|_| not_allowed())?;
+        if !rest
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(not_allowed());
+        }
+        let canonical = existing.canonicalize().map_err(|_| not_allowed())?;
+        self.inside(path, canonical.join(rest))
+    }
+
+    fn inside(&self, path: &str, canonical: PathBuf) -> Result<PathBuf, Error> {
+        if self
+            .allowed
+            .iter()
+            .any(|folder| canonical.starts_with(folder))
+        {
+            Ok(canonical)
+        } else {
+            Err(Error::NotAllowed(path.to_string()))
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::path::Path;
+use tauri_plugin_fs::FsExt;
+
+use seg_core::error::Error;
+
+// Fail unless `path` is in the file system scope: picked in a dialog, now
+// or in an earlier session, or in a folder picked in one. The webview can
+// only name files the user has chosen.
+pub(crate) fn check(app: &tauri::AppHandle, path: impl AsRef<Path>) -> Result<(), Error> {
+    let path = path.as_ref();
+    if app.fs_scope().is_allowed(path) {
+        Ok(())
+    } else {
+        Err(Error::NotAllowed(path.display().to_string()))
+    }
+}
+
+// Add a file to the scope, one picked by a dialog on this side or written
+// next to a picked one under a new name, so it can be revealed or opened.
+pub(crate) fn allow(app: &tauri::AppHandle, path: impl AsRef<Path>) {
+    if let Err(err) = app.fs_scope().allow_file(path.as_ref()) {
+        eprintln!(
+            "Could not add {} to the scope: {}",
+            path.as_ref().display(),
+            err
+        );
+    }
+}
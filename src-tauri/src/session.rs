@@ -0,0 +1,140 @@
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use seg_core::error::Error;
+use seg_core::queue::Pending;
+
+// Width of the proof render saved with a session.
+const PROOF_WIDTH: u32 = 512;
+
+// What is saved of a session to pick it up again after a crash.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Saved {
+    // The state of the controls, kept as the frontend sent it.
+    pub controls: serde_json::Value,
+    pub image_path: Option<String>,
+    // Path of a small render made with the controls, if there was one.
+    pub proof: Option<String>,
+}
+
+// Exports and batch jobs left to do, each with the image it renders from.
+#[derive(Serialize, Deserialize)]
+pub struct SavedQueue {
+    // The image of jobs saved before each kept its own.
+    #[serde(default, skip_serializing)]
+    pub image_path: Option<String>,
+    pub jobs: Vec<Pending>,
+}
+
+// Autosaves a session to the app data dir. A marker file is kept there
+// while the app runs, finding it at launch means the last run crashed.
+pub struct Session {
+    dir: PathBuf,
+    crashed: bool,
+}
+
+impl Session {
+    pub fn start(dir: PathBuf) -> Result<Self, Error> {
+        fs::create_dir_all(&dir).map_err(|err| Error::CreateFolder {
+            path: dir.display().to_string(),
+            reason: err.to_string(),
+        })?;
+        let marker = dir.join("running");
+        let crashed = marker.exists() && dir.join("session.json").exists();
+        fs::write(&marker, b"").map_err(|err| unsaved(&marker, err))?;
+        Ok(Session { dir, crashed })
+    }
+
+    // Write the session, shrinking `render` to a proof if given.
+    pub fn save(
+        &self,
+        controls: serde_json::Value,
+        image_path: Option<String>,
+        render: Option<&RgbaImage>,
+    ) -> Result<(), Error> {
+        let proof = match render {
+            Some(img) if img.width() > 0 => {
+                let height = (img.height() as u64 * PROOF_WIDTH as u64 / img.width() as u64).max(1);
+                let proof = imageops::resize(
+                    img,
+                    PROOF_WIDTH,
+                    height as u32,
+                    imageops::FilterType::Triangle,
+                );
+                let path = self.dir.join("proof.png");
+                proof.save(&path).map_err(|err| unsaved(&path, err))?;
+                Some(path.to_string_lossy().into_owned())
+            }
+            _ => None,
+        };
+        let saved = Saved {
+            controls,
+            image_path,
+            proof,
+        };
+        write_json(&self.dir.join("session.json"), &saved)
+    }
+
+    // Keep the pending jobs on disk so they outlast a restart. Jobs
+    // rendering an image that did not come from a file can not be
+    // restored and are left out.
+    pub fn save_queue(&self, jobs: Vec<Pending>) -> Result<(), Error> {
+        let path = self.dir.join("queue.json");
+        let jobs: Vec<Pending> = jobs
+            .into_iter()
+            .filter(|job| job.image_path.is_some())
+            .collect();
+        if !jobs.is_empty() {
+            let queue = SavedQueue {
+                image_path: None,
+                jobs,
+            };
+            return write_json(&path, &queue);
+        }
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Error::Remove {
+                path: path.display().to_string(),
+                reason: err.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // The jobs left over from the last run, whether or not it crashed.
+    pub fn load_queue(&self) -> Option<SavedQueue> {
+        let json = fs::read_to_string(self.dir.join("queue.json")).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    // The session autosaved by the last run, if that run crashed.
+    pub fn recover(&self) -> Option<Saved> {
+        if !self.crashed {
+            return None;
+        }
+        let json = fs::read_to_string(self.dir.join("session.json")).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    // Remove the marker on a clean exit.
+    pub fn end(&self) {
+        let _ = fs::remove_file(self.dir.join("running"));
+    }
+}
+
+// Write then rename so a crash mid write leaves the last version.
+pub(crate) fn write_json(path: &Path, value: &impl Serialize) -> Result<(), Error> {
+    let json = serde_json::to_string(value).map_err(|err| unsaved(path, err))?;
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, json)
+        .and_then(|_| fs::rename(&partial, path))
+        .map_err(|err| unsaved(path, err))
+}
+
+fn unsaved(path: &Path, err: impl std::fmt::Display) -> Error {
+    Error::Save {
+        path: path.display().to_string(),
+        reason: err.to_string(),
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use seg_core::error::{Error, FieldError, Problem};
+use seg_core::Style;
+
+use crate::session::write_json;
+
+// Bounds on the width of the previews sent to the js side.
+const MIN_PREVIEW: u32 = 256;
+const MAX_PREVIEW: u32 = 4096;
+// The smallest limit on source images, a megapixel.
+const MIN_SOURCE_PIXELS: u64 = 1_000_000;
+
+// How the app is set up, kept in the app data dir across runs. Fields
+// missing from the file, as after an update adds one, take their default.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Where save dialogs open before a file has been picked.
+    pub export_folder: Option<PathBuf>,
+    // The style a new session starts with.
+    pub style: Style,
+    pub preview_width: u32,
+    // Render threads, 0 uses one per core.
+    pub threads: usize,
+    // Seconds between autosaves of the controls, 0 turns autosave off.
+    pub autosave_interval: u32,
+    // Count what is rendered, see usage.rs. Off unless the user opts in.
+    pub usage_stats: bool,
+    // Larger images are scaled down to this many pixels as they are
+    // loaded, so renders of them stay manageable.
+    pub max_source_pixels: Option<u64>,
+    // Write a manifest of the settings and checksums beside each export,
+    // see manifest.rs.
+    pub manifests: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            export_folder: None,
+            style: Style::default(),
+            preview_width: crate::W as u32,
+            threads: 0,
+            autosave_interval: 30,
+            usage_stats: false,
+            max_source_pixels: None,
+            manifests: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(self) -> Result<Self, Error> {
+        let mut errors = Vec::new();
+        if !(MIN_PREVIEW..=MAX_PREVIEW).contains(&self.preview_width) {
+            errors.push(FieldError {
+                field: "settings.preview_width".to_string(),
+                problem: Problem::OutOfRange {
+                    min: MIN_PREVIEW.into(),
+                    max: MAX_PREVIEW.into(),
+                    value: self.preview_width.into(),
+                },
+            });
+        }
+        if let Some(max) = self
+            .max_source_pixels
+            .filter(|&max| max < MIN_SOURCE_PIXELS)
+        {
+            errors.push(FieldError {
+                field: "settings.max_source_pixels".to_string(),
+                problem: Problem::TooSmall {
+                    min: MIN_SOURCE_PIXELS,
+                    value: max,
+                },
+            });
+        }
+        if let Some(folder) = self.export_folder.as_ref().filter(|f| !f.is_dir()) {
+            errors.push(FieldError {
+                field: "settings.export_folder".to_string(),
+                problem: Problem::NotAFolder(folder.display().to_string()),
+            });
+        }
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::InvalidOptions(errors))
+        }
+    }
+
+    // The settings saved in `dir`, the defaults if there are none or they
+    // can't be read.
+    pub fn load(dir: &Path) -> Self {
+        let Ok(json) = fs::read_to_string(dir.join("settings.json")) else {
+            return Settings::default();
+        };
+        match serde_json::from_str::<Settings>(&json).map(Settings::validate) {
+            Ok(Ok(settings)) => settings,
+            Ok(Err(err)) => {
+                eprintln!("The saved settings are invalid: {}", err);
+                Settings::default()
+            }
+            Err(err) => {
+                eprintln!("The saved settings could not be read: {}", err);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), Error> {
+        write_json(&dir.join("settings.json"), self)
+    }
+}
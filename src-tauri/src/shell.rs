@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use seg_core::error::Error;
+use seg_core::messages::Message;
+
+use crate::scope;
+
+// Show a file, usually a fresh export, selected in the file manager.
+#[tauri::command]
+pub(crate) fn reveal_file(path: &str, app: tauri::AppHandle) -> Result<(), Message> {
+    scope::check(&app, path)?;
+    let path = existing(path)?;
+    let mut command = reveal_command(&path);
+    launch(&mut command, &path)
+}
+
+// Open a file in the app the OS uses for its type.
+#[tauri::command]
+pub(crate) fn open_with_default_app(path: &str, app: tauri::AppHandle) -> Result<(), Message> {
+    scope::check(&app, path)?;
+    let path = existing(path)?;
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(&path);
+        command
+    } else if cfg!(target_os = "windows") {
+        // The empty argument is the window title `start` expects first.
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(&path);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(&path);
+        command
+    };
+    launch(&mut command, &path)
+}
+
+fn existing(path: &str) -> Result<PathBuf, Error> {
+    std::fs::metadata(path)
+        .map(|_| PathBuf::from(path))
+        .map_err(|err| Error::Open {
+            path: path.to_string(),
+            reason: err.to_string(),
+        })
+}
+
+fn reveal_command(path: &Path) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        // There is no common way to select a file on Linux, the folder is
+        // opened instead.
+        let mut command = Command::new("xdg-open");
+        let folder = path.parent().filter(|p| !p.as_os_str().is_empty());
+        command.arg(folder.unwrap_or(Path::new(".")));
+        command
+    }
+}
+
+// Start the helper without blocking on it, the file manager or app may
+// stay open long after. It is waited for on its own thread so it does not
+// linger as a zombie once it exits.
+fn launch(command: &mut Command, path: &Path) -> Result<(), Message> {
+    let mut child = command.spawn().map_err(|err| Error::Launch {
+        path: path.display().to_string(),
+        reason: err.to_string(),
+    })?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
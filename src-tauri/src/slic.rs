@@ -0,0 +1,194 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::mask;
+use crate::planes::pixel_to_hue;
+
+// Rounds of moving the centers, SLIC has mostly settled after ten.
+const ITERATIONS: usize = 10;
+
+// Settings for superpixel segmentation.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Slic {
+    // Roughly how many segments to cut the source into.
+    pub count: u32,
+    // How much segments prefer staying compact over following color, 10
+    // is typical, higher gives squarer segments.
+    pub compactness: f32,
+}
+
+// What a segment looks like on average.
+pub struct Segment {
+    // Hue of the mean color in degrees.
+    pub hue: i32,
+    // Saturation of the mean color in [0, 1], near 0 for grays.
+    pub saturation: f32,
+    // Standard deviation of darkness, high for busy regions.
+    pub texture: f32,
+}
+
+// The source cut into superpixels, regions of similar color that follow
+// its edges. Stretched over the source like a mask.
+pub struct Segments {
+    pub width: u32,
+    pub height: u32,
+    index: Vec<u32>,
+    pub segments: Vec<Segment>,
+}
+
+impl Segments {
+    pub fn segment_at(&self, x: u32, y: u32, width: u32, height: u32) -> &Segment {
+        let i = self.index[mask::stretched(self.width, self.height, x, y, width, height)];
+        &self.segments[i as usize]
+    }
+}
+
+// Cut `img` into about `slic.count` superpixels with SLIC: centers on a
+// grid each claim the pixels near them that are closest in color and
+// position, then move to the middle of what they claimed.
+pub fn segment(img: &RgbaImage, slic: &Slic) -> Segments {
+    let (width, height) = img.dimensions();
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        return Segments {
+            width,
+            height,
+            index: Vec::new(),
+            segments: Vec::new(),
+        };
+    }
+    let lab: Vec<[f32; 3]> = img.pixels().map(to_lab).collect();
+    let step = ((w * h) as f32 / slic.count.max(1) as f32).sqrt().max(1.0);
+    let weight = (slic.compactness.max(0.0) / step).powi(2);
+    let mut centers = Vec::new();
+    let mut y = step / 2.0;
+    while y < h as f32 {
+        let mut x = step / 2.0;
+        while x < w as f32 {
+            let c = lab[y as usize * w + x as usize];
+            centers.push([c[0], c[1], c[2], x, y]);
+            x += step;
+        }
+        y += step;
+    }
+    // A thin strip can be narrower than half a step.
+    if centers.is_empty() {
+        let c = lab[h / 2 * w + w / 2];
+        centers.push([c[0], c[1], c[2], (w / 2) as f32, (h / 2) as f32]);
+    }
+    let columns = (w as f32 / step).ceil().max(1.0) as usize;
+    let mut index = vec![u32::MAX; w * h];
+    let mut distance = vec![f32::INFINITY; w * h];
+    for _ in 0..ITERATIONS {
+        distance.fill(f32::INFINITY);
+        for (k, c) in centers.iter().enumerate() {
+            let x0 = (c[3] - step).max(0.0) as usize;
+            let x1 = ((c[3] + step) as usize).min(w);
+            let y0 = (c[4] - step).max(0.0) as usize;
+            let y1 = ((c[4] + step) as usize).min(h);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let i = y * w + x;
+                    let p = lab[i];
+                    let color =
+                        (p[0] - c[0]).powi(2) + (p[1] - c[1]).powi(2) + (p[2] - c[2]).powi(2);
+                    let space = (x as f32 - c[3]).powi(2) + (y as f32 - c[4]).powi(2);
+                    let d = color + space * weight;
+                    if d < distance[i] {
+                        distance[i] = d;
+                        index[i] = k as u32;
+                    }
+                }
+            }
+        }
+        let mut sums = vec![[0.0f64; 6]; centers.len()];
+        for (i, &k) in index.iter().enumerate() {
+            if k == u32::MAX {
+                continue;
+            }
+            let p = lab[i];
+            let sum = &mut sums[k as usize];
+            sum[0] += p[0] as f64;
+            sum[1] += p[1] as f64;
+            sum[2] += p[2] as f64;
+            sum[3] += (i % w) as f64;
+            sum[4] += (i / w) as f64;
+            sum[5] += 1.0;
+        }
+        for (c, sum) in centers.iter_mut().zip(&sums) {
+            if sum[5] > 0.0 {
+                *c = std::array::from_fn(|j| (sum[j] / sum[5]) as f32);
+            }
+        }
+    }
+    // Pixels no center reached join the grid cell they lie in.
+    for (i, k) in index.iter_mut().enumerate() {
+        if *k == u32::MAX {
+            let (x, y) = ((i % w) as f32, (i / w) as f32);
+            let cell = (y / step) as usize * columns + (x / step) as usize;
+            *k = cell.min(centers.len().saturating_sub(1)) as u32;
+        }
+    }
+    Segments {
+        width,
+        height,
+        segments: describe(img, &index, centers.len()),
+        index,
+    }
+}
+
+// The mean color and texture of each segment.
+fn describe(img: &RgbaImage, index: &[u32], count: usize) -> Vec<Segment> {
+    let mut sums = vec![[0.0f64; 6]; count];
+    for (px, &k) in img.pixels().zip(index) {
+        let sum = &mut sums[k as usize];
+        let t =
+            1.0 - (0.2989 * px[0] as f64 + 0.5870 * px[1] as f64 + 0.1140 * px[2] as f64) / 255.0;
+        sum[0] += px[0] as f64;
+        sum[1] += px[1] as f64;
+        sum[2] += px[2] as f64;
+        sum[3] += t;
+        sum[4] += t * t;
+        sum[5] += 1.0;
+    }
+    sums.iter()
+        .map(|sum| {
+            let n = sum[5].max(1.0);
+            let [r, g, b] = [sum[0] / n, sum[1] / n, sum[2] / n].map(|c| c.round() as u8);
+            let max = r.max(g).max(b) as f32;
+            let min = r.min(g).min(b) as f32;
+            let mean = sum[3] / n;
+            Segment {
+                hue: pixel_to_hue(&Rgba([r, g, b, 255])),
+                saturation: if max > 0.0 { (max - min) / max } else { 0.0 },
+                texture: (sum[4] / n - mean * mean).max(0.0).sqrt() as f32,
+            }
+        })
+        .collect()
+}
+
+// CIELAB under D65, where distances roughly match how different colors
+// look.
+pub fn to_lab(px: &Rgba<u8>) -> [f32; 3] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(px[0]), linear(px[1]), linear(px[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
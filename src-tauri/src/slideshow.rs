@@ -0,0 +1,166 @@
+use image::RgbaImage;
+use rand::Rng;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+use seg_core::error::Error;
+use seg_core::messages::Message;
+use seg_core::planes;
+use seg_core::queue::JobKind;
+use seg_core::{RenderOptions, Style};
+
+use crate::State;
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpeg", "jpg", "tiff", "webp"];
+// Slide ids count up across slideshows, so the seg protocol never serves
+// a cached slide from an earlier one.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+// How often a waiting slideshow checks whether it was stopped.
+const POLL: Duration = Duration::from_millis(100);
+// The seconds a slide may be shown, whatever the js side asks for.
+const MIN_INTERVAL: f32 = 1.0;
+const MAX_INTERVAL: f32 = 3600.0;
+
+// A rendered slide, ready to fade in. The render is served as a PNG by the
+// seg protocol at `png`.
+#[derive(Clone, Serialize)]
+struct Slide {
+    id: u64,
+    // The image the slide was rendered from.
+    source: String,
+    width: u32,
+    height: u32,
+    png: String,
+}
+
+// The slideshow running in the background, if any.
+#[derive(Default)]
+pub(crate) struct Slideshow {
+    stop: Option<Arc<AtomicBool>>,
+    // The slide on screen, kept for the seg protocol.
+    pub(crate) current: Option<(u64, Arc<RgbaImage>)>,
+}
+
+// Render every image in `folder` in turn, one every `interval` seconds,
+// looping until stopped. Each slide uses `options`, or with `randomize` a
+// random style and seed on top of them. A "slide" event is sent as each
+// one is ready, a "slideshow-error" event for images that fail.
+#[tauri::command]
+pub(crate) fn start_slideshow(
+    folder: String,
+    interval: f32,
+    options: RenderOptions,
+    randomize: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<State>,
+) -> Result<(), Message> {
+    let options = options.validate()?;
+    crate::scope::check(&app, &folder)?;
+    let folder = crate::sandbox(&state).check_read(&folder)?;
+    let images = images(&folder)?;
+    if images.is_empty() {
+        return Err(Error::NoImages(folder.display().to_string()).into());
+    }
+    // `max` drops a NaN and `min` an infinity.
+    let interval = Duration::from_secs_f32(interval.max(MIN_INTERVAL).min(MAX_INTERVAL));
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let mut slideshow = state.slideshow.lock().expect("Could not lock state mutex");
+        if let Some(old) = slideshow.stop.replace(stop.clone()) {
+            old.store(true, Ordering::Relaxed);
+        }
+    }
+    let randomize = randomize.unwrap_or(false);
+    std::thread::spawn(move || run(app, images, options, randomize, interval, stop));
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn stop_slideshow(state: tauri::State<State>) {
+    let mut slideshow = state.slideshow.lock().expect("Could not lock state mutex");
+    if let Some(stop) = slideshow.stop.take() {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// The images in a folder in name order.
+pub(crate) fn images(folder: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = std::fs::read_dir(folder).map_err(|err| Error::Open {
+        path: folder.display().to_string(),
+        reason: err.to_string(),
+    })?;
+    let mut images: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+        })
+        .collect();
+    images.sort();
+    Ok(images)
+}
+
+fn run(
+    app: tauri::AppHandle,
+    images: Vec<PathBuf>,
+    options: RenderOptions,
+    randomize: bool,
+    interval: Duration,
+    stop: Arc<AtomicBool>,
+) {
+    let state = app.state::<State>();
+    let mut rng = rand::thread_rng();
+    for path in images.iter().cycle() {
+        let started = Instant::now();
+        let mut options = options.clone();
+        if randomize {
+            options.style = Style::ALL[rng.gen_range(0..Style::ALL.len())];
+            options.seed = rng.gen();
+        }
+        let source = path.to_string_lossy().into_owned();
+        let rendered = crate::open_image(&source)
+            .map_err(Message::from)
+            .and_then(|img| {
+                // Limited and built like the loaded image, so a slide
+                // matches its preview.
+                let img = Arc::new(crate::limit_source(&state, img).0);
+                let planes = planes::build(&img, &options, planes::Inputs::default());
+                crate::render_planes(&state, &img, &planes, &options, JobKind::Preview)
+            });
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        match rendered {
+            Ok(img) => {
+                let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                let slide = Slide {
+                    id,
+                    source,
+                    width: img.width(),
+                    height: img.height(),
+                    png: format!("slide/{}.png", id),
+                };
+                state
+                    .slideshow
+                    .lock()
+                    .expect("Could not lock state mutex")
+                    .current = Some((id, Arc::new(img)));
+                crate::emit(&app, "slide", slide);
+            }
+            Err(message) => crate::emit(&app, "slideshow-error", message),
+        }
+        // Wait out the rest of the interval, rendering counts towards it.
+        while started.elapsed() < interval {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(POLL);
+        }
+    }
+}
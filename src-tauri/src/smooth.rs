@@ -0,0 +1,131 @@
+use crate::display::Primitive;
+
+// Ends closer than this, in output pixels, join.
+const JOIN: f32 = 0.01;
+
+// Runs of lines in the same color that each start where the one before
+// ends become one smooth curve: the run is thinned to the points it can't
+// do without, straying at most `tolerance` output pixels, and a
+// Catmull-Rom spline is passed through them. Other marks are kept as they
+// are.
+pub fn smooth(primitives: Vec<Primitive>, tolerance: f32) -> Vec<Primitive> {
+    let mut smoothed = Vec::with_capacity(primitives.len());
+    let mut run: Vec<Primitive> = Vec::new();
+    for primitive in primitives {
+        let joins = match (run.last(), &primitive) {
+            (
+                Some(Primitive::Line {
+                    to, color: before, ..
+                }),
+                Primitive::Line { from, color, .. },
+            ) => before == color && (to[0] - from[0]).hypot(to[1] - from[1]) <= JOIN,
+            _ => false,
+        };
+        if !joins {
+            flush(&mut run, tolerance, &mut smoothed);
+        }
+        match primitive {
+            Primitive::Line { .. } => run.push(primitive),
+            other => smoothed.push(other),
+        }
+    }
+    flush(&mut run, tolerance, &mut smoothed);
+    smoothed
+}
+
+// Add a run of joined lines to `out`, as a curve when there is more than
+// one.
+fn flush(run: &mut Vec<Primitive>, tolerance: f32, out: &mut Vec<Primitive>) {
+    if run.len() < 2 {
+        out.append(run);
+        return;
+    }
+    let mut points = Vec::with_capacity(run.len() + 1);
+    let (mut weights, mut color) = (0.0, [0; 4]);
+    for primitive in run.iter() {
+        if let Primitive::Line {
+            from,
+            to,
+            weight,
+            color: c,
+        } = primitive
+        {
+            if points.is_empty() {
+                points.push(*from);
+            }
+            points.push(*to);
+            weights += weight;
+            color = *c;
+        }
+    }
+    // The wobble of a hand drawn line varies its weight along the way.
+    let weight = weights / run.len() as f32;
+    run.clear();
+    let points = simplify(&points, tolerance.max(0.0));
+    if points.len() == 2 {
+        out.push(Primitive::Line {
+            from: points[0],
+            to: points[1],
+            weight,
+            color,
+        });
+        return;
+    }
+    let n = points.len();
+    let segments = (0..n - 1)
+        .map(|i| {
+            let p0 = points[i.saturating_sub(1)];
+            let (p1, p2) = (points[i], points[i + 1]);
+            let p3 = points[(i + 2).min(n - 1)];
+            [
+                [p1[0] + (p2[0] - p0[0]) / 6.0, p1[1] + (p2[1] - p0[1]) / 6.0],
+                [p2[0] - (p3[0] - p1[0]) / 6.0, p2[1] - (p3[1] - p1[1]) / 6.0],
+                p2,
+            ]
+        })
+        .collect();
+    out.push(Primitive::Curve {
+        start: points[0],
+        segments,
+        weight,
+        color,
+    });
+}
+
+// Douglas-Peucker: keep the ends, and the point farthest from the line
+// between them if it is more than `tolerance` away, then do the same on
+// either side of it.
+fn simplify(points: &[[f32; 2]], tolerance: f32) -> Vec<[f32; 2]> {
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((a, b)) = spans.pop() {
+        let (pa, pb) = (points[a], points[b]);
+        let (dx, dy) = (pb[0] - pa[0], pb[1] - pa[1]);
+        let len = dx.hypot(dy);
+        let farthest = (a + 1..b)
+            .map(|i| {
+                let p = points[i];
+                let d = if len == 0.0 {
+                    (p[0] - pa[0]).hypot(p[1] - pa[1])
+                } else {
+                    ((p[0] - pa[0]) * dy - (p[1] - pa[1]) * dx).abs() / len
+                };
+                (i, d)
+            })
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, d)) = farthest {
+            if d > tolerance {
+                keep[i] = true;
+                spans.push((a, i));
+                spans.push((i, b));
+            }
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(p, kept)| kept.then_some(*p))
+        .collect()
+}
@@ -0,0 +1,93 @@
+use image::{Rgba, RgbaImage};
+use qrcode::{Color, EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::RenderOptions;
+
+// Light modules the code needs around it to scan.
+const QUIET_ZONE: u32 = 4;
+// Largest module, in output pixels.
+pub const MAX_MODULE: u32 = 64;
+
+// A QR code in a corner of the export, so someone holding a print can
+// reproduce the piece or find out more about it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stamp {
+    pub content: StampContent,
+    pub corner: Corner,
+    // Side of each module of the code in output pixels.
+    pub module: u32,
+    // Space between the code, with its quiet zone, and the edges.
+    pub margin: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum StampContent {
+    // The options of the render as JSON, without those that are off.
+    Parameters,
+    // A link, like to a page where the piece is shared.
+    Url(String),
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Stamp {
+    // What the code holds for a render with `options`.
+    pub fn text(&self, options: &RenderOptions) -> String {
+        match &self.content {
+            StampContent::Url(url) => url.clone(),
+            StampContent::Parameters => {
+                let mut json =
+                    serde_json::to_value(options).expect("Render options serialize to JSON");
+                if let Value::Object(fields) = &mut json {
+                    fields.retain(|name, value| !value.is_null() && name != "stamp");
+                }
+                json.to_string()
+            }
+        }
+    }
+}
+
+// Draw the code for `text` in a corner of the image, on white.
+pub fn apply(img: &mut RgbaImage, stamp: &Stamp, text: &str) -> Result<(), Error> {
+    let code = QrCode::with_error_correction_level(text, EcLevel::L)
+        .map_err(|_| Error::QrTooLong(text.len()))?;
+    let modules = code.width() as u32;
+    let module = stamp.module.max(1);
+    let side = (modules + 2 * QUIET_ZONE) * module;
+    if side + stamp.margin > img.width() || side + stamp.margin > img.height() {
+        return Err(Error::QrTooBig(side + stamp.margin));
+    }
+    let (left, top) = match stamp.corner {
+        Corner::TopLeft => (stamp.margin, stamp.margin),
+        Corner::TopRight => (img.width() - side - stamp.margin, stamp.margin),
+        Corner::BottomLeft => (stamp.margin, img.height() - side - stamp.margin),
+        Corner::BottomRight => (
+            img.width() - side - stamp.margin,
+            img.height() - side - stamp.margin,
+        ),
+    };
+    let colors = code.to_colors();
+    for y in 0..side {
+        for x in 0..side {
+            let (mx, my) = (
+                (x / module) as i64 - QUIET_ZONE as i64,
+                (y / module) as i64 - QUIET_ZONE as i64,
+            );
+            let dark = (0..modules as i64).contains(&mx)
+                && (0..modules as i64).contains(&my)
+                && colors[(my * modules as i64 + mx) as usize] == Color::Dark;
+            let v = if dark { 0 } else { 255 };
+            img.put_pixel(left + x, top + y, Rgba([v, v, v, 255]));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,412 @@
+use rand::rngs::SmallRng;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+use wassily::prelude::*;
+
+use crate::display::{rgba, Primitive};
+use crate::pen::Pen;
+use crate::sampling::{bool_vec, filled, halton_seq};
+
+// Where a mark is drawn: a square of `size` output pixels with its top
+// left corner at (x0, y0), turned by `angle` radians about its center.
+#[derive(Clone, Copy)]
+pub struct Cell {
+    pub x0: f32,
+    pub y0: f32,
+    pub size: u32,
+    pub angle: f32,
+}
+
+impl Cell {
+    // Cell (x, y) of a regular grid.
+    pub fn grid(size: u32, x: u32, y: u32) -> Self {
+        Cell {
+            x0: (x * size) as f32,
+            y0: (y * size) as f32,
+            size,
+            angle: 0.0,
+        }
+    }
+
+    pub fn center(&self) -> Point {
+        let h = (self.size / 2) as f32;
+        pt(self.x0 + h, self.y0 + h)
+    }
+
+    // Map a point in cell coordinates, (0, 0) at the top left corner and
+    // (size, size) at the bottom right, onto the canvas.
+    fn to_canvas(&self, u: f32, v: f32) -> Point {
+        if self.angle == 0.0 {
+            return pt(self.x0 + u, self.y0 + v);
+        }
+        let h = self.size as f32 / 2.0;
+        let (sin, cos) = self.angle.sin_cos();
+        let (du, dv) = (u - h, v - h);
+        pt(
+            self.x0 + h + du * cos - dv * sin,
+            self.y0 + h + du * sin + dv * cos,
+        )
+    }
+}
+
+// The mark drawn by the `Dots` style.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum DotShape {
+    #[default]
+    Circle,
+    Square,
+    Diamond,
+    // A stroked circle.
+    Ring,
+    Star {
+        points: u32,
+    },
+    // Vertices around the cell center, the unit circle spans a full size dot.
+    Polygon {
+        vertices: Vec<[f32; 2]>,
+    },
+}
+
+// How dots meet their neighbors as they darken.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum DotFill {
+    // Dots grow past their cell and fuse into scalloped blobs.
+    #[default]
+    Grow,
+    // Dots stop at the edges of their cell.
+    Clamp,
+    // Dots too big for their cell are drawn as squares with the same ink,
+    // which fill the cell at full darkness.
+    Square,
+    // Dots reach past the corners of their cell by `bleed` times its half
+    // diagonal at full darkness, so dark regions are solid ink.
+    Bleed {
+        bleed: f32,
+    },
+}
+
+// What, if anything, turns each dot.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum DotRotation {
+    #[default]
+    None,
+    // Up to a quarter turn, darker dots turn further.
+    Luminance,
+    // The hue of the source pixel in degrees.
+    Hue,
+}
+
+// Draw a dot in `color` rotated by `angle` radians relative to the cell. The shapes
+// are sized so each covers about the same area as the circle for the
+// same `t`, then fitted to the cell as `fill` says.
+pub fn dots(
+    cell: &Cell,
+    t: f32,
+    shape: &DotShape,
+    fill: DotFill,
+    angle: f32,
+    color: Color,
+    list: &mut Vec<Primitive>,
+) {
+    let center = cell.center();
+    let size = cell.size as f32;
+    let r = match fill {
+        DotFill::Bleed { bleed } => t * size * FRAC_1_SQRT_2 * (1.0 + bleed),
+        _ => t * size * 0.6036, // mid way between sqrt(2)/2 and 1/2.
+    };
+    if matches!(fill, DotFill::Square) && r > size / 2.0 {
+        // As much ink as the dot, square with the cell so it is the whole
+        // cell at full darkness.
+        let h = t.sqrt() * size / 2.0;
+        list.push(Primitive::Polygon {
+            points: turn(&[[-h, -h], [h, -h], [h, h], [-h, h]], cell.angle)
+                .into_iter()
+                .map(|[x, y]| [center.x + x, center.y + y])
+                .collect(),
+            color: rgba(color),
+        });
+        return;
+    }
+    let angle = angle + cell.angle;
+    let vertices: Vec<[f32; 2]> = match shape {
+        DotShape::Circle => {
+            list.push(Primitive::Circle {
+                x: center.x,
+                y: center.y,
+                radius: clamp(fill, r, 1.0, size),
+                color: rgba(color),
+            });
+            return;
+        }
+        DotShape::Ring => {
+            // Outer edge at 1.15 r and inner edge at 0.55 r has the area
+            // of a disc of radius r.
+            let r = clamp(fill, r, 1.15, size);
+            list.push(Primitive::Ring {
+                x: center.x,
+                y: center.y,
+                radius: 0.85 * r,
+                weight: 0.6 * r,
+                color: rgba(color),
+            });
+            return;
+        }
+        DotShape::Square => {
+            let h = 0.886; // sqrt(pi) / 2
+            vec![[-h, -h], [h, -h], [h, h], [-h, h]]
+        }
+        DotShape::Diamond => {
+            let h = 1.253; // sqrt(pi / 2)
+            vec![[0.0, -h], [h, 0.0], [0.0, h], [-h, 0.0]]
+        }
+        DotShape::Star { points } => {
+            let n = (*points).max(3);
+            (0..2 * n)
+                .map(|i| {
+                    let a = std::f32::consts::PI * i as f32 / n as f32;
+                    let k = if i % 2 == 0 { 1.4 } else { 0.6 };
+                    [k * a.sin(), -k * a.cos()]
+                })
+                .collect()
+        }
+        DotShape::Polygon { vertices } => vertices.clone(),
+    };
+    if vertices.len() < 3 {
+        return;
+    }
+    // How far the shape reaches along the sides of the cell, for a clamp.
+    let reach = turn(&vertices, angle - cell.angle)
+        .iter()
+        .map(|[x, y]| x.abs().max(y.abs()))
+        .fold(0.0, f32::max);
+    let r = clamp(fill, r, reach, size);
+    list.push(Primitive::Polygon {
+        points: turn(&vertices, angle)
+            .into_iter()
+            .map(|[x, y]| [center.x + r * x, center.y + r * y])
+            .collect(),
+        color: rgba(color),
+    });
+}
+
+// The radius of a dot reaching `reach` times its radius from its center,
+// kept inside its cell when `fill` clamps.
+fn clamp(fill: DotFill, r: f32, reach: f32, size: f32) -> f32 {
+    match fill {
+        DotFill::Clamp if reach > 0.0 => r.min(size / (2.0 * reach)),
+        _ => r,
+    }
+}
+
+fn turn(points: &[[f32; 2]], angle: f32) -> Vec<[f32; 2]> {
+    let (sin, cos) = angle.sin_cos();
+    points
+        .iter()
+        .map(|[x, y]| [x * cos - y * sin, x * sin + y * cos])
+        .collect()
+}
+
+// Which way the line styles run in each cell.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum HatchDirection {
+    #[default]
+    Fixed,
+    // Lines follow the luminance gradient, across the contours.
+    AlongGradient,
+    // Lines follow the contours of the image like an engraving.
+    AcrossGradient,
+}
+
+impl HatchDirection {
+    // The angle vertical lines are turned to at a cell, `None` keeps them
+    // axis aligned.
+    pub fn angle(&self, gradient: Option<f32>) -> Option<f32> {
+        match self {
+            HatchDirection::Fixed => None,
+            HatchDirection::AlongGradient => gradient,
+            HatchDirection::AcrossGradient => gradient.map(|a| a + FRAC_PI_2),
+        }
+    }
+}
+
+// Vertical lines, or lines at `angle` radians when it is given.
+pub fn vline(
+    cell: &Cell,
+    t: f32,
+    angle: Option<f32>,
+    color: Color,
+    pen: &Pen,
+    rng: &mut SmallRng,
+    list: &mut Vec<Primitive>,
+) {
+    match angle {
+        Some(angle) => hatch(cell, t, angle - cell.angle, color, pen, rng, list),
+        None => lines(cell, t, true, color, pen, rng, list),
+    }
+}
+
+// Horizontal lines, or lines at a right angle to `angle` when it is given.
+pub fn hline(
+    cell: &Cell,
+    t: f32,
+    angle: Option<f32>,
+    color: Color,
+    pen: &Pen,
+    rng: &mut SmallRng,
+    list: &mut Vec<Primitive>,
+) {
+    match angle {
+        Some(angle) => hatch(
+            cell,
+            t,
+            angle + FRAC_PI_2 - cell.angle,
+            color,
+            pen,
+            rng,
+            list,
+        ),
+        None => lines(cell, t, false, color, pen, rng, list),
+    }
+}
+
+pub fn cross(
+    cell: &Cell,
+    t: f32,
+    angle: Option<f32>,
+    color: Color,
+    pen: &Pen,
+    rng: &mut SmallRng,
+    list: &mut Vec<Primitive>,
+) {
+    let mut c = color;
+    c.set_alpha(127.0 / 255.0);
+    match angle {
+        Some(angle) => {
+            hatch(cell, t, angle - cell.angle, c, pen, rng, list);
+            hatch(cell, t, angle + FRAC_PI_2 - cell.angle, c, pen, rng, list);
+        }
+        None => {
+            lines(cell, t, true, c, pen, rng, list);
+            lines(cell, t, false, c, pen, rng, list);
+        }
+    }
+}
+
+// Lines along the sides of the cell on whole pixel offsets, `t` of the
+// possible positions are drawn, fewer for a compensated thick pen.
+fn lines(
+    cell: &Cell,
+    t: f32,
+    vertical: bool,
+    color: Color,
+    pen: &Pen,
+    rng: &mut SmallRng,
+    list: &mut Vec<Primitive>,
+) {
+    let size = cell.size;
+    let gs = bool_vec(size as usize, filled(size as usize, pen.density(t)), rng);
+    for l in 0..size {
+        if gs[l as usize] {
+            let (l, s) = (l as f32, size as f32);
+            let (a, b) = if vertical {
+                (cell.to_canvas(l, 0.0), cell.to_canvas(l, s))
+            } else {
+                (cell.to_canvas(0.0, l), cell.to_canvas(s, l))
+            };
+            pen.line(a, b, color, pen.weight(), list);
+        }
+    }
+}
+
+// Lines running at `angle` radians in cell coordinates, clipped to the
+// cell. As with the axis aligned lines, `t` of the possible line positions
+// across the cell are drawn.
+fn hatch(
+    cell: &Cell,
+    t: f32,
+    angle: f32,
+    color: Color,
+    pen: &Pen,
+    rng: &mut SmallRng,
+    list: &mut Vec<Primitive>,
+) {
+    let size = cell.size;
+    let gs = bool_vec(size as usize, filled(size as usize, pen.density(t)), rng);
+    let s = size as f32;
+    let (ux, uy) = (angle.cos(), angle.sin());
+    let (nx, ny) = (-uy, ux);
+    // Width of the cell measured across the lines.
+    let extent = s * (ux.abs() + uy.abs());
+    for l in 0..size {
+        if gs[l as usize] {
+            let d = ((l as f32 + 0.5) / s - 0.5) * extent;
+            let (px, py) = (s / 2.0 + d * nx, s / 2.0 + d * ny);
+            if let Some((a, b)) = clip(px, py, ux, uy, s) {
+                pen.line(
+                    cell.to_canvas(a.x, a.y),
+                    cell.to_canvas(b.x, b.y),
+                    color,
+                    pen.weight(),
+                    list,
+                );
+            }
+        }
+    }
+}
+
+// Clip the line through (px, py) with direction (ux, uy) to the square
+// from (0, 0) to (s, s).
+fn clip(px: f32, py: f32, ux: f32, uy: f32, s: f32) -> Option<(Point, Point)> {
+    let mut lo = f32::NEG_INFINITY;
+    let mut hi = f32::INFINITY;
+    for (p, u) in [(px, ux), (py, uy)] {
+        if u.abs() < 1e-6 {
+            if p < 0.0 || p > s {
+                return None;
+            }
+        } else {
+            let (a, b) = (-p / u, (s - p) / u);
+            lo = lo.max(a.min(b));
+            hi = hi.min(a.max(b));
+        }
+    }
+    (lo < hi).then(|| {
+        (
+            pt(px + lo * ux, py + lo * uy),
+            pt(px + hi * ux, py + hi * uy),
+        )
+    })
+}
+
+pub fn stipple(cell: &Cell, t: f32, color: Color, rng: &mut SmallRng, list: &mut Vec<Primitive>) {
+    let size = cell.size as f32;
+    let n = filled((size * size) as usize, t);
+    let ps = halton_seq(size, size, n as u32, rng.gen());
+    for p in ps {
+        let q = cell.to_canvas(p.x, p.y);
+        list.push(Primitive::Dot {
+            x: q.x,
+            y: q.y,
+            color: rgba(color),
+        })
+    }
+}
+
+pub fn grid(cell: &Cell, t: f32, color: Color, list: &mut Vec<Primitive>) {
+    let size = cell.size as f32;
+    let s = (1.0 / t).clamp(1.0, size);
+    let mut i = 0.0;
+    while i < size {
+        let mut j = 0.0;
+        while j < size {
+            let p = cell.to_canvas(i, j);
+            list.push(Primitive::Dot {
+                x: p.x,
+                y: p.y,
+                color: rgba(color),
+            });
+            j += s;
+        }
+        i += s;
+    }
+}
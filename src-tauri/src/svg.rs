@@ -0,0 +1,142 @@
+use std::fmt::Write;
+
+use crate::display::{DisplayList, Primitive};
+use crate::units::Page;
+
+// A display list as an SVG document one user unit per output pixel. On a
+// `page` the document is the size of the paper and the view box is
+// widened around the drawing to match, so the marks keep their pixel
+// coordinates.
+pub fn write(list: &DisplayList, background: Option<[u8; 4]>, page: Option<&Page>) -> String {
+    let mut svg = String::new();
+    let (x, y, w, h) = match page {
+        Some(page) => {
+            let s = page.scale;
+            let (x, y) = (-page.offset[0] / s, -page.offset[1] / s);
+            let unit = page.unit.suffix();
+            let _ = writeln!(
+                svg,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}{u}" height="{}{u}" viewBox="{} {} {} {}">"#,
+                page.width,
+                page.height,
+                x,
+                y,
+                page.width / s,
+                page.height / s,
+                u = unit
+            );
+            (x, y, page.width / s, page.height / s)
+        }
+        None => {
+            let _ = writeln!(
+                svg,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                w = list.width,
+                h = list.height
+            );
+            (0.0, 0.0, list.width as f32, list.height as f32)
+        }
+    };
+    if let Some(color) = background {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}"{}/>"#,
+            x,
+            y,
+            w,
+            h,
+            paint("fill", color)
+        );
+    }
+    for primitive in &list.primitives {
+        let color = primitive.color();
+        let _ = match primitive {
+            Primitive::Circle { x, y, radius, .. } => writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}"{}/>"#,
+                x,
+                y,
+                radius,
+                paint("fill", color)
+            ),
+            Primitive::Ring {
+                x,
+                y,
+                radius,
+                weight,
+                ..
+            } => writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke-width="{}"{}/>"#,
+                x,
+                y,
+                radius,
+                weight,
+                paint("stroke", color)
+            ),
+            Primitive::Polygon { points, .. } => writeln!(
+                svg,
+                r#"<polygon points="{}"{}/>"#,
+                points
+                    .iter()
+                    .map(|[x, y]| format!("{},{}", x, y))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                paint("fill", color)
+            ),
+            Primitive::Line {
+                from, to, weight, ..
+            } => writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke-width="{}"{}/>"#,
+                from[0],
+                from[1],
+                to[0],
+                to[1],
+                weight,
+                paint("stroke", color)
+            ),
+            Primitive::Curve {
+                start,
+                segments,
+                weight,
+                ..
+            } => {
+                let mut d = format!("M{},{}", start[0], start[1]);
+                for [c1, c2, end] in segments {
+                    let _ = write!(
+                        d,
+                        " C{},{} {},{} {},{}",
+                        c1[0], c1[1], c2[0], c2[1], end[0], end[1]
+                    );
+                }
+                writeln!(
+                    svg,
+                    r#"<path d="{}" fill="none" stroke-width="{}"{}/>"#,
+                    d,
+                    weight,
+                    paint("stroke", color)
+                )
+            }
+            Primitive::Dot { x, y, .. } => writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="1" height="1"{}/>"#,
+                x.floor(),
+                y.floor(),
+                paint("fill", color)
+            ),
+        };
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// The `fill` or `stroke` attributes for a color, with its opacity when it
+// is not opaque.
+fn paint(attribute: &str, [r, g, b, a]: [u8; 4]) -> String {
+    let mut paint = format!(r#" {}="rgb({},{},{})""#, attribute, r, g, b);
+    if a < 255 {
+        let _ = write!(paint, r#" {}-opacity="{:.3}""#, attribute, a as f32 / 255.0);
+    }
+    paint
+}
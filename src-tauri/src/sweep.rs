@@ -0,0 +1,156 @@
+use image::{imageops, Rgba, RgbaImage};
+use serde_json::Value;
+
+use crate::error::{Error, FieldError, Problem};
+use crate::RenderOptions;
+
+// Most renders in one sweep, past this the tiles get too small to judge.
+pub const MAX_STEPS: u32 = 12;
+// Width of each render in the strip, and the space around them.
+const TILE: u32 = 320;
+const GAP: u32 = 16;
+// Pixels per dot of the label font and the rows it takes under the tiles.
+const LABEL_SCALE: u32 = 4;
+const LABEL_HEIGHT: u32 = 5 * LABEL_SCALE + 2 * GAP;
+const STRIP_BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+// `steps` values from `from` to `to`, both ends included.
+pub fn values(from: f32, to: f32, steps: u32) -> Vec<f32> {
+    let last = steps.saturating_sub(1).max(1) as f32;
+    (0..steps)
+        .map(|i| from + (to - from) * i as f32 / last)
+        .collect()
+}
+
+// The options with the number at `name` set to `value`. `name` is a field
+// of the options, or a dotted path into one like "stroke.weight", as the
+// options are written in JSON. Whole number fields are rounded, so the
+// value actually set is returned too. Fields that are off, like a missing
+// stroke, have to be turned on first.
+pub fn with_value(
+    options: &RenderOptions,
+    name: &str,
+    value: f32,
+) -> Result<(RenderOptions, f32), Error> {
+    let not_a_number = |problem: Problem| {
+        Error::InvalidOptions(vec![FieldError {
+            field: name.to_string(),
+            problem,
+        }])
+    };
+    let mut json = serde_json::to_value(options)
+        .map_err(|err| not_a_number(Problem::Invalid(err.to_string())))?;
+    let mut field = &mut json;
+    for key in name.split('.') {
+        field = field
+            .get_mut(key)
+            .ok_or_else(|| not_a_number(Problem::NotASetting))?;
+    }
+    let value = match field {
+        Value::Number(n) if n.is_f64() => value,
+        Value::Number(n) if n.is_u64() => value.round().max(0.0),
+        Value::Number(_) => value.round(),
+        Value::Null => return Err(not_a_number(Problem::SettingOff)),
+        _ => return Err(not_a_number(Problem::NotNumeric)),
+    };
+    *field = match field {
+        Value::Number(n) if n.is_f64() => Value::from(value as f64),
+        Value::Number(n) if n.is_u64() => Value::from(value as u64),
+        _ => Value::from(value as i64),
+    };
+    let options = serde_json::from_value(json)
+        .map_err(|err| not_a_number(Problem::Invalid(err.to_string())))?;
+    Ok((options, value))
+}
+
+// The label for a value of a swept setting, without trailing zeros.
+pub fn label(value: f32) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+// The renders side by side, each scaled to the same width and labeled
+// underneath, like a contact print.
+pub fn strip(tiles: &[(String, RgbaImage)]) -> RgbaImage {
+    let scaled: Vec<(&str, RgbaImage)> = tiles
+        .iter()
+        .map(|(label, img)| {
+            let height = (img.height() as u64 * TILE as u64 / img.width().max(1) as u64).max(1);
+            let tile = imageops::resize(img, TILE, height as u32, imageops::FilterType::Triangle);
+            (label.as_str(), tile)
+        })
+        .collect();
+    let tallest = scaled
+        .iter()
+        .map(|(_, tile)| tile.height())
+        .max()
+        .unwrap_or(0);
+    let n = scaled.len() as u32;
+    let mut out = RgbaImage::from_pixel(
+        n * TILE + (n + 1) * GAP,
+        GAP + tallest + LABEL_HEIGHT,
+        STRIP_BACKGROUND,
+    );
+    for (i, (label, tile)) in scaled.iter().enumerate() {
+        let x = GAP + i as u32 * (TILE + GAP);
+        imageops::overlay(&mut out, tile, x as i64, GAP as i64);
+        let width = text_width(label);
+        let left = x + TILE.saturating_sub(width) / 2;
+        write(&mut out, label, left, 2 * GAP + tallest);
+    }
+    out
+}
+
+fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * 4).saturating_sub(1) * LABEL_SCALE
+}
+
+// Draw `text` with its top left at (x, y) in a 3 by 5 dot font that has
+// only what numbers need.
+fn write(img: &mut RgbaImage, text: &str, x: u32, y: u32) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * 4 * LABEL_SCALE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..LABEL_SCALE {
+                    for dx in 0..LABEL_SCALE {
+                        let (px, py) = (
+                            left + col * LABEL_SCALE + dx,
+                            y + row as u32 * LABEL_SCALE + dy,
+                        );
+                        if px < img.width() && py < img.height() {
+                            img.put_pixel(px, py, LABEL_COLOR);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The rows of a character, top first, the high bit of each the left dot.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => [0; 5],
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+use crate::display::Primitive;
+
+// More folds than this leave wedges too thin to hold a mark.
+pub const MAX_FOLDS: u32 = 24;
+
+// Ways to repeat part of the marks over the rest of the output, for
+// mandala like pictures from ordinary photos. The marks of the part kept
+// are copied, the rest are dropped.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Symmetry {
+    // The left half mirrored onto the right.
+    Vertical,
+    // The top half mirrored onto the bottom.
+    Horizontal,
+    // The top left quarter mirrored onto the other three.
+    Both,
+    // The wedge of 1 / `folds` of a turn clockwise from 3 o'clock about
+    // `center`, a fraction of the width and height, turned `folds` times
+    // around it. With `mirror` every other copy is flipped, like a
+    // kaleidoscope, so the copies meet seamlessly.
+    Rotational {
+        folds: u32,
+        center: [f32; 2],
+        mirror: bool,
+    },
+}
+
+// The marks of a `width` by `height` output made symmetric.
+pub fn apply(marks: Vec<Primitive>, width: u32, height: u32, symmetry: Symmetry) -> Vec<Primitive> {
+    let (w, h) = (width as f32, height as f32);
+    match symmetry {
+        Symmetry::Vertical => mirror(
+            marks,
+            |[x, _]| x < w / 2.0,
+            &[(false, false), (true, false)],
+            w,
+            h,
+        ),
+        Symmetry::Horizontal => mirror(
+            marks,
+            |[_, y]| y < h / 2.0,
+            &[(false, false), (false, true)],
+            w,
+            h,
+        ),
+        Symmetry::Both => mirror(
+            marks,
+            |[x, y]| x < w / 2.0 && y < h / 2.0,
+            &[(false, false), (true, false), (false, true), (true, true)],
+            w,
+            h,
+        ),
+        Symmetry::Rotational {
+            folds,
+            center,
+            mirror,
+        } => rotate(marks, folds.max(1), [center[0] * w, center[1] * h], mirror),
+    }
+}
+
+// The kept marks copied once per flip, each of which says whether to
+// mirror across the middle of the width and of the height.
+fn mirror(
+    marks: Vec<Primitive>,
+    keep: impl Fn([f32; 2]) -> bool,
+    flips: &[(bool, bool)],
+    w: f32,
+    h: f32,
+) -> Vec<Primitive> {
+    let kept: Vec<Primitive> = marks
+        .into_iter()
+        .filter(|mark| keep(mark.anchor()))
+        .collect();
+    flips
+        .iter()
+        .flat_map(|&(flip_x, flip_y)| {
+            kept.iter().map(move |mark| {
+                mark.clone().mapped(|[x, y]| {
+                    [
+                        if flip_x { w - x } else { x },
+                        if flip_y { h - y } else { y },
+                    ]
+                })
+            })
+        })
+        .collect()
+}
+
+fn rotate(marks: Vec<Primitive>, folds: u32, [cx, cy]: [f32; 2], mirror: bool) -> Vec<Primitive> {
+    let wedge = TAU / folds as f32;
+    let kept: Vec<Primitive> = marks
+        .into_iter()
+        .filter(|mark| {
+            let [x, y] = mark.anchor();
+            (y - cy).atan2(x - cx).rem_euclid(TAU) < wedge
+        })
+        .collect();
+    (0..folds)
+        .flat_map(|k| {
+            // A flipped copy is mirrored across 3 o'clock and then turned
+            // a wedge further, so its edge meets the copy before it.
+            let flip = mirror && k % 2 == 1;
+            let turn = (if flip { k + 1 } else { k }) as f32 * wedge;
+            let (sin, cos) = turn.sin_cos();
+            kept.iter().map(move |mark| {
+                mark.clone().mapped(|[x, y]| {
+                    let (dx, dy) = (x - cx, if flip { cy - y } else { y - cy });
+                    [cx + dx * cos - dy * sin, cy + dx * sin + dy * cos]
+                })
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,32 @@
+use crate::display::Primitive;
+
+// The marks of a `width` by `height` output with those that cross an edge
+// also drawn across the opposite one, as if the output were wrapped
+// around a torus, so it repeats seamlessly as a pattern.
+pub fn wrap(marks: Vec<Primitive>, width: u32, height: u32) -> Vec<Primitive> {
+    let (w, h) = (width as f32, height as f32);
+    let mut wrapped = Vec::with_capacity(marks.len());
+    for mark in marks {
+        let [x0, y0, x1, y1] = mark.bounds();
+        let shifts = |low: f32, high: f32, size: f32| {
+            let mut shifts = vec![0.0];
+            if low < 0.0 {
+                shifts.push(size);
+            }
+            if high > size {
+                shifts.push(-size);
+            }
+            shifts
+        };
+        let (dxs, dys) = (shifts(x0, x1, w), shifts(y0, y1, h));
+        for &dx in &dxs {
+            for &dy in &dys {
+                if dx != 0.0 || dy != 0.0 {
+                    wrapped.push(mark.clone().translated(dx, dy));
+                }
+            }
+        }
+        wrapped.push(mark);
+    }
+    wrapped
+}
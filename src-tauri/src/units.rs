@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::display::{DisplayList, Primitive};
+use crate::error::{Error, FieldError, Problem};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Unit {
+    Millimeters,
+    Inches,
+}
+
+impl Unit {
+    pub fn millimeters(self) -> f32 {
+        match self {
+            Unit::Millimeters => 1.0,
+            Unit::Inches => 25.4,
+        }
+    }
+
+    // The suffix for a length in SVG.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Unit::Millimeters => "mm",
+            Unit::Inches => "in",
+        }
+    }
+}
+
+// How big an output pixel is on the paper.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum Scale {
+    // As large as fits inside the margins.
+    Fit,
+    // This long, in the sheet's unit.
+    Fixed { per_pixel: f32 },
+    // So the typical stroke of the drawing comes out as wide as the pen,
+    // and the pen draws the lines the style had in mind.
+    Pen,
+}
+
+// The paper a vector export is drawn on, every length in `unit`. The
+// drawing is centered on it.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Sheet {
+    pub unit: Unit,
+    pub width: f32,
+    pub height: f32,
+    pub margin: f32,
+    pub scale: Scale,
+    // The width of the line the pen draws.
+    pub pen_width: f32,
+}
+
+impl Sheet {
+    pub fn validate(self) -> Result<Sheet, Error> {
+        let mut errors = Vec::new();
+        let mut positive = |field: &str, value: f32| {
+            if !(value.is_finite() && value > 0.0) {
+                errors.push(FieldError {
+                    field: format!("sheet.{}", field),
+                    problem: Problem::NotPositive(value),
+                });
+            }
+        };
+        positive("width", self.width);
+        positive("height", self.height);
+        positive("pen_width", self.pen_width);
+        if let Scale::Fixed { per_pixel } = self.scale {
+            positive("scale", per_pixel);
+        }
+        if !(self.margin >= 0.0 && 2.0 * self.margin < self.width.min(self.height)) {
+            errors.push(FieldError {
+                field: "sheet.margin".to_string(),
+                problem: Problem::MarginTooWide {
+                    margin: self.margin,
+                    width: self.width,
+                    height: self.height,
+                },
+            });
+        }
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::InvalidOptions(errors))
+        }
+    }
+}
+
+// Where a drawing lands on its sheet: the paper size, the length of an
+// output pixel and the top left corner of the drawing, all in `unit`.
+#[derive(Clone, Copy, Serialize)]
+pub struct Page {
+    pub unit: Unit,
+    pub width: f32,
+    pub height: f32,
+    pub scale: f32,
+    pub offset: [f32; 2],
+}
+
+impl Page {
+    pub fn new(list: &DisplayList, sheet: &Sheet) -> Page {
+        Page::shared(std::slice::from_ref(list), sheet)
+    }
+
+    // One page for drawings of the same size that go on the same paper,
+    // like the pens of a plot, so they line up.
+    pub fn shared(lists: &[DisplayList], sheet: &Sheet) -> Page {
+        let (width, height) = lists.first().map_or((1.0, 1.0), |list| {
+            (list.width.max(1) as f32, list.height.max(1) as f32)
+        });
+        let fit = ((sheet.width - 2.0 * sheet.margin) / width)
+            .min((sheet.height - 2.0 * sheet.margin) / height);
+        let scale = match sheet.scale {
+            Scale::Fit => fit,
+            Scale::Fixed { per_pixel } => per_pixel,
+            Scale::Pen => typical_weight(lists).map_or(fit, |weight| sheet.pen_width / weight),
+        };
+        Page {
+            unit: sheet.unit,
+            width: sheet.width,
+            height: sheet.height,
+            scale,
+            offset: [
+                (sheet.width - width * scale) / 2.0,
+                (sheet.height - height * scale) / 2.0,
+            ],
+        }
+    }
+
+    // A point in output pixels on the paper, in the page's unit.
+    pub fn place(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [
+            self.offset[0] + x * self.scale,
+            self.offset[1] + y * self.scale,
+        ]
+    }
+
+    // Millimeters on the paper per output pixel.
+    pub fn mm_per_pixel(&self) -> f32 {
+        self.scale * self.unit.millimeters()
+    }
+}
+
+// The median stroke weight, `None` when nothing is stroked.
+fn typical_weight(lists: &[DisplayList]) -> Option<f32> {
+    let mut weights: Vec<f32> = lists
+        .iter()
+        .flat_map(|list| &list.primitives)
+        .filter_map(|primitive| match primitive {
+            Primitive::Ring { weight, .. }
+            | Primitive::Line { weight, .. }
+            | Primitive::Curve { weight, .. } => Some(*weight),
+            _ => None,
+        })
+        .filter(|weight| *weight > 0.0)
+        .collect();
+    weights.sort_by(f32::total_cmp);
+    weights.get(weights.len() / 2).copied()
+}
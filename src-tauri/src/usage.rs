@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use seg_core::error::Error;
+use seg_core::RenderOptions;
+
+use crate::session::write_json;
+
+// Renders between writes of the counts to disk.
+const FLUSH_EVERY: u64 = 20;
+
+// How often each style and setting has been rendered, counted only when
+// the user turns it on in the settings. It never leaves the app data dir
+// unless exported, and is only used to put the user's favorites first.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    pub renders: u64,
+    pub styles: BTreeMap<String, u64>,
+    pub layouts: BTreeMap<String, u64>,
+    pub cells: BTreeMap<u32, u64>,
+}
+
+impl UsageStats {
+    fn record(&mut self, options: &RenderOptions) {
+        self.renders += 1;
+        *self.styles.entry(variant(&options.style)).or_default() += 1;
+        *self.layouts.entry(variant(&options.layout)).or_default() += 1;
+        *self.cells.entry(options.cell).or_default() += 1;
+    }
+}
+
+// The counts and the file they are kept in.
+pub struct Usage {
+    file: PathBuf,
+    pub stats: UsageStats,
+    unsaved: u64,
+}
+
+impl Usage {
+    pub fn load(dir: &Path) -> Self {
+        let file = dir.join("usage.json");
+        let stats = fs::read_to_string(&file)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Usage {
+            file,
+            stats,
+            unsaved: 0,
+        }
+    }
+
+    pub fn record(&mut self, options: &RenderOptions) {
+        self.stats.record(options);
+        self.unsaved += 1;
+        if self.unsaved >= FLUSH_EVERY {
+            if let Err(err) = self.save() {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        if self.unsaved == 0 {
+            return Ok(());
+        }
+        write_json(&self.file, &self.stats)?;
+        self.unsaved = 0;
+        Ok(())
+    }
+
+    // Forget every count, on disk too.
+    pub fn delete(&mut self) -> Result<(), Error> {
+        self.stats = UsageStats::default();
+        self.unsaved = 0;
+        match fs::remove_file(&self.file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Error::Remove {
+                path: self.file.display().to_string(),
+                reason: err.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+// The name of an enum variant as it is serialized, without its fields.
+fn variant(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        Ok(serde_json::Value::Object(fields)) => fields.keys().next().cloned().unwrap_or_default(),
+        _ => String::new(),
+    }
+}
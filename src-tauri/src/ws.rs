@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::Duration;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message as Frame, WebSocket};
+
+// Port used when SEG_WS_PORT is not set.
+const DEFAULT_PORT: u16 = 7879;
+// A client that can't take an event this quickly is dropped, so a stalled
+// listener never holds up the renders.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+// A client that hasn't finished the handshake by then is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Everyone listening for events.
+static CLIENTS: Mutex<Vec<WebSocket<TcpStream>>> = Mutex::new(Vec::new());
+
+// The events sent to the js side, as json text frames of the form
+// `{"event": "job-state", "payload": {...}}`. Clients only listen, anything
+// they send is ignored.
+#[derive(Serialize)]
+struct Event<'a, T> {
+    event: &'a str,
+    payload: &'a T,
+}
+
+// Accept websocket clients on localhost alongside the http api. Like the
+// http api, clients must connect to localhost from a local page, if any.
+// Each handshake runs on its own thread so a slow client can't hold up
+// the others.
+pub(crate) fn start() {
+    let port = std::env::var("SEG_WS_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("The event bridge could not start on port {}: {}", port, err);
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err()
+                || stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+            std::thread::spawn(move || {
+                if let Ok(socket) = tungstenite::accept_hdr(stream, check_origin) {
+                    CLIENTS
+                        .lock()
+                        .expect("Could not lock clients mutex")
+                        .push(socket);
+                }
+            });
+        }
+    });
+}
+
+// Refuse the handshake of a client that isn't local.
+fn check_origin(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    if crate::http::is_trusted(header("Host"), header("Origin")) {
+        return Ok(response);
+    }
+    let mut refused = ErrorResponse::new(None);
+    *refused.status_mut() = StatusCode::FORBIDDEN;
+    Err(refused)
+}
+
+// Send an event to every client, dropping those that have gone away.
+pub(crate) fn broadcast<T: Serialize>(event: &str, payload: &T) {
+    let mut clients = CLIENTS.lock().expect("Could not lock clients mutex");
+    if clients.is_empty() {
+        return;
+    }
+    let Ok(text) = serde_json::to_string(&Event { event, payload }) else {
+        return;
+    };
+    clients.retain_mut(|client| client.send(Frame::Text(text.clone())).is_ok());
+}
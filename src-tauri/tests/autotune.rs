@@ -0,0 +1,62 @@
+// Autotune searches a few settings for the render that looks most like
+// the source, and finds the same ones every time for the same seed.
+
+use seg_core::autotune::{tune, Tuned};
+use seg_core::canvas_pool::CanvasPool;
+use seg_core::patterns::{self, TestPattern};
+use seg_core::{RenderOptions, Style};
+
+const SEED: u64 = 7;
+
+fn options() -> RenderOptions {
+    RenderOptions {
+        style: Style::Dots,
+        cell: 8,
+        seed: SEED,
+        ..RenderOptions::default()
+    }
+}
+
+fn run(options: &RenderOptions) -> Tuned {
+    let source = patterns::generate(TestPattern::Gradient, 48);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .build()
+        .expect("Could not start the render threads");
+    tune(&source, options, &pool, &CanvasPool::default())
+}
+
+#[test]
+fn tuning_is_deterministic_for_a_seed() {
+    let (a, b) = (run(&options()), run(&options()));
+    assert_eq!(a.options.cell, b.options.cell);
+    assert_eq!(a.gamma, b.gamma);
+    assert_eq!(a.density, b.density);
+    assert_eq!(a.delta_e, b.delta_e);
+}
+
+#[test]
+fn tuned_settings_are_among_those_tried() {
+    let tuned = run(&options());
+    assert!([0.5, 0.7, 0.85, 1.0, 1.2, 1.5, 2.0].contains(&tuned.gamma));
+    assert!([0.7, 0.85, 1.0, 1.15, 1.3].contains(&tuned.density));
+    assert!(tuned.options.cell <= 10);
+    assert!(tuned.delta_e.is_finite() && tuned.delta_e >= 0.0);
+    // The rest of the options are kept.
+    assert_eq!(tuned.options.seed, SEED);
+    assert!(matches!(tuned.options.style, Style::Dots));
+}
+
+#[test]
+fn gamma_and_density_are_folded_into_the_tone_curve() {
+    let tuned = run(&options());
+    let curve = tuned
+        .options
+        .tone_curve
+        .expect("The tuned options have a tone curve");
+    assert_eq!(curve.points.len(), 17);
+    assert_eq!(curve.points[0], [0.0, 0.0]);
+    let [t, drawn] = curve.points[16];
+    assert_eq!(t, 1.0);
+    assert!((drawn - tuned.density.min(1.0)).abs() < 1e-6);
+}
@@ -0,0 +1,13 @@
+// Helpers shared by the tests that work with files.
+
+use std::fs;
+use std::path::PathBuf;
+
+// A fresh, empty folder under the temp dir, named so tests running at the
+// same time, in this run or another, don't share it.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("seg-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).expect("Could not create the folder");
+    root
+}
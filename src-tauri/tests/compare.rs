@@ -0,0 +1,50 @@
+// Comparing two images scores how alike they are and maps where they
+// differ.
+
+use image::{Rgba, RgbaImage};
+use seg_core::compare::{compare, Metric};
+
+// A diagonal gradient with some structure for SSIM to find.
+fn gradient(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let v = ((x + y) * 255 / (width + height - 2)) as u8;
+        Rgba([v, 255 - v, (x * 7 % 256) as u8, 255])
+    })
+}
+
+#[test]
+fn an_image_is_the_same_as_itself() {
+    let img = gradient(40, 24);
+    for metric in [Metric::Ssim, Metric::DeltaE] {
+        let (scores, map) = compare(&img, &img, metric);
+        assert!((scores.ssim - 1.0).abs() < 1e-4, "ssim {}", scores.ssim);
+        assert_eq!(scores.delta_e, 0.0);
+        assert!(map.pixels().all(|px| px.0 == [0, 0, 0, 255]));
+    }
+}
+
+#[test]
+fn black_and_white_differ() {
+    let black = RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 255]));
+    let white = RgbaImage::from_pixel(16, 16, Rgba([255, 255, 255, 255]));
+    let (scores, map) = compare(&black, &white, Metric::DeltaE);
+    assert!(scores.ssim < 1.0);
+    assert!(scores.delta_e > 50.0);
+    // Past the full heat difference, every pixel is white hot.
+    assert!(map.pixels().all(|px| px.0 == [255, 255, 255, 255]));
+}
+
+// Transparent pixels are seen over white.
+#[test]
+fn transparent_is_white() {
+    let clear = RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 0]));
+    let white = RgbaImage::from_pixel(16, 16, Rgba([255, 255, 255, 255]));
+    let (scores, _) = compare(&clear, &white, Metric::DeltaE);
+    assert_eq!(scores.delta_e, 0.0);
+}
+
+#[test]
+fn the_map_is_the_size_of_the_first_image() {
+    let (_, map) = compare(&gradient(40, 24), &gradient(20, 12), Metric::Ssim);
+    assert_eq!(map.dimensions(), (40, 24));
+}
@@ -0,0 +1,205 @@
+// Renders each style of a small test pattern with a fixed seed and compares
+// it with a reference image in tests/golden, so changes to the renderer
+// can't silently alter the art. A missing reference fails the test, set
+// SEG_BLESS=1 to write them all out after an intended change and commit
+// the new references with it.
+
+use image::RgbaImage;
+use std::path::PathBuf;
+
+use seg_core::canvas_pool::CanvasPool;
+use seg_core::patterns::{self, TestPattern};
+use seg_core::planes::Planes;
+use seg_core::queue::Signal;
+use seg_core::render::generate;
+use seg_core::{RenderOptions, Style};
+
+const SEED: u64 = 42;
+const CELL: u32 = 8;
+// Channel differences up to this are treated as the same color, to allow
+// for floating point differences in anti-aliasing between platforms.
+const CHANNEL_TOLERANCE: u8 = 2;
+// Share of pixels allowed to differ by more than the channel tolerance.
+const PIXEL_TOLERANCE: f64 = 0.001;
+
+fn render(pattern: TestPattern, options: &RenderOptions) -> RgbaImage {
+    render_on(pattern, options, 2)
+}
+
+fn render_on(pattern: TestPattern, options: &RenderOptions, threads: usize) -> RgbaImage {
+    let img = patterns::generate(pattern, 24);
+    let planes = Planes::new(&img, options.needs_hue());
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Could not start the render threads");
+    generate(
+        &planes,
+        options,
+        &Signal::default(),
+        &pool,
+        &CanvasPool::default(),
+    )
+    .expect("An unsignalled render can not be interrupted")
+}
+
+fn check(name: &str, img: &RgbaImage) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+    if std::env::var_os("SEG_BLESS").is_some() {
+        img.save(&path).unwrap_or_else(|err| {
+            panic!("The file at {} could not be saved: {}", path.display(), err)
+        });
+        return;
+    }
+    assert!(
+        path.exists(),
+        "There is no reference for {} at {}, run with SEG_BLESS=1 to write it",
+        name,
+        path.display()
+    );
+    let golden = image::open(&path)
+        .unwrap_or_else(|err| {
+            panic!(
+                "The file at {} could not be opened: {}",
+                path.display(),
+                err
+            )
+        })
+        .to_rgba8();
+    assert_eq!(
+        golden.dimensions(),
+        img.dimensions(),
+        "{} changed size",
+        name
+    );
+    let differing = golden
+        .pixels()
+        .zip(img.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0)
+                .any(|(x, y)| x.abs_diff(y) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let share = differing as f64 / img.pixels().len() as f64;
+    assert!(
+        share <= PIXEL_TOLERANCE,
+        "{} differs from its reference in {} pixels",
+        name,
+        differing
+    );
+}
+
+fn options(style: Style) -> RenderOptions {
+    RenderOptions {
+        cell: CELL,
+        style,
+        seed: SEED,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn dots() {
+    check(
+        "dots",
+        &render(TestPattern::Gradient, &options(Style::Dots)),
+    );
+}
+
+#[test]
+fn vlines() {
+    check(
+        "vlines",
+        &render(TestPattern::Gradient, &options(Style::VLines)),
+    );
+}
+
+#[test]
+fn hlines() {
+    check(
+        "hlines",
+        &render(TestPattern::Gradient, &options(Style::HLines)),
+    );
+}
+
+#[test]
+fn cross() {
+    check(
+        "cross",
+        &render(TestPattern::Gradient, &options(Style::Cross)),
+    );
+}
+
+#[test]
+fn stipple() {
+    check(
+        "stipple",
+        &render(TestPattern::Gradient, &options(Style::Stipple)),
+    );
+}
+
+#[test]
+fn grid() {
+    check(
+        "grid",
+        &render(TestPattern::StepWedge { steps: 6 }, &options(Style::Grid)),
+    );
+}
+
+#[test]
+fn multi() {
+    check(
+        "multi",
+        &render(TestPattern::ColorWheel, &options(Style::Multi)),
+    );
+}
+
+#[test]
+fn same_seed_same_picture() {
+    let options = options(Style::Stipple);
+    let a = render(TestPattern::ZonePlate, &options);
+    let b = render(TestPattern::ZonePlate, &options);
+    assert!(a == b, "Two renders with the same seed differ");
+}
+
+#[test]
+fn same_picture_on_any_number_of_threads() {
+    for style in [Style::Stipple, Style::Cross] {
+        let options = options(style);
+        let one = render_on(TestPattern::ZonePlate, &options, 1);
+        let many = render_on(TestPattern::ZonePlate, &options, 5);
+        assert!(one == many, "The marks depend on the number of threads");
+    }
+}
+
+// Each thread count splits the image into bands of a different size, so
+// this checks that the random choices of each cell and its jitter only
+// depend on where the cell is in the whole image.
+#[test]
+fn jittered_marks_on_any_band_size() {
+    for style in [
+        Style::Dots,
+        Style::VLines,
+        Style::HLines,
+        Style::Cross,
+        Style::Stipple,
+        Style::Grid,
+    ] {
+        let options = RenderOptions {
+            jitter: 0.7,
+            ..options(style)
+        };
+        let one = render_on(TestPattern::Gradient, &options, 1);
+        for threads in [2, 3, 7] {
+            let many = render_on(TestPattern::Gradient, &options, threads);
+            assert!(
+                one == many,
+                "The marks depend on the band size with {} threads",
+                threads
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Options part of the way between two looks: numbers are blended, other
+// settings switch halfway, and the ends are the two looks themselves.
+
+use seg_core::interpolate::interpolate;
+use seg_core::post::Effects;
+use seg_core::{RenderOptions, Style};
+
+fn a() -> RenderOptions {
+    RenderOptions {
+        style: Style::Dots,
+        cell: 4,
+        seed: 1,
+        jitter: 0.25,
+        effects: Some(Effects {
+            vignette: 0.0,
+            grain: 0.5,
+            blur: 1.0,
+        }),
+        ..RenderOptions::default()
+    }
+}
+
+fn b() -> RenderOptions {
+    RenderOptions {
+        style: Style::Cross,
+        cell: 12,
+        seed: 2,
+        jitter: 0.75,
+        underlay_source: 0.5,
+        effects: Some(Effects {
+            vignette: 0.5,
+            grain: 0.0,
+            blur: 2.0,
+        }),
+        ..RenderOptions::default()
+    }
+}
+
+// Options compared as the JSON they are saved as.
+fn json(options: &RenderOptions) -> serde_json::Value {
+    serde_json::to_value(options).expect("Render options serialize to JSON")
+}
+
+#[test]
+fn the_ends_are_the_two_looks() {
+    assert_eq!(json(&interpolate(&a(), &b(), 0.0)), json(&a()));
+    assert_eq!(json(&interpolate(&a(), &b(), 1.0)), json(&b()));
+}
+
+#[test]
+fn t_outside_the_range_is_an_end() {
+    assert_eq!(json(&interpolate(&a(), &b(), -1.0)), json(&a()));
+    assert_eq!(json(&interpolate(&a(), &b(), 2.0)), json(&b()));
+    assert_eq!(json(&interpolate(&a(), &b(), f32::NAN)), json(&a()));
+}
+
+#[test]
+fn numbers_are_blended() {
+    let half = interpolate(&a(), &b(), 0.5);
+    assert_eq!(half.cell, 8);
+    assert_eq!(half.jitter, 0.5);
+    assert_eq!(half.underlay_source, 0.25);
+    let effects = half.effects.expect("Both looks have effects");
+    assert_eq!(
+        [effects.vignette, effects.grain, effects.blur],
+        [0.25, 0.25, 1.5]
+    );
+}
+
+// The style and seed aren't numbers to blend, so they switch halfway.
+#[test]
+fn other_settings_switch_halfway() {
+    let before = interpolate(&a(), &b(), 0.25);
+    assert!(matches!(before.style, Style::Dots));
+    assert_eq!(before.seed, 1);
+    let after = interpolate(&a(), &b(), 0.5);
+    assert!(matches!(after.style, Style::Cross));
+    assert_eq!(after.seed, 2);
+}
+
+#[test]
+fn effects_on_one_side_fade_in() {
+    let plain = RenderOptions {
+        effects: None,
+        ..a()
+    };
+    let effects = interpolate(&plain, &b(), 0.5)
+        .effects
+        .expect("Effects fade in from nothing");
+    assert_eq!(
+        [effects.vignette, effects.grain, effects.blur],
+        [0.25, 0.0, 1.0]
+    );
+    assert!(interpolate(&plain, &plain, 0.5).effects.is_none());
+}
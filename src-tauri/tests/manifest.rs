@@ -0,0 +1,44 @@
+// The manifest saved beside an export records what made it and the
+// checksum of the file.
+
+use std::fs;
+use std::path::PathBuf;
+
+use seg_core::manifest;
+use seg_core::naming;
+use seg_core::RenderOptions;
+
+mod common;
+
+#[test]
+fn manifest_is_named_after_the_whole_file_name() {
+    assert_eq!(
+        naming::manifest_path(&PathBuf::from("/out/a.png")),
+        PathBuf::from("/out/a.png.manifest.json")
+    );
+}
+
+#[test]
+fn manifest_has_the_checksum_of_the_export() {
+    let root = common::temp_dir("manifest");
+    let export = root.join("a.png");
+    fs::write(&export, b"not really a png").expect("Could not create the export");
+    let written = manifest::write(
+        &export,
+        None,
+        manifest::Inputs::default(),
+        &RenderOptions::default(),
+    )
+    .expect("The manifest could not be written");
+    assert_eq!(written, root.join("a.png.manifest.json"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&written).unwrap()).unwrap();
+    assert_eq!(json["file"], "a.png");
+    assert_eq!(
+        json["sha256"].as_str(),
+        Some(manifest::sha256(&export).unwrap().as_str())
+    );
+    assert!(json["source"].is_null());
+    assert!(json["inputs"]["mask"].is_null());
+}
@@ -0,0 +1,72 @@
+// Saved render options from every version of Seg read back as the options
+// they were saved with.
+
+use serde_json::{json, Value};
+
+use seg_core::migrate::{self, CURRENT_VERSION};
+use seg_core::RenderOptions;
+
+fn read(saved: Value) -> Value {
+    let options = migrate::from_value(saved).expect("The saved options could not be read");
+    serde_json::to_value(options).expect("The options could not be written")
+}
+
+fn current(options: Value) -> Value {
+    let options: RenderOptions =
+        serde_json::from_value(options).expect("The options could not be read");
+    serde_json::to_value(options).expect("The options could not be written")
+}
+
+// The first options sent by the app, just the cell size and style.
+#[test]
+fn version_0_cell_and_style() {
+    let options = read(json!({ "cell": 12, "style": "Cross" }));
+    assert_eq!(options["cell"], 12);
+    assert_eq!(options["style"], "Cross");
+    assert_eq!(options["seed"], 0);
+    assert_eq!(options["gradient_map"], Value::Null);
+}
+
+// Unversioned options as saved with an export queue, before versioning.
+#[test]
+fn version_0_full() {
+    let saved = json!({
+        "cell": 8,
+        "style": "Multi",
+        "seed": 7,
+        "layout": { "Brick": { "columns": true } },
+        "jitter": 0.25,
+        "gradient_map": {
+            "stops": [
+                { "at": 0.0, "color": [20, 24, 82] },
+                { "at": 1.0, "color": [240, 200, 120] }
+            ]
+        },
+        "invert_output": true,
+        "trim": null,
+        "dot_shape": "Ring",
+    });
+    assert_eq!(read(saved.clone()), current(saved));
+}
+
+#[test]
+fn current_version_round_trips() {
+    let options = RenderOptions {
+        cell: 20,
+        seed: 99,
+        jitter: 0.5,
+        ..Default::default()
+    };
+    let saved = migrate::to_value(&options).expect("The options could not be saved");
+    assert_eq!(saved["version"], CURRENT_VERSION);
+    assert_eq!(
+        read(saved),
+        serde_json::to_value(&options).expect("The options could not be written")
+    );
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let saved = json!({ "version": CURRENT_VERSION + 1, "cell": 10 });
+    assert!(migrate::from_value(saved).is_err());
+}
@@ -0,0 +1,86 @@
+// Export paths are resolved against what is already on disk according to
+// the conflict setting.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use seg_core::error::Error;
+use seg_core::naming::{self, OnConflict};
+
+mod common;
+
+// A fresh folder under the temp dir holding "a.png".
+fn fixture(name: &str) -> PathBuf {
+    let root = common::temp_dir(&format!("naming-{}", name));
+    fs::write(root.join("a.png"), b"").expect("Could not create the file");
+    root
+}
+
+fn resolve(path: &Path, on_conflict: OnConflict, create_dirs: bool) -> Result<PathBuf, Error> {
+    naming::resolve(&path.to_string_lossy(), on_conflict, create_dirs)
+}
+
+#[test]
+fn free_name_is_kept() {
+    let root = fixture("free");
+    let path = root.join("b.png");
+    for on_conflict in [OnConflict::Overwrite, OnConflict::Rename, OnConflict::Error] {
+        assert_eq!(resolve(&path, on_conflict, false), Ok(path.clone()));
+    }
+}
+
+#[test]
+fn overwrite_keeps_the_name() {
+    let root = fixture("overwrite");
+    let path = root.join("a.png");
+    assert_eq!(resolve(&path, OnConflict::Overwrite, false), Ok(path));
+}
+
+#[test]
+fn error_refuses_the_name() {
+    let root = fixture("error");
+    assert!(matches!(
+        resolve(&root.join("a.png"), OnConflict::Error, false),
+        Err(Error::FileExists(_))
+    ));
+}
+
+#[test]
+fn rename_counts_up_to_a_free_name() {
+    let root = fixture("rename");
+    let path = root.join("a.png");
+    assert_eq!(
+        resolve(&path, OnConflict::Rename, false),
+        Ok(root.join("a (1).png"))
+    );
+    fs::write(root.join("a (1).png"), b"").unwrap();
+    assert_eq!(
+        resolve(&path, OnConflict::Rename, false),
+        Ok(root.join("a (2).png"))
+    );
+}
+
+#[test]
+fn rename_without_extension() {
+    let root = fixture("no-extension");
+    fs::write(root.join("notes"), b"").unwrap();
+    assert_eq!(
+        resolve(&root.join("notes"), OnConflict::Rename, false),
+        Ok(root.join("notes (1)"))
+    );
+}
+
+#[test]
+fn missing_folder_is_created_only_when_asked() {
+    let root = fixture("missing");
+    let path = root.join("new/deeper/b.png");
+    assert!(matches!(
+        resolve(&path, OnConflict::Overwrite, false),
+        Err(Error::NoFolder(_))
+    ));
+    assert_eq!(
+        resolve(&path, OnConflict::Overwrite, true),
+        Ok(path.clone())
+    );
+    assert!(root.join("new/deeper").is_dir());
+}
@@ -0,0 +1,136 @@
+// Tidying a drawing for a plotter keeps what is drawn: merged lines cover
+// the same spans and reordered marks keep their ends.
+
+use seg_core::display::{DisplayList, Primitive};
+use seg_core::optimize::{ends, optimize, Optimize};
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+
+fn line(from: [f32; 2], to: [f32; 2], color: [u8; 4]) -> Primitive {
+    Primitive::Line {
+        from,
+        to,
+        weight: 1.0,
+        color,
+    }
+}
+
+fn run(primitives: Vec<Primitive>, options: Optimize) -> Vec<Primitive> {
+    let list = DisplayList {
+        width: 200,
+        height: 200,
+        primitives,
+    };
+    optimize(list, &options).primitives
+}
+
+const MERGE: Optimize = Optimize {
+    merge: true,
+    order: false,
+    two_opt: false,
+    smooth: None,
+};
+
+const ORDER: Optimize = Optimize {
+    merge: false,
+    order: true,
+    two_opt: true,
+    smooth: None,
+};
+
+// The ends of each line, the smaller first, so a line and its reverse are
+// the same, sorted.
+fn spans(primitives: &[Primitive]) -> Vec<([f32; 2], [f32; 2])> {
+    let mut spans: Vec<_> = primitives
+        .iter()
+        .map(|primitive| {
+            let (a, b) = ends(primitive);
+            if a.partial_cmp(&b) == Some(std::cmp::Ordering::Greater) {
+                (b, a)
+            } else {
+                (a, b)
+            }
+        })
+        .collect();
+    spans.sort_by(|a, b| a.partial_cmp(b).expect("The ends are numbers"));
+    spans
+}
+
+// The distance the pen travels lifted from the end of one mark to the
+// start of the next.
+fn travel(primitives: &[Primitive]) -> f32 {
+    primitives
+        .windows(2)
+        .map(|pair| {
+            let (_, a) = ends(&pair[0]);
+            let (b, _) = ends(&pair[1]);
+            (a[0] - b[0]).hypot(a[1] - b[1])
+        })
+        .sum()
+}
+
+#[test]
+fn merge_keeps_the_outer_ends() {
+    let merged = run(
+        vec![
+            line([0.0, 0.0], [2.0, 0.0], BLACK),
+            line([2.0, 0.0], [5.0, 0.0], BLACK),
+            line([1.0, 0.0], [3.0, 0.0], BLACK),
+            line([7.0, 0.0], [8.0, 0.0], BLACK),
+            // Drawn the other way, it still runs on from the one before.
+            line([9.0, 0.0], [8.0, 0.0], BLACK),
+        ],
+        MERGE,
+    );
+    assert_eq!(
+        spans(&merged),
+        [([0.0, 0.0], [5.0, 0.0]), ([7.0, 0.0], [9.0, 0.0])]
+    );
+}
+
+#[test]
+fn merge_keeps_pens_apart() {
+    let merged = run(
+        vec![
+            line([0.0, 0.0], [2.0, 0.0], BLACK),
+            line([2.0, 0.0], [4.0, 0.0], RED),
+        ],
+        MERGE,
+    );
+    assert_eq!(merged.len(), 2);
+}
+
+#[test]
+fn merge_keeps_other_marks() {
+    let merged = run(
+        vec![
+            Primitive::Dot {
+                x: 3.0,
+                y: 4.0,
+                color: BLACK,
+            },
+            line([0.0, 0.0], [2.0, 0.0], BLACK),
+        ],
+        MERGE,
+    );
+    assert_eq!(merged.len(), 2);
+    assert!(matches!(merged[0], Primitive::Dot { x, y, .. } if x == 3.0 && y == 4.0));
+}
+
+#[test]
+fn order_keeps_every_mark_and_shortens_travel() {
+    // Short lines along a row, in an order that zigzags from end to end.
+    let lines: Vec<Primitive> = [0, 9, 1, 8, 2, 7, 3, 6, 4, 5]
+        .into_iter()
+        .map(|i| {
+            let x = i as f32 * 10.0;
+            line([x, 0.0], [x + 5.0, 0.0], BLACK)
+        })
+        .collect();
+    let ordered = run(lines.clone(), ORDER);
+    assert_eq!(spans(&ordered), spans(&lines));
+    assert!(travel(&ordered) < travel(&lines));
+    // The pen starts at the top left corner, so the nearest end comes first.
+    assert_eq!(ends(&ordered[0]).0, [0.0, 0.0]);
+}
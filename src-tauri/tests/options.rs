@@ -0,0 +1,140 @@
+// Options from outside are checked before they are rendered: fractions
+// are clamped and every field that can't be rendered is reported.
+
+use seg_core::error::{Error, Problem};
+use seg_core::patches::MAX_OCTAVES;
+use seg_core::{RenderOptions, Style};
+
+fn valid() -> RenderOptions {
+    RenderOptions {
+        cell: 8,
+        ..RenderOptions::default()
+    }
+}
+
+// The fields rejected and why, in the order they were checked.
+fn problems(options: RenderOptions) -> Vec<(String, Problem)> {
+    match options.validate().err() {
+        Some(Error::InvalidOptions(errors)) => errors
+            .into_iter()
+            .map(|err| (err.field, err.problem))
+            .collect(),
+        Some(err) => panic!("Validation failed with {}", err),
+        None => Vec::new(),
+    }
+}
+
+fn fields(options: RenderOptions) -> Vec<String> {
+    problems(options)
+        .into_iter()
+        .map(|(field, _)| field)
+        .collect()
+}
+
+#[test]
+fn valid_options_pass() {
+    assert!(valid().validate().is_ok());
+}
+
+#[test]
+fn fractions_are_clamped() {
+    let mut options = valid();
+    options.jitter = 1.5;
+    options.underlay_source = -0.5;
+    let options = options
+        .validate()
+        .unwrap_or_else(|err| panic!("Clamped fractions failed with {}", err));
+    assert_eq!(options.jitter, 1.0);
+    assert_eq!(options.underlay_source, 0.0);
+}
+
+#[test]
+fn cell_out_of_range() {
+    for cell in [0, 100_000] {
+        let options = RenderOptions { cell, ..valid() };
+        assert_eq!(fields(options), ["cell"]);
+    }
+}
+
+#[test]
+fn not_a_number() {
+    let mut options = valid();
+    options.jitter = f32::NAN;
+    options.multi.density.blue = f32::INFINITY;
+    let found = problems(options);
+    assert_eq!(found.len(), 2);
+    assert!(
+        matches!(&found[0], (field, Problem::NotANumber(v)) if field == "jitter" && v.is_nan())
+    );
+    assert_eq!(
+        found[1],
+        (
+            "multi.density.blue".to_string(),
+            Problem::NotANumber(f32::INFINITY)
+        )
+    );
+}
+
+#[test]
+fn each_rejected_field_is_reported() {
+    let mut options = valid();
+    options.multi.density.red = -1.0;
+    options.tonal.shadows_below = 0.9;
+    options.tonal.highlights_above = 0.1;
+    options.patches.scale = 0.5;
+    options.patches.octaves = 0;
+    options.supersample = 99;
+    assert_eq!(
+        problems(options),
+        [
+            ("multi.density.red".to_string(), Problem::Negative(-1.0)),
+            (
+                "tonal.shadows_below".to_string(),
+                Problem::Above {
+                    other: "tonal.highlights_above".to_string(),
+                    value: 0.9,
+                    limit: 0.1,
+                }
+            ),
+            ("patches.scale".to_string(), Problem::BelowOne(0.5)),
+            (
+                "patches.octaves".to_string(),
+                Problem::OutOfRange {
+                    min: 1,
+                    max: MAX_OCTAVES.into(),
+                    value: 0,
+                }
+            ),
+            (
+                "supersample".to_string(),
+                Problem::TooLarge { max: 4, value: 99 }
+            ),
+        ]
+    );
+}
+
+// Multi, Tonal and Patches pick among the other styles, so none of them
+// can be picked by Tonal or Patches in turn.
+#[test]
+fn tonal_and_patches_need_single_styles() {
+    for style in [Style::Multi, Style::Tonal, Style::Patches] {
+        let mut options = valid();
+        options.tonal.shadows = style;
+        options.tonal.highlights = style;
+        options.patches.styles = vec![Style::Dots, style];
+        assert_eq!(
+            problems(options),
+            [
+                ("tonal.shadows".to_string(), Problem::NotSingleStyle),
+                ("tonal.highlights".to_string(), Problem::NotSingleStyle),
+                ("patches.styles[1]".to_string(), Problem::NotSingleStyle),
+            ]
+        );
+    }
+    let mut options = valid();
+    options.patches.styles.clear();
+    assert_eq!(
+        problems(options),
+        [("patches.styles".to_string(), Problem::Empty)]
+    );
+}
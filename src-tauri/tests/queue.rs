@@ -0,0 +1,139 @@
+// The job queue hands out jobs by priority, and cancels and preempts them
+// the way the worker expects.
+
+use std::sync::Arc;
+
+use seg_core::queue::{Interrupt, JobKind, JobState, Queue};
+use seg_core::RenderOptions;
+
+fn push(queue: &Queue<()>, kind: JobKind) -> u64 {
+    queue
+        .push(kind, RenderOptions::default(), None, Arc::new(()))
+        .last()
+        .expect("A push reports the new job")
+        .id
+}
+
+fn state(queue: &Queue<()>, id: u64) -> JobState {
+    queue
+        .snapshot()
+        .into_iter()
+        .find(|info| info.id == id)
+        .expect("The job is in the queue")
+        .state
+}
+
+// Previews first, then exports, then batch jobs, oldest first within a
+// kind.
+#[test]
+fn next_by_kind_then_age() {
+    let queue = Queue::default();
+    let batch = push(&queue, JobKind::Batch);
+    let export = push(&queue, JobKind::Export);
+    let later_export = push(&queue, JobKind::Export);
+    let preview = push(&queue, JobKind::Preview);
+    let mut order = Vec::new();
+    for _ in 0..4 {
+        let (task, info) = queue.next();
+        assert_eq!(info.state, JobState::Running);
+        order.push(task.id);
+        queue.finish(task.id, Ok(()), None);
+    }
+    assert_eq!(order, [preview, export, later_export, batch]);
+}
+
+#[test]
+fn paused_hands_out_previews_only() {
+    let queue = Queue::default();
+    let export = push(&queue, JobKind::Export);
+    queue.pause();
+    let preview = push(&queue, JobKind::Preview);
+    assert_eq!(queue.next().0.id, preview);
+    queue.finish(preview, Ok(()), None);
+    assert_eq!(state(&queue, export), JobState::Queued);
+    queue.resume();
+    assert_eq!(queue.next().0.id, export);
+}
+
+#[test]
+fn new_preview_replaces_waiting_preview() {
+    let queue = Queue::default();
+    let first = push(&queue, JobKind::Preview);
+    let second = push(&queue, JobKind::Preview);
+    assert_eq!(state(&queue, first), JobState::Cancelled);
+    assert_eq!(queue.next().0.id, second);
+}
+
+#[test]
+fn cancel_queued_and_running() {
+    let queue = Queue::default();
+    let running = push(&queue, JobKind::Export);
+    let queued = push(&queue, JobKind::Export);
+    let (task, _) = queue.next();
+    assert_eq!(task.id, running);
+
+    let info = queue.cancel(queued).expect("The job is in the queue");
+    assert_eq!(info.state, JobState::Cancelled);
+
+    // A running job keeps running until the renderer sees its signal.
+    queue.cancel(running);
+    assert!(matches!(task.signal.check(), Err(Interrupt::Cancelled)));
+    let info = queue
+        .finish(running, Err(Interrupt::Cancelled), None)
+        .expect("The job is in the queue");
+    assert_eq!(info.state, JobState::Cancelled);
+
+    assert!(queue.cancel(u64::MAX).is_none());
+}
+
+// A preview stops a running export, which goes back in the queue and runs
+// again after the preview.
+#[test]
+fn preview_preempts_export() {
+    let queue = Queue::default();
+    let export = push(&queue, JobKind::Export);
+    let (task, _) = queue.next();
+    let preview = push(&queue, JobKind::Preview);
+    assert!(matches!(task.signal.check(), Err(Interrupt::Preempted)));
+    let info = queue
+        .finish(export, Err(Interrupt::Preempted), None)
+        .expect("The job is in the queue");
+    assert_eq!(info.state, JobState::Queued);
+
+    assert_eq!(queue.next().0.id, preview);
+    queue.finish(preview, Ok(()), None);
+    let (task, _) = queue.next();
+    assert_eq!(task.id, export);
+    // It restarts with a fresh signal.
+    assert!(task.signal.check().is_ok());
+}
+
+#[test]
+fn abort_cancels_exports_but_not_previews() {
+    let queue = Queue::default();
+    let export = push(&queue, JobKind::Export);
+    let batch = push(&queue, JobKind::Batch);
+    let preview = push(&queue, JobKind::Preview);
+    let (_, signal) = queue.start_save();
+    queue.abort();
+    assert_eq!(state(&queue, export), JobState::Cancelled);
+    assert_eq!(state(&queue, batch), JobState::Cancelled);
+    assert_eq!(state(&queue, preview), JobState::Queued);
+    assert!(signal.is_cancelled());
+}
+
+// Pending jobs carry the image each was queued with, not the latest one.
+#[test]
+fn pending_keeps_each_source() {
+    let queue = Queue::default();
+    let first = Arc::new("a.png".to_string());
+    let second = Arc::new("b.png".to_string());
+    queue.push(JobKind::Export, RenderOptions::default(), None, first);
+    queue.push(JobKind::Batch, RenderOptions::default(), None, second);
+    let pending = queue.pending(|source: &String| Some(source.clone()));
+    let images: Vec<_> = pending
+        .iter()
+        .map(|job| job.image_path.as_deref())
+        .collect();
+    assert_eq!(images, [Some("a.png"), Some("b.png")]);
+}
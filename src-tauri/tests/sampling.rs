@@ -0,0 +1,38 @@
+// Properties of the sampling helpers the styles are built on, for every
+// darkness in [0, 1] and every cell size.
+
+use proptest::prelude::*;
+use rand::{rngs::SmallRng, SeedableRng};
+
+use seg_core::sampling::{bool_vec, filled, halton_seq};
+
+proptest! {
+    #[test]
+    fn halton_points_stay_in_the_cell(
+        width in 1u32..=128,
+        height in 1u32..=128,
+        t in 0.0f32..=1.0,
+        seed: u64,
+    ) {
+        let n = filled((width * height) as usize, t) as u32;
+        let points = halton_seq(width as f32, height as f32, n, seed);
+        prop_assert_eq!(points.len(), n as usize);
+        for p in points {
+            prop_assert!(p.x >= 0.0 && p.x <= (width - 1) as f32);
+            prop_assert!(p.y >= 0.0 && p.y <= (height - 1) as f32);
+        }
+    }
+
+    #[test]
+    fn filled_never_exceeds_the_positions(n in 0usize..=4096, t in any::<f32>()) {
+        prop_assert!(filled(n, t) <= n);
+    }
+
+    #[test]
+    fn bool_vec_sets_k_of_n(n in 0usize..=512, k in 0usize..=1024, seed: u64) {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let flags = bool_vec(n, k, &mut rng);
+        prop_assert_eq!(flags.len(), n);
+        prop_assert_eq!(flags.iter().filter(|set| **set).count(), k.min(n));
+    }
+}
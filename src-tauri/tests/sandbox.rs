@@ -0,0 +1,142 @@
+// Paths sent by the webview are only read or written inside the folders of
+// the sandbox, however they are spelled.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use seg_core::error::Error;
+use seg_core::sandbox::Sandbox;
+
+mod common;
+
+// A fresh folder under the temp dir, with an allowed folder holding an
+// image and a folder beside it that is not allowed.
+fn fixture(name: &str) -> (PathBuf, Sandbox) {
+    let root = common::temp_dir(&format!("sandbox-{}", name));
+    fs::create_dir_all(root.join("allowed")).expect("Could not create the allowed folder");
+    fs::create_dir_all(root.join("outside")).expect("Could not create the outside folder");
+    fs::write(root.join("allowed/a.png"), b"").expect("Could not create the image");
+    fs::write(root.join("outside/b.png"), b"").expect("Could not create the image");
+    let sandbox = Sandbox::new([root.join("allowed")]);
+    (root, sandbox)
+}
+
+fn text(path: &Path) -> &str {
+    path.to_str().expect("The temp dir is not valid unicode")
+}
+
+#[test]
+fn read_inside_is_allowed() {
+    let (root, sandbox) = fixture("read-inside");
+    let path = root.join("allowed/a.png");
+    assert_eq!(
+        sandbox.check_read(text(&path)),
+        Ok(path.canonicalize().unwrap())
+    );
+}
+
+#[test]
+fn read_outside_is_not_allowed() {
+    let (root, sandbox) = fixture("read-outside");
+    let path = root.join("outside/b.png");
+    assert!(matches!(
+        sandbox.check_read(text(&path)),
+        Err(Error::NotAllowed(_))
+    ));
+}
+
+#[test]
+fn dot_dot_can_not_leave() {
+    let (root, sandbox) = fixture("dot-dot");
+    let read = root.join("allowed/../outside/b.png");
+    assert!(matches!(
+        sandbox.check_read(text(&read)),
+        Err(Error::NotAllowed(_))
+    ));
+    let write = root.join("allowed/../outside/c.png");
+    assert!(matches!(
+        sandbox.check_write(text(&write)),
+        Err(Error::NotAllowed(_))
+    ));
+    // Below a folder that doesn't exist yet ".." isn't resolved at all.
+    let write = root.join("allowed/new/../../outside/c.png");
+    assert!(matches!(
+        sandbox.check_write(text(&write)),
+        Err(Error::NotAllowed(_))
+    ));
+}
+
+#[test]
+fn dot_dot_within_is_allowed() {
+    let (root, sandbox) = fixture("dot-dot-within");
+    let path = root.join("allowed/../allowed/a.png");
+    assert_eq!(
+        sandbox.check_read(text(&path)),
+        Ok(root.join("allowed/a.png").canonicalize().unwrap())
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_followed() {
+    let (root, sandbox) = fixture("symlinks");
+    std::os::unix::fs::symlink(root.join("outside"), root.join("allowed/link"))
+        .expect("Could not create the symlink");
+    let read = root.join("allowed/link/b.png");
+    assert!(matches!(
+        sandbox.check_read(text(&read)),
+        Err(Error::NotAllowed(_))
+    ));
+    let write = root.join("allowed/link/c.png");
+    assert!(matches!(
+        sandbox.check_write(text(&write)),
+        Err(Error::NotAllowed(_))
+    ));
+}
+
+#[test]
+fn relative_paths_are_not_allowed() {
+    let (_, sandbox) = fixture("relative");
+    // Relative to the folder the tests run in, which isn't allowed.
+    assert!(matches!(
+        sandbox.check_read("tests/sandbox.rs"),
+        Err(Error::NotAllowed(_))
+    ));
+    assert!(matches!(
+        sandbox.check_write("render.png"),
+        Err(Error::NotAllowed(_))
+    ));
+    assert!(matches!(
+        sandbox.check_write("../render.png"),
+        Err(Error::NotAllowed(_))
+    ));
+}
+
+#[test]
+fn write_into_missing_folders_is_allowed_inside() {
+    let (root, sandbox) = fixture("missing-parent");
+    let path = root.join("allowed/new/deeper/c.png");
+    assert_eq!(
+        sandbox.check_write(text(&path)),
+        Ok(root
+            .join("allowed")
+            .canonicalize()
+            .unwrap()
+            .join("new/deeper/c.png"))
+    );
+    let path = root.join("outside/new/c.png");
+    assert!(matches!(
+        sandbox.check_write(text(&path)),
+        Err(Error::NotAllowed(_))
+    ));
+}
+
+#[test]
+fn missing_file_can_not_be_read() {
+    let (root, sandbox) = fixture("missing-file");
+    let path = root.join("allowed/missing.png");
+    assert!(matches!(
+        sandbox.check_read(text(&path)),
+        Err(Error::Open { .. })
+    ));
+}
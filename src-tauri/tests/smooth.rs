@@ -0,0 +1,80 @@
+// Joined lines become curves through the points they can't do without,
+// from the start of the run to its end.
+
+use seg_core::display::Primitive;
+use seg_core::smooth::smooth;
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+
+// Lines through `points` in turn.
+fn polyline(points: &[[f32; 2]], color: [u8; 4]) -> Vec<Primitive> {
+    points
+        .windows(2)
+        .map(|pair| Primitive::Line {
+            from: pair[0],
+            to: pair[1],
+            weight: 1.0,
+            color,
+        })
+        .collect()
+}
+
+#[test]
+fn straight_run_becomes_one_line() {
+    let lines = polyline(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]], BLACK);
+    let smoothed = smooth(lines, 0.1);
+    assert_eq!(smoothed.len(), 1);
+    assert!(matches!(
+        smoothed[0],
+        Primitive::Line { from, to, .. } if from == [0.0, 0.0] && to == [3.0, 0.0]
+    ));
+}
+
+#[test]
+fn bent_run_becomes_a_curve_through_the_corner() {
+    let lines = polyline(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]], BLACK);
+    let smoothed = smooth(lines, 0.1);
+    assert_eq!(smoothed.len(), 1);
+    let Primitive::Curve {
+        start, segments, ..
+    } = &smoothed[0]
+    else {
+        panic!("A bent run is smoothed into a curve");
+    };
+    assert_eq!(*start, [0.0, 0.0]);
+    let ends: Vec<[f32; 2]> = segments.iter().map(|segment| segment[2]).collect();
+    assert_eq!(ends, [[10.0, 0.0], [10.0, 10.0]]);
+}
+
+// Small wobbles within the tolerance are smoothed away, larger ones kept.
+#[test]
+fn tolerance_decides_what_is_kept() {
+    let points = [[0.0, 0.0], [5.0, 0.5], [10.0, 0.0]];
+    assert!(matches!(
+        smooth(polyline(&points, BLACK), 1.0)[0],
+        Primitive::Line { .. }
+    ));
+    assert!(matches!(
+        smooth(polyline(&points, BLACK), 0.1)[0],
+        Primitive::Curve { .. }
+    ));
+}
+
+#[test]
+fn runs_break_at_gaps_colors_and_other_marks() {
+    let mut marks = polyline(&[[0.0, 0.0], [5.0, 5.0], [10.0, 0.0]], BLACK);
+    marks.extend(polyline(&[[10.0, 0.0], [15.0, 5.0]], RED));
+    marks.push(Primitive::Dot {
+        x: 1.0,
+        y: 1.0,
+        color: BLACK,
+    });
+    marks.extend(polyline(&[[20.0, 0.0], [25.0, 5.0]], BLACK));
+    let smoothed = smooth(marks, 0.1);
+    assert_eq!(smoothed.len(), 4);
+    assert!(matches!(smoothed[0], Primitive::Curve { .. }));
+    assert!(matches!(smoothed[1], Primitive::Line { color, .. } if color == RED));
+    assert!(matches!(smoothed[2], Primitive::Dot { .. }));
+    assert!(matches!(smoothed[3], Primitive::Line { from, .. } if from == [20.0, 0.0]));
+}
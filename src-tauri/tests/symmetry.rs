@@ -0,0 +1,124 @@
+// The marks kept by a symmetry are copied so the output looks the same
+// mirrored or turned.
+
+use seg_core::display::Primitive;
+use seg_core::symmetry::{apply, Symmetry};
+
+const WIDTH: u32 = 100;
+const HEIGHT: u32 = 60;
+
+fn circle(x: f32, y: f32) -> Primitive {
+    Primitive::Circle {
+        x,
+        y,
+        radius: 1.0,
+        color: [0, 0, 0, 255],
+    }
+}
+
+// A scatter of marks over the whole output.
+fn marks() -> Vec<Primitive> {
+    [
+        [10.0, 12.0],
+        [30.0, 40.0],
+        [45.0, 5.0],
+        [70.0, 20.0],
+        [85.0, 50.0],
+    ]
+    .into_iter()
+    .map(|[x, y]| circle(x, y))
+    .collect()
+}
+
+// Whether `a` and `b` have the same anchors, in any order.
+fn same_places(a: &[[f32; 2]], b: &[[f32; 2]]) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|p| {
+            b.iter()
+                .any(|q| (p[0] - q[0]).abs() < 1e-3 && (p[1] - q[1]).abs() < 1e-3)
+        })
+}
+
+fn anchors(marks: &[Primitive]) -> Vec<[f32; 2]> {
+    marks.iter().map(Primitive::anchor).collect()
+}
+
+#[test]
+fn vertical_is_mirrored_left_to_right() {
+    let out = anchors(&apply(marks(), WIDTH, HEIGHT, Symmetry::Vertical));
+    // Three marks are on the left half.
+    assert_eq!(out.len(), 6);
+    let mirrored: Vec<_> = out.iter().map(|&[x, y]| [WIDTH as f32 - x, y]).collect();
+    assert!(same_places(&out, &mirrored));
+}
+
+#[test]
+fn horizontal_is_mirrored_top_to_bottom() {
+    let out = anchors(&apply(marks(), WIDTH, HEIGHT, Symmetry::Horizontal));
+    let mirrored: Vec<_> = out.iter().map(|&[x, y]| [x, HEIGHT as f32 - y]).collect();
+    assert!(same_places(&out, &mirrored));
+}
+
+#[test]
+fn both_is_mirrored_both_ways() {
+    let out = anchors(&apply(marks(), WIDTH, HEIGHT, Symmetry::Both));
+    // Only the marks in the top left quarter are kept, four times each.
+    assert_eq!(out.len(), 4 * 2);
+    let flips: [fn([f32; 2]) -> [f32; 2]; 2] = [
+        |[x, y]| [WIDTH as f32 - x, y],
+        |[x, y]| [x, HEIGHT as f32 - y],
+    ];
+    for flip in flips {
+        let mirrored: Vec<_> = out.iter().map(|&p| flip(p)).collect();
+        assert!(same_places(&out, &mirrored));
+    }
+}
+
+#[test]
+fn rotational_is_the_same_turned_a_fold() {
+    let folds = 6;
+    let center = [0.5, 0.5];
+    let (cx, cy) = (WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0);
+    // In the first wedge, clockwise from 3 o'clock.
+    let kept = vec![circle(cx + 20.0, cy + 5.0), circle(cx + 10.0, cy + 1.0)];
+    let out = anchors(&apply(
+        kept,
+        WIDTH,
+        HEIGHT,
+        Symmetry::Rotational {
+            folds,
+            center,
+            mirror: false,
+        },
+    ));
+    assert_eq!(out.len(), 2 * folds as usize);
+    let (sin, cos) = (std::f32::consts::TAU / folds as f32).sin_cos();
+    let turned: Vec<_> = out
+        .iter()
+        .map(|&[x, y]| {
+            let (dx, dy) = (x - cx, y - cy);
+            [cx + dx * cos - dy * sin, cy + dx * sin + dy * cos]
+        })
+        .collect();
+    assert!(same_places(&out, &turned));
+}
+
+// Kaleidoscope copies are flipped every other fold, so the whole is
+// mirrored across 3 o'clock.
+#[test]
+fn kaleidoscope_is_mirrored_across_the_first_edge() {
+    let (cx, cy) = (WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0);
+    let out = anchors(&apply(
+        vec![circle(cx + 20.0, cy + 5.0)],
+        WIDTH,
+        HEIGHT,
+        Symmetry::Rotational {
+            folds: 4,
+            center: [0.5, 0.5],
+            mirror: true,
+        },
+    ));
+    assert_eq!(out.len(), 4);
+    let mirrored: Vec<_> = out.iter().map(|&[x, y]| [x, 2.0 * cy - y]).collect();
+    assert!(same_places(&out, &mirrored));
+}
@@ -0,0 +1,79 @@
+// A tileable output draws the marks that cross an edge across the opposite
+// one too, so tiles meet seamlessly.
+
+use seg_core::display::Primitive;
+use seg_core::tiling::wrap;
+
+const SIZE: u32 = 100;
+
+fn circle(x: f32, y: f32) -> Primitive {
+    Primitive::Circle {
+        x,
+        y,
+        radius: 3.0,
+        color: [0, 0, 0, 255],
+    }
+}
+
+// The anchors of the marks, sorted.
+fn places(marks: &[Primitive]) -> Vec<[f32; 2]> {
+    let mut places: Vec<_> = marks.iter().map(Primitive::anchor).collect();
+    places.sort_by(|a, b| a.partial_cmp(b).expect("The anchors are numbers"));
+    places
+}
+
+#[test]
+fn marks_inside_are_unchanged() {
+    let wrapped = wrap(vec![circle(50.0, 50.0)], SIZE, SIZE);
+    assert_eq!(places(&wrapped), [[50.0, 50.0]]);
+}
+
+#[test]
+fn marks_across_an_edge_come_back_across_the_other() {
+    let wrapped = wrap(vec![circle(99.0, 50.0), circle(40.0, 1.0)], SIZE, SIZE);
+    assert_eq!(
+        places(&wrapped),
+        [[-1.0, 50.0], [40.0, 1.0], [40.0, 101.0], [99.0, 50.0]]
+    );
+}
+
+#[test]
+fn marks_across_a_corner_come_back_at_the_other_three() {
+    let wrapped = wrap(vec![circle(1.0, 98.0)], SIZE, SIZE);
+    assert_eq!(
+        places(&wrapped),
+        [[1.0, -2.0], [1.0, 98.0], [101.0, -2.0], [101.0, 98.0]]
+    );
+}
+
+// Whatever reaches past one edge is drawn as far in from the other, so the
+// right edge of a tile continues into the left edge of the next.
+#[test]
+fn tiles_are_seamless_at_the_edges() {
+    let marks: Vec<Primitive> = (0..=10)
+        .flat_map(|i| {
+            let t = i as f32 * 10.0;
+            [circle(t, 0.0), circle(0.0, t), circle(t, 100.0)]
+        })
+        .collect();
+    let wrapped = wrap(marks, SIZE, SIZE);
+    let size = SIZE as f32;
+    for mark in &wrapped {
+        let [x0, y0, x1, y1] = mark.bounds();
+        if x0 < 0.0 || x1 > size || y0 < 0.0 || y1 > size {
+            let [x, y] = mark.anchor();
+            for (dx, dy) in [(size, 0.0), (-size, 0.0), (0.0, size), (0.0, -size)] {
+                let (x, y) = (x + dx, y + dy);
+                let reaches_in = x + 3.0 > 0.0 && x - 3.0 < size && y + 3.0 > 0.0 && y - 3.0 < size;
+                if reaches_in {
+                    assert!(
+                        wrapped.iter().any(|other| other.anchor() == [x, y]),
+                        "Nothing at {:?} continues the mark at {:?}",
+                        [x, y],
+                        mark.anchor()
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// Vector exports are laid out on paper in millimeters or inches, and the
+// same sheet in either unit puts the drawing in the same place.
+
+use seg_core::display::{DisplayList, Primitive};
+use seg_core::error::{Error, Problem};
+use seg_core::units::{Page, Scale, Sheet, Unit};
+
+const MM_PER_INCH: f32 = 25.4;
+
+fn drawing(width: u32, height: u32, weight: f32) -> DisplayList {
+    DisplayList {
+        width,
+        height,
+        primitives: vec![Primitive::Line {
+            from: [0.0, 0.0],
+            to: [width as f32, height as f32],
+            weight,
+            color: [0, 0, 0, 255],
+        }],
+    }
+}
+
+// An A4 sheet in millimeters.
+fn a4(scale: Scale) -> Sheet {
+    Sheet {
+        unit: Unit::Millimeters,
+        width: 210.0,
+        height: 297.0,
+        margin: 10.0,
+        scale,
+        pen_width: 0.5,
+    }
+}
+
+// The same sheet measured in inches.
+fn in_inches(sheet: Sheet) -> Sheet {
+    Sheet {
+        unit: Unit::Inches,
+        width: sheet.width / MM_PER_INCH,
+        height: sheet.height / MM_PER_INCH,
+        margin: sheet.margin / MM_PER_INCH,
+        scale: match sheet.scale {
+            Scale::Fixed { per_pixel } => Scale::Fixed {
+                per_pixel: per_pixel / MM_PER_INCH,
+            },
+            scale => scale,
+        },
+        pen_width: sheet.pen_width / MM_PER_INCH,
+    }
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+#[test]
+fn units_round_trip() {
+    assert_eq!(Unit::Millimeters.millimeters(), 1.0);
+    assert_eq!(Unit::Inches.millimeters(), MM_PER_INCH);
+    let list = drawing(400, 300, 2.0);
+    for scale in [Scale::Fit, Scale::Fixed { per_pixel: 0.2 }, Scale::Pen] {
+        let mm = Page::new(&list, &a4(scale));
+        let inches = Page::new(&list, &in_inches(a4(scale)));
+        assert!(close(mm.mm_per_pixel(), inches.mm_per_pixel()));
+        for point in [[0.0, 0.0], [400.0, 300.0], [123.0, 45.0]] {
+            let [x, y] = inches.place(point);
+            let [mx, my] = mm.place(point);
+            assert!(close(x * MM_PER_INCH, mx) && close(y * MM_PER_INCH, my));
+        }
+    }
+}
+
+#[test]
+fn fit_is_centered_inside_the_margins() {
+    let page = Page::new(&drawing(400, 300, 1.0), &a4(Scale::Fit));
+    // The width is what limits a landscape drawing on portrait paper.
+    assert!(close(page.scale, 190.0 / 400.0));
+    let [left, top] = page.place([0.0, 0.0]);
+    let [right, bottom] = page.place([400.0, 300.0]);
+    assert!(close(left, 10.0) && close(right, 200.0));
+    assert!(close(top, 297.0 - bottom));
+}
+
+#[test]
+fn pen_scale_draws_the_typical_stroke_as_wide_as_the_pen() {
+    let page = Page::new(&drawing(400, 300, 2.0), &a4(Scale::Pen));
+    assert!(close(page.mm_per_pixel() * 2.0, 0.5));
+}
+
+#[test]
+fn sheets_without_room_are_rejected() {
+    let sheet = Sheet {
+        width: -1.0,
+        margin: 150.0,
+        ..a4(Scale::Fit)
+    };
+    let fields: Vec<(String, Problem)> = match sheet.validate().err() {
+        Some(Error::InvalidOptions(errors)) => errors
+            .into_iter()
+            .map(|err| (err.field, err.problem))
+            .collect(),
+        _ => panic!("A sheet without room passed"),
+    };
+    assert_eq!(
+        fields[0],
+        ("sheet.width".to_string(), Problem::NotPositive(-1.0))
+    );
+    assert_eq!(fields[1].0, "sheet.margin");
+}
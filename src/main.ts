@@ -1,5 +1,6 @@
-import { invoke } from "@tauri-apps/api/tauri";
-import { dialog } from "@tauri-apps/api";
+import { invoke } from "@tauri-apps/api/core";
+import * as dialog from "@tauri-apps/plugin-dialog";
+import { listen } from "@tauri-apps/api/event";
 import GUI from "lil-gui";
 
 interface Picture {
@@ -8,94 +9,1783 @@ interface Picture {
   data: Uint8Array;
 }
 
-const W = 1024;
+// An error from the backend, `text` is in the locale set with set_locale.
+interface Message {
+  code: string;
+  params: Record<string, string>;
+  text: string;
+}
+
+interface JobInfo {
+  id: number;
+  kind: "Preview" | "Export" | "Batch";
+  state: "Queued" | "Running" | "Done" | "Cancelled" | "Failed";
+  path: string | null;
+  error: Message | null;
+}
+
+interface StyleInfo {
+  id: string;
+  name: string;
+  description: string;
+  sample: Picture;
+}
+
+interface Palette {
+  name: string;
+  colors: number[][];
+}
+
+interface SharedPicture {
+  width: number;
+  height: number;
+  path: string;
+}
+
+interface SavedSession {
+  controls: Partial<typeof controls>;
+  image_path: string | null;
+  proof: string | null;
+}
+
+interface RenderComplete {
+  id: number;
+  picture: Picture | null;
+  shared: SharedPicture | null;
+  png: string;
+  thumb: string;
+}
+
+// Width of the previews, from the settings.
+let W = 1024;
 const gui = new GUI();
 
-// Open an image and save it to the global state.
-// Then display it in the main window.
-async function chooseImage() {
+// Open an image and save it to the global state.
+// Then display it in the main window.
+async function chooseImage() {
+  try {
+    // Query the user for the filepath.
+    const file = (await invoke("pick_input_file")) as string | null;
+    if (file === null) return;
+    if (!(await confirmLarge(file))) return;
+
+    // Open and save the image to the global state.
+    try {
+      await openImage(file, true);
+    } catch (error) {
+      // If the image file could not be opened, display an error.
+      displayError(error as Error);
+    }
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// An image being opened, shown from its quick preview if it has one
+// until the full image is decoded.
+type Opening = {
+  id: number;
+  width: number;
+  height: number;
+  preview: Picture | null;
+};
+
+type ImageLoaded = {
+  id: number;
+  loaded: Loaded | null;
+  error: Message | null;
+};
+
+// The image being opened, and whether to pick tracing from its tones.
+let opening: { id: number; detectTones: boolean } | null = null;
+
+async function openImage(path: string, detectTones: boolean) {
+  const started: Opening = await invoke("get_image", { path });
+  opening = { id: started.id, detectTones };
+  if (started.preview !== null) {
+    const { width, height, data } = started.preview;
+    displayImage(width, height, data);
+  }
+}
+
+// Show the full image once it is decoded.
+listen<ImageLoaded>("image-loaded", (event) => {
+  const { id, loaded, error } = event.payload;
+  if (opening === null || opening.id !== id) return;
+  const { detectTones } = opening;
+  opening = null;
+  if (error !== null) {
+    displayError(error);
+    return;
+  }
+  if (loaded === null) return;
+  // If the image exists show it in the window.
+  displayImage(loaded.width, loaded.height, loaded.data);
+  if (loaded.warning !== null) displayError(loaded.warning);
+  // Scanned line art is traced unless asked otherwise.
+  if (detectTones) {
+    controls.thresholdTrace = loaded.tones === "Bilevel";
+  }
+});
+
+// A base image as loaded, scaled down with a warning when it is larger
+// than the settings allow.
+type Loaded = Picture & {
+  warning: Message | null;
+  tones: "Color" | "Gray" | "Bilevel";
+};
+
+type ImageInfo = {
+  width: number;
+  height: number;
+  format: string;
+  color_type: string | null;
+  file_size: number;
+  exif: { camera: string | null; date: string | null; orientation: number | null } | null;
+  renders: { cell: number; width: number; height: number; bytes: number }[];
+};
+
+// Images above this many megapixels are described before they are loaded.
+const LARGE_IMAGE_MP = 40;
+
+// Whether to go on loading the image at `path`, asking first when it is
+// large enough to be slow.
+async function confirmLarge(path: string) {
+  try {
+    const info: ImageInfo = await invoke("get_image_info", {
+      path,
+      cells: [controls.cellSize],
+    });
+    const mp = (info.width * info.height) / 1e6;
+    if (mp < LARGE_IMAGE_MP) return true;
+    const render = info.renders[0];
+    const lines = [
+      `${info.width} × ${info.height} (${mp.toFixed(0)} MP) ${info.format}, ${info.color_type ?? "unknown color"}`,
+      info.exif?.camera ?? null,
+      info.exif?.date ?? null,
+      `A render at cell size ${render.cell} is ${render.width} × ${render.height}, ${(render.bytes / 1e9).toFixed(1)} GB.`,
+      "Load it anyway?",
+    ];
+    return await dialog.ask(lines.filter((l) => l !== null).join("\n"), {
+      title: "Large Image",
+    });
+  } catch (error) {
+    // Let get_image report what is wrong with the file.
+    return true;
+  }
+}
+
+// The render options sent with every job.
+function renderOptions() {
+  return {
+    cell: controls.cellSize,
+    style: controls.style,
+    seed: controls.seed,
+    multi: {
+      density: {
+        red: controls.redDensity,
+        orange: controls.orangeDensity,
+        yellow: controls.yellowDensity,
+        green: controls.greenDensity,
+        blue: controls.blueDensity,
+        purple: controls.purpleDensity,
+      },
+    },
+    tonal: {
+      shadows: controls.shadowStyle,
+      midtones: controls.midtoneStyle,
+      highlights: controls.highlightStyle,
+      shadows_below: controls.shadowsBelow,
+      highlights_above: controls.highlightsAbove,
+    },
+    patches: {
+      scale: controls.patchScale,
+      octaves: controls.patchOctaves,
+      styles: patchStyles(),
+    },
+    layout: layoutOptions(),
+    jitter: controls.jitter,
+    invert_output: controls.invertOutput,
+    debug_overlay: controls.debugOverlay,
+    shared_preview: controls.sharedPreview,
+    transparent: controls.transparent,
+    quadtree: controls.quadtree
+      ? {
+          max_block: controls.maxBlock,
+          min_block: controls.minBlock,
+          threshold: controls.detailThreshold,
+        }
+      : null,
+    trim:
+      controls.trim === "None"
+        ? null
+        : { [controls.trim]: { padding: controls.trimPadding } },
+    dot_shape:
+      controls.dotShape === "Star"
+        ? { Star: { points: controls.starPoints } }
+        : controls.dotShape,
+    dot_fill:
+      controls.dotFill === "Bleed"
+        ? { Bleed: { bleed: controls.dotBleed } }
+        : controls.dotFill,
+    dot_rotation: controls.dotRotation,
+    hatch_direction: controls.hatchDirection,
+    hand_drawn: controls.handDrawn
+      ? { amplitude: controls.wobbleAmplitude, frequency: controls.wobbleFrequency }
+      : null,
+    stroke:
+      controls.strokeWeight === 1 && !controls.compensateStroke
+        ? null
+        : { weight: controls.strokeWeight, compensate: controls.compensateStroke },
+    paper: paperOptions(),
+    gradient_map: gradientMapOptions(),
+    blend: blendOptions(),
+    layers,
+    underlay_source: controls.underlay,
+    auto_mask: controls.autoMask ? controls.maskThreshold : null,
+    threshold_trace: controls.thresholdTrace ? controls.traceLevel : null,
+    antialias: controls.antialias,
+    one_bit: controls.oneBit ? controls.oneBitLevel : null,
+    supersample: controls.supersample,
+    symmetry: symmetryOptions(),
+    tileable: controls.tileable,
+    cell_map: cellMapOptions(),
+    tone_curve: controls.calibrate ? toneCurve : null,
+    cmy: controls.cmy
+      ? {
+          cyan: controls.cyanDensity,
+          magenta: controls.magentaDensity,
+          yellow: controls.yellowDensity,
+        }
+      : null,
+    regions,
+    segments: controls.superpixels
+      ? { count: controls.segmentCount, compactness: controls.compactness }
+      : null,
+    depth: depthOptions(),
+    faces: controls.faceDetail
+      ? { density: controls.faceDensity, background_cell: controls.faceBackground }
+      : null,
+    source_blend:
+      controls.sourceOpacity === 0
+        ? null
+        : { mode: controls.sourceMode, opacity: controls.sourceOpacity },
+    effects:
+      controls.vignette === 0 && controls.grain === 0 && controls.blur === 0
+        ? null
+        : {
+            vignette: controls.vignette,
+            grain: controls.grain,
+            blur: controls.blur,
+          },
+    border:
+      controls.borderMargin === 0
+        ? null
+        : {
+            margin: controls.borderMargin,
+            color: controls.borderColor,
+            double_rule: controls.doubleRule,
+            corner_radius: controls.cornerRadius,
+          },
+    aspect: aspectOptions(),
+    caption: captionOptions(),
+    stamp: controls.stamp
+      ? {
+          content:
+            controls.stampUrl === "" ? "Parameters" : { Url: controls.stampUrl },
+          corner: controls.stampCorner,
+          module: controls.stampModule,
+          margin: controls.stampMargin,
+        }
+      : null,
+  };
+}
+
+// The caption to write under the render, if any part of it is set.
+function captionOptions() {
+  const { captionTitle, captionDate, captionEdition } = controls;
+  if (!captionTitle && !captionDate && !captionEdition) return null;
+  return {
+    title: captionTitle,
+    date: captionDate || null,
+    edition: captionEdition || null,
+    font: controls.captionFont || null,
+    size: controls.captionSize,
+    color: controls.captionColor,
+    background: controls.captionBackground,
+    align: controls.captionAlign,
+  };
+}
+
+// The poster shape to place the render on, if any.
+function aspectOptions() {
+  if (controls.aspect === "Image") return null;
+  const [width, height] = controls.aspect.split(":").map(Number);
+  const placement =
+    controls.placement === "Center"
+      ? "Center"
+      : {
+          Thirds: {
+            column: controls.placementColumn,
+            row: controls.placementRow,
+          },
+        };
+  const background =
+    controls.backdrop === "Color"
+      ? { Color: controls.backdropColor }
+      : controls.backdrop === "Blur"
+        ? { Blur: { sigma: controls.backdropBlur } }
+        : "Transparent";
+  return { ratio: [width, height], placement, background };
+}
+
+function layoutOptions() {
+  switch (controls.layout) {
+    case "Polar":
+      return { Polar: { center: [controls.centerX, controls.centerY] } };
+    case "Brick":
+      return { Brick: { columns: controls.brickColumns } };
+    case "Screen":
+      return { Screen: { angle: controls.screenAngle } };
+    default:
+      return controls.layout;
+  }
+}
+
+function gradientMapOptions() {
+  if (!controls.gradientMap) return null;
+  const palette = palettes.find((p) => p.name === controls.palette);
+  if (palette !== undefined) {
+    // Spread the palette evenly from dark to light.
+    const last = Math.max(palette.colors.length - 1, 1);
+    return {
+      stops: palette.colors.map((color, i) => ({ at: i / last, color })),
+    };
+  }
+  const stops = [
+    { at: 0, color: controls.gradientDark },
+    { at: 1, color: controls.gradientLight },
+  ];
+  if (controls.gradientAccent) {
+    stops.push({ at: controls.accentAt, color: controls.accentColor });
+  }
+  return { stops };
+}
+
+function blendOptions() {
+  if (!controls.secondaryLoaded) return null;
+  switch (controls.blend) {
+    case "Mix":
+      return { Mix: { amount: controls.blendAmount } };
+    case "Hue":
+      return "Hue";
+    default:
+      return null;
+  }
+}
+
+function paperOptions() {
+  switch (controls.paper) {
+    case "Fiber":
+      return { texture: "Fiber", intensity: controls.paperIntensity };
+    case "Tile":
+      if (controls.paperTile === "") return null;
+      return {
+        texture: { Tile: { path: controls.paperTile } },
+        intensity: controls.paperIntensity,
+      };
+    default:
+      return null;
+  }
+}
+
+// Ask for an image to tile as the paper texture.
+async function choosePaperTile() {
+  try {
+    const file = (await invoke("pick_input_file")) as string | null;
+    if (file !== null) controls.paperTile = file;
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// Use a synthetic test pattern as the base image.
+async function loadTestPattern() {
+  try {
+    const kind =
+      controls.testPattern === "StepWedge"
+        ? { StepWedge: { steps: controls.wedgeSteps } }
+        : controls.testPattern;
+    const picture: Picture = await invoke("load_test_pattern", {
+      kind,
+      size: controls.patternSize,
+    });
+    displayImage(picture.width, picture.height, picture.data);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// The tone curve measured from a printed step wedge, sent with the render
+// options while calibration is on.
+let toneCurve: { points: number[][] } | null = null;
+
+// Measure a scan or photo of the printed step wedge, cropped to the
+// wedge, to correct the tones of later renders for the printer.
+async function measureTone() {
+  try {
+    const file = (await invoke("pick_input_file")) as string | null;
+    if (file === null) return;
+    toneCurve = await invoke("measure_tone", {
+      path: file,
+      steps: controls.wedgeSteps,
+    });
+    controls.calibrate = true;
+    gui.controllersRecursive().forEach((c) => c.updateDisplay());
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Palettes offered for the gradient map, filled in from the backend.
+let palettes: Palette[] = [];
+let styles: StyleInfo[] = [];
+const styleSample = document.createElement("canvas");
+styleSample.className = "style-sample";
+
+// Draw the sample of the chosen style next to the style picker.
+function showStyleSample() {
+  const style = styles.find((s) => s.id === controls.style);
+  if (style === undefined) return;
+  const { width, height, data } = style.sample;
+  styleSample.width = width;
+  styleSample.height = height;
+  styleSample.title = style.description;
+  styleSample
+    .getContext("2d")
+    ?.putImageData(
+      new ImageData(new Uint8ClampedArray(data), width, height),
+      0,
+      0,
+    );
+}
+
+// Fill the style picker with the styles the backend has.
+type UsageStats = {
+  renders: number;
+  styles: Record<string, number>;
+  layouts: Record<string, number>;
+  cells: Record<string, number>;
+};
+
+async function loadStyles() {
+  try {
+    styles = await invoke("list_styles");
+    // Styles the user renders most come first, when usage is counted.
+    const usage: UsageStats = await invoke("get_usage_stats");
+    const uses = (s: StyleInfo) => usage.styles[s.id] ?? 0;
+    styles = [...styles].sort((a, b) => uses(b) - uses(a));
+    styleController = styleController
+      .options(Object.fromEntries(styles.map((s) => [s.name, s.id])))
+      .name("Style")
+      .onChange(showStyleSample);
+    styleController.domElement.appendChild(styleSample);
+    showStyleSample();
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+
+async function loadPalettes() {
+  try {
+    palettes = await invoke("list_palettes");
+    paletteController = paletteController.options([
+      "Custom",
+      ...palettes.map((p) => p.name),
+    ]);
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// Pick a palette from the seed, then bump the seed for next time.
+async function randomPalette() {
+  try {
+    const palette: Palette = await invoke("random_palette", {
+      seed: controls.paletteSeed,
+    });
+    controls.palette = palette.name;
+    controls.paletteSeed += 1;
+    gui.controllersRecursive().forEach((c) => c.updateDisplay());
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// Take a palette from the colors of the base image and select it.
+async function extractPalette() {
+  try {
+    const palette: Palette = await invoke("extract_palette", {
+      n: controls.extractCount,
+    });
+    palettes = [...palettes.filter((p) => p.name !== palette.name), palette];
+    paletteController = paletteController.options([
+      "Custom",
+      ...palettes.map((p) => p.name),
+    ]);
+    controls.palette = palette.name;
+    paletteController.updateDisplay();
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// Open a second image to blend with the base image.
+async function chooseSecondaryImage() {
+  try {
+    const file = (await invoke("pick_input_file")) as string | null;
+    if (file === null) return;
+    await invoke("load_secondary_image", { path: file });
+    controls.secondaryLoaded = true;
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// The id of the latest preview, older previews are ignored.
+let previewJob = -1;
+
+async function generate() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before generating"));
+      return;
+    }
+    // Queue the contamination algorithm on the input image, the result
+    // arrives with a "render-complete" event.
+    previewJob = await invoke("enqueue_render", {
+      options: renderOptions(),
+      kind: "Preview",
+    });
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+// Save the image as a png. The image size will match the
+// original input image.
+async function save() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before saving"));
+      return;
+    }
+    const file = (await invoke("pick_save_path", {
+      // JPEG has no alpha channel to keep a transparent background in.
+      transparent: controls.transparent,
+    })) as string | null;
+    if (file === null) return;
+    await invoke("enqueue_render", {
+      options: renderOptions(),
+      kind: "Export",
+      path: file,
+    });
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// Save the image and a 1024 pixel wide JPEG preview of it beside it, for
+// galleries and the web, from a single render.
+async function saveWithPreview() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before saving"));
+      return;
+    }
+    const file = (await invoke("pick_save_path", {
+      transparent: controls.transparent,
+    })) as string | null;
+    if (file === null) return;
+    const saved: { path: string; preview: string } = await invoke(
+      "save_with_preview",
+      { path: file, options: renderOptions() },
+    );
+    lastExport = saved.path;
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Render into a folder, naming the file from the template so variants of
+// one image do not overwrite each other.
+async function exportToFolder() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before saving"));
+      return;
+    }
+    const folder = (await dialog.open({
+      multiple: false,
+      directory: true,
+    })) as string;
+    if (folder === null) return;
+    await invoke("enqueue_render", {
+      options: renderOptions(),
+      kind: "Export",
+      path: folder,
+      template: controls.fileTemplate,
+    });
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Save the marks as vectors: a list of shapes for p5.js, Blender or CNC
+// tools, or a drawing as SVG, PDF, G-code or DXF.
+async function saveMarks() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before saving"));
+      return;
+    }
+    const path = (await dialog.save({
+      defaultPath: "seg.svg",
+      filters: [
+        { name: "Drawing", extensions: ["svg", "pdf", "gcode", "dxf"] },
+        { name: "Marks", extensions: ["json", "csv"] },
+      ],
+    })) as string | null;
+    if (path === null) return;
+    const report: SaveReport = await invoke("save_marks", {
+      path,
+      options: renderOptions(),
+      optimize: optimizeOptions(),
+      sheet: sheetOptions(),
+      speed: plotSpeed(),
+    });
+    showPlotTime([report]);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// The paper vector drawings are laid out on, in physical units.
+function sheetOptions() {
+  if (controls.paperUnit === "Pixels") return null;
+  const scale =
+    controls.paperScale === "Fixed"
+      ? { Fixed: { per_pixel: controls.pixelSize } }
+      : controls.paperScale;
+  return {
+    unit: controls.paperUnit,
+    width: controls.paperWidth,
+    height: controls.paperHeight,
+    margin: controls.paperMargin,
+    scale,
+    pen_width: controls.penWidth,
+  };
+}
+
+type SaveReport = {
+  path: string;
+  plot: { draw: number; travel: number; lifts: number; seconds: number };
+};
+
+// How fast the plotter draws, moves and lifts its pen.
+function plotSpeed() {
+  return {
+    draw: controls.drawFeed,
+    travel: controls.travelFeed,
+    lift: controls.penLift,
+  };
+}
+
+// Show how long the saved files would take to plot, all together.
+function showPlotTime(reports: SaveReport[]) {
+  const seconds = reports.reduce((sum, r) => sum + r.plot.seconds, 0);
+  const draw = reports.reduce((sum, r) => sum + r.plot.draw, 0);
+  controls.plotTime = `${(seconds / 60).toFixed(1)} min, ${(draw / 1000).toFixed(1)} m drawn`;
+}
+
+// How vector exports are tidied up for a plotter.
+function optimizeOptions() {
+  if (!controls.mergeLines && !controls.orderPaths && !controls.smoothPaths)
+    return null;
+  return {
+    merge: controls.mergeLines,
+    order: controls.orderPaths,
+    two_opt: controls.orderPaths && controls.twoOpt,
+    smooth: controls.smoothPaths ? controls.smoothTolerance : null,
+  };
+}
+
+// Save a risograph separation, a master per drum and a preview of the
+// print, from the drums set up in the Riso folder.
+async function saveRiso() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before saving"));
+      return;
+    }
+    const path = (await dialog.save({
+      defaultPath: "seg.png",
+      filters: [{ name: "Image", extensions: ["png"] }],
+    })) as string | null;
+    if (path === null) return;
+    const drums = RISO_DRUMS.slice(0, controls.risoDrums);
+    const layers = drums.map((drum, i) => {
+      // Each drum lands a little further off register than the last.
+      const off = Math.round(i * controls.risoMisregister);
+      return {
+        name: drum.name,
+        ink: controls[drum.ink],
+        channel: controls[drum.channel],
+        style: controls[drum.style],
+        density: controls[drum.density],
+        offset: [off, Math.round(off / 2)],
+      };
+    });
+    await invoke("save_riso", {
+      path,
+      options: renderOptions(),
+      riso: { layers, grain: controls.risoGrain },
+    });
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// The controls of each risograph drum, bottom first.
+type Control = keyof typeof controls;
+const RISO_DRUMS = [1, 2, 3].map((n) => ({
+  name: `drum${n}`,
+  ink: `riso${n}Ink` as Control,
+  channel: `riso${n}Channel` as Control,
+  style: `riso${n}Style` as Control,
+  density: `riso${n}Density` as Control,
+}));
+
+type CoverageStats = {
+  total: number;
+  regions: { color: number[]; coverage: number; area: number }[];
+};
+
+// Show how much of the render is ink, to judge pen wear before a plot.
+async function showCoverage() {
+  try {
+    const stats: CoverageStats = await invoke("get_coverage_stats", {
+      options: renderOptions(),
+    });
+    const regions = stats.regions.map(
+      (r) => `rgb(${r.color.join(",")}) ${r.coverage.toFixed(1)}%`,
+    );
+    controls.inkCoverage = [`${stats.total.toFixed(1)}%`, ...regions].join(", ");
+  } catch (error) {
+    console.error(`Error: ${error}`);
+  }
+}
+
+// Save one vector file per plotter pen, split by ink color or by tone.
+async function savePenLayers() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before saving"));
+      return;
+    }
+    const path = (await dialog.save({
+      defaultPath: "seg.svg",
+      filters: [{ name: "Drawing", extensions: ["svg", "pdf", "gcode", "dxf"] }],
+    })) as string | null;
+    if (path === null) return;
+    const split =
+      controls.penSplit === "Color" ? "Color" : { Luminance: { pens: controls.pens } };
+    const reports: SaveReport[] = await invoke("save_pen_layers", {
+      path,
+      options: renderOptions(),
+      split,
+      optimize: optimizeOptions(),
+      sheet: sheetOptions(),
+      speed: plotSpeed(),
+    });
+    showPlotTime(reports);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Cycle through the renders of every image in a folder, full screen.
+async function startSlideshow() {
+  try {
+    const folder = (await dialog.open({
+      multiple: false,
+      directory: true,
+    })) as string;
+    if (folder === null) return;
+    await invoke("start_slideshow", {
+      folder,
+      interval: controls.slideInterval,
+      options: renderOptions(),
+      randomize: controls.slideRandomize,
+    });
+    document.getElementById("slideshow")!.style.display = "block";
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function stopSlideshow() {
+  document.getElementById("slideshow")!.style.display = "none";
+  await invoke("stop_slideshow");
+}
+
+// Render every image dropped into a folder with the current settings,
+// saving the renders into another, until stopped.
+async function startHotFolder() {
+  try {
+    const input = (await dialog.open({
+      multiple: false,
+      directory: true,
+      title: "Hot Folder",
+    })) as string | null;
+    if (input === null) return;
+    const output = (await dialog.open({
+      multiple: false,
+      directory: true,
+      title: "Output Folder",
+    })) as string | null;
+    if (output === null) return;
+    await invoke("start_hot_folder", {
+      input,
+      output,
+      options: renderOptions(),
+    });
+    controls.hotFolderStatus = `Watching ${input}`;
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function stopHotFolder() {
+  await invoke("stop_hot_folder");
+  controls.hotFolderStatus = "";
+}
+
+listen<{ source: string; output: string | null; error: Message | null }>(
+  "hot-folder",
+  (event) => {
+    const { output, error } = event.payload;
+    if (error !== null) displayError(error);
+    if (output !== null) controls.hotFolderStatus = `Saved ${output}`;
+  },
+);
+
+// Styles drawn over each other in place of the style control.
+interface Layer {
+  name: string;
+  style: string;
+  enabled: boolean;
+  solo: boolean;
+  mode: string;
+  opacity: number;
+}
+
+const BLEND_MODES = [
+  "Multiply",
+  "Screen",
+  "Overlay",
+  "Darken",
+  "Lighten",
+  "Difference",
+];
+const layers: Layer[] = [];
+
+function addLayer() {
+  const i = layers.length;
+  const layer = {
+    name: `Layer ${i + 1}`,
+    style: controls.style,
+    enabled: true,
+    solo: false,
+    mode: "Darken",
+    opacity: 1,
+  };
+  layers.push(layer);
+  const folder = layersFolder.addFolder(layer.name);
+  folder
+    .add(layer, "name")
+    .name("Name")
+    .onChange((name: string) => folder.title(name));
+  folder
+    .add(layer, "style", Object.fromEntries(styles.map((s) => [s.name, s.id])))
+    .name("Style");
+  folder.add(layer, "enabled").name("Enabled");
+  folder.add(layer, "solo").name("Solo");
+  folder.add(layer, "mode", BLEND_MODES).name("Blend Mode");
+  folder.add(layer, "opacity", 0, 1, 0.01).name("Opacity");
+  folder.add({ renderAlone: () => renderLayer(i) }, "renderAlone").name("Render Alone");
+}
+
+// Show what one layer contributes without touching the stack.
+async function renderLayer(i: number) {
+  try {
+    const picture: Picture = await invoke("render_layer", {
+      i,
+      options: renderOptions(),
+    });
+    displayImage(picture.width, picture.height, picture.data);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// A density mask painted over the image. White draws the marks at full
+// darkness, black leaves them out.
+const maskCanvas = document.getElementById("mask") as HTMLCanvasElement;
+// Density multipliers painted over the image, a gray level of 255 is
+// MAX_BOOST times the darkness of the source.
+const densityCanvas = document.getElementById("density") as HTMLCanvasElement;
+const MAX_BOOST = 4;
+// Regions painted dark are drawn with coarser cells.
+const cellsCanvas = document.getElementById("cells") as HTMLCanvasElement;
+// Width of the cell size map sent with each render.
+const CELL_MAP_WIDTH = 64;
+const overlays = [maskCanvas, densityCanvas, cellsCanvas];
+let cellsPainted = false;
+let painting = false;
+
+// The overlay the brush paints on.
+function paintTarget() {
+  switch (controls.brushTarget) {
+    case "Mask":
+      return maskCanvas;
+    case "Density":
+      return densityCanvas;
+    default:
+      return cellsCanvas;
+  }
+}
+
+function showMask() {
+  const canvas = document.querySelector("canvas") as HTMLCanvasElement;
+  for (const overlay of overlays) {
+    if (overlay.width !== canvas.width || overlay.height !== canvas.height) {
+      overlay.width = canvas.width;
+      overlay.height = canvas.height;
+      resetOverlay(overlay);
+    }
+    overlay.style.display =
+      controls.paintMask && overlay === paintTarget() ? "block" : "none";
+  }
+}
+
+// White for a mask or cell sizes, no change for a density map.
+function resetOverlay(overlay: HTMLCanvasElement) {
+  const ctx = overlay.getContext("2d")!;
+  const level = overlay === densityCanvas ? Math.round(255 / MAX_BOOST) : 255;
+  ctx.fillStyle = `rgb(${level}, ${level}, ${level})`;
+  ctx.fillRect(0, 0, overlay.width, overlay.height);
+}
+
+function paintMask(event: PointerEvent) {
+  if (!painting) return;
+  const overlay = paintTarget();
+  const ctx = overlay.getContext("2d")!;
+  const rect = overlay.getBoundingClientRect();
+  const x = ((event.clientX - rect.left) * overlay.width) / rect.width;
+  const y = ((event.clientY - rect.top) * overlay.height) / rect.height;
+  let level = 0;
+  if (overlay === maskCanvas) {
+    level = Math.round(controls.brushDensity * 255);
+  } else if (overlay === densityCanvas) {
+    level = Math.round((controls.brushBoost / MAX_BOOST) * 255);
+  } else {
+    cellsPainted = true;
+  }
+  ctx.fillStyle = `rgb(${level}, ${level}, ${level})`;
+  ctx.beginPath();
+  ctx.arc(x, y, controls.brushSize, 0, 2 * Math.PI);
+  ctx.fill();
+}
+
+for (const overlay of overlays) {
+  overlay.addEventListener("pointerdown", (event) => {
+    painting = true;
+    paintMask(event);
+  });
+  overlay.addEventListener("pointermove", paintMask);
+}
+window.addEventListener("pointerup", () => (painting = false));
+
+// The red channel of an overlay, one value per pixel.
+function overlayLevels(overlay: HTMLCanvasElement) {
+  const { width, height } = overlay;
+  const pixels = overlay.getContext("2d")!.getImageData(0, 0, width, height).data;
+  return Array.from({ length: width * height }, (_, i) => pixels[4 * i]);
+}
+
+// Send the painted mask, one byte per pixel, and render with it.
+async function applyMask() {
+  const { width, height } = maskCanvas;
+  try {
+    await invoke("set_mask", { width, height, data: overlayLevels(maskCanvas) });
+    generate();
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function clearMask() {
+  resetOverlay(maskCanvas);
+  await invoke("clear_mask");
+  generate();
+}
+
+// The painted cell regions as a coarse cell size map, dark regions get
+// cells `coarseCells` times the cell size.
+function cellMapOptions() {
+  if (!cellsPainted) return null;
+  const width = CELL_MAP_WIDTH;
+  const height = Math.max(1, Math.round((width * cellsCanvas.height) / cellsCanvas.width));
+  const small = document.createElement("canvas");
+  small.width = width;
+  small.height = height;
+  const ctx = small.getContext("2d")!;
+  ctx.drawImage(cellsCanvas, 0, 0, width, height);
+  const pixels = ctx.getImageData(0, 0, width, height).data;
+  const sizes = Array.from({ length: width * height }, (_, i) =>
+    pixels[4 * i] < 128 ? controls.coarseCells : 1,
+  );
+  return { width, height, sizes };
+}
+
+function clearCells() {
+  resetOverlay(cellsCanvas);
+  cellsPainted = false;
+  generate();
+}
+
+async function applyDensity() {
+  const { width, height } = densityCanvas;
+  const data = overlayLevels(densityCanvas).map((level) => (level / 255) * MAX_BOOST);
+  try {
+    await invoke("set_density_map", { width, height, data });
+    generate();
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function clearDensity() {
+  resetOverlay(densityCanvas);
+  await invoke("clear_density_map");
+  generate();
+}
+
+// Styles for the regions of a label mask, one per label color.
+interface Region {
+  color: [number, number, number];
+  style: string;
+  density: number;
+}
+let regions: Region[] = [];
+let regionFolders: GUI[] = [];
+
+async function loadLabelMask() {
   try {
-    // Query the user for the filepath.
-    const file = (await dialog.open({
-      multiple: false,
-      directory: false,
-      filters: [
-        {
-          name: "Images",
-          extensions: ["png", "jpeg", "jpg", "tiff", "webp"],
+    const path = (await invoke("pick_input_file")) as string | null;
+    if (path === null) return;
+    const colors: [number, number, number][] = await invoke("load_label_mask", { path });
+    regionFolders.forEach((folder) => folder.destroy());
+    regions = colors.map((color) => ({ color, style: controls.style, density: 1 }));
+    regionFolders = regions.map((region) => {
+      const folder = regionsFolder.addFolder(`rgb(${region.color.join(", ")})`);
+      folder.domElement.style.borderLeft = `6px solid rgb(${region.color.join(", ")})`;
+      folder
+        .add(region, "style", Object.fromEntries(styles.map((s) => [s.name, s.id])))
+        .name("Style");
+      folder.add(region, "density", 0, 3, 0.05).name("Density");
+      return folder;
+    });
+    generate();
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function loadDepthMap() {
+  try {
+    const path = (await invoke("pick_input_file")) as string | null;
+    if (path === null) return;
+    await invoke("set_depth_map", { path });
+    controls.byDepth = true;
+    gui.controllersRecursive().forEach((c) => c.updateDisplay());
+    generate();
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function clearDepthMap() {
+  controls.byDepth = false;
+  gui.controllersRecursive().forEach((c) => c.updateDisplay());
+  await invoke("clear_depth_map");
+  generate();
+}
+
+// The depth options, "Same" keeps the main style.
+function depthOptions() {
+  if (!controls.byDepth) return null;
+  const style = (s: string) => (s === "Same" ? null : s);
+  return {
+    near_density: controls.nearDensity,
+    far_density: controls.farDensity,
+    near_cell: controls.nearCell,
+    far_cell: controls.farCell,
+    near_style: style(controls.nearStyle),
+    far_style: style(controls.farStyle),
+    split: controls.depthSplit,
+  };
+}
+
+async function clearLabelMask() {
+  regionFolders.forEach((folder) => folder.destroy());
+  regionFolders = [];
+  regions = [];
+  await invoke("clear_label_mask");
+  generate();
+}
+
+// Settings picked by the backend, at random or by evolving.
+interface Variant {
+  options: { cell: number; style: string; seed: number };
+  palette: string | null;
+}
+
+// Set the controls from a variant and render it.
+function applyVariant(variant: Variant) {
+  const { cell, style, seed } = variant.options;
+  Object.assign(controls, { cellSize: cell, style, seed });
+  controls.gradientMap = variant.palette !== null;
+  if (variant.palette !== null) controls.palette = variant.palette;
+  gui.controllersRecursive().forEach((c) => c.updateDisplay());
+  showStyleSample();
+  generate();
+}
+
+// Render with random settings, from any style.
+async function surpriseMe() {
+  try {
+    applyVariant(await invoke("randomize_params"));
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Two looks to scrub between, as render options.
+type Options = ReturnType<typeof renderOptions>;
+const looks: { a: Options | null; b: Options | null } = { a: null, b: null };
+
+// Preview the look `controls.morph` of the way from look A to look B.
+async function morph() {
+  if (looks.a === null || looks.b === null) {
+    displayError(new Error("Keep a look A and a look B to morph between"));
+    return;
+  }
+  try {
+    const options = await invoke("interpolate_params", {
+      a: looks.a,
+      b: looks.b,
+      t: controls.morph,
+    });
+    previewJob = await invoke("enqueue_render", { options, kind: "Preview" });
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// How the marks are repeated, if at all.
+function symmetryOptions() {
+  switch (controls.symmetry) {
+    case "None":
+      return null;
+    case "Rotational":
+      return {
+        Rotational: {
+          folds: controls.folds,
+          center: [controls.symmetryX, controls.symmetryY],
+          mirror: controls.kaleidoscope,
         },
-      ],
-    })) as string;
+      };
+    default:
+      return controls.symmetry;
+  }
+}
 
-    // Open and save the image to the global state.
-    try {
-      const picture: Picture = await invoke("get_image", {
-        path: file,
-      });
-      // If the image exists show it in the window.
-      displayImage(picture.width, picture.height, picture.data);
-    } catch (error) {
-      // If the image file could not be opened, display an error.
-      displayError(error as Error);
+// The styles checked for Patches, Dots if none are.
+function patchStyles() {
+  const checked = ["Dots", "VLines", "HLines", "Cross", "Stipple", "Grid"].filter(
+    (style) => controls[`patch${style}` as keyof typeof controls],
+  );
+  return checked.length > 0 ? checked : ["Dots"];
+}
+
+// The images of a collage and the look each is rendered in.
+const collagePanels: { path: string; options: Options }[] = [];
+
+// Pick the images of a collage, each starting in the current look.
+async function pickCollage() {
+  try {
+    const picked = (await dialog.open({
+      multiple: true,
+      filters: [{ name: "Image", extensions: ["png", "jpg", "jpeg", "tif", "tiff", "webp"] }],
+    })) as string[] | null;
+    if (picked === null) return;
+    collagePanels.length = 0;
+    for (const path of picked) {
+      collagePanels.push({ path, options: renderOptions() });
     }
+    controls.collagePanel = 0;
   } catch (error) {
-    console.error(`Error: ${error}`);
+    displayError(error as Error);
   }
 }
 
-async function generate() {
+// The panels side by side, or stacked for a portrait canvas, with a gap
+// between and around them.
+function collageRects() {
+  const n = collagePanels.length;
+  const { collageWidth: width, collageHeight: height, collageGap: gap } = controls;
+  const across = width >= height;
+  const span = Math.floor(((across ? width : height) - (n + 1) * gap) / n);
+  return collagePanels.map((_, i) => {
+    const start = gap + i * (span + gap);
+    return across
+      ? { x: start, y: gap, width: span, height: height - 2 * gap }
+      : { x: gap, y: start, width: width - 2 * gap, height: span };
+  });
+}
+
+async function renderCollage(save: boolean) {
   try {
-    // Run the contamination algorithm on the input image.
-    const picture: Picture = await invoke("gen_image", {
-      cell: controls.cellSize,
-      style: controls.style,
+    if (collagePanels.length === 0) {
+      displayError(new Error("Pick the images of the collage first"));
+      return;
+    }
+    const path = save
+      ? ((await dialog.save({
+          defaultPath: "collage.png",
+          filters: [{ name: "Image", extensions: ["png", "jpg"] }],
+        })) as string | null)
+      : null;
+    if (save && path === null) return;
+    const rects = collageRects();
+    const picture: Picture = await invoke("render_collage", {
+      collage: {
+        width: controls.collageWidth,
+        height: controls.collageHeight,
+        background: controls.collageBackground,
+        panels: collagePanels.map((panel, i) => ({ ...panel, rect: rects[i] })),
+      },
+      path,
     });
-    // Show the contaminated image in the window.
     displayImage(picture.width, picture.height, picture.data);
   } catch (error) {
-    console.error(`Error: ${error}`);
+    displayError(error as Error);
   }
 }
-// Save the image as a png. The image size will match the
-// original input image.
-async function save() {
+
+// Show the latest render as it looks with a color vision deficiency.
+async function simulateCvd() {
   try {
-    const file = (await dialog.save({
-      defaultPath: "seg.png",
-      filters: [
-        {
-          name: "PNG",
-          extensions: ["png", "jpeg", "jpg"],
-        },
-      ],
-    })) as string;
-    await invoke("save_image", {
-      cell: controls.cellSize,
-      style: controls.style,
-      path: file,
+    const picture: Picture = await invoke("simulate_cvd", {
+      kind: controls.cvdKind,
     });
+    displayImage(picture.width, picture.height, picture.data);
   } catch (error) {
-    console.error(`Error: ${error}`);
+    displayError(error as Error);
+  }
+}
+
+// Options pinned to compare later renders against.
+let pinnedOptions: ReturnType<typeof renderOptions> | null = null;
+
+type Comparison = { ssim: number; delta_e: number; heat_map: Picture };
+
+// Compare the current settings with the pinned ones, or with the source
+// if none are pinned, and show where they differ.
+async function compareRenders() {
+  try {
+    const comparison: Comparison = await invoke("compare_renders", {
+      a: pinnedOptions === null ? "Source" : { Render: pinnedOptions },
+      b: { Render: renderOptions() },
+      metric: controls.compareMetric,
+    });
+    controls.compareScores = `SSIM ${comparison.ssim.toFixed(3)}, ΔE ${comparison.delta_e.toFixed(1)}`;
+    const { width, height, data } = comparison.heat_map;
+    displayImage(width, height, data);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+type Tuned = {
+  options: ReturnType<typeof renderOptions>;
+  gamma: number;
+  density: number;
+  delta_e: number;
+};
+
+// Pick the cell size and the tone of the marks that draw the photo most
+// faithfully, then render with them. The tone is kept as the tone curve.
+async function autoTune() {
+  try {
+    const tuned: Tuned = await invoke("auto_tune", { options: renderOptions() });
+    controls.cellSize = tuned.options.cell;
+    toneCurve = tuned.options.tone_curve;
+    controls.calibrate = true;
+    controls.tuning = `Gamma ${tuned.gamma}, density ${tuned.density}, ΔE ${tuned.delta_e.toFixed(1)}`;
+    gui.controllersRecursive().forEach((c) => c.updateDisplay());
+    generate();
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Settings a sweep can vary, as paths into the render options.
+const SWEEPABLE = [
+  "cell",
+  "jitter",
+  "stroke.weight",
+  "hand_drawn.amplitude",
+  "threshold_trace",
+  "underlay_source",
+  "auto_mask",
+  "one_bit",
+  "patches.scale",
+];
+
+// Render a strip of variants of one setting, labeled with its values, and
+// save it if a file is picked.
+async function sweep() {
+  try {
+    if (!(await invoke("has_image"))) {
+      displayError(new Error("Choose an image before sweeping"));
+      return;
+    }
+    const path = controls.sweepSave
+      ? ((await dialog.save({
+          defaultPath: "sweep.png",
+          filters: [{ name: "Image", extensions: ["png", "jpg"] }],
+        })) as string | null)
+      : null;
+    const picture: Picture = await invoke("sweep_parameter", {
+      name: controls.sweepName,
+      from: controls.sweepFrom,
+      to: controls.sweepTo,
+      steps: controls.sweepSteps,
+      options: renderOptions(),
+      path,
+    });
+    displayImage(picture.width, picture.height, picture.data);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+interface Candidate {
+  id: number;
+  variant: Variant;
+  png: string;
+}
+
+// Show a generation of candidates. Clicking one marks it as a favorite to
+// breed from, double clicking uses its settings.
+function showCandidates(candidates: Candidate[]) {
+  const strip = document.getElementById("evolution")!;
+  strip.replaceChildren(
+    ...candidates.map((candidate) => {
+      const img = document.createElement("img");
+      img.src = SEG_PROTOCOL + candidate.png;
+      img.dataset.id = String(candidate.id);
+      img.title = `${candidate.variant.options.style}, cell ${candidate.variant.options.cell}`;
+      img.addEventListener("click", () => img.classList.toggle("favorite"));
+      img.addEventListener("dblclick", () => applyVariant(candidate.variant));
+      return img;
+    }),
+  );
+}
+
+async function newPopulation() {
+  try {
+    showCandidates(await invoke("start_evolution", { n: controls.population }));
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+async function nextGeneration() {
+  const favorites = Array.from(
+    document.querySelectorAll<HTMLImageElement>("#evolution img.favorite"),
+  ).map((img) => Number(img.dataset.id));
+  try {
+    showCandidates(
+      await invoke("next_generation", {
+        favoriteIds: favorites,
+        n: controls.population,
+      }),
+    );
+  } catch (error) {
+    displayError(error as Error);
   }
 }
 
 // Controls for the gui, two sliders a picker and 3 buttons.
 let controls = {
   cellSize: 10,
+  seed: 0,
+  locale: "En",
   style: "Dots",
+  invertOutput: false,
+  debugOverlay: false,
+  sharedPreview: false,
+  redDensity: 1,
+  orangeDensity: 1,
+  yellowDensity: 1,
+  greenDensity: 1,
+  blueDensity: 1,
+  purpleDensity: 1,
+  transparent: false,
+  layout: "Grid",
+  brickColumns: false,
+  screenAngle: 45,
+  cmy: false,
+  cyanDensity: 1,
+  magentaDensity: 1,
+  yellowDensity: 1,
+  jitter: 0,
+  centerX: 0.5,
+  centerY: 0.5,
+  quadtree: false,
+  maxBlock: 16,
+  minBlock: 1,
+  detailThreshold: 0.005,
+  trim: "None",
+  trimPadding: 0,
+  fileTemplate: "{name}_{style}_{cell}_{seed}.{ext}",
+  pauseExports: async function () {
+    invoke("pause_queue");
+  },
+  resumeExports: async function () {
+    invoke("resume_queue");
+  },
+  abortExports: async function () {
+    invoke("abort_exports");
+  },
+  exportProgress: "",
+  revealExport: async function () {
+    showLastExport("reveal_file");
+  },
+  openExport: async function () {
+    showLastExport("open_with_default_app");
+  },
+  exportToFolder: async function () {
+    exportToFolder();
+  },
+  saveMarks: async function () {
+    saveMarks();
+  },
+  mergeLines: true,
+  orderPaths: true,
+  twoOpt: false,
+  smoothPaths: false,
+  smoothTolerance: 0.5,
+  paperUnit: "Pixels",
+  paperWidth: 210,
+  paperHeight: 297,
+  paperMargin: 15,
+  paperScale: "Fit",
+  pixelSize: 0.1,
+  penWidth: 0.3,
+  drawFeed: 3000,
+  travelFeed: 6000,
+  penLift: 0.25,
+  plotTime: "",
+  penSplit: "Luminance",
+  pens: 3,
+  savePenLayers: async function () {
+    savePenLayers();
+  },
+  showCoverage: false,
+  inkCoverage: "",
+  risoDrums: 2,
+  riso1Ink: [255, 72, 176],
+  riso1Channel: "Magenta",
+  riso1Style: "Dots",
+  riso1Density: 1,
+  riso2Ink: [0, 120, 191],
+  riso2Channel: "Cyan",
+  riso2Style: "VLines",
+  riso2Density: 1,
+  riso3Ink: [255, 232, 0],
+  riso3Channel: "Yellow",
+  riso3Style: "Stipple",
+  riso3Density: 1,
+  risoMisregister: 2,
+  risoGrain: 0.3,
+  saveRiso: async function () {
+    saveRiso();
+  },
+  dotShape: "Circle",
+  starPoints: 5,
+  dotFill: "Grow",
+  dotBleed: 0.1,
+  dotRotation: "None",
+  hatchDirection: "Fixed",
+  strokeWeight: 1,
+  compensateStroke: true,
+  handDrawn: false,
+  wobbleAmplitude: 1.5,
+  wobbleFrequency: 0.05,
+  paper: "None",
+  paperIntensity: 0.5,
+  paperTile: "",
+  choosePaperTile: async function () {
+    choosePaperTile();
+  },
+  gradientMap: false,
+  palette: "Custom",
+  paletteSeed: 1,
+  randomPalette: async function () {
+    randomPalette();
+  },
+  extractCount: 5,
+  extractPalette: async function () {
+    extractPalette();
+  },
+  gradientDark: [20, 24, 82],
+  gradientLight: [240, 200, 120],
+  gradientAccent: false,
+  accentColor: [220, 40, 60],
+  accentAt: 0.5,
+  blend: "None",
+  blendAmount: 0.5,
+  secondaryLoaded: false,
+  chooseSecondaryImage: async function () {
+    chooseSecondaryImage();
+  },
+  vignette: 0,
+  grain: 0,
+  blur: 0,
+  borderMargin: 0,
+  borderColor: [255, 255, 255],
+  aspect: "Image",
+  placement: "Center",
+  placementColumn: 1,
+  placementRow: 1,
+  backdrop: "Color",
+  backdropColor: [255, 255, 255],
+  backdropBlur: 24,
+  doubleRule: false,
+  cornerRadius: 0,
+  captionTitle: "",
+  captionDate: "",
+  captionEdition: "",
+  captionFont: "",
+  captionSize: 48,
+  captionColor: [0, 0, 0],
+  captionBackground: [255, 255, 255],
+  captionAlign: "Center",
+  stamp: false,
+  stampUrl: "",
+  stampCorner: "BottomRight",
+  stampModule: 4,
+  stampMargin: 16,
   chooseImage: async function () {
     chooseImage();
   },
+  slideInterval: 10,
+  slideRandomize: false,
+  startSlideshow: async function () {
+    startSlideshow();
+  },
+  hotFolderStatus: "",
+  startHotFolder: async function () {
+    startHotFolder();
+  },
+  stopHotFolder: async function () {
+    stopHotFolder();
+  },
+  testPattern: "Gradient",
+  wedgeSteps: 11,
+  patternSize: 256,
+  loadTestPattern: async function () {
+    loadTestPattern();
+  },
+  measureTone: async function () {
+    measureTone();
+  },
+  calibrate: false,
   generate: async function () {
     generate();
   },
+  surpriseMe: async function () {
+    surpriseMe();
+  },
+  underlay: 0,
+  thresholdTrace: false,
+  traceLevel: 0.5,
+  antialias: true,
+  oneBit: false,
+  oneBitLevel: 0.5,
+  supersample: 1,
+  symmetry: "None",
+  folds: 6,
+  kaleidoscope: true,
+  symmetryX: 0.5,
+  symmetryY: 0.5,
+  tileable: false,
+  autoMask: false,
+  maskThreshold: 0.9,
+  paintMask: false,
+  brushTarget: "Mask",
+  brushSize: 24,
+  brushDensity: 0,
+  brushBoost: 1.5,
+  coarseCells: 4,
+  clearCells: function () {
+    clearCells();
+  },
+  applyMask: async function () {
+    applyMask();
+  },
+  clearMask: async function () {
+    clearMask();
+  },
+  applyDensity: async function () {
+    applyDensity();
+  },
+  clearDensity: async function () {
+    clearDensity();
+  },
+  sourceMode: "Multiply",
+  sourceOpacity: 0,
+  byDepth: false,
+  nearDensity: 1,
+  farDensity: 1,
+  nearCell: 1,
+  farCell: 1,
+  nearStyle: "Same",
+  farStyle: "Same",
+  depthSplit: 0.5,
+  loadDepthMap: async function () {
+    loadDepthMap();
+  },
+  clearDepthMap: async function () {
+    clearDepthMap();
+  },
+  faceDetail: false,
+  faceDensity: 1.3,
+  faceBackground: 2,
+  superpixels: false,
+  shadowStyle: "Cross",
+  midtoneStyle: "Stipple",
+  highlightStyle: "Dots",
+  shadowsBelow: 0.33,
+  highlightsAbove: 0.67,
+  patchScale: 64,
+  patchOctaves: 3,
+  patchDots: true,
+  patchVLines: false,
+  patchHLines: false,
+  patchCross: true,
+  patchStipple: true,
+  patchGrid: false,
+  segmentCount: 400,
+  compactness: 10,
+  loadLabelMask: async function () {
+    loadLabelMask();
+  },
+  clearLabelMask: async function () {
+    clearLabelMask();
+  },
+  addLayer: function () {
+    addLayer();
+  },
+  keepLookA: function () {
+    looks.a = renderOptions();
+  },
+  keepLookB: function () {
+    looks.b = renderOptions();
+  },
+  morph: 0,
+  collageWidth: 6000,
+  collageHeight: 3000,
+  collageGap: 60,
+  collageBackground: [255, 255, 255],
+  collagePanel: 0,
+  pickCollage: async function () {
+    pickCollage();
+  },
+  keepPanelLook: function () {
+    const panel = collagePanels[controls.collagePanel];
+    if (panel !== undefined) panel.options = renderOptions();
+  },
+  tuning: "",
+  autoTune: async function () {
+    autoTune();
+  },
+  compareMetric: "Ssim",
+  compareScores: "",
+  pinCompare: function () {
+    pinnedOptions = renderOptions();
+  },
+  unpinCompare: function () {
+    pinnedOptions = null;
+  },
+  compareRenders: async function () {
+    compareRenders();
+  },
+  cvdKind: "Deuteranopia",
+  simulateCvd: async function () {
+    simulateCvd();
+  },
+  renderCollage: async function () {
+    renderCollage(false);
+  },
+  saveCollage: async function () {
+    renderCollage(true);
+  },
+  sweepName: "cell",
+  sweepFrom: 4,
+  sweepTo: 20,
+  sweepSteps: 5,
+  sweepSave: false,
+  sweep: async function () {
+    sweep();
+  },
+  population: 8,
+  newPopulation: async function () {
+    newPopulation();
+  },
+  nextGeneration: async function () {
+    nextGeneration();
+  },
   save: async function () {
     save();
   },
+  saveWithPreview: async function () {
+    saveWithPreview();
+  },
 };
 
-gui.add(controls, "cellSize", 1, 100, 1).name("Cell Size");
 gui
+  .add(controls, "locale", { English: "En", Français: "Fr" })
+  .name("Language")
+  .onChange((locale: string) => invoke("set_locale", { locale }));
+gui.add(controls, "cellSize", 1, 100, 1).name("Cell Size");
+gui.add(controls, "seed", 0, 100000, 1).name("Seed");
+let styleController = gui
   .add(controls, "style", [
     "Dots",
     "VLines",
@@ -104,14 +1794,367 @@ gui
     "Stipple",
     "Grid",
     "Multi",
+    "Tonal",
+    "Patches",
   ])
   .name("Style");
+gui.add(controls, "underlay", 0, 1, 0.01).name("Photo Underlay");
+gui.add(controls, "thresholdTrace").name("Threshold Trace").listen();
+gui.add(controls, "traceLevel", 0, 1, 0.01).name("Trace Darker Than");
+gui.add(controls, "antialias").name("Antialias");
+gui.add(controls, "supersample", [1, 2, 3, 4]).name("Supersample");
+gui.add(controls, "oneBit").name("1-Bit Output");
+gui.add(controls, "oneBitLevel", 0, 1, 0.01).name("Black Darker Than");
+const maskFolder = gui.addFolder("Mask");
+maskFolder.add(controls, "autoMask").name("Skip White Background");
+maskFolder.add(controls, "maskThreshold", 0.5, 1, 0.01).name("Whiter Than");
+maskFolder.add(controls, "paintMask").name("Paint").onChange(showMask);
+maskFolder
+  .add(controls, "brushTarget", ["Mask", "Density", "Cells"])
+  .name("Paint On")
+  .onChange(showMask);
+maskFolder.add(controls, "brushSize", 2, 200, 1).name("Brush Size");
+maskFolder.add(controls, "brushDensity", 0, 1, 0.05).name("Mask Density");
+maskFolder.add(controls, "brushBoost", 0, MAX_BOOST, 0.05).name("Density Boost");
+maskFolder.add(controls, "applyMask").name("Apply Mask");
+maskFolder.add(controls, "clearMask").name("Clear Mask");
+maskFolder.add(controls, "applyDensity").name("Apply Density");
+maskFolder.add(controls, "clearDensity").name("Clear Density");
+maskFolder.add(controls, "coarseCells", 2, 16, 1).name("Coarse Cells");
+maskFolder.add(controls, "clearCells").name("Clear Cells");
+maskFolder.close();
+gui.add(controls, "invertOutput").name("Negative");
+gui.add(controls, "transparent").name("Transparent");
+gui.add(controls, "debugOverlay").name("Debug Overlay");
+gui.add(controls, "sharedPreview").name("Full Size Preview");
+const patternFolder = gui.addFolder("Test Pattern");
+patternFolder
+  .add(controls, "testPattern", ["Gradient", "ZonePlate", "StepWedge", "ColorWheel"])
+  .name("Pattern");
+patternFolder.add(controls, "wedgeSteps", 2, 32, 1).name("Wedge Steps");
+patternFolder.add(controls, "patternSize", 16, 1024, 16).name("Size");
+patternFolder.add(controls, "loadTestPattern").name("Load Pattern");
+patternFolder.add(controls, "measureTone").name("Measure Printed Wedge");
+patternFolder.add(controls, "calibrate").name("Use Calibration");
+patternFolder.close();
+const regionsFolder = gui.addFolder("Regions");
+regionsFolder.add(controls, "loadLabelMask").name("Load Label Mask");
+regionsFolder.add(controls, "clearLabelMask").name("Clear Label Mask");
+regionsFolder.close();
+const DEPTH_STYLES = [
+  "Same",
+  "Dots",
+  "VLines",
+  "HLines",
+  "Cross",
+  "Stipple",
+  "Grid",
+  "Multi",
+  "Tonal",
+  "Patches",
+];
+const depthFolder = gui.addFolder("Depth");
+depthFolder.add(controls, "loadDepthMap").name("Load Depth Map");
+depthFolder.add(controls, "clearDepthMap").name("Clear Depth Map");
+depthFolder.add(controls, "byDepth").name("By Depth");
+depthFolder.add(controls, "nearDensity", 0, 3, 0.05).name("Near Density");
+depthFolder.add(controls, "farDensity", 0, 3, 0.05).name("Far Density");
+depthFolder.add(controls, "nearCell", 1, 16, 1).name("Near Cells");
+depthFolder.add(controls, "farCell", 1, 16, 1).name("Far Cells");
+depthFolder.add(controls, "nearStyle", DEPTH_STYLES).name("Near Style");
+depthFolder.add(controls, "farStyle", DEPTH_STYLES).name("Far Style");
+depthFolder.add(controls, "depthSplit", 0, 1, 0.01).name("Near From");
+depthFolder.close();
+const facesFolder = gui.addFolder("Faces");
+facesFolder.add(controls, "faceDetail").name("Face Detail");
+facesFolder.add(controls, "faceDensity", 0, 3, 0.05).name("Face Density");
+facesFolder.add(controls, "faceBackground", 1, 8, 1).name("Background Coarser");
+facesFolder.close();
+const cmyFolder = gui.addFolder("CMY Halftone");
+cmyFolder.add(controls, "cmy").name("Enabled");
+cmyFolder.add(controls, "cyanDensity", 0, 3, 0.05).name("Cyan");
+cmyFolder.add(controls, "magentaDensity", 0, 3, 0.05).name("Magenta");
+cmyFolder.add(controls, "yellowDensity", 0, 3, 0.05).name("Yellow");
+cmyFolder.close();
+const risoFolder = gui.addFolder("Riso");
+risoFolder.add(controls, "risoDrums", 1, 3, 1).name("Drums");
+for (const [i, drum] of RISO_DRUMS.entries()) {
+  risoFolder.addColor(controls, drum.ink, 255).name(`Drum ${i + 1} Ink`);
+  risoFolder
+    .add(controls, drum.channel, ["Cyan", "Magenta", "Yellow", "Key", "Luminance"])
+    .name(`Drum ${i + 1} Channel`);
+  risoFolder
+    .add(controls, drum.style, DEPTH_STYLES.slice(1, -1))
+    .name(`Drum ${i + 1} Style`);
+  risoFolder.add(controls, drum.density, 0, 3, 0.05).name(`Drum ${i + 1} Density`);
+}
+risoFolder.add(controls, "risoMisregister", 0, 10, 0.5).name("Misregister");
+risoFolder.add(controls, "risoGrain", 0, 1, 0.01).name("Grain");
+risoFolder.add(controls, "saveRiso").name("Save Riso");
+risoFolder.close();
+const layersFolder = gui.addFolder("Layers");
+layersFolder.add(controls, "addLayer").name("Add Layer");
+layersFolder.add(controls, "sourceMode", BLEND_MODES).name("Photo Mode");
+layersFolder.add(controls, "sourceOpacity", 0, 1, 0.01).name("Photo Opacity");
+layersFolder.close();
+const morphFolder = gui.addFolder("Morph");
+morphFolder.add(controls, "keepLookA").name("Keep Look A");
+morphFolder.add(controls, "keepLookB").name("Keep Look B");
+morphFolder
+  .add(controls, "morph", 0, 1, 0.01)
+  .name("A to B")
+  .onChange(() => morph());
+morphFolder.close();
+const collageFolder = gui.addFolder("Collage");
+collageFolder.add(controls, "pickCollage").name("Pick Images");
+collageFolder.add(controls, "collageWidth", 500, 20000, 10).name("Width");
+collageFolder.add(controls, "collageHeight", 500, 20000, 10).name("Height");
+collageFolder.add(controls, "collageGap", 0, 500, 1).name("Gap");
+collageFolder.addColor(controls, "collageBackground", 255).name("Background");
+collageFolder.add(controls, "collagePanel", 0, 15, 1).name("Panel").listen();
+collageFolder.add(controls, "keepPanelLook").name("Use Look For Panel");
+collageFolder.add(controls, "renderCollage").name("Preview Collage");
+collageFolder.add(controls, "saveCollage").name("Save Collage");
+collageFolder.close();
+const tuneFolder = gui.addFolder("Auto Tune");
+tuneFolder.add(controls, "autoTune").name("Tune To Photo");
+tuneFolder.add(controls, "tuning").name("Found").listen().disable();
+tuneFolder.close();
+const compareFolder = gui.addFolder("Compare");
+compareFolder.add(controls, "pinCompare").name("Pin Settings");
+compareFolder.add(controls, "unpinCompare").name("Compare With Source");
+compareFolder.add(controls, "compareMetric", { SSIM: "Ssim", "ΔE": "DeltaE" }).name("Heat Map");
+compareFolder.add(controls, "compareRenders").name("Compare");
+compareFolder.add(controls, "compareScores").name("Scores").listen().disable();
+compareFolder.close();
+const cvdFolder = gui.addFolder("Color Blindness");
+cvdFolder
+  .add(controls, "cvdKind", ["Protanopia", "Deuteranopia", "Tritanopia"])
+  .name("Kind");
+cvdFolder.add(controls, "simulateCvd").name("Simulate");
+cvdFolder.close();
+const sweepFolder = gui.addFolder("Sweep");
+sweepFolder.add(controls, "sweepName", SWEEPABLE).name("Setting");
+sweepFolder.add(controls, "sweepFrom").name("From");
+sweepFolder.add(controls, "sweepTo").name("To");
+sweepFolder.add(controls, "sweepSteps", 2, 12, 1).name("Steps");
+sweepFolder.add(controls, "sweepSave").name("Save Strip");
+sweepFolder.add(controls, "sweep").name("Sweep");
+sweepFolder.close();
+const evolveFolder = gui.addFolder("Evolve");
+evolveFolder.add(controls, "population", 2, 24, 1).name("Population");
+evolveFolder.add(controls, "newPopulation").name("New Population");
+evolveFolder.add(controls, "nextGeneration").name("Next Generation");
+evolveFolder.close();
+const slideshowFolder = gui.addFolder("Slideshow");
+slideshowFolder.add(controls, "slideInterval", 1, 120, 1).name("Seconds");
+slideshowFolder.add(controls, "slideRandomize").name("Random Styles");
+slideshowFolder.add(controls, "startSlideshow").name("Start");
+slideshowFolder.close();
+const hotFolder = gui.addFolder("Hot Folder");
+hotFolder.add(controls, "startHotFolder").name("Start Watching");
+hotFolder.add(controls, "stopHotFolder").name("Stop Watching");
+hotFolder.add(controls, "hotFolderStatus").name("Status").listen().disable();
+hotFolder.close();
+const multiFolder = gui.addFolder("Multi Density");
+for (const hue of ["red", "orange", "yellow", "green", "blue", "purple"]) {
+  multiFolder
+    .add(controls, `${hue}Density` as keyof typeof controls, 0, 3, 0.05)
+    .name(hue[0].toUpperCase() + hue.slice(1));
+}
+multiFolder.add(controls, "superpixels").name("By Superpixel");
+multiFolder.add(controls, "segmentCount", 10, 5000, 10).name("Superpixels");
+multiFolder.add(controls, "compactness", 1, 40, 1).name("Compactness");
+const symmetryFolder = gui.addFolder("Symmetry");
+symmetryFolder
+  .add(controls, "symmetry", [
+    "None",
+    "Vertical",
+    "Horizontal",
+    "Both",
+    "Rotational",
+  ])
+  .name("Mirror");
+symmetryFolder.add(controls, "folds", 2, 24, 1).name("Folds");
+symmetryFolder.add(controls, "kaleidoscope").name("Kaleidoscope");
+symmetryFolder.add(controls, "symmetryX", 0, 1, 0.01).name("Center X");
+symmetryFolder.add(controls, "symmetryY", 0, 1, 0.01).name("Center Y");
+symmetryFolder.add(controls, "tileable").name("Tileable");
+symmetryFolder.close();
+const tonalFolder = gui.addFolder("Tonal Bands");
+const BAND_STYLES = ["Dots", "VLines", "HLines", "Cross", "Stipple", "Grid"];
+tonalFolder.add(controls, "shadowStyle", BAND_STYLES).name("Shadows");
+tonalFolder.add(controls, "midtoneStyle", BAND_STYLES).name("Midtones");
+tonalFolder.add(controls, "highlightStyle", BAND_STYLES).name("Highlights");
+tonalFolder.add(controls, "shadowsBelow", 0, 1, 0.01).name("Shadows Below");
+tonalFolder
+  .add(controls, "highlightsAbove", 0, 1, 0.01)
+  .name("Highlights Above");
+tonalFolder.close();
+const patchesFolder = gui.addFolder("Noise Patches");
+patchesFolder.add(controls, "patchScale", 4, 512, 1).name("Patch Size");
+patchesFolder.add(controls, "patchOctaves", 1, 8, 1).name("Octaves");
+for (const style of BAND_STYLES) {
+  patchesFolder
+    .add(controls, `patch${style}` as keyof typeof controls)
+    .name(style);
+}
+patchesFolder.close();
+const exportFolder = gui.addFolder("Export");
+exportFolder
+  .add(controls, "trim", ["None", "Background", "Marks"])
+  .name("Trim");
+exportFolder.add(controls, "trimPadding", 0, 500, 1).name("Trim Padding");
+exportFolder.add(controls, "fileTemplate").name("File Name");
+exportFolder.add(controls, "exportToFolder").name("Export To Folder");
+exportFolder.add(controls, "mergeLines").name("Merge Lines");
+exportFolder.add(controls, "orderPaths").name("Order Paths");
+exportFolder.add(controls, "twoOpt").name("Untangle Paths");
+exportFolder.add(controls, "smoothPaths").name("Smooth Paths");
+exportFolder
+  .add(controls, "smoothTolerance", 0, 5, 0.1)
+  .name("Smooth Tolerance");
+exportFolder
+  .add(controls, "paperUnit", ["Pixels", "Millimeters", "Inches"])
+  .name("Paper Units");
+exportFolder.add(controls, "paperWidth", 1, 2000).name("Paper Width");
+exportFolder.add(controls, "paperHeight", 1, 2000).name("Paper Height");
+exportFolder.add(controls, "paperMargin", 0, 200).name("Paper Margin");
+exportFolder
+  .add(controls, "paperScale", ["Fit", "Fixed", "Pen"])
+  .name("Scale");
+exportFolder.add(controls, "pixelSize", 0.001, 10).name("Pixel Size");
+exportFolder.add(controls, "penWidth", 0.01, 5).name("Pen Width");
+exportFolder.add(controls, "drawFeed", 100, 20000).name("Draw Feed (mm/min)");
+exportFolder.add(controls, "travelFeed", 100, 20000).name("Travel Feed (mm/min)");
+exportFolder.add(controls, "penLift", 0, 2).name("Pen Lift (s)");
+exportFolder.add(controls, "saveMarks").name("Save Vectors");
+exportFolder.add(controls, "penSplit", ["Luminance", "Color"]).name("Split Pens By");
+exportFolder.add(controls, "pens", 1, 8, 1).name("Pens");
+exportFolder.add(controls, "savePenLayers").name("Save Pen Layers");
+exportFolder.add(controls, "plotTime").name("Plot Time").listen().disable();
+exportFolder.add(controls, "showCoverage").name("Measure Ink");
+exportFolder.add(controls, "inkCoverage").name("Ink Coverage").listen().disable();
+exportFolder.add(controls, "pauseExports").name("Pause Exports");
+exportFolder.add(controls, "resumeExports").name("Resume Exports");
+exportFolder.add(controls, "abortExports").name("Abort Exports");
+exportFolder.add(controls, "exportProgress").name("Saving").listen().disable();
+exportFolder.add(controls, "revealExport").name("Show Last Export");
+exportFolder.add(controls, "openExport").name("Open Last Export");
+const layoutFolder = gui.addFolder("Layout");
+layoutFolder
+  .add(controls, "layout", ["Grid", "Polar", "Brick", "Screen"])
+  .name("Layout");
+layoutFolder.add(controls, "centerX", 0, 1, 0.01).name("Center X");
+layoutFolder.add(controls, "centerY", 0, 1, 0.01).name("Center Y");
+layoutFolder.add(controls, "brickColumns").name("Brick Columns");
+layoutFolder.add(controls, "screenAngle", 0, 90, 1).name("Screen Angle");
+layoutFolder.add(controls, "jitter", 0, 1, 0.01).name("Jitter");
+const quadtreeFolder = gui.addFolder("Adaptive Cells");
+quadtreeFolder.add(controls, "quadtree").name("Enabled");
+quadtreeFolder.add(controls, "maxBlock", [2, 4, 8, 16, 32, 64]).name("Max Block");
+quadtreeFolder.add(controls, "minBlock", [1, 2, 4, 8]).name("Min Block");
+quadtreeFolder
+  .add(controls, "detailThreshold", 0, 0.05, 0.0005)
+  .name("Detail Threshold");
+const dotsFolder = gui.addFolder("Dots");
+dotsFolder
+  .add(controls, "dotShape", ["Circle", "Square", "Diamond", "Ring", "Star"])
+  .name("Shape");
+dotsFolder.add(controls, "starPoints", 3, 12, 1).name("Star Points");
+dotsFolder
+  .add(controls, "dotFill", ["Grow", "Clamp", "Square", "Bleed"])
+  .name("Fill");
+dotsFolder.add(controls, "dotBleed", 0, 1, 0.05).name("Bleed");
+dotsFolder
+  .add(controls, "dotRotation", ["None", "Luminance", "Hue"])
+  .name("Rotation");
+const linesFolder = gui.addFolder("Lines");
+linesFolder
+  .add(controls, "hatchDirection", ["Fixed", "AlongGradient", "AcrossGradient"])
+  .name("Direction");
+linesFolder.add(controls, "strokeWeight", 0.1, 8, 0.1).name("Stroke Weight");
+linesFolder.add(controls, "compensateStroke").name("Keep Tone");
+const handFolder = gui.addFolder("Hand Drawn");
+handFolder.add(controls, "handDrawn").name("Enabled");
+handFolder.add(controls, "wobbleAmplitude", 0, 10, 0.1).name("Wobble");
+handFolder.add(controls, "wobbleFrequency", 0.001, 0.5, 0.001).name("Frequency");
+const gradientFolder = gui.addFolder("Gradient Map");
+gradientFolder.add(controls, "gradientMap").name("Enabled");
+let paletteController = gradientFolder
+  .add(controls, "palette", ["Custom"])
+  .name("Palette");
+gradientFolder.add(controls, "paletteSeed", 1, 1000, 1).name("Palette Seed");
+gradientFolder.add(controls, "randomPalette").name("Random Palette");
+gradientFolder.add(controls, "extractCount", 2, 12, 1).name("Extract Count");
+gradientFolder.add(controls, "extractPalette").name("Extract From Image");
+gradientFolder.addColor(controls, "gradientDark", 255).name("Dark");
+gradientFolder.addColor(controls, "gradientLight", 255).name("Light");
+gradientFolder.add(controls, "gradientAccent").name("Accent");
+gradientFolder.addColor(controls, "accentColor", 255).name("Accent Color");
+gradientFolder.add(controls, "accentAt", 0, 1, 0.01).name("Accent At");
+const blendFolder = gui.addFolder("Blend");
+blendFolder.add(controls, "chooseSecondaryImage").name("Choose Image");
+blendFolder.add(controls, "blend", ["None", "Mix", "Hue"]).name("Mode");
+blendFolder.add(controls, "blendAmount", 0, 1, 0.01).name("Amount");
+const paperFolder = gui.addFolder("Paper");
+paperFolder.add(controls, "paper", ["None", "Fiber", "Tile"]).name("Texture");
+paperFolder.add(controls, "paperIntensity", 0, 1, 0.01).name("Intensity");
+paperFolder.add(controls, "choosePaperTile").name("Choose Tile");
+const effectsFolder = gui.addFolder("Effects");
+effectsFolder.add(controls, "vignette", 0, 1, 0.01).name("Vignette");
+effectsFolder.add(controls, "grain", 0, 1, 0.01).name("Grain");
+effectsFolder.add(controls, "blur", 0, 10, 0.1).name("Blur");
+const aspectFolder = gui.addFolder("Canvas");
+aspectFolder
+  .add(controls, "aspect", ["Image", "1:1", "2:3", "3:2", "3:4", "4:5", "16:9", "9:16"])
+  .name("Aspect");
+aspectFolder.add(controls, "placement", ["Center", "Thirds"]).name("Placement");
+aspectFolder.add(controls, "placementColumn", [0, 1, 2]).name("Third Column");
+aspectFolder.add(controls, "placementRow", [0, 1, 2]).name("Third Row");
+aspectFolder
+  .add(controls, "backdrop", ["Color", "Blur", "Transparent"])
+  .name("Background");
+aspectFolder.addColor(controls, "backdropColor", 255).name("Color");
+aspectFolder.add(controls, "backdropBlur", 0, 100, 1).name("Blur");
+aspectFolder.close();
+const borderFolder = gui.addFolder("Border");
+borderFolder.add(controls, "borderMargin", 0, 1000, 1).name("Margin");
+borderFolder.addColor(controls, "borderColor", 255).name("Color");
+borderFolder.add(controls, "doubleRule").name("Double Rule");
+borderFolder.add(controls, "cornerRadius", 0, 500, 1).name("Corner Radius");
+const captionFolder = gui.addFolder("Caption");
+captionFolder.add(controls, "captionTitle").name("Title");
+captionFolder.add(controls, "captionDate").name("Date");
+captionFolder.add(controls, "captionEdition").name("Edition");
+captionFolder.add(controls, "captionFont").name("Font File");
+captionFolder.add(controls, "captionSize", 8, 400, 1).name("Size");
+captionFolder.addColor(controls, "captionColor", 255).name("Color");
+captionFolder.addColor(controls, "captionBackground", 255).name("Background");
+captionFolder.add(controls, "captionAlign", ["Left", "Center", "Right"]).name("Align");
+captionFolder.close();
+const stampFolder = gui.addFolder("QR Stamp");
+stampFolder.add(controls, "stamp").name("Stamp");
+stampFolder.add(controls, "stampUrl").name("URL (Blank: Parameters)");
+stampFolder
+  .add(controls, "stampCorner", ["TopLeft", "TopRight", "BottomLeft", "BottomRight"])
+  .name("Corner");
+stampFolder.add(controls, "stampModule", 1, 16, 1).name("Module Size");
+stampFolder.add(controls, "stampMargin", 0, 200, 1).name("Margin");
+stampFolder.close();
 gui.add(controls, "chooseImage").name("Choose Image");
 gui.add(controls, "generate").name("Generate");
+gui.add(controls, "surpriseMe").name("Surprise Me");
 gui.add(controls, "save").name("Save");
+gui.add(controls, "saveWithPreview").name("Save With Preview");
 
 // Convert the raw image data to a canvas image and put it on the canvas.
-function displayImage(width: number, height: number, data: Uint8Array) {
+function displayImage(
+  width: number,
+  height: number,
+  data: Uint8Array,
+  bitmap?: ImageBitmap,
+) {
   const splash = document.getElementById("splash");
   splash!.style.display = "none";
   const errorElement = document.getElementById("error-message");
@@ -123,26 +2166,355 @@ function displayImage(width: number, height: number, data: Uint8Array) {
   canvas.style.display = "block";
   const aspect = width / height;
   const ctx = canvas.getContext("2d");
+  canvas.width = W;
   canvas.height = W / aspect;
+  if (bitmap !== undefined) {
+    ctx!.drawImage(bitmap, 0, 0, W, W / aspect);
+    return;
+  }
   let clamped_data = new Uint8ClampedArray(data);
   const img_data = new ImageData(clamped_data, width, height);
   ctx!.putImageData(img_data, 0, 0);
 }
 
-function displayError(error: Error) {
+function displayError(error: Error | Message) {
   const splash = document.getElementById("splash");
   splash!.style.display = "none";
   const canvas = document.querySelector("canvas") as HTMLCanvasElement;
   canvas.style.display = "none";
   const errorElement = document.getElementById("error-message");
   if (errorElement instanceof HTMLElement) {
-    errorElement.textContent = error.toString();
+    errorElement.textContent =
+      "text" in error ? error.text : error.toString();
     errorElement.style.display = "block";
   }
 }
 
-// Toggle the control panel.
+// Show the contaminated image in the window.
+listen<RenderComplete>("render-complete", async (event) => {
+  if (event.payload.id !== previewJob) return;
+  const { picture, shared } = event.payload;
+  addToHistory(event.payload.thumb);
+  if (picture !== null) {
+    displayImage(picture.width, picture.height, picture.data);
+  } else if (shared !== null) {
+    await displayShared(shared);
+  }
+  if (controls.showCoverage) showCoverage();
+});
+
+// The seg protocol is served from a different origin on Windows.
+const SEG_PROTOCOL = navigator.userAgent.includes("Windows")
+  ? "http://seg.localhost/"
+  : "seg://localhost/";
+
+// Thumbnails of recent previews, served by the backend so the browser
+// caches them.
+const MAX_HISTORY = 24;
+
+function addToHistory(thumb: string) {
+  const history = document.getElementById("history");
+  if (history === null) return;
+  const img = document.createElement("img");
+  img.src = SEG_PROTOCOL + thumb;
+  history.appendChild(img);
+  while (history.childElementCount > MAX_HISTORY) {
+    history.firstElementChild!.remove();
+  }
+}
+
+// Fetch a full size preview from the backend and draw it scaled to fit.
+async function displayShared(shared: SharedPicture) {
+  try {
+    const response = await fetch(SEG_PROTOCOL + shared.path);
+    if (!response.ok) return;
+    const data = new Uint8ClampedArray(await response.arrayBuffer());
+    const bitmap = await createImageBitmap(
+      new ImageData(data, shared.width, shared.height),
+    );
+    displayImage(shared.width, shared.height, new Uint8Array(0), bitmap);
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Where the last finished export was saved, for the reveal and open
+// buttons.
+let lastExport: string | null = null;
+
+// Show how much of the export being saved has been written.
+listen<{ id: number; saved: number }>("job-progress", (event) => {
+  const { saved } = event.payload;
+  controls.exportProgress = saved < 1 ? `${Math.floor(saved * 100)}%` : "";
+});
+
+listen<JobInfo>("job-state", (event) => {
+  const job = event.payload;
+  if (job.kind !== "Preview" && job.state !== "Running") {
+    controls.exportProgress = "";
+  }
+  if (job.state === "Failed") {
+    displayError(job.error ?? new Error("The render failed"));
+  }
+  if (job.state === "Done" && job.kind !== "Preview" && job.path !== null) {
+    lastExport = job.path;
+  }
+});
+
+// Show the last export in the file manager, or open it.
+async function showLastExport(command: string) {
+  if (lastExport === null) {
+    displayError(new Error("Nothing has been exported yet"));
+    return;
+  }
+  try {
+    await invoke(command, { path: lastExport });
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+loadStyles();
+loadPalettes();
+
+// Save the controls every so often when they have changed, so a crash
+// does not lose them.
+let lastAutosave = "";
+let autosaveTimer: number | undefined;
+
+function startAutosave(seconds: number) {
+  clearInterval(autosaveTimer);
+  if (seconds === 0) return;
+  autosaveTimer = setInterval(async () => {
+    const saved = JSON.stringify(controls);
+    if (saved === lastAutosave) return;
+    try {
+      await invoke("autosave", { controls: JSON.parse(saved) });
+      lastAutosave = saved;
+    } catch (error) {
+      console.error(`Error: ${error}`);
+    }
+  }, seconds * 1000);
+}
+
+type Settings = {
+  export_folder: string | null;
+  style: string;
+  preview_width: number;
+  threads: number;
+  autosave_interval: number;
+  usage_stats: boolean;
+  max_source_pixels: number | null;
+  manifests: boolean;
+};
+
+let settings: Settings | null = null;
+
+const settingsControls = {
+  exportFolder: "",
+  chooseExportFolder: async function () {
+    const folder = (await dialog.open({
+      multiple: false,
+      directory: true,
+    })) as string | null;
+    if (folder === null) return;
+    updateSettings({ export_folder: folder });
+  },
+  styleAsDefault: function () {
+    updateSettings({ style: controls.style });
+  },
+  previewWidth: 1024,
+  threads: 0,
+  autosaveInterval: 30,
+  usageStats: false,
+  // 0 for no limit.
+  maxSourceMP: 0,
+  manifests: false,
+  exportUsage: async function () {
+    try {
+      const path = (await dialog.save({
+        defaultPath: "seg-usage.json",
+        filters: [{ name: "JSON", extensions: ["json"] }],
+      })) as string | null;
+      if (path === null) return;
+      await invoke("export_usage_stats", { path });
+    } catch (error) {
+      displayError(error as Error);
+    }
+  },
+  deleteUsage: async function () {
+    if (!(await dialog.ask("Delete the usage stats?", { title: "Usage Stats" })))
+      return;
+    try {
+      await invoke("delete_usage_stats");
+    } catch (error) {
+      displayError(error as Error);
+    }
+  },
+};
+
+function applySettings(next: Settings) {
+  settings = next;
+  W = next.preview_width;
+  settingsControls.exportFolder = next.export_folder ?? "";
+  settingsControls.previewWidth = next.preview_width;
+  settingsControls.threads = next.threads;
+  settingsControls.autosaveInterval = next.autosave_interval;
+  settingsControls.usageStats = next.usage_stats;
+  settingsControls.maxSourceMP = (next.max_source_pixels ?? 0) / 1e6;
+  settingsControls.manifests = next.manifests;
+  startAutosave(next.autosave_interval);
+}
+
+// The settings start a session, the style only until one is recovered.
+async function loadSettings() {
+  try {
+    applySettings(await invoke("get_settings"));
+    controls.style = settings!.style;
+    gui.controllersRecursive().forEach((c) => c.updateDisplay());
+  } catch (error) {
+    console.error(`Error: ${error}`);
+    startAutosave(30);
+  }
+}
+
+async function updateSettings(changes: Partial<Settings>) {
+  if (settings === null) return;
+  try {
+    await invoke("update_settings", { settings: { ...settings, ...changes } });
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+listen<Settings>("settings-changed", (event) => applySettings(event.payload));
+
+const settingsFolder = gui.addFolder("Settings");
+settingsFolder
+  .add(settingsControls, "exportFolder")
+  .name("Export Folder")
+  .listen()
+  .disable();
+settingsFolder
+  .add(settingsControls, "chooseExportFolder")
+  .name("Choose Export Folder");
+settingsFolder
+  .add(settingsControls, "styleAsDefault")
+  .name("Make Style The Default");
+settingsFolder
+  .add(settingsControls, "previewWidth", 256, 4096, 16)
+  .name("Preview Width")
+  .listen()
+  .onFinishChange((v: number) => updateSettings({ preview_width: v }));
+settingsFolder
+  .add(settingsControls, "threads", 0, 64, 1)
+  .name("Render Threads")
+  .listen()
+  .onFinishChange((v: number) => updateSettings({ threads: v }));
+settingsFolder
+  .add(settingsControls, "autosaveInterval", 0, 600, 5)
+  .name("Autosave (s)")
+  .listen()
+  .onFinishChange((v: number) => updateSettings({ autosave_interval: v }));
+settingsFolder
+  .add(settingsControls, "usageStats")
+  .name("Count My Usage")
+  .listen()
+  .onChange((v: boolean) => updateSettings({ usage_stats: v }));
+settingsFolder
+  .add(settingsControls, "maxSourceMP", 0, 400, 1)
+  .name("Max Source (MP)")
+  .listen()
+  .onFinishChange((v: number) =>
+    updateSettings({ max_source_pixels: v === 0 ? null : v * 1e6 }),
+  );
+settingsFolder
+  .add(settingsControls, "manifests")
+  .name("Write Export Manifests")
+  .listen()
+  .onChange((v: boolean) => updateSettings({ manifests: v }));
+settingsFolder.add(settingsControls, "exportUsage").name("Export Usage");
+settingsFolder.add(settingsControls, "deleteUsage").name("Delete Usage");
+
+// Offer to pick up where the last run left off if it crashed.
+async function recoverSession() {
+  try {
+    const saved: SavedSession | null = await invoke("recover_session");
+    if (saved === null) return;
+    const restore = await dialog.ask(
+      "Seg did not close properly. Restore the last session?",
+      { title: "Restore Session" },
+    );
+    if (!restore) return;
+    Object.assign(controls, saved.controls);
+    await invoke("set_locale", { locale: controls.locale });
+    gui.controllersRecursive().forEach((c) => c.updateDisplay());
+    if (saved.image_path !== null) {
+      await openImage(saved.image_path, false);
+    }
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+// Exports left over from the last run come back paused, offer to carry on
+// with them.
+async function resumeExports() {
+  try {
+    const jobs: JobInfo[] = await invoke("get_queue");
+    const pending = jobs.filter(
+      (job) => job.kind !== "Preview" && job.state === "Queued",
+    ).length;
+    if (pending === 0) return;
+    const resume = await dialog.ask(
+      `${pending} exports were left unfinished. Resume them?`,
+      { title: "Resume Exports" },
+    );
+    await invoke(resume ? "resume_queue" : "abort_exports");
+  } catch (error) {
+    displayError(error as Error);
+  }
+}
+
+loadSettings().then(recoverSession).then(resumeExports);
+
+interface Slide {
+  id: number;
+  source: string;
+  width: number;
+  height: number;
+  png: string;
+}
+
+// Fade the new slide in over the old one once it has loaded.
+listen<Slide>("slide", (event) => {
+  const slides = document.querySelectorAll<HTMLImageElement>("#slideshow img");
+  const [front, back] = slides[0].classList.contains("shown")
+    ? [slides[1], slides[0]]
+    : [slides[0], slides[1]];
+  front.onload = () => {
+    front.classList.add("shown");
+    back.classList.remove("shown");
+  };
+  front.src = SEG_PROTOCOL + event.payload.png;
+});
+
+listen<Message>("slideshow-error", (event) => {
+  console.error(`Error: ${event.payload.text}`);
+});
+
+// The backend panicked, say so rather than leave the window stuck.
+listen<{ message: string; report: string | null }>("backend-panic", (event) => {
+  const { message, report } = event.payload;
+  const saved = report === null ? "" : ` A report was saved to ${report}`;
+  displayError(new Error(`Something went wrong: ${message}.${saved}`));
+});
+
+// Toggle the control panel, escape ends a slideshow.
 document.addEventListener("keydown", (event) => {
+  if (event.key === "Escape") {
+    stopSlideshow();
+  }
   if (event.key === "c" || event.key === "C") {
     gui.show(gui._hidden);
   }